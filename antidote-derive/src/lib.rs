@@ -0,0 +1,219 @@
+//! Derive macros for antidote.
+//!
+//! These are re-exported by antidote with its `derive` feature, and should be
+//! used from there.
+#![doc(html_root_url="https://sfackler.github.io/rust-antidote/doc/v1.0.0")]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Fields, Ident, ImplItemFn};
+
+// Documented on antidote's re-export.
+#[proc_macro_derive(Lockable, attributes(lockable))]
+pub fn derive_lockable(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+// Documented on antidote's re-export.
+#[proc_macro_attribute]
+pub fn synchronized(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let field = if attr.is_empty() {
+        format_ident!("lock")
+    } else {
+        syn::parse_macro_input!(attr as Ident)
+    };
+    let method = syn::parse_macro_input!(item as ImplItemFn);
+    expand_synchronized(&field, method).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand_synchronized(field: &Ident, mut method: ImplItemFn) -> Result<TokenStream2, Error> {
+    let exclusive = match method.sig.receiver() {
+        None => return Err(Error::new(method.sig.span(), "#[synchronized] methods must take `self`")),
+        Some(receiver) if receiver.reference.is_none() && receiver.colon_token.is_none() => {
+            return Err(Error::new(receiver.span(), "#[synchronized] methods must take `self` by reference"));
+        }
+        Some(receiver) => receiver.reference.is_some() && receiver.mutability.is_some(),
+    };
+
+    // A `&mut self` method already has exclusive access, and couldn't use
+    // `self` mutably while a guard borrowed from it is alive.
+    if !exclusive {
+        let block = &method.block;
+        method.block = syn::parse_quote!({
+            let _guard = self.#field.lock();
+            #block
+        });
+    }
+    Ok(quote!(#method))
+}
+
+enum Lock {
+    Mutex,
+    RwLock { write: Ident },
+}
+
+struct Field<'a> {
+    field: &'a syn::Field,
+    ident: &'a Ident,
+    lock: Lock,
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    let mut locked = format_ident!("Locked{}", name);
+    for attr in &input.attrs {
+        if attr.path().is_ident("lockable") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    locked = meta.value()?.parse()?;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `name = ...`"))
+                }
+            })?;
+        }
+    }
+
+    let fields = match input.data {
+        Data::Struct(ref data) => {
+            match data.fields {
+                Fields::Named(ref fields) => &fields.named,
+                _ => {
+                    return Err(Error::new(input.span(), "Lockable can only be derived for structs with named fields"));
+                }
+            }
+        }
+        _ => return Err(Error::new(input.span(), "Lockable can only be derived for structs")),
+    };
+    let fields = fields.iter().map(parse_field).collect::<Result<Vec<_>, _>>()?;
+
+    let vis = &input.vis;
+    let generics = &input.generics;
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let doc = format!("`{}` with each field in its own lock.", name);
+
+    let idents = fields.iter().map(|f| f.ident).collect::<Vec<_>>();
+    let lock_types = fields.iter().map(lock_type).collect::<Vec<_>>();
+    let accessors = fields.iter().map(accessors);
+
+    Ok(quote! {
+        #[doc = #doc]
+        #vis struct #locked #generics #where_clause {
+            #(#idents: #lock_types,)*
+        }
+
+        impl #impl_generics #locked #ty_generics #where_clause {
+            /// Wraps each field of `value` in its own lock.
+            #[inline]
+            #vis fn new(value: #name #ty_generics) -> #locked #ty_generics {
+                #locked {
+                    #(#idents: <#lock_types>::new(value.#idents),)*
+                }
+            }
+
+            /// Consumes the locks, returning the plain struct.
+            #[inline]
+            #vis fn into_inner(self) -> #name #ty_generics {
+                #name {
+                    #(#idents: self.#idents.into_inner(),)*
+                }
+            }
+
+            #(#accessors)*
+        }
+
+        impl #impl_generics ::std::convert::From<#name #ty_generics> for #locked #ty_generics #where_clause {
+            #[inline]
+            fn from(value: #name #ty_generics) -> #locked #ty_generics {
+                #locked::new(value)
+            }
+        }
+    })
+}
+
+fn parse_field<'a>(field: &'a syn::Field) -> Result<Field<'a>, Error> {
+    let ident = field.ident.as_ref().expect("named field");
+    let mut rwlock = false;
+    let mut write: Option<Ident> = None;
+    for attr in &field.attrs {
+        if attr.path().is_ident("lockable") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("mutex") {
+                    rwlock = false;
+                    Ok(())
+                } else if meta.path.is_ident("rwlock") {
+                    rwlock = true;
+                    Ok(())
+                } else if meta.path.is_ident("write") {
+                    write = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `mutex`, `rwlock` or `write = ...`"))
+                }
+            })?;
+        }
+    }
+
+    let lock = match (rwlock, write) {
+        (false, None) => Lock::Mutex,
+        (false, Some(write)) => {
+            return Err(Error::new(write.span(), "`write` is only valid for `rwlock` fields"));
+        }
+        (true, write) => Lock::RwLock { write: write.unwrap_or_else(|| format_ident!("{}_mut", ident)) },
+    };
+    Ok(Field { field, ident, lock })
+}
+
+fn lock_type(field: &Field) -> TokenStream2 {
+    let ty = &field.field.ty;
+    match field.lock {
+        Lock::Mutex => quote!(::antidote::Mutex<#ty>),
+        Lock::RwLock { .. } => quote!(::antidote::RwLock<#ty>),
+    }
+}
+
+fn accessors(field: &Field) -> TokenStream2 {
+    let vis = &field.field.vis;
+    let ident = field.ident;
+    let ty = &field.field.ty;
+    match field.lock {
+        Lock::Mutex => {
+            let doc = format!("Locks `{}`.", ident);
+            quote! {
+                #[doc = #doc]
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                #vis fn #ident(&self) -> ::antidote::MutexGuard<'_, #ty> {
+                    self.#ident.lock()
+                }
+            }
+        }
+        Lock::RwLock { ref write } => {
+            let read_doc = format!("Read-locks `{}`.", ident);
+            let write_doc = format!("Write-locks `{}`.", ident);
+            quote! {
+                #[doc = #read_doc]
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                #vis fn #ident(&self) -> ::antidote::RwLockReadGuard<'_, #ty> {
+                    self.#ident.read()
+                }
+
+                #[doc = #write_doc]
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                #vis fn #write(&self) -> ::antidote::RwLockWriteGuard<'_, #ty> {
+                    self.#ident.write()
+                }
+            }
+        }
+    }
+}
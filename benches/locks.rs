@@ -0,0 +1,231 @@
+//! Compares this crate's locks against those of the standard library and
+//! `parking_lot`.
+//!
+//! ```text
+//! cargo bench
+//! cargo bench --features futex
+//! ```
+//!
+//! In the contended benchmarks every thread performs the given number of
+//! iterations concurrently, so the reported time per iteration is the wall
+//! clock time for one acquisition on each thread.
+extern crate antidote;
+#[macro_use]
+extern crate criterion;
+extern crate parking_lot;
+
+use criterion::{BenchmarkId, Criterion};
+use std::hint::black_box;
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant};
+
+trait BenchMutex: Sync {
+    fn new(v: u64) -> Self;
+    fn with(&self, f: &mut dyn FnMut(&mut u64));
+}
+
+impl BenchMutex for antidote::Mutex<u64> {
+    fn new(v: u64) -> Self {
+        antidote::Mutex::new(v)
+    }
+
+    fn with(&self, f: &mut dyn FnMut(&mut u64)) {
+        f(&mut self.lock())
+    }
+}
+
+impl BenchMutex for std::sync::Mutex<u64> {
+    fn new(v: u64) -> Self {
+        std::sync::Mutex::new(v)
+    }
+
+    fn with(&self, f: &mut dyn FnMut(&mut u64)) {
+        f(&mut self.lock().unwrap())
+    }
+}
+
+impl BenchMutex for parking_lot::Mutex<u64> {
+    fn new(v: u64) -> Self {
+        parking_lot::Mutex::new(v)
+    }
+
+    fn with(&self, f: &mut dyn FnMut(&mut u64)) {
+        f(&mut self.lock())
+    }
+}
+
+trait BenchRwLock: Sync {
+    fn new(v: u64) -> Self;
+    fn read(&self, f: &mut dyn FnMut(&u64));
+    fn write(&self, f: &mut dyn FnMut(&mut u64));
+}
+
+impl BenchRwLock for antidote::RwLock<u64> {
+    fn new(v: u64) -> Self {
+        antidote::RwLock::new(v)
+    }
+
+    fn read(&self, f: &mut dyn FnMut(&u64)) {
+        f(&self.read())
+    }
+
+    fn write(&self, f: &mut dyn FnMut(&mut u64)) {
+        f(&mut self.write())
+    }
+}
+
+impl BenchRwLock for std::sync::RwLock<u64> {
+    fn new(v: u64) -> Self {
+        std::sync::RwLock::new(v)
+    }
+
+    fn read(&self, f: &mut dyn FnMut(&u64)) {
+        f(&self.read().unwrap())
+    }
+
+    fn write(&self, f: &mut dyn FnMut(&mut u64)) {
+        f(&mut self.write().unwrap())
+    }
+}
+
+impl BenchRwLock for parking_lot::RwLock<u64> {
+    fn new(v: u64) -> Self {
+        parking_lot::RwLock::new(v)
+    }
+
+    fn read(&self, f: &mut dyn FnMut(&u64)) {
+        f(&self.read())
+    }
+
+    fn write(&self, f: &mut dyn FnMut(&mut u64)) {
+        f(&mut self.write())
+    }
+}
+
+/// Simulates work done outside of the critical section.
+fn work(amount: u32) {
+    for i in 0..amount {
+        black_box(i);
+    }
+}
+
+/// Runs `f` on `threads` threads at once, returning the wall clock time.
+fn run_concurrently<F>(threads: usize, f: F) -> Duration
+    where F: Fn(usize) + Sync
+{
+    let barrier = Barrier::new(threads + 1);
+    thread::scope(|s| {
+        for i in 0..threads {
+            let barrier = &barrier;
+            let f = &f;
+            s.spawn(move || {
+                barrier.wait();
+                f(i);
+                barrier.wait();
+            });
+        }
+        barrier.wait();
+        let start = Instant::now();
+        barrier.wait();
+        start.elapsed()
+    })
+}
+
+fn heavy_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1).max(4)
+}
+
+fn mutex<M: BenchMutex>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group("mutex");
+
+    group.bench_function(BenchmarkId::new("uncontended", name), |b| {
+        let m = M::new(0);
+        b.iter(|| m.with(&mut |v| *v += 1))
+    });
+
+    group.bench_function(BenchmarkId::new("light", name), |b| {
+        b.iter_custom(|iters| {
+            let m = M::new(0);
+            run_concurrently(2, |_| for _ in 0..iters {
+                m.with(&mut |v| *v += 1);
+                work(100);
+            })
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("heavy", name), |b| {
+        b.iter_custom(|iters| {
+            let m = M::new(0);
+            run_concurrently(heavy_threads(), |_| for _ in 0..iters {
+                m.with(&mut |v| *v += 1);
+            })
+        })
+    });
+
+    group.finish();
+}
+
+fn rwlock<L: BenchRwLock>(c: &mut Criterion, name: &str) {
+    let mut group = c.benchmark_group("rwlock");
+
+    group.bench_function(BenchmarkId::new("uncontended_read", name), |b| {
+        let l = L::new(0);
+        b.iter(|| l.read(&mut |v| {
+            black_box(*v);
+        }))
+    });
+
+    group.bench_function(BenchmarkId::new("uncontended_write", name), |b| {
+        let l = L::new(0);
+        b.iter(|| l.write(&mut |v| *v += 1))
+    });
+
+    group.bench_function(BenchmarkId::new("light", name), |b| {
+        b.iter_custom(|iters| {
+            let l = L::new(0);
+            run_concurrently(2, |i| for n in 0..iters {
+                if i == 0 && n % 10 == 0 {
+                    l.write(&mut |v| *v += 1);
+                } else {
+                    l.read(&mut |v| {
+                        black_box(*v);
+                    });
+                }
+                work(100);
+            })
+        })
+    });
+
+    group.bench_function(BenchmarkId::new("heavy", name), |b| {
+        b.iter_custom(|iters| {
+            let l = L::new(0);
+            run_concurrently(heavy_threads(), |i| for n in 0..iters {
+                if i == 0 && n % 10 == 0 {
+                    l.write(&mut |v| *v += 1);
+                } else {
+                    l.read(&mut |v| {
+                        black_box(*v);
+                    });
+                }
+            })
+        })
+    });
+
+    group.finish();
+}
+
+fn mutexes(c: &mut Criterion) {
+    mutex::<antidote::Mutex<u64>>(c, "antidote");
+    mutex::<std::sync::Mutex<u64>>(c, "std");
+    mutex::<parking_lot::Mutex<u64>>(c, "parking_lot");
+}
+
+fn rwlocks(c: &mut Criterion) {
+    rwlock::<antidote::RwLock<u64>>(c, "antidote");
+    rwlock::<std::sync::RwLock<u64>>(c, "std");
+    rwlock::<parking_lot::RwLock<u64>>(c, "parking_lot");
+}
+
+criterion_group!(benches, mutexes, rwlocks);
+criterion_main!(benches);
@@ -0,0 +1,376 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use console::{AsyncOp, Resource};
+use timer::TimeoutFuture;
+use wait_queue::WaitQueue;
+use {fmt_guard, TryLockError, TryLockResult};
+
+/// An asynchronous mutex which does not poison itself.
+///
+/// Rather than blocking the thread, `lock` returns a future which resolves
+/// once the lock has been acquired. Blocked tasks are woken through their
+/// `Waker`s alone, so this works with any executor and has no runtime
+/// dependencies. Waiting tasks acquire the lock in roughly the order they
+/// started waiting.
+///
+/// Unlike `Mutex`, the guard may be held across `.await` points and sent
+/// between threads.
+pub struct AsyncMutex<T: ?Sized> {
+    locked: AtomicBool,
+    queue: WaitQueue,
+    resource: Resource,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for AsyncMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for AsyncMutex<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AsyncMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Ok(guard) => fmt.debug_struct("AsyncMutex").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("AsyncMutex").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> AsyncMutex<T> {
+    /// Creates a new asynchronous mutex.
+    #[inline]
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn new(t: T) -> AsyncMutex<T> {
+        AsyncMutex {
+            locked: AtomicBool::new(false),
+            queue: WaitQueue::new(),
+            resource: Resource::new("AsyncMutex"),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> AsyncMutex<T> {
+    /// Returns a future which resolves to a guard once the lock has been
+    /// acquired.
+    ///
+    /// Dropping the future before it resolves gives up its place in the
+    /// queue.
+    #[inline]
+    pub fn lock<'a>(&'a self) -> AsyncMutexLockFuture<'a, T> {
+        AsyncMutexLockFuture {
+            mutex: self,
+            key: None,
+            op: AsyncOp::new(&self.resource, "AsyncMutex::lock"),
+        }
+    }
+
+    /// Like `lock`, but the guard holds a reference count on the mutex
+    /// rather than borrowing it, so it can be moved into a spawned task.
+    #[inline]
+    pub fn lock_owned(self: Arc<Self>) -> AsyncMutexLockOwnedFuture<T> {
+        let op = AsyncOp::new(&self.resource, "AsyncMutex::lock_owned");
+        AsyncMutexLockOwnedFuture {
+            mutex: Some(self),
+            key: None,
+            op,
+        }
+    }
+
+    /// Returns a future which resolves to a guard once the lock has been
+    /// acquired, or to `TryLockError::Timeout` if that doesn't happen within
+    /// `timeout`.
+    #[inline]
+    pub fn lock_timeout<'a>(&'a self, timeout: Duration) -> TimeoutFuture<AsyncMutexLockFuture<'a, T>> {
+        TimeoutFuture::new(self.lock(), timeout)
+    }
+
+    /// Returns a future which resolves to a guard once the lock has been
+    /// acquired, or to `TryLockError::Timeout` if that doesn't happen before
+    /// `deadline`.
+    #[inline]
+    pub fn lock_until<'a>(&'a self, deadline: Instant) -> TimeoutFuture<AsyncMutexLockFuture<'a, T>> {
+        TimeoutFuture::until(self.lock(), deadline)
+    }
+
+    /// Like `Mutex::try_lock`.
+    #[inline]
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<AsyncMutexGuard<'a, T>> {
+        if self.try_acquire() {
+            Ok(AsyncMutexGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `try_lock`, but returns an owned guard.
+    #[inline]
+    pub fn try_lock_owned(self: Arc<Self>) -> TryLockResult<OwnedAsyncMutexGuard<T>> {
+        if self.try_acquire() {
+            Ok(OwnedAsyncMutexGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    // The body of the lock futures' `poll`. `key` identifies the future in
+    // the wait queue once it has been queued.
+    fn poll_acquire(&self, key: &mut Option<u64>, op: &AsyncOp, cx: &mut Context) -> Poll<()> {
+        op.poll(&self.resource, || self.poll_acquire_inner(key, cx))
+    }
+
+    fn poll_acquire_inner(&self, key: &mut Option<u64>, cx: &mut Context) -> Poll<()> {
+        if key.is_none() && self.try_acquire() {
+            return Poll::Ready(());
+        }
+
+        let mut queue = self.queue.lock();
+        queue.register(key, cx.waker(), true);
+        if self.try_acquire() {
+            queue.remove(key.take().unwrap());
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    // Called when a lock future is dropped before completing.
+    fn cancel(&self, key: u64) {
+        // If the future was woken, pass the wakeup on to the next waiter
+        // rather than losing it.
+        let mut queue = self.queue.lock();
+        if !queue.remove(key) {
+            queue.wake_front();
+        }
+    }
+
+    #[inline]
+    fn try_acquire(&self) -> bool {
+        self.locked.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+    }
+
+    #[inline]
+    fn release(&self) {
+        self.resource.locked(false);
+        self.locked.store(false, Ordering::SeqCst);
+        if self.queue.has_waiters() {
+            self.queue.lock().wake_front();
+        }
+    }
+}
+
+impl<T: Default> Default for AsyncMutex<T> {
+    fn default() -> Self {
+        AsyncMutex::new(Default::default())
+    }
+}
+
+/// A future which resolves to an `AsyncMutexGuard`.
+///
+/// This is created by `AsyncMutex::lock`.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncMutexLockFuture<'a, T: ?Sized + 'a> {
+    mutex: &'a AsyncMutex<T>,
+    // Identifies this future in the wait queue once it has been queued.
+    key: Option<u64>,
+    op: AsyncOp,
+}
+
+impl<'a, T: ?Sized> fmt::Debug for AsyncMutexLockFuture<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncMutexLockFuture").field("queued", &self.key.is_some()).finish()
+    }
+}
+
+impl<'a, T: ?Sized> Future for AsyncMutexLockFuture<'a, T> {
+    type Output = AsyncMutexGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<AsyncMutexGuard<'a, T>> {
+        let this = &mut *self;
+        let mutex = this.mutex;
+        match mutex.poll_acquire(&mut this.key, &this.op, cx) {
+            Poll::Ready(()) => Poll::Ready(AsyncMutexGuard::new(mutex)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncMutexLockFuture<'a, T> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.mutex.cancel(key);
+        }
+    }
+}
+
+/// A future which resolves to an `OwnedAsyncMutexGuard`.
+///
+/// This is created by `AsyncMutex::lock_owned`.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncMutexLockOwnedFuture<T: ?Sized> {
+    // Taken when the future completes.
+    mutex: Option<Arc<AsyncMutex<T>>>,
+    key: Option<u64>,
+    op: AsyncOp,
+}
+
+impl<T: ?Sized> fmt::Debug for AsyncMutexLockOwnedFuture<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncMutexLockOwnedFuture").field("queued", &self.key.is_some()).finish()
+    }
+}
+
+impl<T: ?Sized> Future for AsyncMutexLockOwnedFuture<T> {
+    type Output = OwnedAsyncMutexGuard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<OwnedAsyncMutexGuard<T>> {
+        let this = &mut *self;
+        let mutex = this.mutex.as_ref().expect("AsyncMutexLockOwnedFuture polled after completion");
+        match mutex.poll_acquire(&mut this.key, &this.op, cx) {
+            Poll::Ready(()) => Poll::Ready(OwnedAsyncMutexGuard::new(this.mutex.take().unwrap())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncMutexLockOwnedFuture<T> {
+    fn drop(&mut self) {
+        if let (Some(mutex), Some(key)) = (&self.mutex, self.key) {
+            mutex.cancel(key);
+        }
+    }
+}
+
+/// An RAII guard for an `AsyncMutex`.
+#[must_use]
+pub struct AsyncMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a AsyncMutex<T>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: ?Sized> AsyncMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a AsyncMutex<T>) -> AsyncMutexGuard<'a, T> {
+        mutex.resource.locked(true);
+        AsyncMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for AsyncMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "AsyncMutexGuard", self.mutex as *const AsyncMutex<T> as *const () as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for AsyncMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for AsyncMutexGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for AsyncMutexGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncMutexGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.mutex.release()
+    }
+}
+
+/// An RAII guard for an `AsyncMutex` which keeps the mutex alive through an
+/// `Arc`.
+///
+/// Unlike `AsyncMutexGuard`, this is `'static` if `T` is.
+#[must_use]
+pub struct OwnedAsyncMutexGuard<T: ?Sized> {
+    mutex: Arc<AsyncMutex<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ?Sized> OwnedAsyncMutexGuard<T> {
+    #[inline]
+    fn new(mutex: Arc<AsyncMutex<T>>) -> OwnedAsyncMutexGuard<T> {
+        mutex.resource.locked(true);
+        OwnedAsyncMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the mutex this guard locks.
+    #[inline]
+    pub fn mutex(this: &OwnedAsyncMutexGuard<T>) -> &Arc<AsyncMutex<T>> {
+        &this.mutex
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OwnedAsyncMutexGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "OwnedAsyncMutexGuard", &*self.mutex as *const AsyncMutex<T> as *const () as usize, &**self)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for OwnedAsyncMutexGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized> Deref for OwnedAsyncMutexGuard<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for OwnedAsyncMutexGuard<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for OwnedAsyncMutexGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.mutex.release()
+    }
+}
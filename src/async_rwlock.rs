@@ -0,0 +1,747 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use console::{AsyncOp, Resource};
+use timer::TimeoutFuture;
+use wait_queue::{Queue, WaitQueue};
+use {fmt_guard, TryLockError, TryLockResult};
+
+const WRITER: usize = 1;
+const READER: usize = 2;
+
+/// The policy an `AsyncRwLock` uses to arbitrate between readers and
+/// writers.
+///
+/// A task which is starved holds on to everything it has borrowed, and
+/// readers are typically far more numerous than writers, so the right
+/// tradeoff is not always the same as for threads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum AsyncRwLockPolicy {
+    /// Tasks are served in the order they arrive, with consecutive readers
+    /// sharing the lock. Once a writer is waiting, new readers queue up
+    /// behind it.
+    ///
+    /// This is the default.
+    #[default]
+    Fair,
+    /// Waiting writers are served before any waiting reader, and readers
+    /// never acquire the lock while a writer is waiting. Readers may be
+    /// starved by a steady stream of writers.
+    PreferWriters,
+    /// Readers acquire the lock whenever no writer holds it, and waiting
+    /// readers are all woken before any waiting writer. Writers may be
+    /// starved by a steady stream of readers.
+    PreferReaders,
+}
+
+/// An asynchronous reader-writer lock which does not poison itself.
+///
+/// Like `AsyncMutex`, tasks wait through their `Waker`s alone, so this works
+/// with any executor. How readers and writers are arbitrated is configured
+/// by an `AsyncRwLockPolicy`. By default, once a writer is waiting, new
+/// readers queue up behind it so that writers are not starved.
+///
+/// A writer which must not be held up by a flood of readers can be given
+/// priority:
+///
+/// ```
+/// use antidote::{AsyncRwLock, AsyncRwLockPolicy};
+///
+/// let cache = AsyncRwLock::new_with_policy(vec![1, 2, 3], AsyncRwLockPolicy::PreferWriters);
+/// assert_eq!(cache.policy(), AsyncRwLockPolicy::PreferWriters);
+/// ```
+pub struct AsyncRwLock<T: ?Sized> {
+    // `WRITER` if write locked, otherwise `READER` times the number of readers.
+    state: AtomicUsize,
+    // The number of queued writers.
+    writers_pending: AtomicUsize,
+    policy: AsyncRwLockPolicy,
+    queue: WaitQueue,
+    resource: Resource,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for AsyncRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for AsyncRwLock<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for AsyncRwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Ok(guard) => fmt.debug_struct("AsyncRwLock").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("AsyncRwLock").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> AsyncRwLock<T> {
+    /// Creates a new asynchronous reader-writer lock with the `Fair` policy.
+    #[inline]
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn new(t: T) -> AsyncRwLock<T> {
+        AsyncRwLock::new_with_policy(t, AsyncRwLockPolicy::Fair)
+    }
+
+    /// Creates a new asynchronous reader-writer lock which arbitrates between
+    /// readers and writers according to the specified policy.
+    #[inline]
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn new_with_policy(t: T, policy: AsyncRwLockPolicy) -> AsyncRwLock<T> {
+        AsyncRwLock {
+            state: AtomicUsize::new(0),
+            writers_pending: AtomicUsize::new(0),
+            policy,
+            queue: WaitQueue::new(),
+            resource: Resource::new("AsyncRwLock"),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `RwLock::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> AsyncRwLock<T> {
+    /// Returns the policy the lock arbitrates between readers and writers
+    /// with.
+    #[inline]
+    pub fn policy(&self) -> AsyncRwLockPolicy {
+        self.policy
+    }
+
+    /// Returns a future which resolves to a shared guard once the lock has
+    /// been acquired.
+    #[inline]
+    pub fn read<'a>(&'a self) -> AsyncRwLockReadFuture<'a, T> {
+        AsyncRwLockReadFuture {
+            rwlock: self,
+            key: None,
+            op: AsyncOp::new(&self.resource, "AsyncRwLock::read"),
+        }
+    }
+
+    /// Like `read`, but the guard holds a reference count on the lock rather
+    /// than borrowing it, so it can be moved into a spawned task.
+    #[inline]
+    pub fn read_owned(self: Arc<Self>) -> AsyncRwLockReadOwnedFuture<T> {
+        let op = AsyncOp::new(&self.resource, "AsyncRwLock::read_owned");
+        AsyncRwLockReadOwnedFuture {
+            rwlock: Some(self),
+            key: None,
+            op,
+        }
+    }
+
+    /// Returns a future which resolves to a shared guard once the lock has
+    /// been acquired, or to `TryLockError::Timeout` if that doesn't happen
+    /// within `timeout`.
+    #[inline]
+    pub fn read_timeout<'a>(&'a self, timeout: Duration) -> TimeoutFuture<AsyncRwLockReadFuture<'a, T>> {
+        TimeoutFuture::new(self.read(), timeout)
+    }
+
+    /// Returns a future which resolves to a shared guard once the lock has
+    /// been acquired, or to `TryLockError::Timeout` if that doesn't happen
+    /// before `deadline`.
+    #[inline]
+    pub fn read_until<'a>(&'a self, deadline: Instant) -> TimeoutFuture<AsyncRwLockReadFuture<'a, T>> {
+        TimeoutFuture::until(self.read(), deadline)
+    }
+
+    /// Like `RwLock::try_read`.
+    #[inline]
+    pub fn try_read<'a>(&'a self) -> TryLockResult<AsyncRwLockReadGuard<'a, T>> {
+        if self.try_acquire_read(false) {
+            Ok(AsyncRwLockReadGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Returns a future which resolves to an exclusive guard once the lock
+    /// has been acquired.
+    #[inline]
+    pub fn write<'a>(&'a self) -> AsyncRwLockWriteFuture<'a, T> {
+        AsyncRwLockWriteFuture {
+            rwlock: self,
+            key: None,
+            op: AsyncOp::new(&self.resource, "AsyncRwLock::write"),
+        }
+    }
+
+    /// Like `write`, but the guard holds a reference count on the lock rather
+    /// than borrowing it, so it can be moved into a spawned task.
+    #[inline]
+    pub fn write_owned(self: Arc<Self>) -> AsyncRwLockWriteOwnedFuture<T> {
+        let op = AsyncOp::new(&self.resource, "AsyncRwLock::write_owned");
+        AsyncRwLockWriteOwnedFuture {
+            rwlock: Some(self),
+            key: None,
+            op,
+        }
+    }
+
+    /// Returns a future which resolves to an exclusive guard once the lock
+    /// has been acquired, or to `TryLockError::Timeout` if that doesn't
+    /// happen within `timeout`.
+    #[inline]
+    pub fn write_timeout<'a>(&'a self, timeout: Duration) -> TimeoutFuture<AsyncRwLockWriteFuture<'a, T>> {
+        TimeoutFuture::new(self.write(), timeout)
+    }
+
+    /// Returns a future which resolves to an exclusive guard once the lock
+    /// has been acquired, or to `TryLockError::Timeout` if that doesn't
+    /// happen before `deadline`.
+    #[inline]
+    pub fn write_until<'a>(&'a self, deadline: Instant) -> TimeoutFuture<AsyncRwLockWriteFuture<'a, T>> {
+        TimeoutFuture::until(self.write(), deadline)
+    }
+
+    /// Like `RwLock::try_write`.
+    #[inline]
+    pub fn try_write<'a>(&'a self) -> TryLockResult<AsyncRwLockWriteGuard<'a, T>> {
+        if self.try_acquire_write() {
+            Ok(AsyncRwLockWriteGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `try_read`, but returns an owned guard.
+    #[inline]
+    pub fn try_read_owned(self: Arc<Self>) -> TryLockResult<OwnedAsyncRwLockReadGuard<T>> {
+        if self.try_acquire_read(false) {
+            Ok(OwnedAsyncRwLockReadGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `try_write`, but returns an owned guard.
+    #[inline]
+    pub fn try_write_owned(self: Arc<Self>) -> TryLockResult<OwnedAsyncRwLockWriteGuard<T>> {
+        if self.try_acquire_write() {
+            Ok(OwnedAsyncRwLockWriteGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `RwLock::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    // With the fair policy, a reader which has been woken has already waited
+    // its turn, so it doesn't defer to writers queued behind it.
+    fn try_acquire_read(&self, woken: bool) -> bool {
+        let defer = match self.policy {
+            AsyncRwLockPolicy::Fair => !woken,
+            AsyncRwLockPolicy::PreferWriters => true,
+            AsyncRwLockPolicy::PreferReaders => false,
+        };
+        if defer && self.writers_pending.load(Ordering::SeqCst) != 0 {
+            return false;
+        }
+
+        let mut state = self.state.load(Ordering::SeqCst);
+        loop {
+            if state & WRITER != 0 {
+                return false;
+            }
+            match self.state.compare_exchange_weak(state, state + READER, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    // The body of the read futures' `poll`. `key` identifies the future in
+    // the wait queue once it has been queued.
+    fn poll_read(&self, key: &mut Option<u64>, op: &AsyncOp, cx: &mut Context) -> Poll<()> {
+        op.poll(&self.resource, || self.poll_read_inner(key, cx))
+    }
+
+    fn poll_read_inner(&self, key: &mut Option<u64>, cx: &mut Context) -> Poll<()> {
+        if key.is_none() && self.try_acquire_read(false) {
+            return Poll::Ready(());
+        }
+
+        let mut queue = self.queue.lock();
+        let woken = key.is_some_and(|key| !queue.contains(key));
+        queue.register(key, cx.waker(), false);
+        if self.try_acquire_read(woken) {
+            queue.remove(key.take().unwrap());
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    // Called when a read future is dropped before completing.
+    fn cancel_read(&self, key: u64) {
+        let mut queue = self.queue.lock();
+        if !queue.remove(key) {
+            self.wake(&mut queue);
+        }
+    }
+
+    // Wakes the next waiters to try to acquire the lock, according to the
+    // policy.
+    fn wake(&self, queue: &mut Queue) {
+        match self.policy {
+            AsyncRwLockPolicy::Fair => queue.wake_front(),
+            AsyncRwLockPolicy::PreferWriters => {
+                if !queue.wake_exclusive() {
+                    queue.wake_front();
+                }
+            }
+            AsyncRwLockPolicy::PreferReaders => {
+                if !queue.wake_shared() {
+                    queue.wake_front();
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn try_acquire_write(&self) -> bool {
+        self.state.compare_exchange(0, WRITER, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+    }
+
+    // The body of the write futures' `poll`. A queued writer is counted in
+    // `writers_pending`.
+    fn poll_write(&self, key: &mut Option<u64>, op: &AsyncOp, cx: &mut Context) -> Poll<()> {
+        op.poll(&self.resource, || self.poll_write_inner(key, cx))
+    }
+
+    fn poll_write_inner(&self, key: &mut Option<u64>, cx: &mut Context) -> Poll<()> {
+        if key.is_none() && self.try_acquire_write() {
+            return Poll::Ready(());
+        }
+
+        let mut queue = self.queue.lock();
+        if key.is_none() {
+            self.writers_pending.fetch_add(1, Ordering::SeqCst);
+        }
+        queue.register(key, cx.waker(), true);
+        if self.try_acquire_write() {
+            queue.remove(key.take().unwrap());
+            self.writers_pending.fetch_sub(1, Ordering::SeqCst);
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    // Called when a write future is dropped before completing.
+    fn cancel_write(&self, key: u64) {
+        // Readers may have been held back on this writer's behalf, and if it
+        // was woken the wakeup must be passed on, so either way let the front
+        // of the queue try again.
+        let mut queue = self.queue.lock();
+        queue.remove(key);
+        self.writers_pending.fetch_sub(1, Ordering::SeqCst);
+        self.wake(&mut queue);
+    }
+
+    #[inline]
+    fn read_release(&self) {
+        self.resource.reader_released();
+        if self.state.fetch_sub(READER, Ordering::SeqCst) == READER && self.queue.has_waiters() {
+            self.wake(&mut self.queue.lock());
+        }
+    }
+
+    #[inline]
+    fn write_release(&self) {
+        self.resource.write_locked(false);
+        self.state.store(0, Ordering::SeqCst);
+        if self.queue.has_waiters() {
+            self.wake(&mut self.queue.lock());
+        }
+    }
+}
+
+impl<T: Default> Default for AsyncRwLock<T> {
+    fn default() -> Self {
+        AsyncRwLock::new(Default::default())
+    }
+}
+
+/// A future which resolves to an `AsyncRwLockReadGuard`.
+///
+/// This is created by `AsyncRwLock::read`.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncRwLockReadFuture<'a, T: ?Sized + 'a> {
+    rwlock: &'a AsyncRwLock<T>,
+    key: Option<u64>,
+    op: AsyncOp,
+}
+
+impl<'a, T: ?Sized> fmt::Debug for AsyncRwLockReadFuture<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncRwLockReadFuture").field("queued", &self.key.is_some()).finish()
+    }
+}
+
+impl<'a, T: ?Sized> Future for AsyncRwLockReadFuture<'a, T> {
+    type Output = AsyncRwLockReadGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<AsyncRwLockReadGuard<'a, T>> {
+        let this = &mut *self;
+        let rwlock = this.rwlock;
+        match rwlock.poll_read(&mut this.key, &this.op, cx) {
+            Poll::Ready(()) => Poll::Ready(AsyncRwLockReadGuard::new(rwlock)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncRwLockReadFuture<'a, T> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.rwlock.cancel_read(key);
+        }
+    }
+}
+
+/// A future which resolves to an `OwnedAsyncRwLockReadGuard`.
+///
+/// This is created by `AsyncRwLock::read_owned`.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncRwLockReadOwnedFuture<T: ?Sized> {
+    // Taken when the future completes.
+    rwlock: Option<Arc<AsyncRwLock<T>>>,
+    key: Option<u64>,
+    op: AsyncOp,
+}
+
+impl<T: ?Sized> fmt::Debug for AsyncRwLockReadOwnedFuture<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncRwLockReadOwnedFuture").field("queued", &self.key.is_some()).finish()
+    }
+}
+
+impl<T: ?Sized> Future for AsyncRwLockReadOwnedFuture<T> {
+    type Output = OwnedAsyncRwLockReadGuard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<OwnedAsyncRwLockReadGuard<T>> {
+        let this = &mut *self;
+        let rwlock = this.rwlock.as_ref().expect("AsyncRwLockReadOwnedFuture polled after completion");
+        match rwlock.poll_read(&mut this.key, &this.op, cx) {
+            Poll::Ready(()) => Poll::Ready(OwnedAsyncRwLockReadGuard::new(this.rwlock.take().unwrap())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockReadOwnedFuture<T> {
+    fn drop(&mut self) {
+        if let (Some(rwlock), Some(key)) = (&self.rwlock, self.key) {
+            rwlock.cancel_read(key);
+        }
+    }
+}
+
+/// A future which resolves to an `AsyncRwLockWriteGuard`.
+///
+/// This is created by `AsyncRwLock::write`.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncRwLockWriteFuture<'a, T: ?Sized + 'a> {
+    rwlock: &'a AsyncRwLock<T>,
+    key: Option<u64>,
+    op: AsyncOp,
+}
+
+impl<'a, T: ?Sized> fmt::Debug for AsyncRwLockWriteFuture<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncRwLockWriteFuture").field("queued", &self.key.is_some()).finish()
+    }
+}
+
+impl<'a, T: ?Sized> Future for AsyncRwLockWriteFuture<'a, T> {
+    type Output = AsyncRwLockWriteGuard<'a, T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<AsyncRwLockWriteGuard<'a, T>> {
+        let this = &mut *self;
+        let rwlock = this.rwlock;
+        match rwlock.poll_write(&mut this.key, &this.op, cx) {
+            Poll::Ready(()) => Poll::Ready(AsyncRwLockWriteGuard::new(rwlock)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncRwLockWriteFuture<'a, T> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.rwlock.cancel_write(key);
+        }
+    }
+}
+
+/// A future which resolves to an `OwnedAsyncRwLockWriteGuard`.
+///
+/// This is created by `AsyncRwLock::write_owned`.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncRwLockWriteOwnedFuture<T: ?Sized> {
+    // Taken when the future completes.
+    rwlock: Option<Arc<AsyncRwLock<T>>>,
+    key: Option<u64>,
+    op: AsyncOp,
+}
+
+impl<T: ?Sized> fmt::Debug for AsyncRwLockWriteOwnedFuture<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncRwLockWriteOwnedFuture").field("queued", &self.key.is_some()).finish()
+    }
+}
+
+impl<T: ?Sized> Future for AsyncRwLockWriteOwnedFuture<T> {
+    type Output = OwnedAsyncRwLockWriteGuard<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<OwnedAsyncRwLockWriteGuard<T>> {
+        let this = &mut *self;
+        let rwlock = this.rwlock.as_ref().expect("AsyncRwLockWriteOwnedFuture polled after completion");
+        match rwlock.poll_write(&mut this.key, &this.op, cx) {
+            Poll::Ready(()) => Poll::Ready(OwnedAsyncRwLockWriteGuard::new(this.rwlock.take().unwrap())),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T: ?Sized> Drop for AsyncRwLockWriteOwnedFuture<T> {
+    fn drop(&mut self) {
+        if let (Some(rwlock), Some(key)) = (&self.rwlock, self.key) {
+            rwlock.cancel_write(key);
+        }
+    }
+}
+
+/// An RAII guard granting shared access to an `AsyncRwLock`.
+#[must_use]
+pub struct AsyncRwLockReadGuard<'a, T: ?Sized + 'a> {
+    rwlock: &'a AsyncRwLock<T>,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T: ?Sized> AsyncRwLockReadGuard<'a, T> {
+    #[inline]
+    fn new(rwlock: &'a AsyncRwLock<T>) -> AsyncRwLockReadGuard<'a, T> {
+        rwlock.resource.reader_acquired();
+        AsyncRwLockReadGuard {
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for AsyncRwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "AsyncRwLockReadGuard", self.rwlock as *const AsyncRwLock<T> as *const () as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for AsyncRwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for AsyncRwLockReadGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncRwLockReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.rwlock.read_release()
+    }
+}
+
+/// An RAII guard granting exclusive access to an `AsyncRwLock`.
+#[must_use]
+pub struct AsyncRwLockWriteGuard<'a, T: ?Sized + 'a> {
+    rwlock: &'a AsyncRwLock<T>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T: ?Sized> AsyncRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn new(rwlock: &'a AsyncRwLock<T>) -> AsyncRwLockWriteGuard<'a, T> {
+        rwlock.resource.write_locked(true);
+        AsyncRwLockWriteGuard {
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for AsyncRwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "AsyncRwLockWriteGuard", self.rwlock as *const AsyncRwLock<T> as *const () as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for AsyncRwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for AsyncRwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for AsyncRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for AsyncRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.rwlock.write_release()
+    }
+}
+
+/// An RAII guard granting shared access to an `AsyncRwLock`, which keeps the
+/// lock alive through an `Arc`.
+///
+/// Unlike `AsyncRwLockReadGuard`, this is `'static` if `T` is.
+#[must_use]
+pub struct OwnedAsyncRwLockReadGuard<T: ?Sized> {
+    rwlock: Arc<AsyncRwLock<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ?Sized> OwnedAsyncRwLockReadGuard<T> {
+    #[inline]
+    fn new(rwlock: Arc<AsyncRwLock<T>>) -> OwnedAsyncRwLockReadGuard<T> {
+        rwlock.resource.reader_acquired();
+        OwnedAsyncRwLockReadGuard {
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(this: &OwnedAsyncRwLockReadGuard<T>) -> &Arc<AsyncRwLock<T>> {
+        &this.rwlock
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OwnedAsyncRwLockReadGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "OwnedAsyncRwLockReadGuard", &*self.rwlock as *const AsyncRwLock<T> as *const () as usize, &**self)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for OwnedAsyncRwLockReadGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized> Deref for OwnedAsyncRwLockReadGuard<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for OwnedAsyncRwLockReadGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.rwlock.read_release()
+    }
+}
+
+/// An RAII guard granting exclusive access to an `AsyncRwLock`, which keeps
+/// the lock alive through an `Arc`.
+///
+/// Unlike `AsyncRwLockWriteGuard`, this is `'static` if `T` is.
+#[must_use]
+pub struct OwnedAsyncRwLockWriteGuard<T: ?Sized> {
+    rwlock: Arc<AsyncRwLock<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ?Sized> OwnedAsyncRwLockWriteGuard<T> {
+    #[inline]
+    fn new(rwlock: Arc<AsyncRwLock<T>>) -> OwnedAsyncRwLockWriteGuard<T> {
+        rwlock.resource.write_locked(true);
+        OwnedAsyncRwLockWriteGuard {
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(this: &OwnedAsyncRwLockWriteGuard<T>) -> &Arc<AsyncRwLock<T>> {
+        &this.rwlock
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for OwnedAsyncRwLockWriteGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "OwnedAsyncRwLockWriteGuard", &*self.rwlock as *const AsyncRwLock<T> as *const () as usize, &**self)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for OwnedAsyncRwLockWriteGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized> Deref for OwnedAsyncRwLockWriteGuard<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for OwnedAsyncRwLockWriteGuard<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for OwnedAsyncRwLockWriteGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.rwlock.write_release()
+    }
+}
@@ -0,0 +1,438 @@
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use console::{AsyncOp, Resource};
+use wait_queue::WaitQueue;
+use {TryLockError, TryLockResult};
+
+/// An asynchronous counting semaphore, for limiting the concurrency of
+/// tasks.
+///
+/// Like `Semaphore`, permits can be acquired in any number at a time, and
+/// waiting tasks are served in FIFO order. Once `close`d, pending and future
+/// acquisitions fail with `TryLockError::Closed`, which lets tasks waiting
+/// for admission notice that a service is shutting down.
+///
+/// ```
+/// use antidote::{AsyncSemaphore, TryLockError};
+/// use std::future::Future;
+/// use std::pin::pin;
+/// use std::task::{Context, Poll, Waker};
+///
+/// let semaphore = AsyncSemaphore::new(1);
+/// let permit = semaphore.try_acquire().unwrap();
+///
+/// let mut cx = Context::from_waker(Waker::noop());
+/// let mut acquire = pin!(semaphore.acquire());
+/// assert!(acquire.as_mut().poll(&mut cx).is_pending());
+///
+/// semaphore.close();
+/// assert_eq!(acquire.poll(&mut cx).map(|r| r.err()), Poll::Ready(Some(TryLockError::Closed)));
+/// drop(permit);
+/// ```
+pub struct AsyncSemaphore {
+    permits: AtomicUsize,
+    closed: AtomicBool,
+    queue: WaitQueue,
+    resource: Resource,
+}
+
+impl fmt::Debug for AsyncSemaphore {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncSemaphore")
+            .field("permits", &self.available_permits())
+            .field("closed", &self.is_closed())
+            .finish()
+    }
+}
+
+impl AsyncSemaphore {
+    /// Creates a new `AsyncSemaphore` with `permits` permits available.
+    #[inline]
+    #[cfg_attr(feature = "tracing", track_caller)]
+    pub fn new(permits: usize) -> AsyncSemaphore {
+        AsyncSemaphore {
+            permits: AtomicUsize::new(permits),
+            closed: AtomicBool::new(false),
+            queue: WaitQueue::new(),
+            resource: Resource::new("AsyncSemaphore"),
+        }
+    }
+
+    /// Returns the number of permits currently available.
+    #[inline]
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::SeqCst)
+    }
+
+    /// Adds `n` permits to the semaphore.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of available permits overflows.
+    pub fn add_permits(&self, n: usize) {
+        let old = self.permits.fetch_add(n, Ordering::SeqCst);
+        assert!(old.checked_add(n).is_some(), "AsyncSemaphore permit count overflowed");
+        if self.queue.has_waiters() {
+            self.queue.lock().wake_front();
+        }
+    }
+
+    /// Closes the semaphore, failing every pending and future acquisition
+    /// with `TryLockError::Closed`.
+    ///
+    /// Permits which are already held are unaffected.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.queue.lock().wake_all();
+    }
+
+    /// Returns whether the semaphore has been closed.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Returns a future which resolves to a single permit once one is
+    /// available.
+    ///
+    /// Dropping the future before it resolves gives up its place in the
+    /// queue.
+    #[inline]
+    pub fn acquire<'a>(&'a self) -> AsyncSemaphoreAcquireFuture<'a> {
+        self.acquire_many(1)
+    }
+
+    /// Returns a future which resolves to `n` permits once they are
+    /// available.
+    ///
+    /// Until then, the future blocks acquisitions which arrive later.
+    #[inline]
+    pub fn acquire_many<'a>(&'a self, n: usize) -> AsyncSemaphoreAcquireFuture<'a> {
+        AsyncSemaphoreAcquireFuture {
+            semaphore: self,
+            n,
+            key: None,
+            op: AsyncOp::new(&self.resource, "AsyncSemaphore::acquire"),
+        }
+    }
+
+    /// Like `acquire`, but the permit holds a reference count on the
+    /// semaphore rather than borrowing it, so it can be moved into a spawned
+    /// task.
+    #[inline]
+    pub fn acquire_owned(self: Arc<Self>) -> AsyncSemaphoreAcquireOwnedFuture {
+        self.acquire_many_owned(1)
+    }
+
+    /// Like `acquire_many`, but returns an owned permit.
+    #[inline]
+    pub fn acquire_many_owned(self: Arc<Self>, n: usize) -> AsyncSemaphoreAcquireOwnedFuture {
+        let op = AsyncOp::new(&self.resource, "AsyncSemaphore::acquire_owned");
+        AsyncSemaphoreAcquireOwnedFuture {
+            semaphore: Some(self),
+            n,
+            key: None,
+            op,
+        }
+    }
+
+    /// Attempts to acquire a single permit without waiting.
+    #[inline]
+    pub fn try_acquire<'a>(&'a self) -> TryLockResult<AsyncSemaphorePermit<'a>> {
+        self.try_acquire_many(1)
+    }
+
+    /// Attempts to acquire `n` permits without waiting.
+    ///
+    /// This fails if other tasks are waiting to acquire permits, even if `n`
+    /// are available, so as not to overtake them.
+    pub fn try_acquire_many<'a>(&'a self, n: usize) -> TryLockResult<AsyncSemaphorePermit<'a>> {
+        self.try_acquire_fair(n)?;
+        Ok(AsyncSemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    /// Like `try_acquire`, but returns an owned permit.
+    #[inline]
+    pub fn try_acquire_owned(self: Arc<Self>) -> TryLockResult<OwnedAsyncSemaphorePermit> {
+        self.try_acquire_many_owned(1)
+    }
+
+    /// Like `try_acquire_many`, but returns an owned permit.
+    pub fn try_acquire_many_owned(self: Arc<Self>, n: usize) -> TryLockResult<OwnedAsyncSemaphorePermit> {
+        self.try_acquire_fair(n)?;
+        Ok(OwnedAsyncSemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+
+    fn try_acquire_fair(&self, n: usize) -> TryLockResult<()> {
+        if self.queue.has_waiters() {
+            if self.is_closed() {
+                return Err(TryLockError::Closed);
+            }
+            return Err(TryLockError::WouldBlock);
+        }
+        self.try_take(n)
+    }
+
+    // The body of the acquire futures' `poll`. `key` identifies the future
+    // in the wait queue once it has been queued.
+    fn poll_acquire(&self, n: usize, key: &mut Option<u64>, op: &AsyncOp, cx: &mut Context) -> Poll<TryLockResult<()>> {
+        op.poll(&self.resource, || self.poll_acquire_inner(n, key, cx))
+    }
+
+    fn poll_acquire_inner(&self, n: usize, key: &mut Option<u64>, cx: &mut Context) -> Poll<TryLockResult<()>> {
+        if key.is_none() {
+            match self.try_acquire_fair(n) {
+                Err(TryLockError::WouldBlock) => {}
+                r => return Poll::Ready(r),
+            }
+        }
+
+        let mut queue = self.queue.lock();
+        queue.register(key, cx.waker(), true);
+        // Only the waiter at the front of the queue may take permits, so
+        // that acquisitions are served in order.
+        let r = if queue.is_front(key.unwrap()) {
+            self.try_take(n)
+        } else if self.is_closed() {
+            Err(TryLockError::Closed)
+        } else {
+            Err(TryLockError::WouldBlock)
+        };
+        match r {
+            Err(TryLockError::WouldBlock) => Poll::Pending,
+            r => {
+                queue.remove(key.take().unwrap());
+                // The next waiter may be satisfied by the remaining permits.
+                if r.is_ok() && self.available_permits() != 0 {
+                    queue.wake_front();
+                }
+                Poll::Ready(r)
+            }
+        }
+    }
+
+    // Called when an acquire future is dropped before completing.
+    fn cancel(&self, key: u64) {
+        // If the future was woken, pass the wakeup on to the next waiter
+        // rather than losing it. If it was blocking the queue, the next
+        // waiter may now be able to proceed.
+        let mut queue = self.queue.lock();
+        if !queue.remove(key) || self.available_permits() != 0 {
+            queue.wake_front();
+        }
+    }
+
+    fn try_take(&self, n: usize) -> TryLockResult<()> {
+        let mut permits = self.permits.load(Ordering::SeqCst);
+        loop {
+            if self.is_closed() {
+                return Err(TryLockError::Closed);
+            }
+            if permits < n {
+                return Err(TryLockError::WouldBlock);
+            }
+            match self.permits.compare_exchange_weak(permits, permits - n, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return Ok(()),
+                Err(p) => permits = p,
+            }
+        }
+    }
+
+    #[inline]
+    fn release(&self, n: usize) {
+        if n != 0 {
+            self.add_permits(n);
+        }
+    }
+}
+
+/// A future which resolves to an `AsyncSemaphorePermit`, or to
+/// `TryLockError::Closed` if the semaphore is closed.
+///
+/// This is created by `AsyncSemaphore::acquire` and `acquire_many`.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncSemaphoreAcquireFuture<'a> {
+    semaphore: &'a AsyncSemaphore,
+    n: usize,
+    key: Option<u64>,
+    op: AsyncOp,
+}
+
+impl<'a> fmt::Debug for AsyncSemaphoreAcquireFuture<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncSemaphoreAcquireFuture")
+            .field("permits", &self.n)
+            .field("queued", &self.key.is_some())
+            .finish()
+    }
+}
+
+impl<'a> Future for AsyncSemaphoreAcquireFuture<'a> {
+    type Output = TryLockResult<AsyncSemaphorePermit<'a>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<TryLockResult<AsyncSemaphorePermit<'a>>> {
+        let this = &mut *self;
+        let semaphore = this.semaphore;
+        let n = this.n;
+        semaphore.poll_acquire(n, &mut this.key, &this.op, cx).map(|r| {
+            r.map(|()| {
+                AsyncSemaphorePermit {
+                    semaphore,
+                    permits: n,
+                }
+            })
+        })
+    }
+}
+
+impl<'a> Drop for AsyncSemaphoreAcquireFuture<'a> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.semaphore.cancel(key);
+        }
+    }
+}
+
+/// A future which resolves to an `OwnedAsyncSemaphorePermit`, or to
+/// `TryLockError::Closed` if the semaphore is closed.
+///
+/// This is created by `AsyncSemaphore::acquire_owned` and
+/// `acquire_many_owned`.
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncSemaphoreAcquireOwnedFuture {
+    // Taken when the future completes.
+    semaphore: Option<Arc<AsyncSemaphore>>,
+    n: usize,
+    key: Option<u64>,
+    op: AsyncOp,
+}
+
+impl fmt::Debug for AsyncSemaphoreAcquireOwnedFuture {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncSemaphoreAcquireOwnedFuture")
+            .field("permits", &self.n)
+            .field("queued", &self.key.is_some())
+            .finish()
+    }
+}
+
+impl Future for AsyncSemaphoreAcquireOwnedFuture {
+    type Output = TryLockResult<OwnedAsyncSemaphorePermit>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<TryLockResult<OwnedAsyncSemaphorePermit>> {
+        let this = &mut *self;
+        let semaphore = this.semaphore.as_ref().expect("AsyncSemaphoreAcquireOwnedFuture polled after completion");
+        let n = this.n;
+        match semaphore.poll_acquire(n, &mut this.key, &this.op, cx) {
+            Poll::Ready(Ok(())) => {
+                Poll::Ready(Ok(OwnedAsyncSemaphorePermit {
+                    semaphore: this.semaphore.take().unwrap(),
+                    permits: n,
+                }))
+            }
+            Poll::Ready(Err(e)) => {
+                this.semaphore = None;
+                Poll::Ready(Err(e))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for AsyncSemaphoreAcquireOwnedFuture {
+    fn drop(&mut self) {
+        if let (Some(semaphore), Some(key)) = (&self.semaphore, self.key) {
+            semaphore.cancel(key);
+        }
+    }
+}
+
+/// Permits acquired from an `AsyncSemaphore`, which are returned to it when
+/// this is dropped.
+#[must_use]
+pub struct AsyncSemaphorePermit<'a> {
+    semaphore: &'a AsyncSemaphore,
+    permits: usize,
+}
+
+impl<'a> fmt::Debug for AsyncSemaphorePermit<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AsyncSemaphorePermit").field("permits", &self.permits).finish()
+    }
+}
+
+impl<'a> AsyncSemaphorePermit<'a> {
+    /// Returns the number of permits held.
+    #[inline]
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Like `SemaphorePermit::forget`.
+    #[inline]
+    pub fn forget(mut self) {
+        mem::take(&mut self.permits);
+    }
+}
+
+impl<'a> Drop for AsyncSemaphorePermit<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        self.semaphore.release(self.permits);
+    }
+}
+
+/// Permits acquired from an `AsyncSemaphore` which keep it alive through an
+/// `Arc`.
+///
+/// Unlike `AsyncSemaphorePermit`, this is `'static`.
+#[must_use]
+pub struct OwnedAsyncSemaphorePermit {
+    semaphore: Arc<AsyncSemaphore>,
+    permits: usize,
+}
+
+impl fmt::Debug for OwnedAsyncSemaphorePermit {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("OwnedAsyncSemaphorePermit").field("permits", &self.permits).finish()
+    }
+}
+
+impl OwnedAsyncSemaphorePermit {
+    /// Returns the number of permits held.
+    #[inline]
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Returns the semaphore the permits were acquired from.
+    #[inline]
+    pub fn semaphore(&self) -> &Arc<AsyncSemaphore> {
+        &self.semaphore
+    }
+
+    /// Like `SemaphorePermit::forget`.
+    #[inline]
+    pub fn forget(mut self) {
+        mem::take(&mut self.permits);
+    }
+}
+
+impl Drop for OwnedAsyncSemaphorePermit {
+    #[inline]
+    fn drop(&mut self) {
+        self.semaphore.release(self.permits);
+    }
+}
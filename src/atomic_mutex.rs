@@ -0,0 +1,192 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use Mutex;
+
+/// A `Copy` type which can be stored in an `AtomicMutex`.
+///
+/// # Safety
+///
+/// The type must not contain padding bytes or any other uninitialized
+/// memory, since its bytes are copied into an atomic integer. Types with
+/// padding, like most tuples and many structs, can be wrapped in a
+/// `Mutex` instead.
+pub unsafe trait Plain: Copy {}
+
+macro_rules! impl_plain {
+    ($($t:ty),*) => {
+        $(
+            unsafe impl Plain for $t {}
+        )*
+    };
+}
+
+impl_plain!((), bool, char, u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: ?Sized> Plain for *const T {}
+unsafe impl<T: ?Sized> Plain for *mut T {}
+
+// Array elements are never separated by padding, since a type's size is a
+// multiple of its alignment.
+unsafe impl<T: Plain, const N: usize> Plain for [T; N] {}
+
+/// A cell for a small `Copy` value, which is lock-free when the value fits
+/// in a machine word.
+///
+/// Values no larger than a `usize` are stored in an `AtomicUsize`, and
+/// larger ones behind a `Mutex`, with the same API either way.
+///
+/// ```
+/// use antidote::{AtomicMutex, Plain};
+///
+/// #[derive(Debug, Copy, Clone, PartialEq)]
+/// #[repr(C)]
+/// struct Position {
+///     x: i16,
+///     y: i16,
+/// }
+///
+/// unsafe impl Plain for Position {}
+///
+/// let pos = AtomicMutex::new(Position { x: 0, y: 0 });
+/// assert!(AtomicMutex::<Position>::is_lock_free());
+/// pos.update(|p| Position { x: p.x + 1, ..p });
+/// assert_eq!(pos.load(), Position { x: 1, y: 0 });
+/// ```
+pub struct AtomicMutex<T: Plain> {
+    inner: Inner<T>,
+}
+
+enum Inner<T> {
+    Atomic(AtomicUsize, PhantomData<T>),
+    Locked(Mutex<T>),
+}
+
+// Like `Mutex`, sharing the cell only moves values of `T` between threads.
+unsafe impl<T: Plain + Send> Send for AtomicMutex<T> {}
+unsafe impl<T: Plain + Send> Sync for AtomicMutex<T> {}
+
+impl<T: Plain + fmt::Debug> fmt::Debug for AtomicMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("AtomicMutex").field("value", &self.load()).finish()
+    }
+}
+
+impl<T: Plain + Default> Default for AtomicMutex<T> {
+    fn default() -> AtomicMutex<T> {
+        AtomicMutex::new(T::default())
+    }
+}
+
+impl<T: Plain> From<T> for AtomicMutex<T> {
+    fn from(t: T) -> AtomicMutex<T> {
+        AtomicMutex::new(t)
+    }
+}
+
+impl<T: Plain> AtomicMutex<T> {
+    /// Creates a new `AtomicMutex`.
+    #[inline]
+    pub fn new(t: T) -> AtomicMutex<T> {
+        let inner = if AtomicMutex::<T>::is_lock_free() {
+            Inner::Atomic(AtomicUsize::new(encode(t)), PhantomData)
+        } else {
+            Inner::Locked(Mutex::new(t))
+        };
+        AtomicMutex { inner }
+    }
+
+    /// Returns whether values of type `T` are stored in an atomic rather
+    /// than behind a mutex.
+    #[inline]
+    pub const fn is_lock_free() -> bool {
+        mem::size_of::<T>() <= mem::size_of::<usize>()
+    }
+
+    /// Returns the value.
+    #[inline]
+    pub fn load(&self) -> T {
+        match self.inner {
+            Inner::Atomic(ref a, _) => unsafe { decode(a.load(Ordering::Acquire)) },
+            Inner::Locked(ref m) => *m.lock(),
+        }
+    }
+
+    /// Replaces the value.
+    #[inline]
+    pub fn store(&self, t: T) {
+        match self.inner {
+            Inner::Atomic(ref a, _) => a.store(encode(t), Ordering::Release),
+            Inner::Locked(ref m) => *m.lock() = t,
+        }
+    }
+
+    /// Replaces the value, returning the old one.
+    #[inline]
+    pub fn swap(&self, t: T) -> T {
+        match self.inner {
+            Inner::Atomic(ref a, _) => unsafe { decode(a.swap(encode(t), Ordering::AcqRel)) },
+            Inner::Locked(ref m) => mem::replace(&mut *m.lock(), t),
+        }
+    }
+
+    /// Replaces the value with the result of applying `f` to it, returning
+    /// the old value.
+    ///
+    /// When the value is stored in an atomic, `f` may be called several
+    /// times if other threads change the value concurrently.
+    #[inline]
+    pub fn update<F>(&self, mut f: F) -> T
+        where F: FnMut(T) -> T
+    {
+        match self.inner {
+            Inner::Atomic(ref a, _) => {
+                let mut bits = a.load(Ordering::Acquire);
+                loop {
+                    let old = unsafe { decode(bits) };
+                    match a.compare_exchange_weak(bits, encode(f(old)), Ordering::AcqRel, Ordering::Acquire) {
+                        Ok(_) => return old,
+                        Err(b) => bits = b,
+                    }
+                }
+            }
+            Inner::Locked(ref m) => {
+                let mut guard = m.lock();
+                let old = *guard;
+                *guard = f(old);
+                old
+            }
+        }
+    }
+
+    /// Consumes the cell, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        match self.inner {
+            Inner::Atomic(a, _) => unsafe { decode(a.into_inner()) },
+            Inner::Locked(m) => m.into_inner(),
+        }
+    }
+}
+
+// Copies a value into the low-addressed bytes of a zeroed word. Equal values
+// always encode identically, since `Plain` types have no padding.
+#[inline]
+fn encode<T: Plain>(t: T) -> usize {
+    let mut bits = 0usize;
+    unsafe {
+        ptr::copy_nonoverlapping(&t as *const T as *const u8,
+                                 &mut bits as *mut usize as *mut u8,
+                                 mem::size_of::<T>());
+    }
+    bits
+}
+
+// The inverse of `encode`. The bits must have been produced by it.
+#[inline]
+unsafe fn decode<T: Plain>(bits: usize) -> T {
+    ptr::read_unaligned(&bits as *const usize as *const T)
+}
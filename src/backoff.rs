@@ -0,0 +1,153 @@
+//! Tuning of the contended path of the blocking locks.
+//!
+//! A thread which fails to acquire a lock backs off for a while before
+//! parking, in the hope that the holder releases it soon. How long that is
+//! worthwhile depends on the machine: the default heuristics assume a server
+//! with cores to spare, while on a machine with only one or two cores
+//! spinning mostly burns time the lock holder could have used.
+//!
+//! The strategy can be replaced for the whole process with `set_default`, or
+//! for an individual lock with constructors like `Mutex::new_with_backoff`.
+//! A per-lock strategy is stored out of line, along with the lock's other
+//! optional configuration, so locks using the process-wide strategy don't
+//! grow to make room for it.
+
+use std::hint;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// A strategy for waiting between attempts to acquire a contended lock.
+pub trait Backoff: Sync {
+    /// Waits before the next attempt to acquire a lock.
+    ///
+    /// `round` counts the attempts which have failed so far, starting at 0.
+    /// Returns `false`, without waiting, when the thread should stop backing
+    /// off and park until the lock is released instead.
+    fn backoff(&self, round: u32) -> bool;
+}
+
+/// A strategy which spins, then yields, then sleeps.
+///
+/// This is the default strategy, with the parameters returned by `new`.
+///
+/// Each phase lasts a configurable number of rounds. Spinning starts at two
+/// iterations and doubles each round, and sleeping starts at the configured
+/// duration and doubles each round. A phase with no rounds is skipped.
+///
+/// The methods are `const` so that a strategy can be stored in a `static`:
+///
+/// ```
+/// use antidote::backoff::ExponentialBackoff;
+/// use antidote::Mutex;
+///
+/// // Don't spin on a single core machine.
+/// static BACKOFF: ExponentialBackoff = ExponentialBackoff::new().spin_rounds(0).yield_rounds(2);
+///
+/// let mutex = Mutex::new_with_backoff(0, &BACKOFF);
+/// *mutex.lock() += 1;
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct ExponentialBackoff {
+    spin_rounds: u32,
+    yield_rounds: u32,
+    sleep_rounds: u32,
+    sleep: Duration,
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> ExponentialBackoff {
+        ExponentialBackoff::new()
+    }
+}
+
+impl ExponentialBackoff {
+    /// Returns the default strategy: six rounds of spinning, followed by four
+    /// of yielding, and no sleeping.
+    #[inline]
+    pub const fn new() -> ExponentialBackoff {
+        ExponentialBackoff {
+            spin_rounds: 6,
+            yield_rounds: 4,
+            sleep_rounds: 0,
+            sleep: Duration::from_micros(0),
+        }
+    }
+
+    /// Sets the number of rounds of spinning.
+    ///
+    /// The number of spin iterations is capped at 2^30.
+    #[inline]
+    pub const fn spin_rounds(mut self, rounds: u32) -> ExponentialBackoff {
+        self.spin_rounds = rounds;
+        self
+    }
+
+    /// Sets the number of rounds of yielding the thread's time slice.
+    #[inline]
+    pub const fn yield_rounds(mut self, rounds: u32) -> ExponentialBackoff {
+        self.yield_rounds = rounds;
+        self
+    }
+
+    /// Sets the number of rounds of sleeping, and the duration of the first.
+    #[inline]
+    pub const fn sleep_rounds(mut self, rounds: u32, first: Duration) -> ExponentialBackoff {
+        self.sleep_rounds = rounds;
+        self.sleep = first;
+        self
+    }
+}
+
+impl Backoff for ExponentialBackoff {
+    #[inline]
+    fn backoff(&self, round: u32) -> bool {
+        if round < self.spin_rounds {
+            for _ in 0..1u32 << (round + 1).min(30) {
+                hint::spin_loop();
+            }
+            return true;
+        }
+
+        let round = round - self.spin_rounds;
+        if round < self.yield_rounds {
+            thread::yield_now();
+            return true;
+        }
+
+        let round = round - self.yield_rounds;
+        if round < self.sleep_rounds {
+            thread::sleep(self.sleep.saturating_mul(1 << round.min(16)));
+            return true;
+        }
+
+        false
+    }
+}
+
+static INITIAL: ExponentialBackoff = ExponentialBackoff::new();
+
+// The process-wide strategy, or null for `INITIAL`. Points to a leaked
+// box, as a `dyn` reference is too wide for an atomic.
+static DEFAULT: AtomicPtr<&'static dyn Backoff> = AtomicPtr::new(ptr::null_mut());
+
+/// Sets the strategy used by locks which were not given one of their own.
+///
+/// This affects locks which are already in use as well as those created
+/// afterwards.
+pub fn set_default(backoff: &'static dyn Backoff) {
+    // The previous strategy may be in use by a blocked thread, so it is
+    // leaked rather than freed. It's a single pointer.
+    DEFAULT.store(Box::into_raw(Box::new(backoff)), Ordering::Release);
+}
+
+pub(crate) fn default() -> &'static dyn Backoff {
+    let ptr = DEFAULT.load(Ordering::Acquire);
+    if ptr.is_null() {
+        &INITIAL
+    } else {
+        unsafe { *ptr }
+    }
+}
+
@@ -0,0 +1,171 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use {Condvar, deadline_after, Mutex};
+
+/// A barrier which lets a group of threads wait for each other, and which
+/// breaks rather than hanging when one of them doesn't arrive in time.
+///
+/// Like `std::sync::Barrier`, except that a thread waiting with a timeout
+/// which expires breaks the barrier, releasing every thread waiting on it
+/// with an error. A broken barrier stays broken, failing every subsequent
+/// wait, until it is `reset`.
+///
+/// ```
+/// use antidote::{Barrier, BarrierError};
+/// use std::time::Duration;
+///
+/// // The second participant never arrives.
+/// let barrier = Barrier::new(2);
+/// assert_eq!(barrier.wait_timeout(Duration::from_millis(10)).unwrap_err(), BarrierError::Timeout);
+/// assert_eq!(barrier.wait().unwrap_err(), BarrierError::Broken);
+/// ```
+pub struct Barrier {
+    state: Mutex<State>,
+    cond: Condvar,
+    n: usize,
+}
+
+struct State {
+    count: usize,
+    // Set when the current generation is broken. Each waiter holds on to its
+    // generation's flag, so it can tell whether that generation was broken
+    // even after the barrier has moved on to the next one.
+    generation: Arc<AtomicBool>,
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock();
+        fmt.debug_struct("Barrier")
+            .field("n", &self.n)
+            .field("count", &state.count)
+            .field("broken", &state.generation.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+impl Barrier {
+    /// Creates a new barrier which releases waiting threads once `n` of them
+    /// are waiting.
+    ///
+    /// As with `std::sync::Barrier`, a barrier for 0 threads behaves like one
+    /// for 1.
+    #[inline]
+    pub fn new(n: usize) -> Barrier {
+        Barrier {
+            state: Mutex::new(State {
+                count: 0,
+                generation: Arc::new(AtomicBool::new(false)),
+            }),
+            cond: Condvar::new(),
+            n,
+        }
+    }
+
+    /// Blocks the current thread until `n` threads are waiting.
+    ///
+    /// Returns `BarrierError::Broken` if the barrier is or becomes broken.
+    pub fn wait(&self) -> Result<BarrierWaitResult, BarrierError> {
+        self.wait_inner(None)
+    }
+
+    /// Like `wait`, but breaks the barrier if the other threads haven't
+    /// arrived after `timeout`, returning `BarrierError::Timeout`.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<BarrierWaitResult, BarrierError> {
+        self.wait_inner(deadline_after(timeout))
+    }
+
+    /// Like `wait`, but breaks the barrier if the other threads haven't
+    /// arrived by `deadline`, returning `BarrierError::Timeout`.
+    pub fn wait_until(&self, deadline: Instant) -> Result<BarrierWaitResult, BarrierError> {
+        self.wait_inner(Some(deadline))
+    }
+
+    fn wait_inner(&self, deadline: Option<Instant>) -> Result<BarrierWaitResult, BarrierError> {
+        let mut state = self.state.lock();
+        let generation = state.generation.clone();
+        if generation.load(Ordering::Relaxed) {
+            return Err(BarrierError::Broken);
+        }
+
+        state.count += 1;
+        if state.count >= self.n {
+            state.count = 0;
+            state.generation = Arc::new(AtomicBool::new(false));
+            self.cond.notify_all();
+            return Ok(BarrierWaitResult(true));
+        }
+
+        loop {
+            state = match deadline {
+                Some(deadline) => self.cond.wait_until(state, deadline).0,
+                None => self.cond.wait(state),
+            };
+            if generation.load(Ordering::Relaxed) {
+                return Err(BarrierError::Broken);
+            }
+            if !Arc::ptr_eq(&generation, &state.generation) {
+                return Ok(BarrierWaitResult(false));
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                generation.store(true, Ordering::Relaxed);
+                self.cond.notify_all();
+                return Err(BarrierError::Timeout);
+            }
+        }
+    }
+
+    /// Returns whether the barrier is broken.
+    pub fn is_broken(&self) -> bool {
+        self.state.lock().generation.load(Ordering::Relaxed)
+    }
+
+    /// Resets the barrier to its initial state.
+    ///
+    /// Threads currently waiting on the barrier are released with
+    /// `BarrierError::Broken`.
+    pub fn reset(&self) {
+        let mut state = self.state.lock();
+        state.generation.store(true, Ordering::Relaxed);
+        state.count = 0;
+        state.generation = Arc::new(AtomicBool::new(false));
+        self.cond.notify_all();
+    }
+}
+
+/// Like `std::sync::BarrierWaitResult`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Like `std::sync::BarrierWaitResult::is_leader`.
+    #[inline]
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+/// The error returned by a failed wait on a `Barrier`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BarrierError {
+    /// The wait timed out, breaking the barrier.
+    Timeout,
+    /// The barrier was broken, by another thread's wait timing out or by
+    /// `reset`.
+    Broken,
+}
+
+impl fmt::Display for BarrierError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BarrierError::Timeout => fmt.write_str("barrier wait timed out"),
+            BarrierError::Broken => fmt.write_str("barrier is broken"),
+        }
+    }
+}
+
+impl Error for BarrierError {}
@@ -0,0 +1,366 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Instant;
+
+use held::{self, Mode};
+use raw::{RawRwLock, SpinWait};
+#[cfg(feature = "testing")]
+use testing::deterministic;
+use {fmt_guard, GuardMarker, RwLockPolicy, TryLockError, TryLockResult};
+
+// The visible readers table shared by all biased locks. A fast-path reader
+// publishes the address of the lock it holds in a slot chosen by hashing its
+// thread and the lock, rather than modifying the lock itself.
+const SLOTS: usize = 4096;
+
+static VISIBLE_READERS: [AtomicUsize; SLOTS] = [const { AtomicUsize::new(0) }; SLOTS];
+
+// How many times longer than a revocation took to wait before biasing the
+// lock towards readers again, bounding the time writers spend revoking.
+const INHIBIT_MULTIPLIER: u64 = 9;
+
+thread_local! {
+    // Only the address of this is used, as a cheap thread identifier.
+    static THREAD_KEY: u8 = const { 0 };
+}
+
+fn slot(addr: usize) -> &'static AtomicUsize {
+    let thread = THREAD_KEY.with(|k| k as *const u8 as usize);
+    let hash = ((thread ^ addr) as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    &VISIBLE_READERS[(hash >> 52) as usize % SLOTS]
+}
+
+fn now_nanos() -> u64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+/// A reader-writer lock biased towards readers, using the BRAVO algorithm.
+///
+/// With `RwLock`, every reader modifies the same atomic counter, so on
+/// machines with many cores read acquisitions of a read-mostly lock contend
+/// on a single cache line. While this lock is biased, readers instead
+/// announce themselves in a large table shared by all biased locks, and
+/// never write to the lock at all.
+///
+/// When a writer arrives the bias is revoked: the writer takes the
+/// underlying lock and waits for the readers in the table to leave, after
+/// which readers go through the underlying lock until the bias is restored.
+/// Revocation is expensive, so the bias is only restored after a period
+/// proportional to how long the last revocation took. This makes the lock
+/// well suited to read-mostly data, and a poor choice for data which is
+/// written frequently.
+pub struct BiasedRwLock<T: ?Sized> {
+    raw: RawRwLock,
+    rbias: AtomicBool,
+    // The time, in nanoseconds since an arbitrary epoch, before which the
+    // bias won't be restored.
+    inhibit_until: AtomicU64,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for BiasedRwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for BiasedRwLock<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for BiasedRwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Ok(guard) => fmt.debug_struct("BiasedRwLock").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("BiasedRwLock").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> BiasedRwLock<T> {
+    /// Creates a new reader-biased lock.
+    #[inline]
+    pub fn new(t: T) -> BiasedRwLock<T> {
+        BiasedRwLock {
+            raw: RawRwLock::new(RwLockPolicy::PreferWriters),
+            rbias: AtomicBool::new(true),
+            inhibit_until: AtomicU64::new(0),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `RwLock::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> BiasedRwLock<T> {
+    /// Like `RwLock::read`.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// lock for writing rather than deadlocking.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read<'a>(&'a self) -> BiasedRwLockReadGuard<'a, T> {
+        held::check("BiasedRwLock", None, self.addr(), Mode::Shared);
+        let slot = match self.try_read_fast() {
+            Some(slot) => Some(slot),
+            None => {
+                self.raw.read();
+                self.maybe_restore_bias();
+                None
+            }
+        };
+        held::acquired("BiasedRwLock", None, self.addr(), Mode::Shared);
+        BiasedRwLockReadGuard::new(self, slot)
+    }
+
+    /// Like `RwLock::try_read`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_read<'a>(&'a self) -> TryLockResult<BiasedRwLockReadGuard<'a, T>> {
+        let slot = match self.try_read_fast() {
+            Some(slot) => Some(slot),
+            None if self.raw.try_read() => None,
+            None => return Err(TryLockError::WouldBlock),
+        };
+        held::acquired("BiasedRwLock", None, self.addr(), Mode::Shared);
+        Ok(BiasedRwLockReadGuard::new(self, slot))
+    }
+
+    /// Like `RwLock::write`.
+    ///
+    /// If the lock is biased towards readers, this revokes the bias and waits
+    /// for all readers to leave.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// lock rather than deadlocking.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn write<'a>(&'a self) -> BiasedRwLockWriteGuard<'a, T> {
+        held::check("BiasedRwLock", None, self.addr(), Mode::Exclusive);
+        self.raw.write();
+        self.revoke_bias();
+        held::acquired("BiasedRwLock", None, self.addr(), Mode::Exclusive);
+        BiasedRwLockWriteGuard::new(self)
+    }
+
+    /// Like `RwLock::try_write`.
+    ///
+    /// If the lock is biased towards readers, this scans the visible readers
+    /// table, which is much slower than `RwLock::try_write`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_write<'a>(&'a self) -> TryLockResult<BiasedRwLockWriteGuard<'a, T>> {
+        if !self.raw.try_write() {
+            return Err(TryLockError::WouldBlock);
+        }
+        if !self.try_revoke_bias() {
+            unsafe { self.raw.write_unlock() };
+            return Err(TryLockError::WouldBlock);
+        }
+        held::acquired("BiasedRwLock", None, self.addr(), Mode::Exclusive);
+        Ok(BiasedRwLockWriteGuard::new(self))
+    }
+
+    /// Like `RwLock::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    #[inline]
+    fn try_read_fast(&self) -> Option<&'static AtomicUsize> {
+        if !self.rbias.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let slot = slot(self.addr());
+        if slot.compare_exchange(0, self.addr(), Ordering::SeqCst, Ordering::Relaxed).is_err() {
+            return None;
+        }
+        // A writer clears the bias before scanning the table, so if it is
+        // still set the writer will wait for this slot to be cleared.
+        if self.rbias.load(Ordering::SeqCst) {
+            Some(slot)
+        } else {
+            slot.store(0, Ordering::Relaxed);
+            None
+        }
+    }
+
+    // Called with the underlying lock write-locked. Fails, leaving the lock
+    // biased, if a fast-path reader holds it.
+    fn try_revoke_bias(&self) -> bool {
+        if !self.rbias.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        self.rbias.store(false, Ordering::SeqCst);
+        let addr = self.addr();
+        if VISIBLE_READERS.iter().any(|slot| slot.load(Ordering::SeqCst) == addr) {
+            self.rbias.store(true, Ordering::Relaxed);
+            false
+        } else {
+            true
+        }
+    }
+
+    fn maybe_restore_bias(&self) {
+        if !self.rbias.load(Ordering::Relaxed) && now_nanos() >= self.inhibit_until.load(Ordering::Relaxed) {
+            self.rbias.store(true, Ordering::Release);
+        }
+    }
+
+    // Called with the underlying lock write-locked.
+    fn revoke_bias(&self) {
+        if !self.rbias.load(Ordering::Relaxed) {
+            return;
+        }
+
+        self.rbias.store(false, Ordering::SeqCst);
+        let start = now_nanos();
+        let addr = self.addr();
+        for slot in VISIBLE_READERS.iter() {
+            let mut spin = SpinWait::new();
+            while slot.load(Ordering::SeqCst) == addr {
+                #[cfg(feature = "testing")]
+                {
+                    if deterministic::is_managed() {
+                        deterministic::yield_now();
+                        continue;
+                    }
+                }
+
+                if !spin.spin() {
+                    thread::yield_now();
+                }
+            }
+        }
+        let now = now_nanos();
+        self.inhibit_until.store(now + (now - start) * INHIBIT_MULTIPLIER, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn addr(&self) -> usize {
+        &self.raw as *const RawRwLock as usize
+    }
+}
+
+impl<T: Default> Default for BiasedRwLock<T> {
+    fn default() -> Self {
+        BiasedRwLock::new(Default::default())
+    }
+}
+
+/// An RAII guard for shared read access to a `BiasedRwLock`.
+#[must_use]
+pub struct BiasedRwLockReadGuard<'a, T: ?Sized + 'a> {
+    rwlock: &'a BiasedRwLock<T>,
+    // The visible readers slot, if the lock was acquired on the fast path.
+    slot: Option<&'static AtomicUsize>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for BiasedRwLockReadGuard<'a, T> {}
+
+impl<'a, T: ?Sized> BiasedRwLockReadGuard<'a, T> {
+    #[inline]
+    fn new(rwlock: &'a BiasedRwLock<T>, slot: Option<&'static AtomicUsize>) -> BiasedRwLockReadGuard<'a, T> {
+        BiasedRwLockReadGuard {
+            rwlock,
+            slot,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for BiasedRwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "BiasedRwLockReadGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for BiasedRwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for BiasedRwLockReadGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for BiasedRwLockReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.rwlock.addr(), Mode::Shared);
+        match self.slot {
+            Some(slot) => slot.store(0, Ordering::Release),
+            None => unsafe { self.rwlock.raw.read_unlock() },
+        }
+        held::check_released(owned);
+    }
+}
+
+/// An RAII guard for exclusive write access to a `BiasedRwLock`.
+#[must_use]
+pub struct BiasedRwLockWriteGuard<'a, T: ?Sized + 'a> {
+    rwlock: &'a BiasedRwLock<T>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for BiasedRwLockWriteGuard<'a, T> {}
+
+impl<'a, T: ?Sized> BiasedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn new(rwlock: &'a BiasedRwLock<T>) -> BiasedRwLockWriteGuard<'a, T> {
+        BiasedRwLockWriteGuard {
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for BiasedRwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "BiasedRwLockWriteGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for BiasedRwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for BiasedRwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for BiasedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for BiasedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.rwlock.addr(), Mode::Exclusive);
+        unsafe { self.rwlock.raw.write_unlock() };
+        held::check_released(owned);
+    }
+}
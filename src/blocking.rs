@@ -0,0 +1,107 @@
+//! Acquisition of the blocking locks from async code.
+//!
+//! Rather than blocking an executor thread, the acquisition runs as a task on
+//! a blocking thread pool supplied by the caller, and the resulting guard is
+//! handed back to the waiting future.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use Mutex;
+
+/// A blocking task, as passed to the spawner given to methods like
+/// `Mutex::lock_async_via`.
+pub type BlockingTask = Box<dyn FnOnce() + Send + 'static>;
+
+struct State<G> {
+    guard: Option<G>,
+    waker: Option<Waker>,
+    // Set if the task was dropped without sending a guard.
+    abandoned: bool,
+}
+
+struct Handoff<G> {
+    state: Mutex<State<G>>,
+}
+
+pub(crate) fn handoff<G>() -> (Sender<G>, BlockingLockFuture<G>) {
+    let handoff = Arc::new(Handoff {
+        state: Mutex::new(State {
+            guard: None,
+            waker: None,
+            abandoned: false,
+        }),
+    });
+    (Sender(Some(handoff.clone())), BlockingLockFuture { handoff })
+}
+
+// Held by the blocking task. If the task is dropped without running, the
+// future is told rather than left waiting forever.
+pub(crate) struct Sender<G>(Option<Arc<Handoff<G>>>);
+
+impl<G> Sender<G> {
+    pub(crate) fn send(mut self, guard: G) {
+        let handoff = self.0.take().unwrap();
+        let waker = {
+            let mut state = handoff.state.lock();
+            state.guard = Some(guard);
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<G> Drop for Sender<G> {
+    fn drop(&mut self) {
+        if let Some(handoff) = self.0.take() {
+            let waker = {
+                let mut state = handoff.state.lock();
+                state.abandoned = true;
+                state.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// A future which resolves to a guard acquired on a blocking thread pool.
+///
+/// This is created by `Mutex::lock_async_via`. If the future is dropped
+/// before the lock is acquired, the guard is released as soon as the blocking
+/// task acquires it.
+#[must_use = "futures do nothing unless polled"]
+pub struct BlockingLockFuture<G> {
+    handoff: Arc<Handoff<G>>,
+}
+
+impl<G> fmt::Debug for BlockingLockFuture<G> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BlockingLockFuture").finish()
+    }
+}
+
+impl<G> Future for BlockingLockFuture<G> {
+    type Output = G;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<G> {
+        let mut state = self.handoff.state.lock();
+        if let Some(guard) = state.guard.take() {
+            return Poll::Ready(guard);
+        }
+        if state.abandoned {
+            panic!("the blocking task was dropped without being run");
+        }
+        match state.waker {
+            Some(ref waker) if waker.will_wake(cx.waker()) => {}
+            _ => state.waker = Some(cx.waker().clone()),
+        }
+        Poll::Pending
+    }
+}
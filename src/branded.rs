@@ -0,0 +1,191 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+
+use {Mutex, MutexGuard};
+
+// Ties mutexes, tokens and cells to a single call of `BrandedMutex::with`.
+// Being invariant, one brand can't be converted into another.
+type Brand<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// A mutex which protects any number of `BrandCell`s, all accessed through
+/// the single token its lock returns.
+///
+/// Each mutex has a unique brand, a lifetime which only exists inside the
+/// closure passed to `BrandedMutex::with`. Cells of the same brand can only
+/// be accessed with that mutex's token, so the lock is taken once for a
+/// whole structure rather than once per cell, and the cells themselves cost
+/// nothing beyond their values.
+///
+/// ```
+/// use antidote::{BrandCell, BrandedMutex};
+/// use std::thread;
+///
+/// BrandedMutex::with(|mutex| {
+///     let nodes = (0..100).map(BrandCell::new).collect::<Vec<_>>();
+///
+///     thread::scope(|s| {
+///         for _ in 0..4 {
+///             s.spawn(|| {
+///                 let mut token = mutex.lock();
+///                 for node in &nodes {
+///                     *node.borrow_mut(&mut token) += 1;
+///                 }
+///             });
+///         }
+///     });
+///
+///     let token = mutex.lock();
+///     assert_eq!(*nodes[0].borrow(&token), 4);
+/// });
+/// ```
+///
+/// Cells can't be accessed with another mutex's token:
+///
+/// ```compile_fail
+/// use antidote::{BrandCell, BrandedMutex};
+///
+/// BrandedMutex::with(|a| {
+///     BrandedMutex::with(|b| {
+///         let cell = BrandCell::new(0);
+///         let _ = cell.borrow(&a.lock());
+///         let _ = cell.borrow(&b.lock());
+///     });
+/// });
+/// ```
+pub struct BrandedMutex<'brand> {
+    mutex: Mutex<()>,
+    _brand: Brand<'brand>,
+}
+
+impl<'brand> fmt::Debug for BrandedMutex<'brand> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BrandedMutex").finish_non_exhaustive()
+    }
+}
+
+impl<'brand> BrandedMutex<'brand> {
+    /// Creates a mutex with a new brand, and passes it to `f`.
+    ///
+    /// The brand only exists for the duration of `f`, so cells must be
+    /// created and accessed within it.
+    #[inline]
+    pub fn with<F, R>(f: F) -> R
+        where F: for<'new> FnOnce(BrandedMutex<'new>) -> R
+    {
+        f(BrandedMutex {
+            mutex: Mutex::new(()),
+            _brand: PhantomData,
+        })
+    }
+
+    /// Acquires the mutex, returning a token for accessing its cells.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// lock rather than deadlocking.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock<'a>(&'a self) -> BrandToken<'a, 'brand> {
+        BrandToken {
+            _guard: self.mutex.lock(),
+            _brand: PhantomData,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Attempts to acquire the mutex without blocking.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock<'a>(&'a self) -> Option<BrandToken<'a, 'brand>> {
+        self.mutex.try_lock().ok().map(|guard| {
+            BrandToken {
+                _guard: guard,
+                _brand: PhantomData,
+                _marker: PhantomData,
+            }
+        })
+    }
+}
+
+/// Access to the cells of a `BrandedMutex`, which holds the mutex locked
+/// until it is dropped.
+///
+/// This is created by `BrandedMutex::lock`. Unlike other guards, it is
+/// never `Send` or `Sync`, since it grants access to values which may not
+/// be.
+#[must_use]
+pub struct BrandToken<'a, 'brand> {
+    _guard: MutexGuard<'a, ()>,
+    _brand: Brand<'brand>,
+    _marker: PhantomData<*mut ()>,
+}
+
+impl<'a, 'brand> fmt::Debug for BrandToken<'a, 'brand> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BrandToken").finish_non_exhaustive()
+    }
+}
+
+/// A value protected by the `BrandedMutex` of the same brand.
+pub struct BrandCell<'brand, T: ?Sized> {
+    _brand: Brand<'brand>,
+    value: UnsafeCell<T>,
+}
+
+// Like `Mutex`, only the thread holding the token can access the value.
+unsafe impl<'brand, T: ?Sized + Send> Send for BrandCell<'brand, T> {}
+unsafe impl<'brand, T: ?Sized + Send> Sync for BrandCell<'brand, T> {}
+
+impl<'brand, T: ?Sized> fmt::Debug for BrandCell<'brand, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BrandCell").finish_non_exhaustive()
+    }
+}
+
+impl<'brand, T: Default> Default for BrandCell<'brand, T> {
+    fn default() -> BrandCell<'brand, T> {
+        BrandCell::new(T::default())
+    }
+}
+
+impl<'brand, T> BrandCell<'brand, T> {
+    /// Creates a new cell.
+    ///
+    /// Its brand is inferred from the token it is first accessed with.
+    #[inline]
+    pub fn new(t: T) -> BrandCell<'brand, T> {
+        BrandCell {
+            _brand: PhantomData,
+            value: UnsafeCell::new(t),
+        }
+    }
+
+    /// Consumes the cell, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<'brand, T: ?Sized> BrandCell<'brand, T> {
+    /// Returns a reference to the value, for as long as the token is
+    /// borrowed.
+    #[inline]
+    pub fn borrow<'a>(&'a self, _token: &'a BrandToken<'_, 'brand>) -> &'a T {
+        unsafe { &*self.value.get() }
+    }
+
+    /// Returns a mutable reference to the value, for as long as the token
+    /// is mutably borrowed.
+    #[inline]
+    pub fn borrow_mut<'a>(&'a self, _token: &'a mut BrandToken<'_, 'brand>) -> &'a mut T {
+        unsafe { &mut *self.value.get() }
+    }
+
+    /// Returns a mutable reference to the value, which needs no token since
+    /// the cell is borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
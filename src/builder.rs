@@ -0,0 +1,359 @@
+use std::fmt;
+use std::time::Duration;
+
+use backoff::Backoff;
+use config;
+use instrument::{Instrument, Settings, UnlockEvent, UnlockHook};
+use raw::{RawMutex, RawRwLock};
+use {Mutex, RwLock, RwLockPolicy};
+
+/// A builder for a `Mutex` with non-default configuration.
+///
+/// ```
+/// use antidote::MutexBuilder;
+/// use std::time::Duration;
+///
+/// let mutex = MutexBuilder::new()
+///     .name("connection pool")
+///     .watchdog(Duration::from_secs(5))
+///     .stats(true)
+///     .build(vec![1, 2, 3]);
+/// mutex.lock().push(4);
+/// assert_eq!(mutex.stats().unwrap().acquisitions(), 1);
+/// ```
+///
+/// By default, `Mutex` lets a thread acquire the lock as soon as it is
+/// released, even if other threads have been waiting for longer, and only
+/// hands it to a waiter every so often. `fair` makes it hand the lock to a
+/// waiter on every contended unlock instead. Use `TicketMutex` for a mutex
+/// which is acquired in FIFO order.
+#[derive(Clone)]
+pub struct MutexBuilder {
+    name: Option<String>,
+    backoff: Option<&'static dyn Backoff>,
+    watchdog: Option<Duration>,
+    stats: bool,
+    on_unlock: Option<UnlockHook>,
+    track_holder: bool,
+    fair: bool,
+}
+
+impl fmt::Debug for MutexBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MutexBuilder")
+            .field("name", &self.name)
+            .field("backoff", &self.backoff.map(|_| "<custom>"))
+            .field("watchdog", &self.watchdog)
+            .field("stats", &self.stats)
+            .field("on_unlock", &self.on_unlock)
+            .field("track_holder", &self.track_holder)
+            .field("fair", &self.fair)
+            .finish()
+    }
+}
+
+impl Default for MutexBuilder {
+    fn default() -> MutexBuilder {
+        MutexBuilder::new()
+    }
+}
+
+impl MutexBuilder {
+    /// Creates a builder starting from the process-wide defaults set with
+    /// `config::set_defaults`.
+    pub fn new() -> MutexBuilder {
+        let defaults = config::defaults();
+        MutexBuilder {
+            name: None,
+            backoff: None,
+            watchdog: defaults.watchdog,
+            stats: defaults.stats,
+            on_unlock: None,
+            track_holder: false,
+            fair: defaults.fair,
+        }
+    }
+
+    /// Sets the mutex's name, which is returned by `Mutex::name` and
+    /// included in watchdog warnings.
+    pub fn name<S: Into<String>>(mut self, name: S) -> MutexBuilder {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the strategy the mutex uses when it is contended, rather than the
+    /// process-wide one.
+    pub fn backoff(mut self, backoff: &'static dyn Backoff) -> MutexBuilder {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// Enables a watchdog which prints a warning to stderr whenever a thread
+    /// has been blocked acquiring the mutex for longer than `threshold`.
+    ///
+    /// The thread keeps waiting afterwards.
+    pub fn watchdog(mut self, threshold: Duration) -> MutexBuilder {
+        self.watchdog = Some(threshold);
+        self
+    }
+
+    /// Sets whether statistics about the mutex's acquisitions are collected,
+    /// to be returned by `Mutex::stats`.
+    ///
+    /// This costs a few atomic operations per acquisition, and a call to
+    /// `Instant::now` before and after each contended one.
+    pub fn stats(mut self, stats: bool) -> MutexBuilder {
+        self.stats = stats;
+        self
+    }
+
+    /// Registers a callback which is invoked whenever the mutex is unlocked,
+    /// with how long it was held and whether the thread releasing it is
+    /// panicking. The event's mode is always `LockMode::Exclusive`.
+    ///
+    /// The callback runs on the releasing thread, right after the mutex is
+    /// released, so it may lock the mutex itself. Waiting on a `Condvar`
+    /// releases the mutex, and so invokes the callback. This costs a call to
+    /// `Instant::now` per acquisition and release.
+    ///
+    /// ```
+    /// use antidote::MutexBuilder;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let unlocks = Arc::new(AtomicUsize::new(0));
+    /// let mutex = MutexBuilder::new()
+    ///     .on_unlock({
+    ///         let unlocks = unlocks.clone();
+    ///         move |_| {
+    ///             unlocks.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     })
+    ///     .build(0);
+    /// *mutex.lock() += 1;
+    /// assert_eq!(unlocks.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn on_unlock<F>(mut self, f: F) -> MutexBuilder
+        where F: Fn(&UnlockEvent) + Send + Sync + 'static
+    {
+        self.on_unlock = Some(UnlockHook::new(f));
+        self
+    }
+
+    /// Sets whether the mutex records which thread holds it, to be returned
+    /// by `Mutex::holder_info` and included in watchdog warnings.
+    ///
+    /// In debug builds, where the acquisition was made is recorded as well.
+    /// This costs an uncontended lock of a standard library mutex per
+    /// acquisition and release.
+    ///
+    /// ```
+    /// use antidote::MutexBuilder;
+    ///
+    /// let mutex = MutexBuilder::new().track_holder(true).build(0);
+    /// let guard = mutex.lock();
+    /// let holder = mutex.holder_info().unwrap();
+    /// assert_eq!(holder.thread_id(), std::thread::current().id());
+    /// drop(guard);
+    /// assert!(mutex.holder_info().is_none());
+    /// ```
+    pub fn track_holder(mut self, track_holder: bool) -> MutexBuilder {
+        self.track_holder = track_holder;
+        self
+    }
+
+    /// Sets whether the mutex hands itself directly to a waiting thread
+    /// whenever it is unlocked while contended.
+    ///
+    /// Otherwise, a thread which is running may take the mutex ahead of the
+    /// thread woken to acquire it, which is much faster, and a handoff only
+    /// happens every half a millisecond or so to keep waiters from being
+    /// starved. A fair mutex bounds how long a waiter can be overtaken at the
+    /// cost of a context switch per contended unlock. Waiters are still not
+    /// woken in FIFO order on every platform.
+    pub fn fair(mut self, fair: bool) -> MutexBuilder {
+        self.fair = fair;
+        self
+    }
+
+    /// Creates a mutex protecting `t` with this configuration.
+    pub fn build<T>(self, t: T) -> Mutex<T> {
+        Mutex::from_parts(t,
+                          RawMutex::new(),
+                          Instrument::new("Mutex",
+                                          Settings {
+                                              name: self.name,
+                                              backoff: self.backoff,
+                                              watchdog: self.watchdog,
+                                              stats: self.stats,
+                                              on_unlock: self.on_unlock,
+                                              track_holder: self.track_holder,
+                                              fair: self.fair,
+                                          }))
+    }
+}
+
+/// A builder for an `RwLock` with non-default configuration.
+///
+/// ```
+/// use antidote::{RwLockBuilder, RwLockPolicy};
+///
+/// let rwlock = RwLockBuilder::new()
+///     .name("routing table")
+///     .policy(RwLockPolicy::PreferReaders)
+///     .build(0);
+/// assert_eq!(rwlock.name(), Some("routing table"));
+/// ```
+#[derive(Clone)]
+pub struct RwLockBuilder {
+    name: Option<String>,
+    policy: RwLockPolicy,
+    fair: bool,
+    backoff: Option<&'static dyn Backoff>,
+    watchdog: Option<Duration>,
+    stats: bool,
+    on_unlock: Option<UnlockHook>,
+}
+
+impl fmt::Debug for RwLockBuilder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RwLockBuilder")
+            .field("name", &self.name)
+            .field("policy", &self.policy)
+            .field("fair", &self.fair)
+            .field("backoff", &self.backoff.map(|_| "<custom>"))
+            .field("watchdog", &self.watchdog)
+            .field("stats", &self.stats)
+            .field("on_unlock", &self.on_unlock)
+            .finish()
+    }
+}
+
+impl Default for RwLockBuilder {
+    fn default() -> RwLockBuilder {
+        RwLockBuilder::new()
+    }
+}
+
+impl RwLockBuilder {
+    /// Creates a builder starting from the process-wide defaults set with
+    /// `config::set_defaults`.
+    pub fn new() -> RwLockBuilder {
+        let defaults = config::defaults();
+        RwLockBuilder {
+            name: None,
+            policy: defaults.rwlock_policy,
+            fair: defaults.fair,
+            backoff: None,
+            watchdog: defaults.watchdog,
+            stats: defaults.stats,
+            on_unlock: None,
+        }
+    }
+
+    /// Sets the lock's name, which is returned by `RwLock::name` and
+    /// included in watchdog warnings.
+    pub fn name<S: Into<String>>(mut self, name: S) -> RwLockBuilder {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the policy the lock uses to arbitrate between readers and
+    /// writers.
+    pub fn policy(mut self, policy: RwLockPolicy) -> RwLockBuilder {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets whether the lock alternates between readers and writers when
+    /// both are waiting for it, overriding the policy.
+    ///
+    /// New readers wait behind blocked writers, as with
+    /// `RwLockPolicy::PreferWriters`, but a write unlock lets all of the
+    /// readers which were blocked in ahead of the next writer, which then
+    /// acquires the lock once they have released it. Neither readers nor
+    /// writers can be starved, at the cost of more context switches than
+    /// either policy.
+    pub fn fair(mut self, fair: bool) -> RwLockBuilder {
+        self.fair = fair;
+        self
+    }
+
+    /// Sets the strategy the lock uses when it is contended, rather than the
+    /// process-wide one.
+    pub fn backoff(mut self, backoff: &'static dyn Backoff) -> RwLockBuilder {
+        self.backoff = Some(backoff);
+        self
+    }
+
+    /// Enables a watchdog which prints a warning to stderr whenever a thread
+    /// has been blocked acquiring the lock for longer than `threshold`.
+    ///
+    /// The thread keeps waiting afterwards.
+    pub fn watchdog(mut self, threshold: Duration) -> RwLockBuilder {
+        self.watchdog = Some(threshold);
+        self
+    }
+
+    /// Sets whether statistics about the lock's read and write acquisitions
+    /// are collected, to be returned by `RwLock::stats`.
+    ///
+    /// This costs a few atomic operations per acquisition, and a call to
+    /// `Instant::now` before and after each contended one.
+    pub fn stats(mut self, stats: bool) -> RwLockBuilder {
+        self.stats = stats;
+        self
+    }
+
+    /// Registers a callback which is invoked whenever a write, read or
+    /// upgradable read lock is released, with which of them it was, how long
+    /// it was held and whether the thread releasing it is panicking.
+    ///
+    /// Downgrading a write lock releases it. Upgrading an upgradable read
+    /// lock does not: the lock stays held, and is reported as a write lock
+    /// once released, timed from the upgrade. Read locks released through
+    /// `RwLock::force_unlock_read` are not reported, since when they were
+    /// acquired is unknown. The callback runs on the releasing thread, right
+    /// after the lock is released, so it may lock the lock itself. This
+    /// costs a call to `Instant::now` per acquisition and release.
+    ///
+    /// ```
+    /// use antidote::{LockMode, RwLockBuilder};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let writes = Arc::new(AtomicUsize::new(0));
+    /// let rwlock = RwLockBuilder::new()
+    ///     .on_unlock({
+    ///         let writes = writes.clone();
+    ///         move |e| if e.mode() == LockMode::Exclusive {
+    ///             writes.fetch_add(1, Ordering::Relaxed);
+    ///         }
+    ///     })
+    ///     .build(0);
+    /// *rwlock.write() += 1;
+    /// assert_eq!(*rwlock.read(), 1);
+    /// assert_eq!(writes.load(Ordering::Relaxed), 1);
+    /// ```
+    pub fn on_unlock<F>(mut self, f: F) -> RwLockBuilder
+        where F: Fn(&UnlockEvent) + Send + Sync + 'static
+    {
+        self.on_unlock = Some(UnlockHook::new(f));
+        self
+    }
+
+    /// Creates a lock protecting `t` with this configuration.
+    pub fn build<T>(self, t: T) -> RwLock<T> {
+        RwLock::from_parts(t,
+                           RawRwLock::with_fairness(self.policy, self.fair),
+                           Instrument::new("RwLock",
+                                           Settings {
+                                               name: self.name,
+                                               backoff: self.backoff,
+                                               watchdog: self.watchdog,
+                                               stats: self.stats,
+                                               on_unlock: self.on_unlock,
+                                               ..Settings::default()
+                                           }))
+    }
+}
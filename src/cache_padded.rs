@@ -0,0 +1,69 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use {Mutex, RwLock};
+
+/// Pads and aligns a value to the length of a cache line.
+///
+/// Storing small locks next to each other, for example in an array of
+/// counters, causes false sharing: threads working on unrelated locks still
+/// contend on the same cache line. Wrapping each lock in a `CachePadded`
+/// places it on a cache line of its own.
+///
+/// Some architectures prefetch cache lines in adjacent pairs, so the
+/// alignment used is 128 bytes on those and 64 bytes elsewhere.
+#[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "powerpc64"),
+           repr(align(128)))]
+#[cfg_attr(not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "powerpc64")),
+           repr(align(64)))]
+#[derive(Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct CachePadded<T>(T);
+
+/// A `Mutex` padded to the length of a cache line.
+pub type PaddedMutex<T> = CachePadded<Mutex<T>>;
+
+/// An `RwLock` padded to the length of a cache line.
+pub type PaddedRwLock<T> = CachePadded<RwLock<T>>;
+
+impl<T> CachePadded<T> {
+    /// Pads and aligns a value to the length of a cache line.
+    #[inline]
+    pub const fn new(t: T) -> CachePadded<T> {
+        CachePadded(t)
+    }
+
+    /// Returns the inner value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CachePadded<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("CachePadded").field(&self.0).finish()
+    }
+}
+
+impl<T> From<T> for CachePadded<T> {
+    #[inline]
+    fn from(t: T) -> CachePadded<T> {
+        CachePadded::new(t)
+    }
+}
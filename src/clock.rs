@@ -0,0 +1,90 @@
+//! Deadlines measured by a clock other than `std::time::Instant`.
+//!
+//! Some platforms have a monotonic time source of their own, such as an
+//! RTOS tick counter, which `Instant` does not reflect or which is more
+//! precise than it. Methods like `Mutex::try_lock_until_clock` take a
+//! deadline measured by any `Clock`:
+//!
+//! ```
+//! use antidote::Mutex;
+//! use std::sync::atomic::{AtomicU64, Ordering};
+//!
+//! static TICKS: AtomicU64 = AtomicU64::new(0);
+//!
+//! let mutex = Mutex::new(0);
+//! let clock = || TICKS.load(Ordering::Relaxed);
+//! let guard = mutex.try_lock_until_clock(&clock, clock() + 100).unwrap();
+//! # drop(guard);
+//! ```
+//!
+//! Closures returning an ordered value implement `Clock`, as does
+//! `StdClock`.
+//!
+//! A thread waiting for a deadline of another clock still blocks on the
+//! lock, and checks the clock every 10 milliseconds.
+
+use std::time::{Duration, Instant};
+
+use deadline_after;
+
+// How long a thread blocks between checks of a `Clock`.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A monotonic source of time.
+pub trait Clock {
+    /// A point in time.
+    ///
+    /// Later points compare greater than earlier ones.
+    type Instant: Ord;
+
+    /// Returns the current time.
+    fn now(&self) -> Self::Instant;
+}
+
+/// The standard library's monotonic clock, `std::time::Instant`.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct StdClock;
+
+impl Clock for StdClock {
+    type Instant = Instant;
+
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl<F, I> Clock for F
+    where F: Fn() -> I,
+          I: Ord
+{
+    type Instant = I;
+
+    #[inline]
+    fn now(&self) -> I {
+        self()
+    }
+}
+
+// Calls `lock_until` with successive short deadlines until it succeeds or
+// `deadline` passes on `clock`. The lock is attempted once more after the
+// deadline passes, so that an expired deadline behaves like a `try_` method.
+pub(crate) fn lock_until<C, F>(clock: &C, deadline: C::Instant, mut lock_until: F) -> bool
+    where C: ?Sized + Clock,
+          F: FnMut(Option<Instant>) -> bool
+{
+    loop {
+        let expired = clock.now() >= deadline;
+        let slice = if expired {
+            Some(Instant::now())
+        } else {
+            deadline_after(POLL_INTERVAL)
+        };
+        if lock_until(slice) {
+            return true;
+        }
+        if expired {
+            return false;
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Locks with exactly the standard library's signatures.
+//!
+//! The types here return `LockResult`s and `std::sync::TryLockResult`s just
+//! like their standard library counterparts, except that the results are
+//! never poisoned. A crate can switch to antidote by changing its imports
+//! from `std::sync` to `antidote::compat`, leaving every `.unwrap()` intact,
+//! and then migrate to the top-level types at its leisure.
+
+use std::fmt;
+use std::sync::{self, LockResult};
+use std::time::Duration;
+
+use TryLockError;
+
+pub use {MutexGuard, RwLockReadGuard, RwLockWriteGuard, WaitTimeoutResult};
+
+fn try_lock_result<G>(r: Result<G, TryLockError>) -> sync::TryLockResult<G> {
+    r.map_err(|_| sync::TryLockError::WouldBlock)
+}
+
+/// Like `std::sync::Mutex`, but never poisoned.
+#[derive(Default)]
+pub struct Mutex<T: ?Sized>(::Mutex<T>);
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(fmt)
+    }
+}
+
+impl<T> Mutex<T> {
+    /// Like `std::sync::Mutex::new`.
+    #[inline]
+    pub fn new(t: T) -> Mutex<T> {
+        Mutex(::Mutex::new(t))
+    }
+
+    /// Like `std::sync::Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> LockResult<T> {
+        Ok(self.0.into_inner())
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Like `std::sync::Mutex::lock`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock<'a>(&'a self) -> LockResult<MutexGuard<'a, T>> {
+        Ok(self.0.lock())
+    }
+
+    /// Like `std::sync::Mutex::try_lock`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock<'a>(&'a self) -> sync::TryLockResult<MutexGuard<'a, T>> {
+        try_lock_result(self.0.try_lock())
+    }
+
+    /// Like `std::sync::Mutex::is_poisoned`.
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Like `std::sync::Mutex::clear_poison`.
+    #[inline]
+    pub fn clear_poison(&self) {}
+
+    /// Like `std::sync::Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        Ok(self.0.get_mut())
+    }
+}
+
+impl<T> From<T> for Mutex<T> {
+    #[inline]
+    fn from(t: T) -> Mutex<T> {
+        Mutex::new(t)
+    }
+}
+
+/// Like `std::sync::RwLock`, but never poisoned.
+#[derive(Default)]
+pub struct RwLock<T: ?Sized>(::RwLock<T>);
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(fmt)
+    }
+}
+
+impl<T> RwLock<T> {
+    /// Like `std::sync::RwLock::new`.
+    #[inline]
+    pub fn new(t: T) -> RwLock<T> {
+        RwLock(::RwLock::new(t))
+    }
+
+    /// Like `std::sync::RwLock::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> LockResult<T> {
+        Ok(self.0.into_inner())
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Like `std::sync::RwLock::read`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read<'a>(&'a self) -> LockResult<RwLockReadGuard<'a, T>> {
+        Ok(self.0.read())
+    }
+
+    /// Like `std::sync::RwLock::try_read`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_read<'a>(&'a self) -> sync::TryLockResult<RwLockReadGuard<'a, T>> {
+        try_lock_result(self.0.try_read())
+    }
+
+    /// Like `std::sync::RwLock::write`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn write<'a>(&'a self) -> LockResult<RwLockWriteGuard<'a, T>> {
+        Ok(self.0.write())
+    }
+
+    /// Like `std::sync::RwLock::try_write`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_write<'a>(&'a self) -> sync::TryLockResult<RwLockWriteGuard<'a, T>> {
+        try_lock_result(self.0.try_write())
+    }
+
+    /// Like `std::sync::RwLock::is_poisoned`.
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        false
+    }
+
+    /// Like `std::sync::RwLock::clear_poison`.
+    #[inline]
+    pub fn clear_poison(&self) {}
+
+    /// Like `std::sync::RwLock::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        Ok(self.0.get_mut())
+    }
+}
+
+impl<T> From<T> for RwLock<T> {
+    #[inline]
+    fn from(t: T) -> RwLock<T> {
+        RwLock::new(t)
+    }
+}
+
+/// Like `std::sync::Condvar`, for use with this module's `Mutex`.
+///
+/// The standard library's `WaitTimeoutResult` cannot be constructed outside
+/// of it, so `wait_timeout` returns this crate's equivalent instead.
+#[derive(Default)]
+pub struct Condvar(::Condvar);
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Condvar").finish()
+    }
+}
+
+impl Condvar {
+    /// Like `std::sync::Condvar::new`.
+    #[inline]
+    pub fn new() -> Condvar {
+        Condvar(::Condvar::new())
+    }
+
+    /// Like `std::sync::Condvar::wait`.
+    #[inline]
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+        Ok(self.0.wait(guard))
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout`.
+    #[inline]
+    pub fn wait_timeout<'a, T: ?Sized>(&self,
+                                       guard: MutexGuard<'a, T>,
+                                       dur: Duration)
+                                       -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        Ok(self.0.wait_timeout(guard, dur))
+    }
+
+    /// Like `std::sync::Condvar::wait_while`.
+    #[inline]
+    pub fn wait_while<'a, T: ?Sized, F>(&self, guard: MutexGuard<'a, T>, condition: F) -> LockResult<MutexGuard<'a, T>>
+        where F: FnMut(&mut T) -> bool
+    {
+        Ok(self.0.wait_while(guard, condition))
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout_while`.
+    #[inline]
+    pub fn wait_timeout_while<'a, T: ?Sized, F>(&self,
+                                                guard: MutexGuard<'a, T>,
+                                                dur: Duration,
+                                                condition: F)
+                                                -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+        where F: FnMut(&mut T) -> bool
+    {
+        Ok(self.0.wait_timeout_while(guard, dur, condition))
+    }
+
+    /// Like `std::sync::Condvar::notify_one`.
+    #[inline]
+    pub fn notify_one(&self) {
+        self.0.notify_one();
+    }
+
+    /// Like `std::sync::Condvar::notify_all`.
+    #[inline]
+    pub fn notify_all(&self) {
+        self.0.notify_all();
+    }
+}
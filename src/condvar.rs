@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+
+use raw::RawCondvar;
+use {deadline_after, MutexGuard, WaitTimeoutResult};
+
+/// Like `std::sync::Condvar`.
+pub struct Condvar(RawCondvar);
+
+impl Condvar {
+    /// Like `std::sync::Condvar::new`.
+    #[inline]
+    pub fn new() -> Condvar {
+        Condvar(RawCondvar::new())
+    }
+
+    /// Like `std::sync::Condvar::wait`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        unsafe {
+            guard.mutex.wait_on(&self.0, None);
+        }
+        guard
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn wait_timeout<'a, T: ?Sized>(&self,
+                                       guard: MutexGuard<'a, T>,
+                                       dur: Duration)
+                                       -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        let deadline = deadline_after(dur);
+        let woken = unsafe { guard.mutex.wait_on(&self.0, deadline) };
+        (guard, WaitTimeoutResult(!woken))
+    }
+
+    /// Like `wait_timeout`, but waits until `deadline` rather than for a
+    /// duration.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn wait_until<'a, T: ?Sized>(&self,
+                                     guard: MutexGuard<'a, T>,
+                                     deadline: Instant)
+                                     -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        let woken = unsafe { guard.mutex.wait_on(&self.0, Some(deadline)) };
+        (guard, WaitTimeoutResult(!woken))
+    }
+
+    /// Like `std::sync::Condvar::wait_while`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn wait_while<'a, T: ?Sized, F>(&self, guard: MutexGuard<'a, T>, condition: F) -> MutexGuard<'a, T>
+        where F: FnMut(&mut T) -> bool
+    {
+        self.wait_while_until_inner(guard, None, condition).0
+    }
+
+    /// Like `std::sync::Condvar::wait_timeout_while`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn wait_timeout_while<'a, T: ?Sized, F>(&self,
+                                                guard: MutexGuard<'a, T>,
+                                                dur: Duration,
+                                                condition: F)
+                                                -> (MutexGuard<'a, T>, WaitTimeoutResult)
+        where F: FnMut(&mut T) -> bool
+    {
+        self.wait_while_until_inner(guard, deadline_after(dur), condition)
+    }
+
+    /// Like `wait_timeout_while`, but waits until `deadline` rather than for
+    /// a duration.
+    ///
+    /// The result reports a timeout only if `condition` still holds once the
+    /// deadline has passed.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn wait_while_until<'a, T: ?Sized, F>(&self,
+                                              guard: MutexGuard<'a, T>,
+                                              deadline: Instant,
+                                              condition: F)
+                                              -> (MutexGuard<'a, T>, WaitTimeoutResult)
+        where F: FnMut(&mut T) -> bool
+    {
+        self.wait_while_until_inner(guard, Some(deadline), condition)
+    }
+
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn wait_while_until_inner<'a, T: ?Sized, F>(&self,
+                                                mut guard: MutexGuard<'a, T>,
+                                                deadline: Option<Instant>,
+                                                mut condition: F)
+                                                -> (MutexGuard<'a, T>, WaitTimeoutResult)
+        where F: FnMut(&mut T) -> bool
+    {
+        loop {
+            if !condition(&mut *guard) {
+                return (guard, WaitTimeoutResult(false));
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return (guard, WaitTimeoutResult(true));
+            }
+            unsafe {
+                guard.mutex.wait_on(&self.0, deadline);
+            }
+        }
+    }
+
+    /// Like `std::sync::Condvar::notify_one`.
+    ///
+    /// Returns whether a thread was waiting on the condition variable, and
+    /// so will return from its wait. Threads waiting on this condition
+    /// variable are counted by the condition variable itself, so this is
+    /// exact on every platform. A thread whose wait is timing out at the
+    /// same moment counts as notified, and reports that it was.
+    #[inline]
+    pub fn notify_one(&self) -> bool {
+        self.0.notify_one()
+    }
+
+    /// Like `std::sync::Condvar::notify_all`.
+    ///
+    /// Returns the number of threads which were waiting on the condition
+    /// variable and had not been notified yet. Like `notify_one`, this is
+    /// exact on every platform.
+    #[inline]
+    pub fn notify_all(&self) -> usize {
+        self.0.notify_all()
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Condvar {
+        Condvar::new()
+    }
+}
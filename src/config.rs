@@ -0,0 +1,113 @@
+//! Process-wide defaults for new locks.
+//!
+//! These apply to `Mutex`es and `RwLock`s created by their constructors, and
+//! are the starting point of `MutexBuilder` and `RwLockBuilder`. This allows
+//! instrumentation or fairness to be turned on for every lock in a program
+//! without touching each place a lock is created:
+//!
+//! ```
+//! use antidote::config::{self, Defaults};
+//! use antidote::Mutex;
+//!
+//! config::set_defaults(Defaults::new().stats(true));
+//!
+//! let mutex = Mutex::new(0);
+//! *mutex.lock() += 1;
+//! assert_eq!(mutex.stats().unwrap().acquisitions(), 1);
+//! ```
+//!
+//! Locks which already exist are unaffected by changes to the defaults.
+//! Note that the defaults also apply to the locks used internally by this
+//! crate's other primitives.
+
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::time::Duration;
+
+use RwLockPolicy;
+
+/// Configuration applied to new locks by default.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Defaults {
+    pub(crate) stats: bool,
+    pub(crate) watchdog: Option<Duration>,
+    pub(crate) rwlock_policy: RwLockPolicy,
+    pub(crate) fair: bool,
+}
+
+impl Defaults {
+    /// Returns the initial defaults: no instrumentation, writer-preferring
+    /// `RwLock`s, and locks which are not strictly fair.
+    #[inline]
+    pub const fn new() -> Defaults {
+        Defaults {
+            stats: false,
+            watchdog: None,
+            rwlock_policy: RwLockPolicy::PreferWriters,
+            fair: false,
+        }
+    }
+
+    /// Sets whether locks collect statistics about their acquisitions.
+    ///
+    /// See `MutexBuilder::stats`.
+    #[inline]
+    pub const fn stats(mut self, stats: bool) -> Defaults {
+        self.stats = stats;
+        self
+    }
+
+    /// Sets the threshold of the watchdog which warns about threads blocked
+    /// acquiring a lock, or `None` to disable it.
+    ///
+    /// See `MutexBuilder::watchdog`.
+    #[inline]
+    pub const fn watchdog(mut self, threshold: Option<Duration>) -> Defaults {
+        self.watchdog = threshold;
+        self
+    }
+
+    /// Sets the policy `RwLock`s use to arbitrate between readers and
+    /// writers.
+    #[inline]
+    pub const fn rwlock_policy(mut self, policy: RwLockPolicy) -> Defaults {
+        self.rwlock_policy = policy;
+        self
+    }
+
+    /// Sets whether locks hand themselves over to the threads waiting for
+    /// them, rather than letting running threads barge ahead.
+    ///
+    /// See `MutexBuilder::fair` and `RwLockBuilder::fair`.
+    #[inline]
+    pub const fn fair(mut self, fair: bool) -> Defaults {
+        self.fair = fair;
+        self
+    }
+}
+
+// The current defaults, or null for `Defaults::new()`. Points to a leaked
+// box, as `Defaults` is too large for an atomic.
+static DEFAULTS: AtomicPtr<Defaults> = AtomicPtr::new(ptr::null_mut());
+
+/// Sets the defaults for locks created afterwards.
+///
+/// This is intended to be called once, early in `main`. Each call leaks a
+/// small allocation.
+pub fn set_defaults(defaults: Defaults) {
+    DEFAULTS.store(Box::into_raw(Box::new(defaults)), Ordering::Release);
+}
+
+/// Returns the current defaults.
+///
+/// Together with `set_defaults`, this allows individual settings to be
+/// changed: `set_defaults(defaults().stats(true))`.
+#[inline]
+pub fn defaults() -> Defaults {
+    let ptr = DEFAULTS.load(Ordering::Acquire);
+    if ptr.is_null() {
+        Defaults::new()
+    } else {
+        unsafe { *ptr }
+    }
+}
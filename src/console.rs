@@ -0,0 +1,140 @@
+//! Instrumentation of the async locks for tokio-console.
+//!
+//! With the `tracing` feature, each async lock is a resource span, and each
+//! future waiting on one an async op span, following the conventions of
+//! tokio's own synchronization primitives which console-subscriber
+//! understands. Without the feature these types are empty and everything
+//! here compiles away.
+
+use std::task::Poll;
+
+#[cfg(feature = "tracing")]
+use std::panic::Location;
+#[cfg(feature = "tracing")]
+use tracing::{self, Span};
+
+// An async lock, as seen by the console.
+pub(crate) struct Resource {
+    #[cfg(feature = "tracing")]
+    span: Span,
+}
+
+// A future waiting on a `Resource`.
+pub(crate) struct AsyncOp {
+    #[cfg(feature = "tracing")]
+    span: Span,
+    #[cfg(feature = "tracing")]
+    poll_span: Span,
+}
+
+#[cfg(feature = "tracing")]
+impl Resource {
+    #[track_caller]
+    pub(crate) fn new(concrete_type: &'static str) -> Resource {
+        let location = Location::caller();
+        let span = tracing::trace_span!(target: "runtime::resource",
+                                        parent: None,
+                                        "runtime.resource",
+                                        concrete_type,
+                                        kind = "Sync",
+                                        loc.file = location.file(),
+                                        loc.line = location.line(),
+                                        loc.col = location.column());
+        Resource { span }
+    }
+
+    pub(crate) fn locked(&self, locked: bool) {
+        self.span.in_scope(|| {
+            tracing::trace!(target: "runtime::resource::state_update", locked);
+        });
+    }
+
+    pub(crate) fn write_locked(&self, write_locked: bool) {
+        self.span.in_scope(|| {
+            tracing::trace!(target: "runtime::resource::state_update",
+                            write_locked,
+                            write_locked.op = "override");
+        });
+    }
+
+    pub(crate) fn reader_acquired(&self) {
+        self.span.in_scope(|| {
+            tracing::trace!(target: "runtime::resource::state_update",
+                            current_readers = 1,
+                            current_readers.op = "add");
+        });
+    }
+
+    pub(crate) fn reader_released(&self) {
+        self.span.in_scope(|| {
+            tracing::trace!(target: "runtime::resource::state_update",
+                            current_readers = 1,
+                            current_readers.op = "sub");
+        });
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl Resource {
+    #[inline]
+    pub(crate) fn new(_: &'static str) -> Resource {
+        Resource {}
+    }
+
+    #[inline]
+    pub(crate) fn locked(&self, _: bool) {}
+
+    #[inline]
+    pub(crate) fn write_locked(&self, _: bool) {}
+
+    #[inline]
+    pub(crate) fn reader_acquired(&self) {}
+
+    #[inline]
+    pub(crate) fn reader_released(&self) {}
+}
+
+#[cfg(feature = "tracing")]
+impl AsyncOp {
+    pub(crate) fn new(resource: &Resource, source: &'static str) -> AsyncOp {
+        let span = resource.span.in_scope(|| {
+            tracing::trace_span!(target: "runtime::resource",
+                                 "runtime.resource.async_op",
+                                 source,
+                                 inherits_child_attrs = false)
+        });
+        let poll_span = span.in_scope(|| {
+            tracing::trace_span!(target: "runtime::resource", "runtime.resource.async_op.poll")
+        });
+        AsyncOp { span, poll_span }
+    }
+
+    // Polls the operation within its spans, reporting whether it completed.
+    pub(crate) fn poll<R, F>(&self, resource: &Resource, f: F) -> Poll<R>
+        where F: FnOnce() -> Poll<R>
+    {
+        let _resource = resource.span.enter();
+        let _op = self.span.enter();
+        let _poll = self.poll_span.enter();
+        let poll = f();
+        tracing::trace!(target: "runtime::resource::poll_op",
+                        op_name = "poll_acquire",
+                        is_ready = poll.is_ready());
+        poll
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl AsyncOp {
+    #[inline]
+    pub(crate) fn new(_: &Resource, _: &'static str) -> AsyncOp {
+        AsyncOp {}
+    }
+
+    #[inline]
+    pub(crate) fn poll<R, F>(&self, _: &Resource, f: F) -> Poll<R>
+        where F: FnOnce() -> Poll<R>
+    {
+        f()
+    }
+}
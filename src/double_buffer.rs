@@ -0,0 +1,173 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use {fmt_guard, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A pair of buffers, one which readers see and one which a writer prepares,
+/// swapped atomically.
+///
+/// The writer mutates the back buffer through `write`, and publishes it with
+/// `swap`, which makes it the front buffer and the old front buffer the new
+/// back one. Readers only ever lock the front buffer, so they don't contend
+/// with the writer except on a buffer which was swapped out from under them.
+///
+/// ```
+/// use antidote::DoubleBuffer;
+///
+/// let frames = DoubleBuffer::new(vec![], vec![]);
+///
+/// frames.write().push("frame 1");
+/// assert!(frames.read().is_empty());
+///
+/// frames.swap();
+/// assert_eq!(*frames.read(), ["frame 1"]);
+///
+/// // The back buffer now holds the previous frame.
+/// frames.write().clear();
+/// ```
+pub struct DoubleBuffer<T> {
+    buffers: [RwLock<T>; 2],
+    front: AtomicUsize,
+    // Held by writers, so that a swap doesn't publish a half-written buffer.
+    writer: Mutex<()>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for DoubleBuffer<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let front = self.front.load(Ordering::Acquire);
+        fmt.debug_struct("DoubleBuffer")
+            .field("front", &self.buffers[front])
+            .field("back", &self.buffers[1 - front])
+            .finish()
+    }
+}
+
+impl<T: Default> Default for DoubleBuffer<T> {
+    fn default() -> DoubleBuffer<T> {
+        DoubleBuffer::new(T::default(), T::default())
+    }
+}
+
+impl<T> DoubleBuffer<T> {
+    /// Creates a new `DoubleBuffer`.
+    #[inline]
+    pub fn new(front: T, back: T) -> DoubleBuffer<T> {
+        DoubleBuffer {
+            buffers: [RwLock::new(front), RwLock::new(back)],
+            front: AtomicUsize::new(0),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Consumes the buffers, returning the front and back values.
+    #[inline]
+    pub fn into_inner(self) -> (T, T) {
+        let front = self.front.load(Ordering::Relaxed);
+        let [a, b] = self.buffers;
+        let (a, b) = (a.into_inner(), b.into_inner());
+        if front == 0 { (a, b) } else { (b, a) }
+    }
+
+    /// Locks the front buffer for reading.
+    ///
+    /// This only blocks while a writer is writing to a buffer which was
+    /// swapped to the back after this call had picked it as the front.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read<'a>(&'a self) -> RwLockReadGuard<'a, T> {
+        loop {
+            let front = self.front.load(Ordering::Acquire);
+            let guard = self.buffers[front].read();
+            if self.front.load(Ordering::Acquire) == front {
+                return guard;
+            }
+        }
+    }
+
+    /// Returns a copy of the front buffer.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn snapshot(&self) -> T
+        where T: Clone
+    {
+        self.read().clone()
+    }
+
+    /// Locks the back buffer for writing.
+    ///
+    /// Only one writer can exist at a time, and `swap` waits for it to be
+    /// dropped. This blocks while readers which picked the back buffer before
+    /// it was swapped out are still reading it.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn write<'a>(&'a self) -> DoubleBufferWriteGuard<'a, T> {
+        let writer = self.writer.lock();
+        let back = 1 - self.front.load(Ordering::Acquire);
+        DoubleBufferWriteGuard {
+            buffer: self,
+            guard: self.buffers[back].write(),
+            _writer: writer,
+        }
+    }
+
+    /// Publishes the back buffer, making it the front one, and makes the old
+    /// front buffer the back one.
+    ///
+    /// Readers which locked the old front buffer before the swap keep reading
+    /// it until they release it.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn swap(&self) {
+        let _writer = self.writer.lock();
+        self.front.fetch_xor(1, Ordering::AcqRel);
+    }
+
+    /// Returns mutable references to the front and back buffers, which need
+    /// no locking since the buffers are borrowed mutably.
+    #[inline]
+    pub fn get_mut(&mut self) -> (&mut T, &mut T) {
+        let front = *self.front.get_mut();
+        let [ref mut a, ref mut b] = self.buffers;
+        let (a, b) = (a.get_mut(), b.get_mut());
+        if front == 0 { (a, b) } else { (b, a) }
+    }
+}
+
+/// An RAII guard for writing to the back buffer of a `DoubleBuffer`.
+///
+/// This is created by `DoubleBuffer::write`.
+#[must_use]
+pub struct DoubleBufferWriteGuard<'a, T: 'a> {
+    buffer: &'a DoubleBuffer<T>,
+    guard: RwLockWriteGuard<'a, T>,
+    _writer: MutexGuard<'a, ()>,
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for DoubleBufferWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt,
+                  "DoubleBufferWriteGuard",
+                  self.buffer as *const DoubleBuffer<T> as usize,
+                  &**self)
+    }
+}
+
+impl<'a, T: fmt::Display> fmt::Display for DoubleBufferWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T> Deref for DoubleBufferWriteGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for DoubleBufferWriteGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
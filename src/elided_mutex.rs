@@ -0,0 +1,164 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use raw::{self, RawElidedMutex};
+use {fmt_guard, TryLockError, TryLockResult};
+
+/// A mutex which attempts hardware lock elision before acquiring the lock.
+///
+/// On x86-64 processors with Restricted Transactional Memory, `lock` runs
+/// the critical section as a transaction rather than acquiring the lock.
+/// Transactions which don't touch the same data run in parallel, which makes
+/// this a good fit for short, mostly read-only critical sections. A
+/// transaction which conflicts with another thread, or which does anything
+/// that can't be rolled back, such as a system call, is aborted by the
+/// processor. Its effects are undone and it is rerun with the lock held. On
+/// other processors this is a regular mutex.
+///
+/// A guard for an elided lock keeps the thread in its transaction until the
+/// guard is dropped. A leaked guard would leave it there, to be rolled back
+/// to `lock` by whatever aborts the transaction next, possibly long after
+/// the code which leaked the guard has returned. Creating an `ElidedMutex`
+/// is therefore `unsafe`.
+///
+/// ```
+/// use antidote::ElidedMutex;
+///
+/// // Safety: the guards below are all dropped.
+/// let mutex = unsafe { ElidedMutex::new(0) };
+/// *mutex.lock() += 1;
+/// assert_eq!(mutex.into_inner(), 1);
+/// ```
+pub struct ElidedMutex<T: ?Sized> {
+    raw: RawElidedMutex,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for ElidedMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for ElidedMutex<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ElidedMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Ok(guard) => fmt.debug_struct("ElidedMutex").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("ElidedMutex").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> ElidedMutex<T> {
+    /// Creates a new eliding mutex.
+    ///
+    /// # Safety
+    ///
+    /// No guard for the mutex may be leaked, with `mem::forget`, a reference
+    /// cycle or otherwise, by the caller or by any code the mutex is passed
+    /// to, including code generic over `BlockingLock`.
+    #[inline]
+    pub unsafe fn new(t: T) -> ElidedMutex<T> {
+        ElidedMutex {
+            raw: RawElidedMutex::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> ElidedMutex<T> {
+    /// Returns whether the processor supports lock elision.
+    #[inline]
+    pub fn elision_supported() -> bool {
+        raw::elision_supported()
+    }
+
+    /// Like `Mutex::lock`, but first attempts to elide the lock.
+    #[inline]
+    pub fn lock<'a>(&'a self) -> ElidedMutexGuard<'a, T> {
+        let elided = self.raw.lock();
+        ElidedMutexGuard::new(self, elided)
+    }
+
+    /// Like `Mutex::try_lock`.
+    ///
+    /// This never elides the lock.
+    #[inline]
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<ElidedMutexGuard<'a, T>> {
+        if self.raw.try_lock() {
+            Ok(ElidedMutexGuard::new(self, false))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+/// An RAII guard for an `ElidedMutex`.
+///
+/// An elided critical section is a transaction of the current thread, so
+/// unlike the other guards this is never `Send`.
+#[must_use]
+pub struct ElidedMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a ElidedMutex<T>,
+    elided: bool,
+    _marker: PhantomData<*mut ()>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for ElidedMutexGuard<'a, T> {}
+
+impl<'a, T: ?Sized> ElidedMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a ElidedMutex<T>, elided: bool) -> ElidedMutexGuard<'a, T> {
+        ElidedMutexGuard {
+            mutex,
+            elided,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for ElidedMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "ElidedMutexGuard", self.mutex as *const ElidedMutex<T> as *const () as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for ElidedMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for ElidedMutexGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for ElidedMutexGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for ElidedMutexGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.mutex.raw.unlock(self.elided) }
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use {Condvar, deadline_after, Mutex, WaitTimeoutResult};
+
+/// A manual-reset event.
+///
+/// An event is either set or unset. Threads waiting on it block until it is
+/// set, and it stays set, releasing every current and future waiter, until
+/// it is explicitly reset. An event which is never reset is a one-shot
+/// signal.
+pub struct Event {
+    set: Mutex<bool>,
+    cond: Condvar,
+}
+
+impl fmt::Debug for Event {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Event").field("set", &self.is_set()).finish()
+    }
+}
+
+impl Event {
+    /// Creates a new `Event` which is initially unset.
+    #[inline]
+    pub fn new() -> Event {
+        Event::with_state(false)
+    }
+
+    /// Creates a new `Event` with the specified initial state.
+    #[inline]
+    pub fn with_state(set: bool) -> Event {
+        Event {
+            set: Mutex::new(set),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Sets the event, waking all threads waiting on it.
+    pub fn set(&self) {
+        let mut set = self.set.lock();
+        if !*set {
+            *set = true;
+            self.cond.notify_all();
+        }
+    }
+
+    /// Resets the event, so that subsequent waits block until it is set
+    /// again.
+    pub fn reset(&self) {
+        *self.set.lock() = false;
+    }
+
+    /// Returns whether the event is set.
+    pub fn is_set(&self) -> bool {
+        *self.set.lock()
+    }
+
+    /// Blocks the current thread until the event is set.
+    pub fn wait(&self) {
+        let mut set = self.set.lock();
+        while !*set {
+            set = self.cond.wait(set);
+        }
+    }
+
+    /// Blocks the current thread until the event is set, or the timeout
+    /// expires.
+    pub fn wait_timeout(&self, dur: Duration) -> WaitTimeoutResult {
+        match deadline_after(dur) {
+            Some(deadline) => self.wait_until(deadline),
+            None => {
+                self.wait();
+                WaitTimeoutResult(false)
+            }
+        }
+    }
+
+    /// Blocks the current thread until the event is set, or `deadline` passes.
+    pub fn wait_until(&self, deadline: Instant) -> WaitTimeoutResult {
+        let mut set = self.set.lock();
+        while !*set {
+            if Instant::now() >= deadline {
+                return WaitTimeoutResult(true);
+            }
+            set = self.cond.wait_until(set, deadline).0;
+        }
+        WaitTimeoutResult(false)
+    }
+}
+
+impl Default for Event {
+    fn default() -> Event {
+        Event::new()
+    }
+}
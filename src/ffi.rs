@@ -0,0 +1,194 @@
+//! A C API for this crate's locks.
+//!
+//! Locks are passed to C as opaque pointers to a `Mutex<()>` or an
+//! `RwLock<()>`, so C and Rust code can share the same lock: a lock created
+//! in C can be used from Rust by dereferencing its handle, and a lock created
+//! in Rust can be handed to C as a pointer. Locks created through this API
+//! use the process-wide defaults set with `config::set_defaults`, so they are
+//! instrumented like any other.
+//!
+//! The corresponding C declarations are:
+//!
+//! ```c
+//! typedef struct antidote_mutex antidote_mutex;
+//! typedef struct antidote_rwlock antidote_rwlock;
+//!
+//! antidote_mutex *antidote_mutex_new(const char *name);
+//! void antidote_mutex_free(antidote_mutex *mutex);
+//! void antidote_mutex_lock(const antidote_mutex *mutex);
+//! bool antidote_mutex_try_lock(const antidote_mutex *mutex);
+//! void antidote_mutex_unlock(const antidote_mutex *mutex);
+//!
+//! antidote_rwlock *antidote_rwlock_new(const char *name);
+//! void antidote_rwlock_free(antidote_rwlock *rwlock);
+//! void antidote_rwlock_read(const antidote_rwlock *rwlock);
+//! bool antidote_rwlock_try_read(const antidote_rwlock *rwlock);
+//! void antidote_rwlock_read_unlock(const antidote_rwlock *rwlock);
+//! void antidote_rwlock_write(const antidote_rwlock *rwlock);
+//! bool antidote_rwlock_try_write(const antidote_rwlock *rwlock);
+//! void antidote_rwlock_write_unlock(const antidote_rwlock *rwlock);
+//! ```
+//!
+//! A panic, for example when the debug-build deadlock detection fires,
+//! aborts the process rather than unwinding into C.
+//!
+//! This module is only available with the `ffi` feature.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+
+use {Mutex, MutexBuilder, RwLock, RwLockBuilder};
+
+unsafe fn name(name: *const c_char) -> Option<String> {
+    if name.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr(name).to_string_lossy().into_owned())
+    }
+}
+
+/// Creates a mutex, named `name` unless it is null.
+///
+/// # Safety
+///
+/// `name` must be null or a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_new(name: *const c_char) -> *mut Mutex<()> {
+    let mut builder = MutexBuilder::new();
+    if let Some(name) = self::name(name) {
+        builder = builder.name(name);
+    }
+    Box::into_raw(Box::new(builder.build(())))
+}
+
+/// Destroys a mutex created by `antidote_mutex_new`. Does nothing if
+/// `mutex` is null.
+///
+/// # Safety
+///
+/// `mutex` must be null or have been returned by `antidote_mutex_new`, and
+/// must be unlocked and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_free(mutex: *mut Mutex<()>) {
+    if !mutex.is_null() {
+        drop(Box::from_raw(mutex));
+    }
+}
+
+/// Like `Mutex::raw_lock`.
+///
+/// # Safety
+///
+/// `mutex` must point to a live mutex.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_lock(mutex: *const Mutex<()>) {
+    (*mutex).raw_lock();
+}
+
+/// Like `Mutex::raw_try_lock`.
+///
+/// # Safety
+///
+/// `mutex` must point to a live mutex.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_try_lock(mutex: *const Mutex<()>) -> bool {
+    (*mutex).raw_try_lock()
+}
+
+/// Like `Mutex::force_unlock`.
+///
+/// # Safety
+///
+/// `mutex` must point to a live mutex, locked by the caller.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_mutex_unlock(mutex: *const Mutex<()>) {
+    (*mutex).force_unlock();
+}
+
+/// Creates a reader-writer lock, named `name` unless it is null.
+///
+/// # Safety
+///
+/// `name` must be null or a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_new(name: *const c_char) -> *mut RwLock<()> {
+    let mut builder = RwLockBuilder::new();
+    if let Some(name) = self::name(name) {
+        builder = builder.name(name);
+    }
+    Box::into_raw(Box::new(builder.build(())))
+}
+
+/// Destroys a lock created by `antidote_rwlock_new`. Does nothing if
+/// `rwlock` is null.
+///
+/// # Safety
+///
+/// `rwlock` must be null or have been returned by `antidote_rwlock_new`, and
+/// must be unlocked and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_free(rwlock: *mut RwLock<()>) {
+    if !rwlock.is_null() {
+        drop(Box::from_raw(rwlock));
+    }
+}
+
+/// Like `RwLock::raw_read`.
+///
+/// # Safety
+///
+/// `rwlock` must point to a live lock.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_read(rwlock: *const RwLock<()>) {
+    (*rwlock).raw_read();
+}
+
+/// Like `RwLock::raw_try_read`.
+///
+/// # Safety
+///
+/// `rwlock` must point to a live lock.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_try_read(rwlock: *const RwLock<()>) -> bool {
+    (*rwlock).raw_try_read()
+}
+
+/// Like `RwLock::force_unlock_read`.
+///
+/// # Safety
+///
+/// `rwlock` must point to a live lock, read-locked by the caller.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_read_unlock(rwlock: *const RwLock<()>) {
+    (*rwlock).force_unlock_read();
+}
+
+/// Like `RwLock::raw_write`.
+///
+/// # Safety
+///
+/// `rwlock` must point to a live lock.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_write(rwlock: *const RwLock<()>) {
+    (*rwlock).raw_write();
+}
+
+/// Like `RwLock::raw_try_write`.
+///
+/// # Safety
+///
+/// `rwlock` must point to a live lock.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_try_write(rwlock: *const RwLock<()>) -> bool {
+    (*rwlock).raw_try_write()
+}
+
+/// Like `RwLock::force_unlock_write`.
+///
+/// # Safety
+///
+/// `rwlock` must point to a live lock, write-locked by the caller.
+#[no_mangle]
+pub unsafe extern "C" fn antidote_rwlock_write_unlock(rwlock: *const RwLock<()>) {
+    (*rwlock).force_unlock_write();
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+use {Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A reader-writer lock for coordinating between processes through an
+/// advisory lock on a file.
+///
+/// This uses `flock` on Unix and `LockFileEx` on Windows. Since those locks
+/// belong to an open file rather than to a thread, the lock is also guarded
+/// by an in-process `RwLock` so that it excludes threads of the same process
+/// sharing one `FsRwLock` as well.
+///
+/// The locks are advisory: they only exclude other processes which also lock
+/// the file, and do not prevent anyone from reading or writing it.
+pub struct FsRwLock {
+    file: File,
+    inner: RwLock<()>,
+    // The number of in-process readers. The first reader takes the shared
+    // file lock and the last one releases it.
+    readers: Mutex<usize>,
+}
+
+impl fmt::Debug for FsRwLock {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("FsRwLock").field("file", &self.file).finish()
+    }
+}
+
+impl FsRwLock {
+    /// Opens the lock file at the specified path, creating it if it does not
+    /// exist.
+    ///
+    /// The contents of the file are not modified.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FsRwLock> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        Ok(FsRwLock::from_file(file))
+    }
+
+    /// Creates a lock using an already open file.
+    pub fn from_file(file: File) -> FsRwLock {
+        FsRwLock {
+            file,
+            inner: RwLock::new(()),
+            readers: Mutex::new(0),
+        }
+    }
+
+    /// Returns the underlying file.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Acquires a shared lock on the file, blocking until it is available.
+    pub fn read<'a>(&'a self) -> io::Result<FsRwLockReadGuard<'a>> {
+        let guard = self.inner.read();
+        let mut readers = self.readers.lock();
+        if *readers == 0 {
+            self.file.lock_shared()?;
+        }
+        *readers += 1;
+        Ok(FsRwLockReadGuard {
+            lock: self,
+            _guard: guard,
+        })
+    }
+
+    /// Attempts to acquire a shared lock on the file without blocking.
+    ///
+    /// Returns `None` if the lock is held exclusively by another thread or
+    /// process.
+    pub fn try_read<'a>(&'a self) -> io::Result<Option<FsRwLockReadGuard<'a>>> {
+        let guard = match self.inner.try_read() {
+            Ok(guard) => guard,
+            Err(_) => return Ok(None),
+        };
+        let mut readers = self.readers.lock();
+        if *readers == 0 {
+            match self.file.try_lock_shared() {
+                Ok(()) => {}
+                Err(fs::TryLockError::WouldBlock) => return Ok(None),
+                Err(fs::TryLockError::Error(e)) => return Err(e),
+            }
+        }
+        *readers += 1;
+        Ok(Some(FsRwLockReadGuard {
+            lock: self,
+            _guard: guard,
+        }))
+    }
+
+    /// Acquires an exclusive lock on the file, blocking until it is
+    /// available.
+    pub fn write<'a>(&'a self) -> io::Result<FsRwLockWriteGuard<'a>> {
+        let guard = self.inner.write();
+        self.file.lock()?;
+        Ok(FsRwLockWriteGuard {
+            lock: self,
+            _guard: guard,
+        })
+    }
+
+    /// Attempts to acquire an exclusive lock on the file without blocking.
+    ///
+    /// Returns `None` if the lock is held by another thread or process.
+    pub fn try_write<'a>(&'a self) -> io::Result<Option<FsRwLockWriteGuard<'a>>> {
+        let guard = match self.inner.try_write() {
+            Ok(guard) => guard,
+            Err(_) => return Ok(None),
+        };
+        match self.file.try_lock() {
+            Ok(()) => {}
+            Err(fs::TryLockError::WouldBlock) => return Ok(None),
+            Err(fs::TryLockError::Error(e)) => return Err(e),
+        }
+        Ok(Some(FsRwLockWriteGuard {
+            lock: self,
+            _guard: guard,
+        }))
+    }
+}
+
+/// An RAII guard holding a shared lock on an `FsRwLock`.
+#[must_use]
+pub struct FsRwLockReadGuard<'a> {
+    lock: &'a FsRwLock,
+    _guard: RwLockReadGuard<'a, ()>,
+}
+
+impl<'a> fmt::Debug for FsRwLockReadGuard<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("FsRwLockReadGuard").field("lock", self.lock).finish()
+    }
+}
+
+impl<'a> Drop for FsRwLockReadGuard<'a> {
+    fn drop(&mut self) {
+        let mut readers = self.lock.readers.lock();
+        *readers -= 1;
+        if *readers == 0 {
+            let _ = self.lock.file.unlock();
+        }
+    }
+}
+
+/// An RAII guard holding an exclusive lock on an `FsRwLock`.
+#[must_use]
+pub struct FsRwLockWriteGuard<'a> {
+    lock: &'a FsRwLock,
+    _guard: RwLockWriteGuard<'a, ()>,
+}
+
+impl<'a> fmt::Debug for FsRwLockWriteGuard<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("FsRwLockWriteGuard").field("lock", self.lock).finish()
+    }
+}
+
+impl<'a> Drop for FsRwLockWriteGuard<'a> {
+    fn drop(&mut self) {
+        let _ = self.lock.file.unlock();
+    }
+}
@@ -0,0 +1,137 @@
+//! Futex operations on macOS and iOS, performed with
+//! `os_sync_wait_on_address` and `os_sync_wake_by_address_*`.
+//!
+//! Those functions are public API from macOS 14.4 and iOS 17.4 on. They are
+//! looked up when first needed rather than linked, so that the same binary
+//! still runs on older systems, where every operation goes to the portable
+//! parking implementation instead. The choice is made once per process, so
+//! waiters and wakers always agree on it.
+
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use libc::{self, c_char, c_int, c_void};
+
+use super::fallback;
+
+const OS_SYNC_WAIT_ON_ADDRESS_NONE: u32 = 0;
+const OS_SYNC_WAKE_BY_ADDRESS_NONE: u32 = 0;
+const OS_CLOCK_MACH_ABSOLUTE_TIME: u32 = 32;
+
+type WaitFn = unsafe extern "C" fn(*mut c_void, u64, usize, u32) -> c_int;
+type WaitWithTimeoutFn = unsafe extern "C" fn(*mut c_void, u64, usize, u32, u32, u64) -> c_int;
+type WakeFn = unsafe extern "C" fn(*mut c_void, usize, u32) -> c_int;
+
+struct OsSync {
+    wait: WaitFn,
+    wait_with_timeout: WaitWithTimeoutFn,
+    wake_any: WakeFn,
+    wake_all: WakeFn,
+}
+
+impl OsSync {
+    fn load() -> Option<OsSync> {
+        unsafe {
+            Some(OsSync {
+                wait: mem::transmute::<*mut c_void, WaitFn>(lookup(b"os_sync_wait_on_address\0")?),
+                wait_with_timeout: mem::transmute::<*mut c_void, WaitWithTimeoutFn>(
+                    lookup(b"os_sync_wait_on_address_with_timeout\0")?
+                ),
+                wake_any: mem::transmute::<*mut c_void, WakeFn>(lookup(b"os_sync_wake_by_address_any\0")?),
+                wake_all: mem::transmute::<*mut c_void, WakeFn>(lookup(b"os_sync_wake_by_address_all\0")?),
+            })
+        }
+    }
+}
+
+fn lookup(name: &[u8]) -> Option<*mut c_void> {
+    let ptr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr() as *const c_char) };
+    if ptr.is_null() {
+        None
+    } else {
+        Some(ptr)
+    }
+}
+
+fn os_sync() -> Option<&'static OsSync> {
+    static OS_SYNC: OnceLock<Option<OsSync>> = OnceLock::new();
+    OS_SYNC.get_or_init(OsSync::load).as_ref()
+}
+
+fn addr(futex: &AtomicU32) -> *mut c_void {
+    futex as *const AtomicU32 as *mut c_void
+}
+
+pub fn wait(futex: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+    let os_sync = match os_sync() {
+        Some(os_sync) => os_sync,
+        None => return fallback::wait(futex, expected, deadline),
+    };
+
+    loop {
+        if futex.load(Ordering::Relaxed) != expected {
+            return true;
+        }
+
+        // The timeout is recomputed from the deadline on every iteration so
+        // that interruptions don't extend the total wait.
+        let r = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return false;
+                }
+                let timeout = deadline - now;
+                let timeout_ns = timeout.as_nanos().clamp(1, u64::MAX as u128) as u64;
+                unsafe {
+                    (os_sync.wait_with_timeout)(addr(futex),
+                                                expected as u64,
+                                                mem::size_of::<AtomicU32>(),
+                                                OS_SYNC_WAIT_ON_ADDRESS_NONE,
+                                                OS_CLOCK_MACH_ABSOLUTE_TIME,
+                                                timeout_ns)
+                }
+            }
+            None => unsafe {
+                (os_sync.wait)(addr(futex),
+                               expected as u64,
+                               mem::size_of::<AtomicU32>(),
+                               OS_SYNC_WAIT_ON_ADDRESS_NONE)
+            },
+        };
+
+        if r < 0 {
+            match io::Error::last_os_error().raw_os_error() {
+                Some(libc::ETIMEDOUT) | Some(libc::EINTR) => continue,
+                _ => return true,
+            }
+        }
+        return true;
+    }
+}
+
+pub fn wake_one(futex: &AtomicU32) -> bool {
+    match os_sync() {
+        Some(os_sync) => {
+            // Fails with `ENOENT` if nobody was waiting.
+            let r = unsafe { (os_sync.wake_any)(addr(futex), mem::size_of::<AtomicU32>(), OS_SYNC_WAKE_BY_ADDRESS_NONE) };
+            r == 0
+        }
+        None => fallback::wake_one(futex),
+    }
+}
+
+pub fn wake_all(futex: &AtomicU32) -> usize {
+    match os_sync() {
+        Some(os_sync) => {
+            // The number of threads woken isn't reported, only whether there
+            // were any.
+            let r = unsafe { (os_sync.wake_all)(addr(futex), mem::size_of::<AtomicU32>(), OS_SYNC_WAKE_BY_ADDRESS_NONE) };
+            (r == 0) as usize
+        }
+        None => fallback::wake_all(futex),
+    }
+}
@@ -0,0 +1,127 @@
+//! A portable futex emulation built on `std::thread::park`.
+//!
+//! Waiting threads are queued in a fixed table of buckets keyed by the
+//! address of the atomic they are waiting on. Each bucket is protected by a
+//! standard library mutex, which is never held while running user code.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, Thread};
+use std::time::Instant;
+
+const BUCKET_BITS: u32 = 6;
+
+struct Waiter {
+    key: usize,
+    thread: Thread,
+    woken: *const AtomicBool,
+}
+
+// The `woken` pointer refers to the waiting thread's stack, which stays alive
+// until that thread has either been woken or removed itself from the queue,
+// both of which happen under the bucket lock.
+unsafe impl Send for Waiter {}
+
+struct Bucket {
+    queue: Mutex<Vec<Waiter>>,
+}
+
+impl Bucket {
+    const fn new() -> Bucket {
+        Bucket { queue: Mutex::new(Vec::new()) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Waiter>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+static TABLE: [Bucket; 1 << BUCKET_BITS] = [const { Bucket::new() }; 1 << BUCKET_BITS];
+
+fn bucket(key: usize) -> &'static Bucket {
+    let hash = (key as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - BUCKET_BITS);
+    &TABLE[hash as usize]
+}
+
+pub fn wait(futex: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+    let key = futex as *const AtomicU32 as usize;
+    let bucket = bucket(key);
+    let woken = AtomicBool::new(false);
+
+    {
+        let mut queue = bucket.lock();
+        // Wakers modify the futex before taking the bucket lock, so checking
+        // it here cannot miss a wakeup.
+        if futex.load(Ordering::Relaxed) != expected {
+            return true;
+        }
+        queue.push(Waiter {
+            key,
+            thread: thread::current(),
+            woken: &woken,
+        });
+    }
+
+    loop {
+        if woken.load(Ordering::Acquire) {
+            return true;
+        }
+
+        match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    break;
+                }
+                thread::park_timeout(deadline - now);
+            }
+            None => thread::park(),
+        }
+    }
+
+    let mut queue = bucket.lock();
+    if woken.load(Ordering::Acquire) {
+        return true;
+    }
+    let ptr = &woken as *const AtomicBool;
+    queue.retain(|w| w.woken != ptr);
+    false
+}
+
+pub fn wake_one(futex: &AtomicU32) -> bool {
+    let key = futex as *const AtomicU32 as usize;
+    let thread = {
+        let mut queue = bucket(key).lock();
+        let idx = match queue.iter().position(|w| w.key == key) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let waiter = queue.remove(idx);
+        unsafe { (*waiter.woken).store(true, Ordering::Release) };
+        waiter.thread
+    };
+    thread.unpark();
+    true
+}
+
+pub fn wake_all(futex: &AtomicU32) -> usize {
+    let key = futex as *const AtomicU32 as usize;
+    let mut threads = vec![];
+    {
+        let mut queue = bucket(key).lock();
+        let mut i = 0;
+        while i < queue.len() {
+            if queue[i].key == key {
+                let waiter = queue.remove(i);
+                unsafe { (*waiter.woken).store(true, Ordering::Release) };
+                threads.push(waiter.thread);
+            } else {
+                i += 1;
+            }
+        }
+    }
+    for thread in &threads {
+        thread.unpark();
+    }
+    threads.len()
+}
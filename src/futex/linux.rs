@@ -0,0 +1,70 @@
+//! Futex operations on Linux, performed directly with `futex(2)`.
+
+use std::io;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
+use libc;
+
+pub fn wait(futex: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+    loop {
+        if futex.load(Ordering::Relaxed) != expected {
+            return true;
+        }
+
+        // The timeout is recomputed from the deadline on every iteration so
+        // that interruptions by signals don't extend the total wait.
+        let timeout = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return false;
+                }
+                let timeout = deadline - now;
+                Some(libc::timespec {
+                    tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+                    tv_nsec: timeout.subsec_nanos() as _,
+                })
+            }
+            None => None,
+        };
+
+        let r = unsafe {
+            libc::syscall(libc::SYS_futex,
+                          futex as *const AtomicU32,
+                          libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                          expected,
+                          timeout.as_ref().map_or(ptr::null(), |t| t as *const libc::timespec))
+        };
+
+        if r < 0 {
+            match io::Error::last_os_error().raw_os_error() {
+                Some(libc::ETIMEDOUT) => return false,
+                Some(libc::EINTR) => continue,
+                _ => return true,
+            }
+        }
+        return true;
+    }
+}
+
+pub fn wake_one(futex: &AtomicU32) -> bool {
+    let r = unsafe {
+        libc::syscall(libc::SYS_futex,
+                      futex as *const AtomicU32,
+                      libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                      1)
+    };
+    r > 0
+}
+
+pub fn wake_all(futex: &AtomicU32) -> usize {
+    let r = unsafe {
+        libc::syscall(libc::SYS_futex,
+                      futex as *const AtomicU32,
+                      libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                      i32::MAX)
+    };
+    if r > 0 { r as usize } else { 0 }
+}
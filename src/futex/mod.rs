@@ -0,0 +1,75 @@
+//! Futex-style waiting on an atomic word.
+//!
+//! The raw lock implementations are written against these three operations,
+//! which mirror the Linux `futex(2)` interface. On Linux and Android they
+//! are that system call. With the `futex` feature they also map directly
+//! onto the native equivalent on other platforms: `WaitOnAddress` on Windows,
+//! and `os_sync_wait_on_address` on macOS 14.4 and iOS 17.4 or later. Older
+//! Apple systems have no public futex API, so there the choice is made at
+//! runtime, falling back to the emulation. On WebAssembly without threads
+//! there is nobody to wait for, so contention panics rather than hanging.
+//! Everywhere else they are emulated, which on WebAssembly with threads ends
+//! up in `memory.atomic.wait32` through the standard library's thread
+//! parking.
+
+use std::sync::atomic::AtomicU32;
+use std::time::Instant;
+
+#[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+use wasm;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[path = "linux.rs"]
+mod imp;
+#[cfg(all(feature = "futex", windows))]
+#[path = "windows.rs"]
+mod imp;
+#[cfg(all(feature = "futex", any(target_os = "macos", target_os = "ios")))]
+#[path = "darwin.rs"]
+mod imp;
+#[cfg(all(feature = "futex", any(target_os = "macos", target_os = "ios")))]
+#[path = "generic.rs"]
+mod fallback;
+#[cfg(all(target_family = "wasm", not(target_feature = "atomics")))]
+#[path = "wasm_single.rs"]
+mod imp;
+#[cfg(not(any(target_os = "linux",
+              target_os = "android",
+              all(feature = "futex", any(target_os = "macos", target_os = "ios", windows)),
+              all(target_family = "wasm", not(target_feature = "atomics")))))]
+#[path = "generic.rs"]
+mod imp;
+
+/// Blocks the current thread while `futex` contains `expected`.
+///
+/// Returns `false` if the deadline passed before the thread was woken. Like a
+/// real futex, this may return spuriously.
+#[inline]
+pub fn wait(futex: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+    #[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+    {
+        if !wasm::blocking_allowed() {
+            return wasm::spin_wait(futex, expected, deadline);
+        }
+    }
+
+    imp::wait(futex, expected, deadline)
+}
+
+/// Wakes a single thread blocked on `futex`, returning whether one was woken.
+///
+/// Some platforms don't report this, in which case this returns `false`.
+#[inline]
+pub fn wake_one(futex: &AtomicU32) -> bool {
+    imp::wake_one(futex)
+}
+
+/// Wakes all threads blocked on `futex`, returning the number woken.
+///
+/// Some platforms only report whether any thread was woken, in which case
+/// this returns 1 if one was, and others don't report anything, in which case
+/// this returns 0.
+#[inline]
+pub fn wake_all(futex: &AtomicU32) -> usize {
+    imp::wake_all(futex)
+}
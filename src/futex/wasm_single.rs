@@ -0,0 +1,26 @@
+//! Futex operations on WebAssembly targets without threads.
+//!
+//! With only one thread nothing can change a futex while it is being waited
+//! on, so a wait which would block can never end. Rather than hang, waiting
+//! panics, and a timed wait gives up immediately.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
+pub fn wait(futex: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+    if futex.load(Ordering::Relaxed) != expected {
+        return true;
+    }
+    if deadline.is_some() {
+        return false;
+    }
+    panic!("lock is contended, which would deadlock on a single-threaded target");
+}
+
+pub fn wake_one(_: &AtomicU32) -> bool {
+    false
+}
+
+pub fn wake_all(_: &AtomicU32) -> usize {
+    0
+}
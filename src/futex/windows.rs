@@ -0,0 +1,70 @@
+//! Futex operations on Windows, performed directly with `WaitOnAddress`.
+//!
+//! The wake functions don't report whether any thread was woken, so they
+//! claim that none was. Callers treat that as a cue to wake someone else,
+//! which costs at most a spurious wakeup.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
+const INFINITE: u32 = !0;
+const ERROR_TIMEOUT: u32 = 1460;
+
+#[link(name = "synchronization")]
+extern "system" {
+    fn WaitOnAddress(address: *const u32, compare_address: *const u32, address_size: usize, milliseconds: u32) -> i32;
+    fn WakeByAddressSingle(address: *const u32);
+    fn WakeByAddressAll(address: *const u32);
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetLastError() -> u32;
+}
+
+pub fn wait(futex: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+    loop {
+        if futex.load(Ordering::Relaxed) != expected {
+            return true;
+        }
+
+        // The timeout is recomputed from the deadline on every iteration, as
+        // `WaitOnAddress` may return spuriously.
+        let timeout = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return false;
+                }
+                // Round up, so that we don't wake before the deadline and
+                // spin on zero-length waits.
+                let timeout = deadline - now;
+                let millis = timeout.as_millis() + (timeout.subsec_nanos() % 1_000_000 != 0) as u128;
+                millis.min(INFINITE as u128 - 1) as u32
+            }
+            None => INFINITE,
+        };
+
+        let r = unsafe {
+            WaitOnAddress(futex as *const AtomicU32 as *const u32,
+                          &expected,
+                          4,
+                          timeout)
+        };
+
+        if r == 0 && unsafe { GetLastError() } == ERROR_TIMEOUT {
+            continue;
+        }
+        return true;
+    }
+}
+
+pub fn wake_one(futex: &AtomicU32) -> bool {
+    unsafe { WakeByAddressSingle(futex as *const AtomicU32 as *const u32) }
+    false
+}
+
+pub fn wake_all(futex: &AtomicU32) -> usize {
+    unsafe { WakeByAddressAll(futex as *const AtomicU32 as *const u32) }
+    0
+}
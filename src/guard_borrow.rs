@@ -0,0 +1,90 @@
+use std::borrow::{Borrow, BorrowMut};
+
+#[cfg(feature = "pi_mutex")]
+use PiMutexGuard;
+#[cfg(all(feature = "shm", any(target_os = "linux", target_os = "android")))]
+use ShmMutexGuard;
+use {ArcMutexGuard, ArcRwLockReadGuard, ArcRwLockWriteGuard, AsyncMutexGuard, AsyncRwLockReadGuard,
+     AsyncRwLockWriteGuard, BiasedRwLockReadGuard, BiasedRwLockWriteGuard, DoubleBufferWriteGuard, ElidedMutexGuard,
+     LeftRightReadGuard, MappedArcMutexGuard, MappedArcRwLockReadGuard, MappedArcRwLockWriteGuard, MappedMutexGuard,
+     MappedRwLockReadGuard, MappedRwLockWriteGuard, MaybeMutexGuard, MutexGuard, OwnedAsyncMutexGuard,
+     OwnedAsyncRwLockReadGuard, OwnedAsyncRwLockWriteGuard, RankedMutexGuard, RwLockReadGuard,
+     RwLockUpgradableReadGuard, RwLockWriteGuard, Threading, TicketMutexGuard, VersionedRwLockWriteGuard};
+
+// Lets a guard be passed where a reference to the locked data is expected
+// through `AsRef` or `Borrow`.
+macro_rules! forward_borrow {
+    ([$($params:tt)*] $guard:ty => $target:ident) => {
+        impl<$($params)*> AsRef<$target> for $guard {
+            #[inline]
+            fn as_ref(&self) -> &$target {
+                self
+            }
+        }
+
+        impl<$($params)*> Borrow<$target> for $guard {
+            #[inline]
+            fn borrow(&self) -> &$target {
+                self
+            }
+        }
+    };
+}
+
+// Like `forward_borrow`, and also forwards `AsMut` and `BorrowMut` for an
+// exclusive guard.
+macro_rules! forward_borrow_mut {
+    ([$($params:tt)*] $guard:ty => $target:ident) => {
+        forward_borrow!([$($params)*] $guard => $target);
+
+        impl<$($params)*> AsMut<$target> for $guard {
+            #[inline]
+            fn as_mut(&mut self) -> &mut $target {
+                self
+            }
+        }
+
+        impl<$($params)*> BorrowMut<$target> for $guard {
+            #[inline]
+            fn borrow_mut(&mut self) -> &mut $target {
+                self
+            }
+        }
+    };
+}
+
+forward_borrow_mut!(['a, T: ?Sized] MutexGuard<'a, T> => T);
+forward_borrow_mut!(['a, T: ?Sized] MappedMutexGuard<'a, T> => T);
+forward_borrow_mut!([T: ?Sized] ArcMutexGuard<T> => T);
+forward_borrow_mut!([T: ?Sized, U: ?Sized] MappedArcMutexGuard<T, U> => U);
+
+forward_borrow!(['a, T: ?Sized] RwLockReadGuard<'a, T> => T);
+forward_borrow!(['a, T: ?Sized] MappedRwLockReadGuard<'a, T> => T);
+forward_borrow!(['a, T: ?Sized] RwLockUpgradableReadGuard<'a, T> => T);
+forward_borrow!([T: ?Sized] ArcRwLockReadGuard<T> => T);
+forward_borrow!([T: ?Sized, U: ?Sized] MappedArcRwLockReadGuard<T, U> => U);
+forward_borrow_mut!(['a, T: ?Sized] RwLockWriteGuard<'a, T> => T);
+forward_borrow_mut!(['a, T: ?Sized] MappedRwLockWriteGuard<'a, T> => T);
+forward_borrow_mut!([T: ?Sized] ArcRwLockWriteGuard<T> => T);
+forward_borrow_mut!([T: ?Sized, U: ?Sized] MappedArcRwLockWriteGuard<T, U> => U);
+
+forward_borrow_mut!(['a, T: ?Sized] AsyncMutexGuard<'a, T> => T);
+forward_borrow_mut!([T: ?Sized] OwnedAsyncMutexGuard<T> => T);
+forward_borrow!(['a, T: ?Sized] AsyncRwLockReadGuard<'a, T> => T);
+forward_borrow!([T: ?Sized] OwnedAsyncRwLockReadGuard<T> => T);
+forward_borrow_mut!(['a, T: ?Sized] AsyncRwLockWriteGuard<'a, T> => T);
+forward_borrow_mut!([T: ?Sized] OwnedAsyncRwLockWriteGuard<T> => T);
+
+forward_borrow!(['a, T: ?Sized] BiasedRwLockReadGuard<'a, T> => T);
+forward_borrow_mut!(['a, T: ?Sized] BiasedRwLockWriteGuard<'a, T> => T);
+forward_borrow_mut!(['a, T] DoubleBufferWriteGuard<'a, T> => T);
+forward_borrow_mut!(['a, T: ?Sized] ElidedMutexGuard<'a, T> => T);
+forward_borrow!(['a, T] LeftRightReadGuard<'a, T> => T);
+forward_borrow_mut!(['a, T: ?Sized, M: Threading] MaybeMutexGuard<'a, T, M> => T);
+#[cfg(feature = "pi_mutex")]
+forward_borrow_mut!(['a, T: ?Sized] PiMutexGuard<'a, T> => T);
+forward_borrow_mut!(['a, 't, T: ?Sized, const RANK: u32] RankedMutexGuard<'a, 't, T, RANK> => T);
+#[cfg(all(feature = "shm", any(target_os = "linux", target_os = "android")))]
+forward_borrow_mut!(['a, T: ?Sized] ShmMutexGuard<'a, T> => T);
+forward_borrow_mut!(['a, T: ?Sized] TicketMutexGuard<'a, T> => T);
+forward_borrow_mut!(['a, T: ?Sized] VersionedRwLockWriteGuard<'a, T> => T);
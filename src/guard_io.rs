@@ -0,0 +1,97 @@
+use std::fmt;
+use std::io::{self, IoSlice, IoSliceMut, SeekFrom};
+
+use mutex::MutexGuard;
+use rwlock::RwLockWriteGuard;
+
+// Forwards the I/O traits implemented by the locked data to an exclusive
+// guard, so that the guard can be used directly as a reader or writer.
+macro_rules! forward_io {
+    ($guard:ident) => {
+        impl<'a, T: ?Sized + io::Read> io::Read for $guard<'a, T> {
+            #[inline]
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                (**self).read(buf)
+            }
+
+            #[inline]
+            fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+                (**self).read_vectored(bufs)
+            }
+
+            #[inline]
+            fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+                (**self).read_to_end(buf)
+            }
+
+            #[inline]
+            fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+                (**self).read_to_string(buf)
+            }
+
+            #[inline]
+            fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+                (**self).read_exact(buf)
+            }
+        }
+
+        impl<'a, T: ?Sized + io::Write> io::Write for $guard<'a, T> {
+            #[inline]
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                (**self).write(buf)
+            }
+
+            #[inline]
+            fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
+                (**self).write_vectored(bufs)
+            }
+
+            #[inline]
+            fn flush(&mut self) -> io::Result<()> {
+                (**self).flush()
+            }
+
+            #[inline]
+            fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+                (**self).write_all(buf)
+            }
+
+            #[inline]
+            fn write_fmt(&mut self, args: fmt::Arguments) -> io::Result<()> {
+                (**self).write_fmt(args)
+            }
+        }
+
+        impl<'a, T: ?Sized + io::Seek> io::Seek for $guard<'a, T> {
+            #[inline]
+            fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+                (**self).seek(pos)
+            }
+
+            #[inline]
+            fn stream_position(&mut self) -> io::Result<u64> {
+                (**self).stream_position()
+            }
+        }
+
+        impl<'a, T: ?Sized + fmt::Write> fmt::Write for $guard<'a, T> {
+            #[inline]
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                (**self).write_str(s)
+            }
+
+            #[inline]
+            fn write_char(&mut self, c: char) -> fmt::Result {
+                (**self).write_char(c)
+            }
+
+            #[inline]
+            fn write_fmt(&mut self, args: fmt::Arguments) -> fmt::Result {
+                (**self).write_fmt(args)
+            }
+        }
+    };
+}
+
+forward_io!(MutexGuard);
+forward_io!(RwLockWriteGuard);
@@ -0,0 +1,353 @@
+//! Tracking of the locks held by the current thread.
+//!
+//! In debug builds, each thread records the locks it holds and where it
+//! acquired them, so that an attempt to acquire a lock it already holds
+//! panics rather than deadlocking forever, and so that a guard smuggled to
+//! another thread with `unsafe` code panics when it is dropped there rather
+//! than unlocking a lock that thread never acquired. The lock is still
+//! released, and its record is removed from the thread which acquired it, so
+//! that thread isn't later told it would deadlock on it. Tracking is disabled
+//! when the `send_guard` feature is enabled, since guards may then be
+//! released on a different thread than the one which acquired them.
+//!
+//! Only dropping a guard is checked. Using one from another thread is
+//! legitimate when the protected value is `Sync`, as the guard is then
+//! `Sync` as well.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+pub use self::imp::*;
+
+// Set once `install_panic_hook` has been called. Names are only recorded
+// from then on, or while a `LockTracker` exists, as doing so allocates.
+static PANIC_HOOK: AtomicBool = AtomicBool::new(false);
+
+// The number of `LockTracker`s which currently exist.
+pub static TRACKERS: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(all(debug_assertions, not(feature = "send_guard")))]
+fn record_names() -> bool {
+    PANIC_HOOK.load(Ordering::Relaxed) || TRACKERS.load(Ordering::Relaxed) != 0
+}
+
+/// Installs a panic hook which lists the locks held by the panicking
+/// thread, with their names and where they were acquired.
+///
+/// Other threads which go on to block on one of those locks are a common
+/// consequence of a panic, and the report identifies the culprit. The
+/// previously installed hook still runs first. Calling this more than once
+/// has no further effect.
+///
+/// The report relies on the tracking of held locks, so this only has an
+/// effect in debug builds without the `send_guard` feature. It covers locks
+/// acquired after it is installed.
+///
+/// ```
+/// use antidote::MutexBuilder;
+///
+/// antidote::install_panic_hook();
+///
+/// let mutex = MutexBuilder::new().name("accounts").build(0);
+/// let _guard = mutex.lock();
+/// // A panic here prints something like:
+/// //
+/// // antidote: thread 'main' panicked while holding 1 lock:
+/// //   Mutex "accounts" at 0x7ffd5ac2a6f0, acquired exclusively at src/main.rs:6:20
+/// ```
+pub fn install_panic_hook() {
+    if !PANIC_HOOK.swap(true, Ordering::Relaxed) {
+        imp::install_panic_hook();
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Exclusive,
+    Shared,
+    Upgradable,
+}
+
+#[cfg(all(debug_assertions, not(feature = "send_guard")))]
+mod imp {
+    use std::cell::Cell;
+    use std::fmt::Write;
+    use std::panic::{self, Location};
+    use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError, Weak};
+    use std::thread;
+
+    use super::{record_names, Mode};
+    use lock_graph;
+
+    struct HeldLock {
+        kind: &'static str,
+        name: Option<String>,
+        addr: usize,
+        mode: Mode,
+        location: &'static Location<'static>,
+        // The order in which the thread acquired its locks.
+        seq: u64,
+    }
+
+    // Returns whether a lock held in one mode blocks acquiring it in the
+    // other. Only one upgradable reader may exist at a time.
+    fn conflicts(held: Mode, mode: Mode) -> bool {
+        !matches!((held, mode),
+                  (Mode::Shared, Mode::Shared) | (Mode::Shared, Mode::Upgradable) | (Mode::Upgradable, Mode::Shared))
+    }
+
+    type HeldList = Mutex<Vec<HeldLock>>;
+
+    // The lists of every live thread which has tracked a lock, so that a
+    // lock released on a thread other than the one which acquired it can be
+    // removed from the right one.
+    static THREADS: Mutex<Vec<Weak<HeldList>>> = Mutex::new(Vec::new());
+
+    thread_local! {
+        static HELD: Arc<HeldList> = register();
+        static NEXT_SEQ: Cell<u64> = const { Cell::new(0) };
+    }
+
+    fn register() -> Arc<HeldList> {
+        let held = Arc::new(Mutex::new(Vec::new()));
+        let mut threads = lock(&THREADS);
+        threads.retain(|t| t.strong_count() > 0);
+        threads.push(Arc::downgrade(&held));
+        held
+    }
+
+    fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Like `lock`, but fails rather than deadlocking if the current thread
+    // is panicking while it holds the lock.
+    fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+        match mutex.try_lock() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Panics if acquiring the lock at `addr` would deadlock because the
+    /// current thread already holds it.
+    ///
+    /// This is called before acquisitions which may block, which are also
+    /// recorded by `lock_graph` while it is running.
+    #[track_caller]
+    pub fn check(kind: &'static str, name: Option<&str>, addr: usize, mode: Mode) {
+        let conflict = HELD.try_with(|held| {
+                let held = lock(held);
+                if lock_graph::is_running() {
+                    lock_graph::record(kind,
+                                       name,
+                                       addr,
+                                       Location::caller(),
+                                       held.iter().map(|h| (h.addr, h.location)));
+                }
+                held.iter()
+                    .rev()
+                    .find(|h| h.addr == addr && conflicts(h.mode, mode))
+                    .map(|h| h.location)
+            })
+            .unwrap_or(None);
+
+        if let Some(location) = conflict {
+            panic!("deadlock detected: {} is already held by the current thread\n  \
+                    previously acquired at {}\n  \
+                    acquired again at {}",
+                   kind,
+                   location,
+                   Location::caller());
+        }
+    }
+
+    #[track_caller]
+    pub fn acquired(kind: &'static str, name: Option<&str>, addr: usize, mode: Mode) {
+        let location = Location::caller();
+        let name = if record_names() { name.map(str::to_string) } else { None };
+        let seq = mark();
+        let _ = NEXT_SEQ.try_with(|next| next.set(seq + 1));
+        let _ = HELD.try_with(|held| {
+            lock(held).push(HeldLock {
+                kind,
+                name,
+                addr,
+                mode,
+                location,
+                seq,
+            })
+        });
+    }
+
+    /// Returns a marker for the locks the current thread acquires from now
+    /// on, for `held_since`.
+    pub fn mark() -> u64 {
+        NEXT_SEQ.try_with(Cell::get).unwrap_or(0)
+    }
+
+    /// Describes the locks acquired by the current thread since `mark` was
+    /// called which it still holds, if there are any.
+    pub fn held_since(mark: u64) -> Option<String> {
+        HELD.try_with(|held| {
+                let held = try_lock(held)?;
+                let leaked = held.iter().filter(|h| h.seq >= mark).collect::<Vec<_>>();
+                if leaked.is_empty() {
+                    return None;
+                }
+                let mut report = format!("{} lock{} acquired within a LockTracker {} still held:\n",
+                                         leaked.len(),
+                                         if leaked.len() == 1 { "" } else { "s" },
+                                         if leaked.len() == 1 { "is" } else { "are" });
+                for h in leaked {
+                    describe(&mut report, h);
+                }
+                Some(report)
+            })
+            .unwrap_or(None)
+    }
+
+    /// Records that the current thread has released the lock at `addr`,
+    /// returning whether it held it.
+    ///
+    /// If it didn't, the record is removed from the thread which did. The
+    /// result is passed to `check_released` once the lock has actually been
+    /// released, so that the panic doesn't leave it locked.
+    pub fn released(addr: usize, mode: Mode) -> bool {
+        let held = release(addr, mode);
+        if !held {
+            release_elsewhere(addr, mode);
+        }
+        held
+    }
+
+    /// Panics if `released` found that the current thread didn't hold the
+    /// lock, which means its guard was sent to this thread.
+    pub fn check_released(held: bool) {
+        // Panicking while already unwinding would abort.
+        if !held && !thread::panicking() {
+            panic!("lock guard dropped on a thread which didn't acquire the lock");
+        }
+    }
+
+    /// Like `released`, but for a lock released without its guard, which
+    /// may legitimately happen on another thread.
+    #[cfg(feature = "raw")]
+    pub fn force_released(addr: usize, mode: Mode) {
+        if !release(addr, mode) {
+            release_elsewhere(addr, mode);
+        }
+    }
+
+    fn release(addr: usize, mode: Mode) -> bool {
+        HELD.try_with(|held| remove(held, addr, mode))
+            // The thread's record is already gone during thread-local
+            // destruction, so there is nothing to check.
+            .unwrap_or(true)
+    }
+
+    // Removes the record of a lock released by another thread from the list
+    // of the thread which acquired it. A read lock may be held by several
+    // threads, in which case there is no telling whose it was, and the first
+    // record found is removed.
+    fn release_elsewhere(addr: usize, mode: Mode) {
+        let threads = lock(&THREADS);
+        for held in threads.iter().filter_map(Weak::upgrade) {
+            if remove(&held, addr, mode) {
+                return;
+            }
+        }
+    }
+
+    fn remove(held: &HeldList, addr: usize, mode: Mode) -> bool {
+        let mut held = lock(held);
+        match held.iter().rposition(|h| h.addr == addr && h.mode == mode) {
+            Some(idx) => {
+                held.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn install_panic_hook() {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            if let Some(report) = report() {
+                eprint!("{}", report);
+            }
+        }));
+    }
+
+    // Describes the locks held by the current thread, if there are any.
+    fn report() -> Option<String> {
+        HELD.try_with(|held| {
+                // The panic may have been raised while the list was locked.
+                let held = try_lock(held)?;
+                if held.is_empty() {
+                    return None;
+                }
+                let thread = thread::current();
+                let mut report = format!("antidote: thread {} panicked while holding {} lock{}:\n",
+                                         thread.name()
+                                             .map_or_else(|| format!("{:?}", thread.id()), |s| format!("'{}'", s)),
+                                         held.len(),
+                                         if held.len() == 1 { "" } else { "s" });
+                for h in held.iter() {
+                    describe(&mut report, h);
+                }
+                Some(report)
+            })
+            .unwrap_or(None)
+    }
+
+    fn describe(report: &mut String, h: &HeldLock) {
+        let _ = writeln!(report,
+                         "  {}{} at {:#x}, acquired {} at {}",
+                         h.kind,
+                         h.name.as_ref().map_or_else(String::new, |name| format!(" {:?}", name)),
+                         h.addr,
+                         match h.mode {
+                             Mode::Exclusive => "exclusively",
+                             Mode::Shared => "for reading",
+                             Mode::Upgradable => "upgradably",
+                         },
+                         h.location);
+    }
+}
+
+#[cfg(not(all(debug_assertions, not(feature = "send_guard"))))]
+mod imp {
+    use super::Mode;
+
+    #[inline]
+    pub fn check(_: &'static str, _: Option<&str>, _: usize, _: Mode) {}
+
+    #[inline]
+    pub fn acquired(_: &'static str, _: Option<&str>, _: usize, _: Mode) {}
+
+    #[inline]
+    pub fn released(_: usize, _: Mode) -> bool {
+        true
+    }
+
+    #[inline]
+    pub fn check_released(_: bool) {}
+
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn force_released(_: usize, _: Mode) {}
+
+    #[inline]
+    pub fn mark() -> u64 {
+        0
+    }
+
+    #[inline]
+    pub fn held_since(_: u64) -> Option<String> {
+        None
+    }
+
+    pub fn install_panic_hook() {}
+}
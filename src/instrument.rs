@@ -0,0 +1,471 @@
+//! Optional per-lock instrumentation, enabled through the lock builders.
+//!
+//! Locks without any instrumentation pay for a single pointer which is
+//! checked on acquisition; everything else lives out of line, including
+//! per-lock settings like a custom backoff strategy.
+
+use std::fmt;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, Thread, ThreadId};
+use std::time::{Duration, Instant};
+
+use backoff::Backoff;
+use profiler;
+#[cfg(feature = "prometheus")]
+use prometheus;
+
+/// Statistics about a lock's acquisitions, as returned by methods like
+/// `Mutex::stats`.
+///
+/// Only successful acquisitions are counted. Upgrading an upgradable read
+/// lock to a write lock counts as an acquisition of its own.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct LockStats {
+    acquisitions: u64,
+    contended: u64,
+    total_wait: Duration,
+    max_wait: Duration,
+}
+
+impl LockStats {
+    /// Returns the number of times the lock has been acquired.
+    #[inline]
+    pub fn acquisitions(&self) -> u64 {
+        self.acquisitions
+    }
+
+    /// Returns the number of acquisitions which had to wait for the lock to
+    /// be released.
+    #[inline]
+    pub fn contended_acquisitions(&self) -> u64 {
+        self.contended
+    }
+
+    /// Returns the total time spent waiting for the lock.
+    #[inline]
+    pub fn total_wait(&self) -> Duration {
+        self.total_wait
+    }
+
+    /// Returns the longest time a single acquisition waited for the lock.
+    #[inline]
+    pub fn max_wait(&self) -> Duration {
+        self.max_wait
+    }
+}
+
+/// The kind of access a lock was held with, as reported by
+/// `UnlockEvent::mode`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LockMode {
+    /// Exclusive access: a mutex, or a reader-writer lock's write lock.
+    Exclusive,
+    /// Shared read access.
+    Shared,
+    /// Upgradable read access.
+    Upgradable,
+}
+
+/// Information about a release of a lock, passed to the callback registered
+/// with `MutexBuilder::on_unlock` or `RwLockBuilder::on_unlock`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct UnlockEvent {
+    mode: LockMode,
+    held: Duration,
+    panicking: bool,
+}
+
+impl UnlockEvent {
+    /// Returns the kind of access which was released.
+    #[inline]
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+
+    /// Returns how long the lock was held.
+    #[inline]
+    pub fn held(&self) -> Duration {
+        self.held
+    }
+
+    /// Returns whether the lock was released while the releasing thread was
+    /// panicking.
+    #[inline]
+    pub fn panicking(&self) -> bool {
+        self.panicking
+    }
+}
+
+/// The thread holding a lock, as returned by `Mutex::holder_info`.
+#[derive(Debug, Clone)]
+pub struct HolderInfo {
+    thread: Thread,
+    location: Option<&'static Location<'static>>,
+}
+
+impl HolderInfo {
+    /// Returns the ID of the thread holding the lock.
+    #[inline]
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread.id()
+    }
+
+    /// Returns the name of the thread holding the lock, if it has one.
+    #[inline]
+    pub fn thread_name(&self) -> Option<&str> {
+        self.thread.name()
+    }
+
+    /// Returns where the lock was acquired.
+    ///
+    /// This is only known in debug builds, where the lock methods are
+    /// `#[track_caller]`.
+    #[inline]
+    pub fn location(&self) -> Option<&'static Location<'static>> {
+        self.location
+    }
+}
+
+impl fmt::Display for HolderInfo {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.thread.name() {
+            Some(name) => write!(fmt, "thread '{}'", name)?,
+            None => write!(fmt, "thread {:?}", self.thread.id())?,
+        }
+        match self.location {
+            Some(location) => write!(fmt, " at {}", location),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A callback invoked whenever a lock is released.
+#[derive(Clone)]
+pub(crate) struct UnlockHook(Arc<dyn Fn(&UnlockEvent) + Send + Sync>);
+
+impl UnlockHook {
+    pub(crate) fn new<F>(f: F) -> UnlockHook
+        where F: Fn(&UnlockEvent) + Send + Sync + 'static
+    {
+        UnlockHook(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for UnlockHook {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("<custom>")
+    }
+}
+
+struct OnUnlock {
+    hook: UnlockHook,
+    epoch: Instant,
+    // When the lock was last acquired exclusively, in nanoseconds since
+    // `epoch`. There is only ever one such holder.
+    acquired_at: AtomicU64,
+    // Likewise for the one upgradable read lock holder. Shared holds overlap,
+    // so their guards keep their own `HoldStart` instead.
+    upgradable_at: AtomicU64,
+}
+
+impl OnUnlock {
+    fn now(&self) -> u64 {
+        self.epoch.elapsed().as_nanos().min(u64::MAX as u128) as u64
+    }
+}
+
+/// When a shared hold started, kept by the read guards of locks with an
+/// unlock hook.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct HoldStart(u64);
+
+impl HoldStart {
+    /// The start of a hold which is not timed.
+    pub(crate) const NONE: HoldStart = HoldStart(0);
+}
+
+/// A hold being released, as passed to `Instrument::release`.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum Hold {
+    Exclusive,
+    Shared(HoldStart),
+    Upgradable,
+}
+
+impl LockStats {
+    // Combines the statistics of two locks.
+    #[cfg(feature = "prometheus")]
+    pub(crate) fn merge(&mut self, other: &LockStats) {
+        self.acquisitions += other.acquisitions;
+        self.contended += other.contended;
+        self.total_wait += other.total_wait;
+        self.max_wait = self.max_wait.max(other.max_wait);
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Counters {
+    acquisitions: AtomicU64,
+    contended: AtomicU64,
+    wait_nanos: AtomicU64,
+    max_wait_nanos: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) fn stats(&self) -> LockStats {
+        LockStats {
+            acquisitions: self.acquisitions.load(Ordering::Relaxed),
+            contended: self.contended.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(self.wait_nanos.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(self.max_wait_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The per-lock settings of the lock builders.
+#[derive(Default)]
+pub(crate) struct Settings {
+    pub(crate) name: Option<String>,
+    pub(crate) backoff: Option<&'static dyn Backoff>,
+    pub(crate) watchdog: Option<Duration>,
+    pub(crate) stats: bool,
+    pub(crate) on_unlock: Option<UnlockHook>,
+    pub(crate) track_holder: bool,
+    pub(crate) fair: bool,
+}
+
+pub(crate) struct Instrument {
+    kind: &'static str,
+    name: Option<String>,
+    // The strategy for the contended path, if not the process-wide one.
+    backoff: Option<&'static dyn Backoff>,
+    watchdog: Option<Duration>,
+    // Shared with the registry read by `prometheus::gather`.
+    counters: Option<Arc<Counters>>,
+    // The number of threads in `acquire_contended`.
+    waiters: AtomicUsize,
+    on_unlock: Option<OnUnlock>,
+    // Whether every contended unlock hands the lock over to a waiter.
+    fair: bool,
+    // Recorded by exclusive locks only, like the unlock hook. A standard
+    // library mutex is used since this is updated while acquiring one of
+    // this crate's locks.
+    holder: Option<Mutex<Option<HolderInfo>>>,
+}
+
+impl Instrument {
+    /// Returns `None` if nothing is enabled.
+    pub(crate) fn new(kind: &'static str, settings: Settings) -> Option<Box<Instrument>> {
+        let Settings { name, backoff, watchdog, stats, on_unlock, track_holder, fair } = settings;
+        if name.is_none() && backoff.is_none() && watchdog.is_none() && !stats && on_unlock.is_none() && !track_holder &&
+           !fair {
+            return None;
+        }
+        let counters = if stats { Some(Arc::new(Counters::default())) } else { None };
+        #[cfg(feature = "prometheus")]
+        {
+            if let Some(ref counters) = counters {
+                prometheus::register(kind, name.as_deref(), counters);
+            }
+        }
+        Some(Box::new(Instrument {
+            kind,
+            name,
+            backoff,
+            watchdog,
+            counters,
+            waiters: AtomicUsize::new(0),
+            on_unlock: on_unlock.map(|hook| {
+                OnUnlock {
+                    hook,
+                    epoch: Instant::now(),
+                    acquired_at: AtomicU64::new(0),
+                    upgradable_at: AtomicU64::new(0),
+                }
+            }),
+            fair,
+            holder: if track_holder { Some(Mutex::new(None)) } else { None },
+        }))
+    }
+
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub(crate) fn backoff(&self) -> Option<&'static dyn Backoff> {
+        self.backoff
+    }
+
+    pub(crate) fn fair(&self) -> bool {
+        self.fair
+    }
+
+    pub(crate) fn stats(&self) -> Option<LockStats> {
+        self.counters.as_ref().map(|c| c.stats())
+    }
+
+    pub(crate) fn waiter_count(&self) -> usize {
+        self.waiters.load(Ordering::Relaxed)
+    }
+
+    /// Returns the lock's current holder, if it is tracked and the lock is
+    /// held.
+    pub(crate) fn holder_info(&self) -> Option<HolderInfo> {
+        self.holder.as_ref().and_then(|holder| lock(holder).clone())
+    }
+
+    /// Records an acquisition which did not wait.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub(crate) fn acquired(&self, exclusive: bool) {
+        if let Some(ref c) = self.counters {
+            c.acquisitions.fetch_add(1, Ordering::Relaxed);
+        }
+        if exclusive {
+            self.start_hold();
+        }
+    }
+
+    /// Acquires a lock with `lock_until`, after `try_lock` has failed.
+    ///
+    /// If the watchdog is enabled and the wait exceeds its threshold, a
+    /// warning is printed to stderr and the wait continues.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub(crate) fn acquire_contended<F>(&self,
+                                       addr: usize,
+                                       exclusive: bool,
+                                       deadline: Option<Instant>,
+                                       lock_until: F)
+                                       -> bool
+        where F: Fn(Option<Instant>) -> bool
+    {
+        if let Some(ref name) = self.name {
+            if profiler::is_running() {
+                profiler::name(addr, name);
+            }
+        }
+
+        self.waiters.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let locked = match self.watchdog.and_then(|t| start.checked_add(t)) {
+            Some(alarm) if deadline.is_none_or(|d| alarm < d) => {
+                lock_until(Some(alarm)) || {
+                    // An interruptible acquisition can also give up early.
+                    if Instant::now() >= alarm {
+                        self.report(addr, start);
+                    }
+                    lock_until(deadline)
+                }
+            }
+            _ => lock_until(deadline),
+        };
+        self.waiters.fetch_sub(1, Ordering::Relaxed);
+
+        if locked {
+            if let Some(ref c) = self.counters {
+                let wait = start.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+                c.acquisitions.fetch_add(1, Ordering::Relaxed);
+                c.contended.fetch_add(1, Ordering::Relaxed);
+                c.wait_nanos.fetch_add(wait, Ordering::Relaxed);
+                c.max_wait_nanos.fetch_max(wait, Ordering::Relaxed);
+            }
+            if exclusive {
+                self.start_hold();
+            }
+        }
+        locked
+    }
+
+    /// Records that the lock is now held exclusively, by an acquisition or
+    /// an upgrade.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub(crate) fn start_hold(&self) {
+        if let Some(ref on_unlock) = self.on_unlock {
+            on_unlock.acquired_at.store(on_unlock.now(), Ordering::Relaxed);
+        }
+        if let Some(ref holder) = self.holder {
+            *lock(holder) = Some(HolderInfo {
+                thread: thread::current(),
+                location: if cfg!(debug_assertions) { Some(Location::caller()) } else { None },
+            });
+        }
+    }
+
+    /// Records that the lock is now held for upgradable reading, by an
+    /// acquisition or a downgrade.
+    #[inline]
+    pub(crate) fn start_upgradable_hold(&self) {
+        if let Some(ref on_unlock) = self.on_unlock {
+            on_unlock.upgradable_at.store(on_unlock.now(), Ordering::Relaxed);
+        }
+    }
+
+    /// Records that the lock is now held for shared reading, returning the
+    /// start of the hold for the guard to pass back to `release`.
+    #[inline]
+    pub(crate) fn start_shared_hold(&self) -> HoldStart {
+        match self.on_unlock {
+            Some(ref on_unlock) => HoldStart(on_unlock.now()),
+            None => HoldStart::NONE,
+        }
+    }
+
+    /// Releases a hold on the lock with `unlock`, and then invokes the
+    /// unlock hook, if there is one.
+    #[inline]
+    pub(crate) fn release<F>(&self, hold: Hold, unlock: F)
+        where F: FnOnce()
+    {
+        if let (Hold::Exclusive, Some(holder)) = (hold, &self.holder) {
+            *lock(holder) = None;
+        }
+        match self.on_unlock {
+            None => unlock(),
+            Some(ref on_unlock) => {
+                // The acquisition time must be read before another thread can
+                // acquire the lock and overwrite it.
+                let (mode, acquired_at) = match hold {
+                    Hold::Exclusive => (LockMode::Exclusive, on_unlock.acquired_at.load(Ordering::Relaxed)),
+                    Hold::Shared(start) => (LockMode::Shared, start.0),
+                    Hold::Upgradable => (LockMode::Upgradable, on_unlock.upgradable_at.load(Ordering::Relaxed)),
+                };
+                let held = on_unlock.now().saturating_sub(acquired_at);
+                unlock();
+                (on_unlock.hook.0)(&UnlockEvent {
+                    mode,
+                    held: Duration::from_nanos(held),
+                    panicking: thread::panicking(),
+                });
+            }
+        }
+    }
+
+    #[cold]
+    fn report(&self, addr: usize, start: Instant) {
+        let thread = thread::current();
+        eprintln!("antidote: thread {} has waited {:?} to acquire {}{} at {:#x}{}",
+                  thread.name().map_or_else(|| format!("{:?}", thread.id()), |s| format!("'{}'", s)),
+                  start.elapsed(),
+                  self.kind,
+                  self.name.as_ref().map_or_else(String::new, |name| format!(" {:?}", name)),
+                  addr,
+                  self.holder_info().map_or_else(String::new, |holder| format!(", held by {}", holder)));
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl Drop for Instrument {
+    fn drop(&mut self) {
+        if let Some(ref counters) = self.counters {
+            prometheus::unregister(counters);
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
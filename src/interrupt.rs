@@ -0,0 +1,122 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{self, MutexGuard, PoisonError};
+use std::thread;
+
+use raw::RawMutex;
+
+/// A token which interrupts `Mutex::lock_interruptible`.
+///
+/// Once the token is interrupted, threads blocked in `lock_interruptible`
+/// with it are woken and give up with `TryLockError::Interrupted`, and later
+/// calls with it fail straight away. A token stays interrupted.
+///
+/// `interrupt` takes a lock, so it can't be called from a signal handler. To
+/// stop workers queued on a contended mutex when the process receives a
+/// signal, call it from a thread which waits for the signal instead, such as
+/// one iterating over `signal_hook::iterator::Signals`.
+///
+/// ```
+/// use antidote::{Interrupt, Mutex, TryLockError};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let mutex = Arc::new(Mutex::new(0));
+/// let interrupt = Arc::new(Interrupt::new());
+/// let guard = mutex.lock();
+/// let worker = {
+///     let (mutex, interrupt) = (mutex.clone(), interrupt.clone());
+///     thread::spawn(move || mutex.lock_interruptible(&interrupt).err())
+/// };
+/// interrupt.interrupt();
+/// assert_eq!(worker.join().unwrap(), Some(TryLockError::Interrupted));
+/// # drop(guard);
+/// ```
+pub struct Interrupt {
+    interrupted: AtomicBool,
+    // The address of the raw mutex each blocked thread is parked on. A
+    // standard library mutex is used since this is updated while acquiring
+    // one of this crate's locks.
+    waiting: sync::Mutex<Vec<usize>>,
+}
+
+impl fmt::Debug for Interrupt {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Interrupt").field("interrupted", &self.is_interrupted()).finish()
+    }
+}
+
+impl Default for Interrupt {
+    fn default() -> Interrupt {
+        Interrupt::new()
+    }
+}
+
+impl Interrupt {
+    /// Creates a token which has not been interrupted.
+    #[inline]
+    pub fn new() -> Interrupt {
+        Interrupt {
+            interrupted: AtomicBool::new(false),
+            waiting: sync::Mutex::new(vec![]),
+        }
+    }
+
+    /// Returns whether the token has been interrupted.
+    #[inline]
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Acquire)
+    }
+
+    /// Interrupts the token, returning once every thread blocked with it has
+    /// woken up.
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Release);
+        // A thread which checked the token just before it was interrupted
+        // may not have parked yet, and would sleep through a single wakeup,
+        // so the mutexes are woken until every thread has left.
+        loop {
+            {
+                let waiting = self.waiting();
+                if waiting.is_empty() {
+                    return;
+                }
+                for &raw in waiting.iter() {
+                    // Threads remove their entry before they return, so the
+                    // mutex is still alive.
+                    unsafe { (*(raw as *const RawMutex)).wake_all() };
+                }
+            }
+            thread::yield_now();
+        }
+    }
+
+    // Runs `lock`, which blocks on `raw`, with `raw` registered to be woken
+    // when the token is interrupted.
+    pub(crate) fn wait_on<F>(&self, raw: &RawMutex, lock: F) -> bool
+        where F: FnOnce() -> bool
+    {
+        struct Registration<'a> {
+            interrupt: &'a Interrupt,
+            raw: usize,
+        }
+
+        impl<'a> Drop for Registration<'a> {
+            fn drop(&mut self) {
+                let mut waiting = self.interrupt.waiting();
+                if let Some(i) = waiting.iter().position(|&raw| raw == self.raw) {
+                    waiting.swap_remove(i);
+                }
+            }
+        }
+
+        let raw = raw as *const RawMutex as usize;
+        self.waiting().push(raw);
+        let _registration = Registration { interrupt: self, raw };
+        lock()
+    }
+
+    fn waiting(&self) -> MutexGuard<'_, Vec<usize>> {
+        self.waiting.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
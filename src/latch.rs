@@ -0,0 +1,86 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use {Condvar, deadline_after, Mutex, WaitTimeoutResult};
+
+/// A latch which releases waiting threads once it has been counted down to
+/// zero, like Java's `CountDownLatch`.
+///
+/// A latch created with a count of one is a start gate: any number of
+/// threads wait on it until a single `count_down` opens it. A latch created
+/// with a count of `n` is a finish gate, opened once `n` tasks have counted
+/// down. Once open, a latch stays open.
+pub struct CountDownLatch {
+    count: Mutex<usize>,
+    cond: Condvar,
+}
+
+impl fmt::Debug for CountDownLatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("CountDownLatch").field("count", &self.count()).finish()
+    }
+}
+
+impl CountDownLatch {
+    /// Creates a new `CountDownLatch` which opens after `count` calls to
+    /// `count_down`.
+    #[inline]
+    pub fn new(count: usize) -> CountDownLatch {
+        CountDownLatch {
+            count: Mutex::new(count),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Decrements the count, opening the latch and waking all waiting threads
+    /// if it reaches zero.
+    ///
+    /// This has no effect if the latch is already open.
+    pub fn count_down(&self) {
+        let mut count = self.count.lock();
+        if *count == 0 {
+            return;
+        }
+        *count -= 1;
+        if *count == 0 {
+            self.cond.notify_all();
+        }
+    }
+
+    /// Returns the current count.
+    pub fn count(&self) -> usize {
+        *self.count.lock()
+    }
+
+    /// Blocks the current thread until the latch is open.
+    pub fn wait(&self) {
+        let mut count = self.count.lock();
+        while *count != 0 {
+            count = self.cond.wait(count);
+        }
+    }
+
+    /// Blocks the current thread until the latch is open, or the timeout
+    /// expires.
+    pub fn wait_timeout(&self, dur: Duration) -> WaitTimeoutResult {
+        match deadline_after(dur) {
+            Some(deadline) => self.wait_until(deadline),
+            None => {
+                self.wait();
+                WaitTimeoutResult(false)
+            }
+        }
+    }
+
+    /// Blocks the current thread until the latch is open, or `deadline` passes.
+    pub fn wait_until(&self, deadline: Instant) -> WaitTimeoutResult {
+        let mut count = self.count.lock();
+        while *count != 0 {
+            if Instant::now() >= deadline {
+                return WaitTimeoutResult(true);
+            }
+            count = self.cond.wait_until(count, deadline).0;
+        }
+        WaitTimeoutResult(false)
+    }
+}
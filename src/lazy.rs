@@ -0,0 +1,96 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use OnceCell;
+
+/// A value which is initialized on first access, like `std::sync::LazyLock`
+/// except that it does not poison itself.
+///
+/// If the initialization function panics, the value remains uninitialized
+/// and the next access runs the function again. This is why the function
+/// must be `Fn` rather than `FnOnce`.
+pub struct Lazy<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: F,
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Lazy<T, F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.cell.get() {
+            Some(value) => fmt.debug_tuple("Lazy").field(value).finish(),
+            None => fmt.write_str("Lazy(<uninit>)"),
+        }
+    }
+}
+
+impl<T, F> Lazy<T, F> {
+    /// Creates a new lazy value with the given initialization function.
+    #[inline]
+    pub fn new(init: F) -> Lazy<T, F> {
+        Lazy {
+            cell: OnceCell::new(),
+            init,
+        }
+    }
+
+    /// Consumes the lazy value, returning the value if it has been
+    /// initialized, or the initialization function otherwise.
+    #[inline]
+    pub fn into_inner(this: Lazy<T, F>) -> Result<T, F> {
+        match this.cell.into_inner() {
+            Some(value) => Ok(value),
+            None => Err(this.init),
+        }
+    }
+
+    /// Returns a reference to the value, if it has been initialized.
+    #[inline]
+    pub fn get(this: &Lazy<T, F>) -> Option<&T> {
+        this.cell.get()
+    }
+}
+
+impl<T, F: Fn() -> T> Lazy<T, F> {
+    /// Forces the evaluation of the lazy value, returning a reference to it.
+    ///
+    /// This is equivalent to the `Deref` impl.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn force(this: &Lazy<T, F>) -> &T {
+        this.cell.get_or_init(&this.init)
+    }
+
+    /// Forces the evaluation of the lazy value, returning a mutable reference
+    /// to it.
+    #[inline]
+    pub fn force_mut(this: &mut Lazy<T, F>) -> &mut T {
+        if this.cell.get_mut().is_none() {
+            let value = (this.init)();
+            let _ = this.cell.set(value);
+        }
+        this.cell.get_mut().unwrap()
+    }
+}
+
+impl<T, F: Fn() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
+
+impl<T, F: Fn() -> T> DerefMut for Lazy<T, F> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        Lazy::force_mut(self)
+    }
+}
+
+impl<T: Default> Default for Lazy<T> {
+    fn default() -> Lazy<T> {
+        Lazy::new(T::default)
+    }
+}
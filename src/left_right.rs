@@ -0,0 +1,212 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use held::{self, Mode};
+use raw::SpinWait;
+#[cfg(feature = "testing")]
+use testing::deterministic;
+use {fmt_guard, CachePadded, GuardMarker, Mutex};
+
+/// A concurrency primitive keeping two copies of a value, so that readers
+/// never wait, using the Left-Right algorithm.
+///
+/// Readers always have a copy to read which no writer is modifying, so
+/// `read` is wait-free. Writes are serialized, and each one is applied to
+/// both copies in turn, waiting for readers to move off of a copy before
+/// modifying it. This makes writes more expensive than with an `RwLock`,
+/// and doubles the memory used by the value, in exchange for reads which
+/// are never blocked by a writer.
+///
+/// Since a write closure is run once on each copy, it must make the same
+/// change to both. If it panics, the copy it was modifying is restored from
+/// the other copy before the panic propagates.
+pub struct LeftRight<T> {
+    copies: [UnsafeCell<T>; 2],
+    // The copy readers use.
+    left_right: AtomicUsize,
+    // The read indicator new readers arrive at.
+    version: AtomicUsize,
+    readers: [CachePadded<AtomicUsize>; 2],
+    writer: Mutex<()>,
+}
+
+unsafe impl<T: Send> Send for LeftRight<T> {}
+unsafe impl<T: Send + Sync> Sync for LeftRight<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for LeftRight<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("LeftRight").field("data", &&*self.read()).finish()
+    }
+}
+
+impl<T: Clone> LeftRight<T> {
+    /// Creates a new `LeftRight` holding two copies of `t`.
+    pub fn new(t: T) -> LeftRight<T> {
+        LeftRight {
+            copies: [UnsafeCell::new(t.clone()), UnsafeCell::new(t)],
+            left_right: AtomicUsize::new(0),
+            version: AtomicUsize::new(0),
+            readers: [CachePadded::new(AtomicUsize::new(0)), CachePadded::new(AtomicUsize::new(0))],
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Modifies the value by running `f` on each copy in turn, blocking the
+    /// current thread until it has been applied to both.
+    ///
+    /// Readers see the change as soon as it has been applied to the first
+    /// copy. Concurrent writes are serialized.
+    ///
+    /// In debug builds, this panics if the current thread holds a read guard
+    /// on the value rather than deadlocking.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn write<F>(&self, mut f: F)
+        where F: FnMut(&mut T)
+    {
+        held::check("LeftRight", None, self.addr(), Mode::Exclusive);
+        let _guard = self.writer.lock();
+
+        // Readers aren't using the other copy, so it can be modified
+        // immediately, and then published to new readers.
+        let current = self.left_right.load(Ordering::SeqCst);
+        self.apply(1 - current, &mut f);
+        self.left_right.store(1 - current, Ordering::SeqCst);
+
+        // Readers still on the old copy have arrived at one of the two read
+        // indicators. Switching new readers to the other indicator and then
+        // waiting for both to drain means none of them remain.
+        let version = self.version.load(Ordering::SeqCst);
+        self.wait_for_readers(1 - version);
+        self.version.store(1 - version, Ordering::SeqCst);
+        self.wait_for_readers(version);
+
+        self.apply(current, &mut f);
+    }
+
+    fn apply<F>(&self, idx: usize, f: &mut F)
+        where F: FnMut(&mut T)
+    {
+        struct Resync<'a, T: Clone + 'a> {
+            dst: &'a UnsafeCell<T>,
+            src: &'a UnsafeCell<T>,
+        }
+
+        impl<'a, T: Clone> Drop for Resync<'a, T> {
+            fn drop(&mut self) {
+                unsafe { *self.dst.get() = (*self.src.get()).clone() }
+            }
+        }
+
+        let resync = Resync {
+            dst: &self.copies[idx],
+            src: &self.copies[1 - idx],
+        };
+        f(unsafe { &mut *self.copies[idx].get() });
+        // Only restore the copy if `f` unwound.
+        mem::forget(resync);
+    }
+}
+
+impl<T> LeftRight<T> {
+    /// Returns a guard for reading the value.
+    ///
+    /// This never blocks, even while a write is in progress.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read<'a>(&'a self) -> LeftRightReadGuard<'a, T> {
+        let version = self.version.load(Ordering::SeqCst);
+        self.readers[version].fetch_add(1, Ordering::SeqCst);
+        let idx = self.left_right.load(Ordering::SeqCst);
+        held::acquired("LeftRight", None, self.addr(), Mode::Shared);
+        LeftRightReadGuard {
+            left_right: self,
+            version,
+            data: unsafe { &*self.copies[idx].get() },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Consumes the `LeftRight`, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        let [left, right] = self.copies;
+        match self.left_right.into_inner() {
+            0 => left.into_inner(),
+            _ => right.into_inner(),
+        }
+    }
+
+    fn wait_for_readers(&self, version: usize) {
+        let mut spin = SpinWait::new();
+        while self.readers[version].load(Ordering::SeqCst) != 0 {
+            #[cfg(feature = "testing")]
+            {
+                if deterministic::is_managed() {
+                    deterministic::yield_now();
+                    continue;
+                }
+            }
+
+            if !spin.spin() {
+                thread::yield_now();
+            }
+        }
+    }
+
+    #[inline]
+    fn addr(&self) -> usize {
+        self as *const LeftRight<T> as usize
+    }
+}
+
+impl<T: Clone + Default> Default for LeftRight<T> {
+    fn default() -> LeftRight<T> {
+        LeftRight::new(Default::default())
+    }
+}
+
+/// An RAII guard for reading the value of a `LeftRight`.
+#[must_use]
+pub struct LeftRightReadGuard<'a, T: 'a> {
+    left_right: &'a LeftRight<T>,
+    version: usize,
+    data: &'a T,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: Sync + 'a> Sync for LeftRightReadGuard<'a, T> {}
+
+impl<'a, T: fmt::Debug> fmt::Debug for LeftRightReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "LeftRightReadGuard", self.left_right.addr(), self.data)
+    }
+}
+
+impl<'a, T: fmt::Display> fmt::Display for LeftRightReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T> Deref for LeftRightReadGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.data
+    }
+}
+
+impl<'a, T> Drop for LeftRightReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.left_right.addr(), Mode::Shared);
+        self.left_right.readers[self.version].fetch_sub(1, Ordering::SeqCst);
+        held::check_released(owned);
+    }
+}
@@ -1,128 +1,275 @@
 //! Mutex and RwLock types that do not poison themselves.
 //!
 //! These types expose identical APIs to the standard library `Mutex` and
-//! `RwLock` except that they do not return `PoisonError`s.
-#![doc(html_root_url="https://sfackler.github.io/rust-antidote/doc/v1.0.0")]
+//! `RwLock` except that they do not return `PoisonError`s. The `compat`
+//! module provides versions whose signatures match the standard library's
+//! exactly, for ease of migration, and the `backoff` module allows the
+//! behavior of contended locks to be tuned.
+//!
+//! None of the locks in this crate are ever poisoned. A guard dropped while
+//! its thread panics releases the lock as usual, and the next thread to
+//! acquire it sees the data however the panicking thread left it.
+//!
+//! On WebAssembly targets without the `atomics` feature there is only one
+//! thread, so a lock which is held can never be released by anyone else.
+//! Blocking on such a lock panics instead of hanging. With `atomics`, see
+//! the `wasm` module for threads which are not allowed to block.
+//!
+//! # Features
+//!
+//! * `derive` - Adds `#[derive(Lockable)]`, which generates a version of a
+//!   struct with each field in its own lock, and the `#[synchronized]`
+//!   attribute, which makes a method's body run with a lock held.
+//! * `ffi` - Adds the `ffi` module, a C API for creating and locking
+//!   mutexes and reader-writer locks.
+//! * `futex` - Blocks threads directly with the platform's futex-like
+//!   primitive on Windows and Apple platforms, rather than through the
+//!   portable parking implementation: `WaitOnAddress` on Windows, and
+//!   `os_sync_wait_on_address` on macOS 14.4 and iOS 17.4 or later. Older
+//!   Apple systems lack it, and keep the portable implementation, detected at
+//!   runtime. Linux and Android always use the `futex` system call, with or
+//!   without this feature. Only the waiting is native: the locks are still
+//!   this crate's own, built on an atomic word, rather than wrappers around
+//!   `SRWLOCK` or `os_unfair_lock`, which could not support timeouts,
+//!   upgradable reads or the other extensions.
+//! * `mutex-trait` - Implements the `mutex-trait` crate's `Mutex` trait for
+//!   references to `Mutex`, `TicketMutex` and `RwLock`, for use with
+//!   embedded drivers written against it.
+//! * `named_mutex` - Adds `NamedMutex`, a mutex shared between processes by
+//!   name. Only available on Unix platforms.
+//! * `parking_lot` - Implements `BlockingLock` and `BlockingRwLock` for
+//!   `parking_lot`'s locks.
+//! * `pi_mutex` - Adds `PiMutex`, a mutex using priority inheritance.
+//! * `prometheus` - Adds the `prometheus` module, which exports lock
+//!   statistics as Prometheus metrics.
+//! * `proptest` - Implies `testing`, and adds `proptest` strategies which
+//!   generate operation sequences for the `testing::model` checker.
+//! * `raw` - Adds methods to acquire and release locks without guards, for
+//!   FFI code where a guard's lifetime cannot be expressed, and to access
+//!   the native objects behind `NamedMutex` and `PiMutex`. `Mutex` and
+//!   `RwLock` are not built on native objects, so they have none.
+//! * `send_guard` - Makes the lock guards `Send`, allowing a lock to be
+//!   acquired on one thread and released on another.
+//! * `shm` - Adds `ShmMutex`, a mutex which can be shared between processes
+//!   through shared memory. Only available on Linux and Android.
+//! * `testing` - Adds the `testing` module, with utilities for testing code
+//!   built on these locks.
+//! * `tokio` - Adds conversions between the async locks and their
+//!   `tokio::sync` counterparts, along with tokio's blocking lock methods.
+//! * `tracing` - Reports the async locks and the tasks waiting on them to
+//!   tokio-console, through the `runtime::resource` spans collected by
+//!   `console-subscriber`.
+//!
+//! # Upgrading from 1.x
+//!
+//! Version 1 wrapped the standard library's locks. Version 2 replaces them
+//! with this crate's own implementation, which is what makes timeouts,
+//! upgradable reads and the other extensions possible. On Linux and Android
+//! contended locks still block on the `futex` system call, like the
+//! standard library's. A few types changed:
+//!
+//! * `WaitTimeoutResult` is now this crate's own type rather than a
+//!   re-export of `std::sync::WaitTimeoutResult`, which cannot be
+//!   constructed outside the standard library. It has the same
+//!   `timed_out` method.
+//! * `TryLockError` is an enum which says why an attempt failed, rather
+//!   than an opaque struct.
+//! * `Mutex` and `RwLock` convert to and from the standard library's locks
+//!   by moving the protected value, and a borrowed standard library lock
+//!   cannot be viewed as one of them. `Unpoisoned` offers this crate's API
+//!   over a borrowed standard library lock instead.
+#![doc(html_root_url="https://sfackler.github.io/rust-antidote/doc/v2.0.0")]
 #![warn(missing_docs)]
 
+#[cfg(feature = "derive")]
+extern crate antidote_derive;
+#[cfg(any(feature = "libc", target_os = "linux", target_os = "android"))]
+extern crate libc;
+#[cfg(feature = "mutex-trait")]
+extern crate mutex_trait;
+#[cfg(feature = "parking_lot")]
+extern crate parking_lot;
+#[cfg(feature = "proptest")]
+extern crate proptest;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
 use std::error::Error;
 use std::fmt;
-use std::ops::{Deref, DerefMut};
-use std::sync;
-use std::time::Duration;
-
-#[doc(inline)]
-pub use std::sync::WaitTimeoutResult;
-
-/// Like `std::sync::Mutex` except that it does not poison itself.
-pub struct Mutex<T: ?Sized>(sync::Mutex<T>);
-
-impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(&self.0, fmt)
-    }
+use std::time::{Duration, Instant};
+
+pub use async_mutex::{AsyncMutex, AsyncMutexGuard, AsyncMutexLockFuture, AsyncMutexLockOwnedFuture,
+                      OwnedAsyncMutexGuard};
+pub use async_rwlock::{AsyncRwLock, AsyncRwLockPolicy, AsyncRwLockReadFuture, AsyncRwLockReadGuard,
+                       AsyncRwLockReadOwnedFuture, AsyncRwLockWriteFuture, AsyncRwLockWriteGuard,
+                       AsyncRwLockWriteOwnedFuture, OwnedAsyncRwLockReadGuard, OwnedAsyncRwLockWriteGuard};
+pub use async_semaphore::{AsyncSemaphore, AsyncSemaphoreAcquireFuture, AsyncSemaphoreAcquireOwnedFuture,
+                          AsyncSemaphorePermit, OwnedAsyncSemaphorePermit};
+pub use atomic_mutex::{AtomicMutex, Plain};
+pub use barrier::{Barrier, BarrierError, BarrierWaitResult};
+pub use biased_rwlock::{BiasedRwLock, BiasedRwLockReadGuard, BiasedRwLockWriteGuard};
+pub use blocking::{BlockingLockFuture, BlockingTask};
+pub use branded::{BrandCell, BrandToken, BrandedMutex};
+pub use builder::{MutexBuilder, RwLockBuilder};
+pub use cache_padded::{CachePadded, PaddedMutex, PaddedRwLock};
+pub use condvar::Condvar;
+pub use double_buffer::{DoubleBuffer, DoubleBufferWriteGuard};
+pub use elided_mutex::{ElidedMutex, ElidedMutexGuard};
+pub use event::Event;
+pub use fs_rwlock::{FsRwLock, FsRwLockReadGuard, FsRwLockWriteGuard};
+pub use held::install_panic_hook;
+pub use instrument::{HolderInfo, LockMode, LockStats, UnlockEvent};
+pub use interrupt::Interrupt;
+pub use latch::CountDownLatch;
+pub use lazy::Lazy;
+pub use left_right::{LeftRight, LeftRightReadGuard};
+pub use lock_tracker::LockTracker;
+pub use lock_traits::{BlockingLock, BlockingRwLock};
+#[cfg(feature = "derive")]
+pub use lockable::Lockable;
+pub use locked_hash_map::{MutexHashMap, MutexHashMapEntry, RwLockHashMap, RwLockHashMapEntry};
+pub use maybe_mutex::{MaybeMutex, MaybeMutexGuard, MultiThreaded, SingleThreaded, Threading};
+pub use mutex::{ArcMutex, ArcMutexGuard, MappedArcMutexGuard, MappedMutexGuard, Mutex, MutexGuard};
+pub use mutex_map::{MutexMap, MutexMapGuard};
+#[cfg(all(feature = "named_mutex", unix))]
+pub use named_mutex::{NamedMutex, NamedMutexGuard};
+pub use once_cell::OnceCell;
+pub use owned_iter::OwnedIter;
+#[cfg(feature = "pi_mutex")]
+pub use pi_mutex::{PiMutex, PiMutexGuard};
+pub use pin_mutex::PinMutex;
+#[doc(hidden)]
+pub use project::Project;
+pub use queue::{BlockingQueue, BoundedQueue};
+pub use ranked::{LockToken, RankedMutex, RankedMutexGuard};
+pub use rwlock::{ArcRwLock, ArcRwLockReadGuard, ArcRwLockWriteGuard, MappedArcRwLockReadGuard,
+                 MappedArcRwLockWriteGuard, MappedRwLockReadGuard, MappedRwLockWriteGuard, OptimisticRead, RwLock,
+                 RwLockPolicy, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
+pub use semaphore::{Semaphore, SemaphorePermit};
+#[cfg(all(feature = "shm", any(target_os = "linux", target_os = "android")))]
+pub use shm_mutex::{ShmMutex, ShmMutexGuard};
+pub use striped::{Striped, Stripes};
+#[cfg(feature = "derive")]
+pub use synchronized::synchronized;
+pub use ticket_mutex::{TicketMutex, TicketMutexGuard};
+pub use timer::TimeoutFuture;
+pub use unpoisoned::Unpoisoned;
+pub use versioned::{VersionedRwLock, VersionedRwLockWriteGuard};
+pub use wait_group::WaitGroup;
+pub use zip_guard::{ZipGuard, ZipGuards};
+
+mod async_mutex;
+mod async_rwlock;
+mod async_semaphore;
+mod atomic_mutex;
+pub mod backoff;
+mod barrier;
+mod biased_rwlock;
+mod blocking;
+mod branded;
+mod builder;
+mod cache_padded;
+pub mod clock;
+pub mod compat;
+pub mod config;
+mod condvar;
+mod console;
+mod double_buffer;
+mod elided_mutex;
+mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fs_rwlock;
+mod futex;
+mod guard_borrow;
+mod guard_io;
+mod held;
+mod instrument;
+mod interrupt;
+mod latch;
+mod lazy;
+mod left_right;
+pub mod lock_graph;
+mod lock_tracker;
+mod lock_traits;
+#[cfg(feature = "derive")]
+mod lockable;
+mod locked_hash_map;
+mod maybe_mutex;
+mod mutex;
+mod mutex_map;
+#[cfg(feature = "mutex-trait")]
+mod mutex_trait_interop;
+#[cfg(all(feature = "named_mutex", unix))]
+mod named_mutex;
+mod once_cell;
+mod owned_iter;
+#[cfg(feature = "pi_mutex")]
+mod pi_mutex;
+mod pin_mutex;
+pub mod profiler;
+#[cfg(feature = "prometheus")]
+pub mod prometheus;
+mod project;
+mod queue;
+mod ranked;
+mod raw;
+mod rwlock;
+mod semaphore;
+#[cfg(all(feature = "shm", any(target_os = "linux", target_os = "android")))]
+mod shm_mutex;
+mod striped;
+#[cfg(feature = "derive")]
+mod synchronized;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod ticket_mutex;
+mod timer;
+#[cfg(feature = "tokio")]
+mod tokio_interop;
+mod unpoisoned;
+mod versioned;
+mod wait_group;
+mod wait_queue;
+#[cfg(all(target_family = "wasm", target_feature = "atomics"))]
+pub mod wasm;
+pub mod watch;
+mod zip_guard;
+
+// Guards are `!Send` unless the `send_guard` feature is enabled. Their `Sync`
+// impls are written out by hand, so the marker is never `Sync`.
+#[cfg(not(feature = "send_guard"))]
+type GuardMarker = *mut ();
+#[cfg(feature = "send_guard")]
+type GuardMarker = std::cell::Cell<()>;
+
+// Formats a guard, identifying its lock by address so that guards for the
+// same lock can be matched up in logs.
+fn fmt_guard<T: ?Sized + fmt::Debug>(fmt: &mut fmt::Formatter, name: &str, lock: usize, data: &T) -> fmt::Result {
+    fmt.debug_struct(name).field("lock", &format_args!("{:#x}", lock)).field("data", &data).finish()
 }
 
-impl<T> Mutex<T> {
-    /// Like `std::sync::Mutex::new`.
-    #[inline]
-    pub fn new(t: T) -> Mutex<T> {
-        Mutex(sync::Mutex::new(t))
-    }
-
-    /// Like `std::sync::Mutex::into_inner`.
-    #[inline]
-    pub fn into_inner(self) -> T {
-        self.0.into_inner().unwrap_or_else(|e| e.into_inner())
-    }
+// Returns the deadline `dur` from now, or `None` if it can't be represented.
+// An unrepresentable deadline is as good as no deadline at all.
+#[inline]
+fn deadline_after(dur: Duration) -> Option<Instant> {
+    Instant::now().checked_add(dur)
 }
 
-impl<T: ?Sized> Mutex<T> {
-    /// Like `std::sync::Mutex::lock`.
-    #[inline]
-    pub fn lock<'a>(&'a self) -> MutexGuard<'a, T> {
-        MutexGuard(self.0.lock().unwrap_or_else(|e| e.into_inner()))
-    }
-
-    /// Like `std::sync::Mutex::try_lock`.
-    #[inline]
-    pub fn try_lock<'a>(&'a self) -> TryLockResult<MutexGuard<'a, T>> {
-        match self.0.try_lock() {
-            Ok(t) => Ok(MutexGuard(t)),
-            Err(sync::TryLockError::Poisoned(e)) => Ok(MutexGuard(e.into_inner())),
-            Err(sync::TryLockError::WouldBlock) => Err(TryLockError(())),
-        }
-    }
-
-    /// Like `std::sync::Mutex::get_mut`.
-    #[inline]
-    pub fn get_mut(&mut self) -> &mut T {
-        self.0.get_mut().unwrap_or_else(|e| e.into_inner())
-    }
-}
-
-/// Like `std::sync::MutexGuard`.
-#[must_use]
-pub struct MutexGuard<'a, T: ?Sized + 'a>(sync::MutexGuard<'a, T>);
-
-impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
-    type Target = T;
-
-    #[inline]
-    fn deref(&self) -> &T {
-        self.0.deref()
-    }
-}
-
-impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
-    #[inline]
-    fn deref_mut(&mut self) -> &mut T {
-        self.0.deref_mut()
-    }
-}
-
-impl<T: Default> Default for Mutex<T> {
-    fn default() -> Self {
-        Mutex(Default::default())
-    }
-}
-
-/// Like `std::sync::Condvar`.
-pub struct Condvar(sync::Condvar);
-
-impl Condvar {
-    /// Like `std::sync::Condvar::new`.
-    #[inline]
-    pub fn new() -> Condvar {
-        Condvar(sync::Condvar::new())
-    }
-
-    /// Like `std::sync::Condvar::wait`.
-    #[inline]
-    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
-        MutexGuard(self.0.wait(guard.0).unwrap_or_else(|e| e.into_inner()))
-    }
-
-    /// Like `std::sync::Condvar::wait_timeout`.
-    #[inline]
-    pub fn wait_timeout<'a, T>(&self,
-                               guard: MutexGuard<'a, T>,
-                               dur: Duration)
-                               -> (MutexGuard<'a, T>, WaitTimeoutResult) {
-        let (guard, result) = self.0.wait_timeout(guard.0, dur).unwrap_or_else(|e| e.into_inner());
-        (MutexGuard(guard), result)
-    }
-
-    /// Like `std::sync::Condvar::notify_one`.
-    #[inline]
-    pub fn notify_one(&self) {
-        self.0.notify_one()
-    }
+/// Like `std::sync::WaitTimeoutResult`.
+///
+/// This is a distinct type from the standard library's, so code which names
+/// `std::sync::WaitTimeoutResult` has to switch to this one.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WaitTimeoutResult(bool);
 
-    /// Like `std::sync::Condvar::notify_all`.
+impl WaitTimeoutResult {
+    /// Like `std::sync::WaitTimeoutResult::timed_out`.
     #[inline]
-    pub fn notify_all(&self) {
-        self.0.notify_all()
+    pub fn timed_out(&self) -> bool {
+        self.0
     }
 }
 
@@ -130,113 +277,31 @@ impl Condvar {
 pub type TryLockResult<T> = Result<T, TryLockError>;
 
 /// Like `std::sync::TryLockError`.
-#[derive(Debug)]
-pub struct TryLockError(());
-
-impl fmt::Display for TryLockError {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt.write_str(self.description())
-    }
+///
+/// Unlike the standard library's error there is no poisoned case. Instead,
+/// this distinguishes why an acquisition attempt failed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TryLockError {
+    /// The lock could not be acquired without blocking.
+    WouldBlock,
+    /// The lock could not be acquired before the timeout expired.
+    Timeout,
+    /// The acquisition was interrupted before the lock could be acquired.
+    Interrupted,
+    /// The primitive was closed, as by `AsyncSemaphore::close`.
+    Closed,
 }
 
-impl Error for TryLockError {
-    fn description(&self) -> &str {
-        "lock call failed because the operation would block"
-    }
-}
-
-/// Like `std::sync::RwLock` except that it does not poison itself.
-pub struct RwLock<T: ?Sized>(sync::RwLock<T>);
-
-impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+impl fmt::Display for TryLockError {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(&self.0, fmt)
-    }
-}
-
-impl<T> RwLock<T> {
-    /// Like `std::sync::RwLock::new`.
-    #[inline]
-    pub fn new(t: T) -> RwLock<T> {
-        RwLock(sync::RwLock::new(t))
-    }
-
-    /// Like `std::sync::RwLock::into_inner`.
-    #[inline]
-    pub fn into_inner(self) -> T where T: Sized {
-        self.0.into_inner().unwrap_or_else(|e| e.into_inner())
-    }
-}
-
-impl<T: ?Sized> RwLock<T> {
-    /// Like `std::sync::RwLock::read`.
-    #[inline]
-    pub fn read<'a>(&'a self) -> RwLockReadGuard<'a, T> {
-        RwLockReadGuard(self.0.read().unwrap_or_else(|e| e.into_inner()))
-    }
-
-    /// Like `std::sync::RwLock::try_read`.
-    #[inline]
-    pub fn try_read<'a>(&'a self) -> TryLockResult<RwLockReadGuard<'a, T>> {
-        match self.0.try_read() {
-            Ok(t) => Ok(RwLockReadGuard(t)),
-            Err(sync::TryLockError::Poisoned(e)) => Ok(RwLockReadGuard(e.into_inner())),
-            Err(sync::TryLockError::WouldBlock) => Err(TryLockError(())),
+        match *self {
+            TryLockError::WouldBlock => fmt.write_str("lock call failed because the operation would block"),
+            TryLockError::Timeout => fmt.write_str("lock call failed because the timeout expired"),
+            TryLockError::Interrupted => fmt.write_str("lock call failed because it was interrupted"),
+            TryLockError::Closed => fmt.write_str("lock call failed because the primitive was closed"),
         }
     }
-
-    /// Like `std::sync::RwLock::write`.
-    #[inline]
-    pub fn write<'a>(&'a self) -> RwLockWriteGuard<'a, T> {
-        RwLockWriteGuard(self.0.write().unwrap_or_else(|e| e.into_inner()))
-    }
-
-    /// Like `std::sync::RwLock::try_write`.
-    #[inline]
-    pub fn try_write<'a>(&'a self) -> TryLockResult<RwLockWriteGuard<'a, T>> {
-        match self.0.try_write() {
-            Ok(t) => Ok(RwLockWriteGuard(t)),
-            Err(sync::TryLockError::Poisoned(e)) => Ok(RwLockWriteGuard(e.into_inner())),
-            Err(sync::TryLockError::WouldBlock) => Err(TryLockError(())),
-        }
-    }
-
-    /// Like `std::sync::RwLock::get_mut`.
-    #[inline]
-    pub fn get_mut(&mut self) -> &mut T {
-        self.0.get_mut().unwrap_or_else(|e| e.into_inner())
-    }
 }
 
-/// Like `std::sync::RwLockReadGuard`.
-#[must_use]
-pub struct RwLockReadGuard<'a, T: ?Sized + 'a>(sync::RwLockReadGuard<'a, T>);
-
-impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
-    type Target = T;
-
-    #[inline]
-    fn deref(&self) -> &T {
-        self.0.deref()
-    }
-}
-
-/// Like `std::sync::RwLockWriteGuard`.
-#[must_use]
-pub struct RwLockWriteGuard<'a, T: ?Sized + 'a>(sync::RwLockWriteGuard<'a, T>);
-
-impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
-    type Target = T;
-
-    #[inline]
-    fn deref(&self) -> &T {
-        self.0.deref()
-    }
-}
-
-impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
-    #[inline]
-    fn deref_mut(&mut self) -> &mut T {
-        self.0.deref_mut()
-    }
-}
+impl Error for TryLockError {}
@@ -0,0 +1,243 @@
+//! Recording of the order locks are acquired in.
+//!
+//! While recording, each time a thread blocks to acquire a lock while
+//! already holding others, an edge is added from each held lock to the one
+//! being acquired. The result is the graph of lock dependencies the program
+//! actually exhibited, which can be compared against its intended lock
+//! hierarchy: a cycle means two threads could deadlock. It can be written
+//! out as Graphviz DOT or as JSON:
+//!
+//! ```no_run
+//! use antidote::lock_graph;
+//! use std::fs::File;
+//!
+//! lock_graph::start();
+//! // run the workload...
+//! let graph = lock_graph::stop();
+//! graph.write_dot(File::create("locks.dot").unwrap()).unwrap();
+//! ```
+//!
+//! ```text
+//! $ dot -Tsvg locks.dot > locks.svg
+//! ```
+//!
+//! Locks are identified by their address and, if they have one, their name.
+//! A lock created at the address of one which was dropped appears as the
+//! same lock.
+//!
+//! Recording is built on the held lock tracking which detects a thread
+//! acquiring a lock twice, so it only takes place in debug builds without
+//! the `send_guard` feature. Acquisitions which can't block, like
+//! `Mutex::try_lock`, add no edges, since they can't take part in a
+//! deadlock.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+#[cfg(all(debug_assertions, not(feature = "send_guard")))]
+use std::panic::Location;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+// The graph is recorded into a standard library mutex, as acquiring one of
+// this crate's locks would recursively record an edge.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static GRAPH: Mutex<Option<LockGraph>> = Mutex::new(None);
+
+/// Starts recording lock acquisition order.
+///
+/// Edges are accumulated until `stop` is called.
+pub fn start() {
+    lock(&GRAPH).get_or_insert_with(LockGraph::default);
+    RUNNING.store(true, Ordering::Relaxed);
+}
+
+/// Stops recording, returning the graph recorded since `start` was called.
+pub fn stop() -> LockGraph {
+    RUNNING.store(false, Ordering::Relaxed);
+    lock(&GRAPH).take().unwrap_or_default()
+}
+
+/// Returns whether lock acquisition order is being recorded.
+#[inline]
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Records the acquisition of the lock at `addr` by a thread holding the
+// locks in `held`, each given with the location it was acquired at.
+#[cfg(all(debug_assertions, not(feature = "send_guard")))]
+#[cold]
+pub(crate) fn record<I>(kind: &'static str,
+                        name: Option<&str>,
+                        addr: usize,
+                        location: &'static Location<'static>,
+                        held: I)
+    where I: IntoIterator<Item = (usize, &'static Location<'static>)>
+{
+    let mut graph = lock(&GRAPH);
+    let graph = match *graph {
+        Some(ref mut graph) => graph,
+        None => return,
+    };
+    graph.nodes.entry(addr).or_insert_with(|| {
+        Node {
+            kind,
+            name: name.map(str::to_string),
+        }
+    });
+    for (from, held_at) in held {
+        if from == addr {
+            continue;
+        }
+        let edge = graph.edges.entry((from, addr)).or_insert_with(|| {
+            Edge {
+                count: 0,
+                held_at: held_at.to_string(),
+                acquired_at: location.to_string(),
+            }
+        });
+        edge.count += 1;
+    }
+}
+
+struct Node {
+    kind: &'static str,
+    name: Option<String>,
+}
+
+struct Edge {
+    count: u64,
+    // Where the held lock and the lock acquired while holding it were
+    // acquired, the first time the edge was seen.
+    held_at: String,
+    acquired_at: String,
+}
+
+/// The lock acquisition order recorded between `start` and `stop`.
+#[derive(Default)]
+pub struct LockGraph {
+    nodes: HashMap<usize, Node>,
+    edges: HashMap<(usize, usize), Edge>,
+}
+
+impl fmt::Debug for LockGraph {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("LockGraph").field("edges", &self.edges.len()).finish()
+    }
+}
+
+impl LockGraph {
+    /// Returns whether no edges were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// Returns the number of distinct edges recorded.
+    pub fn edges(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Writes the graph in the Graphviz DOT format.
+    ///
+    /// Each edge is labelled with the number of times it was seen, and its
+    /// tooltip gives the locations of the first pair of acquisitions which
+    /// produced it.
+    pub fn write_dot<W: Write>(&self, mut w: W) -> io::Result<()> {
+        writeln!(w, "digraph locks {{")?;
+        for addr in self.addrs() {
+            writeln!(w, "    \"{:#x}\" [label=\"{}\"];", addr, escape(&self.label(addr)))?;
+        }
+        for (&(from, to), edge) in self.sorted_edges() {
+            let tooltip = format!("held since {}, then acquired at {}", edge.held_at, edge.acquired_at);
+            writeln!(w,
+                     "    \"{:#x}\" -> \"{:#x}\" [label=\"{}\", tooltip=\"{}\"];",
+                     from,
+                     to,
+                     edge.count,
+                     escape(&tooltip))?;
+        }
+        writeln!(w, "}}")
+    }
+
+    /// Writes the graph as a JSON object.
+    ///
+    /// The object has a `nodes` array of locks, with their `id` (their
+    /// address), `kind` and `name` (each `null` if unknown), and an `edges`
+    /// array with the `from` and `to` node ids, the `count` of times the
+    /// edge was seen, and the `held_at` and `acquired_at` locations of the
+    /// first pair of acquisitions which produced it.
+    pub fn write_json<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "{{\"nodes\":[")?;
+        for (i, addr) in self.addrs().into_iter().enumerate() {
+            let node = self.nodes.get(&addr);
+            write!(w,
+                   "{}{{\"id\":\"{:#x}\",\"kind\":{},\"name\":{}}}",
+                   if i == 0 { "" } else { "," },
+                   addr,
+                   json_string(node.map(|n| n.kind)),
+                   json_string(node.and_then(|n| n.name.as_deref())))?;
+        }
+        write!(w, "],\"edges\":[")?;
+        for (i, (&(from, to), edge)) in self.sorted_edges().into_iter().enumerate() {
+            write!(w,
+                   "{}{{\"from\":\"{:#x}\",\"to\":\"{:#x}\",\"count\":{},\"held_at\":\"{}\",\"acquired_at\":\"{}\"}}",
+                   if i == 0 { "" } else { "," },
+                   from,
+                   to,
+                   edge.count,
+                   escape(&edge.held_at),
+                   escape(&edge.acquired_at))?;
+        }
+        writeln!(w, "]}}")
+    }
+
+    // The addresses of every lock appearing in an edge, in order. A lock
+    // which was held but never blocked on has no node of its own.
+    fn addrs(&self) -> Vec<usize> {
+        let mut addrs = self.edges.keys().flat_map(|&(from, to)| vec![from, to]).collect::<Vec<_>>();
+        addrs.sort_unstable();
+        addrs.dedup();
+        addrs
+    }
+
+    fn sorted_edges(&self) -> Vec<(&(usize, usize), &Edge)> {
+        let mut edges = self.edges.iter().collect::<Vec<_>>();
+        edges.sort_unstable_by_key(|&(&key, _)| key);
+        edges
+    }
+
+    fn label(&self, addr: usize) -> String {
+        match self.nodes.get(&addr) {
+            Some(&Node { kind, name: Some(ref name) }) => format!("{} {:?} ({:#x})", kind, name, addr),
+            Some(&Node { kind, name: None }) => format!("{} {:#x}", kind, addr),
+            None => format!("{:#x}", addr),
+        }
+    }
+}
+
+fn json_string(s: Option<&str>) -> String {
+    match s {
+        Some(s) => format!("\"{}\"", escape(s)),
+        None => "null".to_string(),
+    }
+}
+
+// Escapes a string for inclusion in a double-quoted JSON or DOT string.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,67 @@
+use std::marker::PhantomData;
+use std::sync::atomic::Ordering;
+use std::thread;
+
+use held::{self, TRACKERS};
+
+/// Fails a test if a lock acquired while this exists is still held when it
+/// is dropped.
+///
+/// Dropping the tracker panics, listing each such lock with its name and
+/// where it was acquired, unless the thread is already panicking.
+///
+/// ```
+/// use antidote::{LockTracker, Mutex};
+///
+/// let mutex = Mutex::new(0);
+/// let tracker = LockTracker::new();
+/// *mutex.lock() += 1;
+/// // Holding on to the guard, or leaking it, would panic here.
+/// drop(tracker);
+/// ```
+///
+/// Only the locks acquired by the current thread are tracked. Tracking
+/// relies on the same records as the detection of a thread acquiring a lock
+/// twice, so it only takes place in debug builds without the `send_guard`
+/// feature; otherwise the tracker has no effect.
+#[derive(Debug)]
+pub struct LockTracker {
+    mark: u64,
+    _p: PhantomData<*const ()>,
+}
+
+impl Default for LockTracker {
+    fn default() -> LockTracker {
+        LockTracker::new()
+    }
+}
+
+impl LockTracker {
+    /// Starts tracking the locks acquired by the current thread.
+    pub fn new() -> LockTracker {
+        TRACKERS.fetch_add(1, Ordering::Relaxed);
+        LockTracker {
+            mark: held::mark(),
+            _p: PhantomData,
+        }
+    }
+
+    /// Panics if a lock acquired since the tracker was created is still
+    /// held.
+    #[track_caller]
+    pub fn assert_released(&self) {
+        if let Some(report) = held::held_since(self.mark) {
+            panic!("{}", report);
+        }
+    }
+}
+
+impl Drop for LockTracker {
+    fn drop(&mut self) {
+        TRACKERS.fetch_sub(1, Ordering::Relaxed);
+        // Panicking while already unwinding would abort.
+        if !thread::panicking() {
+            self.assert_released();
+        }
+    }
+}
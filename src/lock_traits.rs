@@ -0,0 +1,253 @@
+use std::ops::{Deref, DerefMut};
+use std::sync;
+
+#[cfg(feature = "parking_lot")]
+use parking_lot;
+
+#[cfg(feature = "pi_mutex")]
+use {PiMutex, PiMutexGuard};
+use {BiasedRwLock, BiasedRwLockReadGuard, BiasedRwLockWriteGuard, ElidedMutex, ElidedMutexGuard, Mutex, MutexGuard,
+     RwLock, RwLockReadGuard, RwLockWriteGuard, TicketMutex, TicketMutexGuard, TryLockError, TryLockResult};
+
+/// A mutual exclusion lock which blocks the thread while waiting.
+///
+/// This allows libraries to be written against a lock without choosing its
+/// implementation: it is implemented by this crate's mutexes, by
+/// `std::sync::Mutex`, and, with the `parking_lot` feature, by
+/// `parking_lot::Mutex`. Poisoning is ignored for the standard library's
+/// mutex, as it is throughout this crate.
+///
+/// ```
+/// use antidote::BlockingLock;
+///
+/// struct Counter<L> {
+///     count: L,
+/// }
+///
+/// impl<L: BlockingLock<Target = u64>> Counter<L> {
+///     fn increment(&self) -> u64 {
+///         let mut count = self.count.lock();
+///         *count += 1;
+///         *count
+///     }
+/// }
+///
+/// let counter = Counter { count: antidote::Mutex::new(0) };
+/// assert_eq!(counter.increment(), 1);
+///
+/// let counter = Counter { count: std::sync::Mutex::new(0) };
+/// assert_eq!(counter.increment(), 1);
+/// ```
+pub trait BlockingLock {
+    /// The type of the protected value.
+    type Target: ?Sized;
+
+    /// The guard which holds the lock until it is dropped.
+    type Guard<'a>: DerefMut<Target = Self::Target>
+        where Self: 'a;
+
+    /// Acquires the lock, blocking the current thread until it is able to
+    /// do so.
+    fn lock(&self) -> Self::Guard<'_>;
+
+    /// Attempts to acquire the lock without blocking.
+    fn try_lock(&self) -> TryLockResult<Self::Guard<'_>>;
+}
+
+/// A reader-writer lock which blocks the thread while waiting.
+///
+/// Like `BlockingLock`, this is implemented by this crate's reader-writer
+/// locks, by `std::sync::RwLock`, and, with the `parking_lot` feature, by
+/// `parking_lot::RwLock`.
+pub trait BlockingRwLock {
+    /// The type of the protected value.
+    type Target: ?Sized;
+
+    /// The guard which holds shared read access until it is dropped.
+    type ReadGuard<'a>: Deref<Target = Self::Target>
+        where Self: 'a;
+
+    /// The guard which holds exclusive write access until it is dropped.
+    type WriteGuard<'a>: DerefMut<Target = Self::Target>
+        where Self: 'a;
+
+    /// Acquires shared read access, blocking the current thread until it is
+    /// able to do so.
+    fn read(&self) -> Self::ReadGuard<'_>;
+
+    /// Attempts to acquire shared read access without blocking.
+    fn try_read(&self) -> TryLockResult<Self::ReadGuard<'_>>;
+
+    /// Acquires exclusive write access, blocking the current thread until it
+    /// is able to do so.
+    fn write(&self) -> Self::WriteGuard<'_>;
+
+    /// Attempts to acquire exclusive write access without blocking.
+    fn try_write(&self) -> TryLockResult<Self::WriteGuard<'_>>;
+}
+
+macro_rules! impl_blocking_lock {
+    ($($(#[$attr:meta])* $lock:ident => $guard:ident;)*) => {
+        $(
+            $(#[$attr])*
+            impl<T: ?Sized> BlockingLock for $lock<T> {
+                type Target = T;
+                type Guard<'a> = $guard<'a, T> where T: 'a;
+
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                fn lock(&self) -> $guard<'_, T> {
+                    $lock::lock(self)
+                }
+
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                fn try_lock(&self) -> TryLockResult<$guard<'_, T>> {
+                    $lock::try_lock(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_blocking_lock! {
+    Mutex => MutexGuard;
+    TicketMutex => TicketMutexGuard;
+    ElidedMutex => ElidedMutexGuard;
+    #[cfg(feature = "pi_mutex")]
+    PiMutex => PiMutexGuard;
+}
+
+macro_rules! impl_blocking_rwlock {
+    ($($lock:ident => $read:ident, $write:ident;)*) => {
+        $(
+            impl<T: ?Sized> BlockingRwLock for $lock<T> {
+                type Target = T;
+                type ReadGuard<'a> = $read<'a, T> where T: 'a;
+                type WriteGuard<'a> = $write<'a, T> where T: 'a;
+
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                fn read(&self) -> $read<'_, T> {
+                    $lock::read(self)
+                }
+
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                fn try_read(&self) -> TryLockResult<$read<'_, T>> {
+                    $lock::try_read(self)
+                }
+
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                fn write(&self) -> $write<'_, T> {
+                    $lock::write(self)
+                }
+
+                #[inline]
+                #[cfg_attr(debug_assertions, track_caller)]
+                fn try_write(&self) -> TryLockResult<$write<'_, T>> {
+                    $lock::try_write(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_blocking_rwlock! {
+    RwLock => RwLockReadGuard, RwLockWriteGuard;
+    BiasedRwLock => BiasedRwLockReadGuard, BiasedRwLockWriteGuard;
+}
+
+// Converts the result of a standard library `try_` method, ignoring poison.
+pub(crate) fn from_std<G>(r: sync::TryLockResult<G>) -> TryLockResult<G> {
+    match r {
+        Ok(guard) => Ok(guard),
+        Err(sync::TryLockError::Poisoned(e)) => Ok(e.into_inner()),
+        Err(sync::TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+    }
+}
+
+impl<T: ?Sized> BlockingLock for sync::Mutex<T> {
+    type Target = T;
+    type Guard<'a> = sync::MutexGuard<'a, T> where T: 'a;
+
+    #[inline]
+    fn lock(&self) -> sync::MutexGuard<'_, T> {
+        sync::Mutex::lock(self).unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<sync::MutexGuard<'_, T>> {
+        from_std(sync::Mutex::try_lock(self))
+    }
+}
+
+impl<T: ?Sized> BlockingRwLock for sync::RwLock<T> {
+    type Target = T;
+    type ReadGuard<'a> = sync::RwLockReadGuard<'a, T> where T: 'a;
+    type WriteGuard<'a> = sync::RwLockWriteGuard<'a, T> where T: 'a;
+
+    #[inline]
+    fn read(&self) -> sync::RwLockReadGuard<'_, T> {
+        sync::RwLock::read(self).unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    #[inline]
+    fn try_read(&self) -> TryLockResult<sync::RwLockReadGuard<'_, T>> {
+        from_std(sync::RwLock::try_read(self))
+    }
+
+    #[inline]
+    fn write(&self) -> sync::RwLockWriteGuard<'_, T> {
+        sync::RwLock::write(self).unwrap_or_else(sync::PoisonError::into_inner)
+    }
+
+    #[inline]
+    fn try_write(&self) -> TryLockResult<sync::RwLockWriteGuard<'_, T>> {
+        from_std(sync::RwLock::try_write(self))
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl<T: ?Sized> BlockingLock for parking_lot::Mutex<T> {
+    type Target = T;
+    type Guard<'a> = parking_lot::MutexGuard<'a, T> where T: 'a;
+
+    #[inline]
+    fn lock(&self) -> parking_lot::MutexGuard<'_, T> {
+        parking_lot::Mutex::lock(self)
+    }
+
+    #[inline]
+    fn try_lock(&self) -> TryLockResult<parking_lot::MutexGuard<'_, T>> {
+        parking_lot::Mutex::try_lock(self).ok_or(TryLockError::WouldBlock)
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+impl<T: ?Sized> BlockingRwLock for parking_lot::RwLock<T> {
+    type Target = T;
+    type ReadGuard<'a> = parking_lot::RwLockReadGuard<'a, T> where T: 'a;
+    type WriteGuard<'a> = parking_lot::RwLockWriteGuard<'a, T> where T: 'a;
+
+    #[inline]
+    fn read(&self) -> parking_lot::RwLockReadGuard<'_, T> {
+        parking_lot::RwLock::read(self)
+    }
+
+    #[inline]
+    fn try_read(&self) -> TryLockResult<parking_lot::RwLockReadGuard<'_, T>> {
+        parking_lot::RwLock::try_read(self).ok_or(TryLockError::WouldBlock)
+    }
+
+    #[inline]
+    fn write(&self) -> parking_lot::RwLockWriteGuard<'_, T> {
+        parking_lot::RwLock::write(self)
+    }
+
+    #[inline]
+    fn try_write(&self) -> TryLockResult<parking_lot::RwLockWriteGuard<'_, T>> {
+        parking_lot::RwLock::try_write(self).ok_or(TryLockError::WouldBlock)
+    }
+}
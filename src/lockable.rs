@@ -0,0 +1,43 @@
+/// Derives a "locked mirror" of a struct, which wraps each field in its own
+/// lock for fine-grained locking.
+///
+/// Requires the `derive` feature. The mirror is named `Locked` followed by
+/// the struct's name, unless another is given with `#[lockable(name = ...)]`
+/// on the struct. Fields are wrapped in a `Mutex` by default, or in an
+/// `RwLock` if they are annotated with `#[lockable(rwlock)]`.
+///
+/// The mirror has a method for each field, named after it, which locks the
+/// field and returns the guard. Read locks are taken by the method named
+/// after the field, and write locks by the method named `<field>_mut`, or
+/// as given by `#[lockable(rwlock, write = ...)]`. Accessors have the
+/// visibility of their fields. The mirror is created from the plain struct
+/// with `new` or `From`, and converted back with `into_inner`.
+///
+/// Generic structs are supported, and attributes on fields other than
+/// `#[lockable(...)]`, such as doc comments, are kept on the plain struct.
+///
+/// ```
+/// extern crate antidote;
+///
+/// use antidote::Lockable;
+///
+/// #[derive(Debug, Default, Lockable)]
+/// #[lockable(name = LockedCache)]
+/// pub struct Cache<V> {
+///     /// The number of lookups served from the cache.
+///     pub hits: u64,
+///     #[lockable(rwlock, write = entries_mut)]
+///     pub entries: Vec<V>,
+/// }
+///
+/// # fn main() {
+/// let cache = LockedCache::new(Cache::default());
+/// *cache.hits() += 1;
+/// cache.entries_mut().push("/");
+/// assert_eq!(cache.entries().len(), 1);
+///
+/// let cache = cache.into_inner();
+/// assert_eq!(cache.hits, 1);
+/// # }
+/// ```
+pub use antidote_derive::Lockable;
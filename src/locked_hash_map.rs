@@ -0,0 +1,338 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+
+use {MappedMutexGuard, MappedRwLockWriteGuard, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// A `HashMap` behind a `Mutex`.
+///
+/// Each method locks the map only for the duration of the operation. `lock`
+/// provides access to the map itself for anything else.
+///
+/// ```
+/// use antidote::MutexHashMap;
+///
+/// let sessions = MutexHashMap::new();
+/// sessions.insert("alice", 1);
+/// *sessions.entry("bob").or_insert(0) += 1;
+/// assert_eq!(sessions.get_cloned("bob"), Some(1));
+/// assert_eq!(sessions.remove("alice"), Some(1));
+/// ```
+pub struct MutexHashMap<K, V> {
+    map: Mutex<HashMap<K, V>>,
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for MutexHashMap<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.map.fmt(fmt)
+    }
+}
+
+impl<K: Eq + Hash, V> Default for MutexHashMap<K, V> {
+    fn default() -> MutexHashMap<K, V> {
+        MutexHashMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V> From<HashMap<K, V>> for MutexHashMap<K, V> {
+    fn from(map: HashMap<K, V>) -> MutexHashMap<K, V> {
+        MutexHashMap { map: Mutex::new(map) }
+    }
+}
+
+impl<K: Eq + Hash, V> MutexHashMap<K, V> {
+    /// Creates a new, empty map.
+    #[inline]
+    pub fn new() -> MutexHashMap<K, V> {
+        MutexHashMap::from(HashMap::new())
+    }
+
+    /// Returns a clone of the value for `key`.
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash,
+              V: Clone
+    {
+        self.map.lock().get(key).cloned()
+    }
+
+    /// Returns whether the map contains a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash
+    {
+        self.map.lock().contains_key(key)
+    }
+
+    /// Like `HashMap::insert`.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.map.lock().insert(key, value)
+    }
+
+    /// Like `HashMap::remove`.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash
+    {
+        self.map.lock().remove(key)
+    }
+
+    /// Locks the map and returns the entry for `key`.
+    ///
+    /// The map stays locked until the entry, or the guard for its value
+    /// returned by the `or_insert` methods, is dropped.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn entry<'a>(&'a self, key: K) -> MutexHashMapEntry<'a, K, V> {
+        MutexHashMapEntry {
+            map: self.map.lock(),
+            key,
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.lock().len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.lock().is_empty()
+    }
+
+    /// Locks the map.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock<'a>(&'a self) -> MutexGuard<'a, HashMap<K, V>> {
+        self.map.lock()
+    }
+
+    /// Consumes the lock, returning the map.
+    #[inline]
+    pub fn into_inner(self) -> HashMap<K, V> {
+        self.map.into_inner()
+    }
+}
+
+/// An entry in a locked `MutexHashMap`.
+///
+/// This is created by `MutexHashMap::entry`, and keeps the map locked.
+pub struct MutexHashMapEntry<'a, K: 'a, V: 'a> {
+    map: MutexGuard<'a, HashMap<K, V>>,
+    key: K,
+}
+
+impl<'a, K: fmt::Debug, V> fmt::Debug for MutexHashMapEntry<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MutexHashMapEntry").field("key", &self.key).finish()
+    }
+}
+
+impl<'a, K: Eq + Hash, V> MutexHashMapEntry<'a, K, V> {
+    /// Returns the entry's key.
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Calls `f` with the value if the entry is occupied.
+    pub fn and_modify<F>(mut self, f: F) -> MutexHashMapEntry<'a, K, V>
+        where F: FnOnce(&mut V)
+    {
+        if let Some(value) = self.map.get_mut(&self.key) {
+            f(value);
+        }
+        self
+    }
+
+    /// Inserts `default` if the entry is vacant, returning a guard for the
+    /// value.
+    pub fn or_insert(self, default: V) -> MappedMutexGuard<'a, V> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the result of `default` if the entry is vacant, returning a
+    /// guard for the value.
+    pub fn or_insert_with<F>(self, default: F) -> MappedMutexGuard<'a, V>
+        where F: FnOnce() -> V
+    {
+        let key = self.key;
+        MutexGuard::map(self.map, |map| map.entry(key).or_insert_with(default))
+    }
+
+    /// Inserts the default value if the entry is vacant, returning a guard
+    /// for the value.
+    pub fn or_default(self) -> MappedMutexGuard<'a, V>
+        where V: Default
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Removes the entry's value, if it has one.
+    pub fn remove(mut self) -> Option<V> {
+        self.map.remove(&self.key)
+    }
+}
+
+/// A `HashMap` behind an `RwLock`.
+///
+/// Like `MutexHashMap`, but lookups only take a read lock, so they proceed in
+/// parallel.
+pub struct RwLockHashMap<K, V> {
+    map: RwLock<HashMap<K, V>>,
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for RwLockHashMap<K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.map.fmt(fmt)
+    }
+}
+
+impl<K: Eq + Hash, V> Default for RwLockHashMap<K, V> {
+    fn default() -> RwLockHashMap<K, V> {
+        RwLockHashMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V> From<HashMap<K, V>> for RwLockHashMap<K, V> {
+    fn from(map: HashMap<K, V>) -> RwLockHashMap<K, V> {
+        RwLockHashMap { map: RwLock::new(map) }
+    }
+}
+
+impl<K: Eq + Hash, V> RwLockHashMap<K, V> {
+    /// Creates a new, empty map.
+    #[inline]
+    pub fn new() -> RwLockHashMap<K, V> {
+        RwLockHashMap::from(HashMap::new())
+    }
+
+    /// Returns a clone of the value for `key`.
+    pub fn get_cloned<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash,
+              V: Clone
+    {
+        self.map.read().get(key).cloned()
+    }
+
+    /// Returns whether the map contains a value for `key`.
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash
+    {
+        self.map.read().contains_key(key)
+    }
+
+    /// Like `HashMap::insert`.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.map.write().insert(key, value)
+    }
+
+    /// Like `HashMap::remove`.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: ?Sized + Eq + Hash
+    {
+        self.map.write().remove(key)
+    }
+
+    /// Write-locks the map and returns the entry for `key`.
+    ///
+    /// The map stays locked until the entry, or the guard for its value
+    /// returned by the `or_insert` methods, is dropped.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn entry<'a>(&'a self, key: K) -> RwLockHashMapEntry<'a, K, V> {
+        RwLockHashMapEntry {
+            map: self.map.write(),
+            key,
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.map.read().len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.read().is_empty()
+    }
+
+    /// Read-locks the map.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read<'a>(&'a self) -> RwLockReadGuard<'a, HashMap<K, V>> {
+        self.map.read()
+    }
+
+    /// Write-locks the map.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn write<'a>(&'a self) -> RwLockWriteGuard<'a, HashMap<K, V>> {
+        self.map.write()
+    }
+
+    /// Consumes the lock, returning the map.
+    #[inline]
+    pub fn into_inner(self) -> HashMap<K, V> {
+        self.map.into_inner()
+    }
+}
+
+/// An entry in a write-locked `RwLockHashMap`.
+///
+/// This is created by `RwLockHashMap::entry`, and keeps the map locked.
+pub struct RwLockHashMapEntry<'a, K: 'a, V: 'a> {
+    map: RwLockWriteGuard<'a, HashMap<K, V>>,
+    key: K,
+}
+
+impl<'a, K: fmt::Debug, V> fmt::Debug for RwLockHashMapEntry<'a, K, V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RwLockHashMapEntry").field("key", &self.key).finish()
+    }
+}
+
+impl<'a, K: Eq + Hash, V> RwLockHashMapEntry<'a, K, V> {
+    /// Returns the entry's key.
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Calls `f` with the value if the entry is occupied.
+    pub fn and_modify<F>(mut self, f: F) -> RwLockHashMapEntry<'a, K, V>
+        where F: FnOnce(&mut V)
+    {
+        if let Some(value) = self.map.get_mut(&self.key) {
+            f(value);
+        }
+        self
+    }
+
+    /// Inserts `default` if the entry is vacant, returning a guard for the
+    /// value.
+    pub fn or_insert(self, default: V) -> MappedRwLockWriteGuard<'a, V> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the result of `default` if the entry is vacant, returning a
+    /// guard for the value.
+    pub fn or_insert_with<F>(self, default: F) -> MappedRwLockWriteGuard<'a, V>
+        where F: FnOnce() -> V
+    {
+        let key = self.key;
+        RwLockWriteGuard::map(self.map, |map| map.entry(key).or_insert_with(default))
+    }
+
+    /// Inserts the default value if the entry is vacant, returning a guard
+    /// for the value.
+    pub fn or_default(self) -> MappedRwLockWriteGuard<'a, V>
+        where V: Default
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Removes the entry's value, if it has one.
+    pub fn remove(mut self) -> Option<V> {
+        self.map.remove(&self.key)
+    }
+}
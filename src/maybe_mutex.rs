@@ -0,0 +1,247 @@
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use raw::RawMutex;
+use {fmt_guard, GuardMarker, TryLockError, TryLockResult};
+
+/// The implementation behind a `MaybeMutex`.
+///
+/// This is implemented by `MultiThreaded` and `SingleThreaded`. Its methods
+/// are not part of the public API.
+///
+/// # Safety
+///
+/// `try_lock` and `lock` must not succeed while the lock is held.
+pub unsafe trait Threading {
+    #[doc(hidden)]
+    fn new() -> Self;
+
+    #[doc(hidden)]
+    fn try_lock(&self) -> bool;
+
+    #[doc(hidden)]
+    fn lock(&self);
+
+    #[doc(hidden)]
+    unsafe fn unlock(&self);
+}
+
+/// Makes a `MaybeMutex` a real mutex, which can be shared between threads.
+pub struct MultiThreaded(RawMutex);
+
+impl fmt::Debug for MultiThreaded {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("MultiThreaded")
+    }
+}
+
+unsafe impl Threading for MultiThreaded {
+    #[inline]
+    fn new() -> MultiThreaded {
+        MultiThreaded(RawMutex::new())
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        self.0.try_lock()
+    }
+
+    #[inline]
+    fn lock(&self) {
+        self.0.lock()
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        self.0.unlock()
+    }
+}
+
+/// Makes a `MaybeMutex` a flag checked like a `RefCell`'s, which costs no
+/// atomic operations but confines the `MaybeMutex` to one thread.
+///
+/// Locking a `MaybeMutex` which is already locked panics, as a thread can't
+/// wait for itself.
+pub struct SingleThreaded(Cell<bool>);
+
+impl fmt::Debug for SingleThreaded {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("SingleThreaded")
+    }
+}
+
+unsafe impl Threading for SingleThreaded {
+    #[inline]
+    fn new() -> SingleThreaded {
+        SingleThreaded(Cell::new(false))
+    }
+
+    #[inline]
+    fn try_lock(&self) -> bool {
+        !self.0.replace(true)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn lock(&self) {
+        if !self.try_lock() {
+            panic!("single-threaded MaybeMutex is already locked");
+        }
+    }
+
+    #[inline]
+    unsafe fn unlock(&self) {
+        self.0.set(false);
+    }
+}
+
+/// A mutex which is either a real `Mutex` or a single-threaded cell,
+/// depending on its type parameter.
+///
+/// This allows generic code to be written once against a single guard API,
+/// and instantiated by single-threaded users without the cost of atomic
+/// operations. With `SingleThreaded` the `MaybeMutex` is not `Sync`, so the
+/// compiler rules out sharing it between threads.
+///
+/// ```
+/// use antidote::{MaybeMutex, SingleThreaded, Threading};
+///
+/// struct Cache<M: Threading> {
+///     hits: MaybeMutex<u64, M>,
+/// }
+///
+/// impl<M: Threading> Cache<M> {
+///     fn hit(&self) {
+///         *self.hits.lock() += 1;
+///     }
+/// }
+///
+/// let cache = Cache::<SingleThreaded> { hits: MaybeMutex::new(0) };
+/// cache.hit();
+/// assert_eq!(*cache.hits.lock(), 1);
+/// ```
+pub struct MaybeMutex<T: ?Sized, M = MultiThreaded> {
+    lock: M,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send, M: Threading + Send> Send for MaybeMutex<T, M> {}
+unsafe impl<T: ?Sized + Send, M: Threading + Sync> Sync for MaybeMutex<T, M> {}
+
+impl<T: ?Sized + fmt::Debug, M: Threading> fmt::Debug for MaybeMutex<T, M> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Ok(guard) => fmt.debug_struct("MaybeMutex").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("MaybeMutex").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T: Default, M: Threading> Default for MaybeMutex<T, M> {
+    fn default() -> MaybeMutex<T, M> {
+        MaybeMutex::new(T::default())
+    }
+}
+
+impl<T, M: Threading> MaybeMutex<T, M> {
+    /// Creates a new `MaybeMutex`.
+    #[inline]
+    pub fn new(t: T) -> MaybeMutex<T, M> {
+        MaybeMutex {
+            lock: M::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized, M: Threading> MaybeMutex<T, M> {
+    /// Like `Mutex::lock`.
+    ///
+    /// # Panics
+    ///
+    /// With `SingleThreaded`, panics if the `MaybeMutex` is already locked.
+    #[inline]
+    #[track_caller]
+    pub fn lock<'a>(&'a self) -> MaybeMutexGuard<'a, T, M> {
+        self.lock.lock();
+        MaybeMutexGuard::new(self)
+    }
+
+    /// Like `Mutex::try_lock`.
+    #[inline]
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<MaybeMutexGuard<'a, T, M>> {
+        if self.lock.try_lock() {
+            Ok(MaybeMutexGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+/// An RAII guard for a `MaybeMutex`.
+#[must_use]
+pub struct MaybeMutexGuard<'a, T: ?Sized + 'a, M: Threading + 'a = MultiThreaded> {
+    mutex: &'a MaybeMutex<T, M>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a, M: Threading + Sync + 'a> Sync for MaybeMutexGuard<'a, T, M> {}
+
+impl<'a, T: ?Sized, M: Threading> MaybeMutexGuard<'a, T, M> {
+    #[inline]
+    fn new(mutex: &'a MaybeMutex<T, M>) -> MaybeMutexGuard<'a, T, M> {
+        MaybeMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug, M: Threading> fmt::Debug for MaybeMutexGuard<'a, T, M> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "MaybeMutexGuard", self.mutex as *const MaybeMutex<T, M> as *const () as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display, M: Threading> fmt::Display for MaybeMutexGuard<'a, T, M> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized, M: Threading> Deref for MaybeMutexGuard<'a, T, M> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized, M: Threading> DerefMut for MaybeMutexGuard<'a, T, M> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized, M: Threading> Drop for MaybeMutexGuard<'a, T, M> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.mutex.lock.unlock() }
+    }
+}
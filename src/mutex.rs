@@ -0,0 +1,1090 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe, RefUnwindSafe, UnwindSafe};
+use std::pin::Pin;
+use std::ptr;
+use std::sync::{self, Arc, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use backoff::Backoff;
+use blocking::{self, BlockingLockFuture, BlockingTask};
+use clock::{self, Clock};
+use held::{self, Mode};
+use instrument::{Hold, HolderInfo, Instrument, LockStats};
+use interrupt::Interrupt;
+use owned_iter::OwnedIter;
+use raw::{RawCondvar, RawMutex};
+use {deadline_after, fmt_guard, GuardMarker, MutexBuilder, TryLockError, TryLockResult, ZipGuard};
+
+/// A `Mutex` shared through an `Arc`, which `Mutex::lock_arc` locks with a
+/// guard of its own.
+pub type ArcMutex<T> = Arc<Mutex<T>>;
+
+/// Like `std::sync::Mutex` except that it does not poison itself.
+pub struct Mutex<T: ?Sized> {
+    pub(crate) raw: RawMutex,
+    instrument: Option<Box<Instrument>>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+// These match the standard library. Without poisoning, code which observes
+// the data after a panic is responsible for checking its invariants.
+impl<T: ?Sized> UnwindSafe for Mutex<T> {}
+impl<T: ?Sized> RefUnwindSafe for Mutex<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Ok(guard) => fmt.debug_struct("Mutex").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("Mutex").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> Mutex<T> {
+    /// Like `std::sync::Mutex::new`.
+    #[inline]
+    pub fn new(t: T) -> Mutex<T> {
+        MutexBuilder::new().build(t)
+    }
+
+    /// Creates a new `Mutex` which uses the specified strategy, rather than
+    /// the process-wide one, when it is contended.
+    #[inline]
+    pub fn new_with_backoff(t: T, backoff: &'static dyn Backoff) -> Mutex<T> {
+        MutexBuilder::new().backoff(backoff).build(t)
+    }
+
+    #[inline]
+    pub(crate) fn from_parts(t: T, raw: RawMutex, instrument: Option<Box<Instrument>>) -> Mutex<T> {
+        Mutex {
+            raw,
+            instrument,
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Creates a new `Mutex` in an `Arc`.
+    #[inline]
+    pub fn new_arc(t: T) -> ArcMutex<T> {
+        Arc::new(Mutex::new(t))
+    }
+
+    /// Like `std::sync::Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+
+    /// Swaps the values protected by this mutex and `other`.
+    ///
+    /// Both mutexes are locked in order of their addresses, so concurrent
+    /// swaps of the same pair in either direction cannot deadlock. Swapping
+    /// a mutex with itself does nothing.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn swap(&self, other: &Mutex<T>) {
+        if ptr::eq(self, other) {
+            return;
+        }
+        let (first, second) = if self.addr() < other.addr() { (self, other) } else { (other, self) };
+        let mut first = first.lock();
+        let mut second = second.lock();
+        mem::swap(&mut *first, &mut *second);
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Like `std::sync::Mutex::lock`.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// lock rather than deadlocking.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock<'a>(&'a self) -> MutexGuard<'a, T> {
+        held::check("Mutex", self.name(), self.addr(), Mode::Exclusive);
+        self.lock_raw();
+        held::acquired("Mutex", self.name(), self.addr(), Mode::Exclusive);
+        MutexGuard::new(self)
+    }
+
+    /// Like `std::sync::Mutex::try_lock`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<MutexGuard<'a, T>> {
+        if self.raw.try_lock() {
+            self.record_acquired();
+            held::acquired("Mutex", self.name(), self.addr(), Mode::Exclusive);
+            Ok(MutexGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Locks two mutexes, returning their guards bundled together.
+    ///
+    /// The mutexes are locked in order of their addresses, so concurrent
+    /// calls with the same pair in either order cannot deadlock. This is an
+    /// associated function, called as `Mutex::lock_both(&a, &b)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` and `b` are the same mutex.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock_both<'a, U: ?Sized>(a: &'a Mutex<T>,
+                                    b: &'a Mutex<U>)
+                                    -> ZipGuard<(MutexGuard<'a, T>, MutexGuard<'a, U>)> {
+        assert!(a.addr() != b.addr(), "Mutex::lock_both called with the same mutex twice");
+        let guards = if a.addr() < b.addr() {
+            let a = a.lock();
+            (a, b.lock())
+        } else {
+            let b = b.lock();
+            (a.lock(), b)
+        };
+        ZipGuard::new(guards)
+    }
+
+    /// Like `lock`, but returns a guard which holds a reference count on the
+    /// mutex rather than borrowing it.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock_arc(self: &Arc<Self>) -> ArcMutexGuard<T> {
+        held::check("Mutex", self.name(), self.addr(), Mode::Exclusive);
+        self.lock_raw();
+        ArcMutexGuard::new(self.clone())
+    }
+
+    /// Like `try_lock`, but returns a guard which holds a reference count on
+    /// the mutex rather than borrowing it.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock_arc(self: &Arc<Self>) -> TryLockResult<ArcMutexGuard<T>> {
+        if self.raw.try_lock() {
+            self.record_acquired();
+            Ok(ArcMutexGuard::new(self.clone()))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `try_lock`, but if the mutex is held, busy-waits for up to
+    /// `max_spins` rounds for it to be released.
+    ///
+    /// The thread never parks or yields, so this never enters the kernel.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock_spin<'a>(&'a self, max_spins: u32) -> TryLockResult<MutexGuard<'a, T>> {
+        if self.raw.try_lock_spin(max_spins) {
+            self.record_acquired();
+            held::acquired("Mutex", self.name(), self.addr(), Mode::Exclusive);
+            Ok(MutexGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Attempts to acquire the mutex, blocking for at most `timeout`.
+    ///
+    /// Returns `TryLockError::Timeout` if the mutex could not be acquired in
+    /// time. In debug builds, this panics if the current thread already
+    /// holds the lock rather than waiting for the timeout.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock_for<'a>(&'a self, timeout: Duration) -> TryLockResult<MutexGuard<'a, T>> {
+        self.try_lock_until_inner(deadline_after(timeout))
+    }
+
+    /// Attempts to acquire the mutex, blocking until `deadline` at the
+    /// latest.
+    ///
+    /// Returns `TryLockError::Timeout` if the mutex could not be acquired in
+    /// time. In debug builds, this panics if the current thread already
+    /// holds the lock rather than waiting for the deadline.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock_until<'a>(&'a self, deadline: Instant) -> TryLockResult<MutexGuard<'a, T>> {
+        self.try_lock_until_inner(Some(deadline))
+    }
+
+    /// Like `try_lock_until`, but with a deadline measured by `clock`.
+    ///
+    /// See the `clock` module for details.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock_until_clock<'a, C>(&'a self, clock: &C, deadline: C::Instant) -> TryLockResult<MutexGuard<'a, T>>
+        where C: ?Sized + Clock
+    {
+        held::check("Mutex", self.name(), self.addr(), Mode::Exclusive);
+        if clock::lock_until(clock, deadline, |d| self.lock_raw_until(d)) {
+            held::acquired("Mutex", self.name(), self.addr(), Mode::Exclusive);
+            Ok(MutexGuard::new(self))
+        } else {
+            Err(TryLockError::Timeout)
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn try_lock_until_inner<'a>(&'a self, deadline: Option<Instant>) -> TryLockResult<MutexGuard<'a, T>> {
+        held::check("Mutex", self.name(), self.addr(), Mode::Exclusive);
+        if self.lock_raw_until(deadline) {
+            held::acquired("Mutex", self.name(), self.addr(), Mode::Exclusive);
+            Ok(MutexGuard::new(self))
+        } else {
+            Err(TryLockError::Timeout)
+        }
+    }
+
+    /// Acquires the mutex, giving up with `TryLockError::Interrupted` once
+    /// `interrupt` is interrupted.
+    ///
+    /// This lets threads queued on a contended mutex be stopped, for example
+    /// when the process is asked to shut down. A thread blocked here is woken
+    /// as soon as the token is interrupted. The token is also checked before
+    /// the mutex is acquired, so every call with an interrupted token fails.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// lock rather than deadlocking.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock_interruptible<'a>(&'a self, interrupt: &Interrupt) -> TryLockResult<MutexGuard<'a, T>> {
+        held::check("Mutex", self.name(), self.addr(), Mode::Exclusive);
+        if interrupt.is_interrupted() {
+            return Err(TryLockError::Interrupted);
+        }
+        if self.lock_raw_interruptible(interrupt) {
+            held::acquired("Mutex", self.name(), self.addr(), Mode::Exclusive);
+            Ok(MutexGuard::new(self))
+        } else {
+            Err(TryLockError::Interrupted)
+        }
+    }
+
+    /// Acquires the mutex and calls `f` with the locked data, catching a
+    /// panic in `f`.
+    ///
+    /// The mutex is released whether or not `f` panics, and the panic is
+    /// returned as the error value, as with `std::panic::catch_unwind`. As
+    /// the mutex is not poisoned, the data may be left half-updated by a
+    /// panic.
+    ///
+    /// ```
+    /// use antidote::Mutex;
+    ///
+    /// let mutex = Mutex::new(vec![1]);
+    /// let result = mutex.lock_catch_unwind(|v| {
+    ///     v.push(2);
+    ///     panic!("request failed");
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!(*mutex.lock(), [1, 2]);
+    /// ```
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock_catch_unwind<R, F>(&self, f: F) -> thread::Result<R>
+        where F: FnOnce(&mut T) -> R
+    {
+        let mut guard = self.lock();
+        panic::catch_unwind(AssertUnwindSafe(|| f(&mut guard)))
+    }
+
+    /// Like `std::sync::Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    /// Returns whether the mutex is held while other threads are waiting to
+    /// acquire it.
+    ///
+    /// This is a single atomic load, cheap enough for hot paths to check
+    /// before choosing a strategy which avoids the mutex while it is busy. It
+    /// is only a hint, and may be out of date by the time it is returned.
+    #[inline]
+    pub fn is_contended(&self) -> bool {
+        self.raw.is_contended()
+    }
+
+    /// Returns the number of threads waiting to acquire the mutex, or `None`
+    /// if the mutex doesn't count them.
+    ///
+    /// Waiting threads are only counted by a mutex with instrumentation
+    /// enabled, such as a name or statistics set through `MutexBuilder`, so
+    /// that the contended path of other mutexes doesn't pay for it. Use
+    /// `is_contended` to tell whether any thread is waiting for those.
+    ///
+    /// This is a best-effort snapshot intended for monitoring, and may be out
+    /// of date by the time it is returned.
+    ///
+    /// ```
+    /// use antidote::{Mutex, MutexBuilder};
+    ///
+    /// assert_eq!(MutexBuilder::new().name("queue").build(()).waiter_count(), Some(0));
+    /// assert_eq!(Mutex::new(()).waiter_count(), None);
+    /// ```
+    #[inline]
+    pub fn waiter_count(&self) -> Option<usize> {
+        self.instrument.as_ref().map(|instrument| instrument.waiter_count())
+    }
+
+    /// Acquires the mutex from async code without blocking the executor.
+    ///
+    /// `spawn` is called with a task which blocks until the mutex is acquired,
+    /// and must run it on a thread where blocking is acceptable, such as
+    /// with tokio's `spawn_blocking`. The returned future resolves to an owned
+    /// guard once the task has acquired the mutex.
+    ///
+    /// # Panics
+    ///
+    /// The future panics if `spawn` drops the task without running it.
+    pub fn lock_async_via<S>(self: Arc<Self>, spawn: S) -> BlockingLockFuture<ArcMutexGuard<T>>
+        where S: FnOnce(BlockingTask),
+              T: Send + 'static
+    {
+        let (sender, future) = blocking::handoff();
+        spawn(Box::new(move || {
+            self.lock_raw();
+            sender.send(ArcMutexGuard::new(self));
+        }));
+        future
+    }
+
+    /// Acquires the mutex, returning an iterator over the locked collection
+    /// which keeps it locked until the iterator is dropped.
+    ///
+    /// Each item is passed through `f`, which turns it into an owned value.
+    /// See `OwnedIter`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock_iter<F, U>(self: Arc<Self>, f: F) -> OwnedIter<ArcMutexGuard<T>, T, F>
+        where T: 'static,
+              for<'a> &'a T: IntoIterator,
+              F: for<'a> FnMut(<&'a T as IntoIterator>::Item) -> U
+    {
+        self.lock_raw();
+        let guard = ArcMutexGuard::new(self);
+        // The guard keeps both the mutex and the lock alive for as long as
+        // the iterator exists.
+        let data = guard.mutex.data.get();
+        unsafe { OwnedIter::new(guard, data, f) }
+    }
+
+    /// Acquires the mutex without creating a guard.
+    ///
+    /// The mutex stays locked until it is released with `force_unlock`. This
+    /// is equivalent to leaking the guard returned by `lock`, and is intended
+    /// for FFI code where the guard's lifetime cannot be expressed.
+    ///
+    /// The mutex is not built on an OS mutex, so there is no native handle,
+    /// such as a `pthread_mutex_t`, which C code could lock in its place. C
+    /// code has to go through these methods or the `ffi` module instead.
+    #[cfg(feature = "raw")]
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn raw_lock(&self) {
+        self.lock_raw();
+    }
+
+    /// Attempts to acquire the mutex without creating a guard, returning
+    /// whether it was acquired.
+    ///
+    /// Like `raw_lock`, the mutex stays locked until it is released with
+    /// `force_unlock`.
+    #[cfg(feature = "raw")]
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn raw_try_lock(&self) -> bool {
+        let locked = self.raw.try_lock();
+        if locked {
+            self.record_acquired();
+        }
+        locked
+    }
+
+    /// Releases the mutex without a guard.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must be locked, and the lock must be logically owned by the
+    /// caller: it was acquired with `raw_lock`, or with a guard which has
+    /// since been leaked. No guard for the lock may be used afterwards.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub unsafe fn force_unlock(&self) {
+        held::force_released(self.addr(), Mode::Exclusive);
+        self.unlock_raw();
+    }
+
+    /// Returns a raw pointer to the protected data.
+    ///
+    /// The pointer is always valid to create, but may only be dereferenced
+    /// while the caller logically holds the lock.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    /// Returns the name given to the mutex by `MutexBuilder::name`, if any.
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.instrument.as_ref().and_then(|i| i.name())
+    }
+
+    /// Returns statistics about the mutex's acquisitions, if they were
+    /// enabled by `MutexBuilder::stats`.
+    #[inline]
+    pub fn stats(&self) -> Option<LockStats> {
+        self.instrument.as_ref().and_then(|i| i.stats())
+    }
+
+    /// Returns the thread holding the mutex, if it is held and holder
+    /// tracking was enabled by `MutexBuilder::track_holder`.
+    ///
+    /// A thread waiting on a `Condvar` with the mutex's guard does not hold
+    /// the mutex while it waits.
+    #[inline]
+    pub fn holder_info(&self) -> Option<HolderInfo> {
+        self.instrument.as_ref().and_then(|i| i.holder_info())
+    }
+
+    /// Panics if the mutex is locked.
+    ///
+    /// This is intended for tests which check that nothing is still holding
+    /// the mutex. If the mutex tracks its holder, the panic message says who
+    /// is.
+    #[track_caller]
+    pub fn assert_unlocked(&self) {
+        if self.raw.is_locked() {
+            match self.holder_info() {
+                Some(holder) => panic!("{} is locked, held by {}", self.describe(), holder),
+                None => panic!("{} is locked", self.describe()),
+            }
+        }
+    }
+
+    // Describes the mutex for panic messages.
+    fn describe(&self) -> String {
+        match self.name() {
+            Some(name) => format!("Mutex {:?}", name),
+            None => "Mutex".to_string(),
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn lock_raw(&self) {
+        if self.instrument.is_none() {
+            self.raw.lock();
+        } else {
+            self.lock_raw_until(None);
+        }
+    }
+
+    // Acquires the raw mutex, recording the acquisition if the mutex is
+    // instrumented.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn lock_raw_until(&self, deadline: Option<Instant>) -> bool {
+        match self.instrument {
+            None => self.raw.lock_until(deadline),
+            Some(ref instrument) => {
+                if self.raw.try_lock() {
+                    instrument.acquired(true);
+                    true
+                } else {
+                    instrument.acquire_contended(self.addr(), true, deadline, |d| {
+                        self.raw.lock_with_backoff(d, instrument.backoff())
+                    })
+                }
+            }
+        }
+    }
+
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn lock_raw_interruptible(&self, interrupt: &Interrupt) -> bool {
+        let interrupted = || interrupt.is_interrupted();
+        match self.instrument {
+            None => {
+                self.raw.try_lock() ||
+                interrupt.wait_on(&self.raw, || self.raw.lock_interruptible(None, None, &interrupted))
+            }
+            Some(ref instrument) => {
+                if self.raw.try_lock() {
+                    instrument.acquired(true);
+                    true
+                } else {
+                    interrupt.wait_on(&self.raw, || {
+                        instrument.acquire_contended(self.addr(), true, None, |d| {
+                            self.raw.lock_interruptible(d, instrument.backoff(), &interrupted)
+                        })
+                    })
+                }
+            }
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn record_acquired(&self) {
+        if let Some(ref instrument) = self.instrument {
+            instrument.acquired(true);
+        }
+    }
+
+    #[inline]
+    unsafe fn unlock_raw(&self) {
+        unlock(&self.raw, self.instrument.as_deref());
+    }
+
+    // Releases the mutex while waiting on a condition variable, so that the
+    // wait is reported to the instrumentation like any other release.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub(crate) unsafe fn wait_on(&self, condvar: &RawCondvar, deadline: Option<Instant>) -> bool {
+        let seq = condvar.prepare_wait();
+        self.unlock_raw();
+        let woken = condvar.park(seq, deadline);
+        self.lock_raw();
+        woken
+    }
+
+    // Identifies the lock to the held lock tracking. The raw lock's address
+    // is used so that mapped guards, which only know the raw lock, agree.
+    #[inline]
+    fn addr(&self) -> usize {
+        &self.raw as *const RawMutex as usize
+    }
+}
+
+// Releases a raw mutex, invoking its unlock hook if it has one.
+#[inline]
+unsafe fn unlock(raw: &RawMutex, instrument: Option<&Instrument>) {
+    match instrument {
+        None => raw.unlock(),
+        Some(instrument) if instrument.fair() => instrument.release(Hold::Exclusive, || raw.unlock_fair()),
+        Some(instrument) => instrument.release(Hold::Exclusive, || raw.unlock()),
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Mutex::new(Default::default())
+    }
+}
+
+/// Adopts a standard library mutex, ignoring any poisoning.
+impl<T> From<sync::Mutex<T>> for Mutex<T> {
+    #[inline]
+    fn from(mutex: sync::Mutex<T>) -> Mutex<T> {
+        Mutex::new(mutex.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl<T> From<Mutex<T>> for sync::Mutex<T> {
+    #[inline]
+    fn from(mutex: Mutex<T>) -> sync::Mutex<T> {
+        sync::Mutex::new(mutex.into_inner())
+    }
+}
+
+/// Like `std::sync::MutexGuard`.
+///
+/// Unlike the standard library's guard, this implements `Send` when the
+/// `send_guard` feature is enabled. It also implements `io::Read`,
+/// `io::Write`, `io::Seek` and `fmt::Write` when the locked data does:
+///
+/// ```
+/// use antidote::Mutex;
+/// use std::io::Write;
+///
+/// let log = Mutex::new(Vec::new());
+/// writeln!(log.lock(), "connected").unwrap();
+/// assert_eq!(*log.lock(), b"connected\n");
+/// ```
+#[must_use]
+pub struct MutexGuard<'a, T: ?Sized + 'a> {
+    pub(crate) mutex: &'a Mutex<T>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for MutexGuard<'a, T> {}
+
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a Mutex<T>) -> MutexGuard<'a, T> {
+        MutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes a new guard for a component of the locked data.
+    ///
+    /// The mutex stays locked until the returned guard is dropped. This is an
+    /// associated function rather than a method to avoid conflicting with
+    /// methods of the protected value.
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedMutexGuard<'a, U>
+        where F: FnOnce(&mut T) -> &mut U
+    {
+        let raw = &orig.mutex.raw;
+        let instrument = orig.mutex.instrument.as_deref();
+        let data = f(unsafe { &mut *orig.mutex.data.get() }) as *mut U;
+        mem::forget(orig);
+        MappedMutexGuard::new(raw, instrument, data, None)
+    }
+
+    /// Attempts to make a new guard for a component of the locked data.
+    ///
+    /// The original guard is returned if the closure returns `None`.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedMutexGuard<'a, U>, Self>
+        where F: FnOnce(&mut T) -> Option<&mut U>
+    {
+        let raw = &orig.mutex.raw;
+        let instrument = orig.mutex.instrument.as_deref();
+        match f(unsafe { &mut *orig.mutex.data.get() }) {
+            Some(data) => {
+                let data = data as *mut U;
+                mem::forget(orig);
+                Ok(MappedMutexGuard::new(raw, instrument, data, None))
+            }
+            None => Err(orig),
+        }
+    }
+
+    /// Splits the guard into guards for two disjoint components of the
+    /// locked data.
+    ///
+    /// The mutex stays locked until both returned guards are dropped.
+    ///
+    /// ```
+    /// use antidote::{Mutex, MutexGuard};
+    ///
+    /// let mutex = Mutex::new((vec![1], String::new()));
+    /// let (mut numbers, mut text) = MutexGuard::split_map(mutex.lock(), |pair| (&mut pair.0, &mut pair.1));
+    /// numbers.push(2);
+    /// text.push_str("two");
+    /// drop(numbers);
+    /// assert!(mutex.try_lock().is_err());
+    /// drop(text);
+    /// assert_eq!(*mutex.lock(), (vec![1, 2], "two".to_string()));
+    /// ```
+    #[inline]
+    pub fn split_map<A: ?Sized, B: ?Sized, F>(orig: Self,
+                                              f: F)
+                                              -> (MappedMutexGuard<'a, A>, MappedMutexGuard<'a, B>)
+        where F: FnOnce(&mut T) -> (&mut A, &mut B)
+    {
+        let raw = &orig.mutex.raw;
+        let instrument = orig.mutex.instrument.as_deref();
+        let (a, b) = f(unsafe { &mut *orig.mutex.data.get() });
+        let (a, b) = (a as *mut A, b as *mut B);
+        mem::forget(orig);
+        let split = Arc::new(());
+        (MappedMutexGuard::new(raw, instrument, a, Some(split.clone())),
+         MappedMutexGuard::new(raw, instrument, b, Some(split)))
+    }
+
+    /// Makes a new pinned guard for a structurally pinned component of the
+    /// locked data.
+    ///
+    /// Pinned guards are returned by `PinMutex::lock`.
+    #[inline]
+    pub fn map_pin<U: ?Sized, F>(orig: Pin<Self>, f: F) -> Pin<MappedMutexGuard<'a, U>>
+        where F: FnOnce(Pin<&mut T>) -> Pin<&mut U>
+    {
+        unsafe {
+            let orig = Pin::into_inner_unchecked(orig);
+            let guard = MutexGuard::map(orig, |data| Pin::get_unchecked_mut(f(Pin::new_unchecked(data))));
+            Pin::new_unchecked(guard)
+        }
+    }
+
+    /// Leaks the guard, returning a reference to the locked data which lasts
+    /// as long as the mutex's borrow.
+    ///
+    /// The mutex stays locked. With the `raw` feature, it can be released
+    /// with `Mutex::force_unlock` once the reference is no longer used.
+    ///
+    /// ```
+    /// use antidote::{Mutex, MutexGuard};
+    ///
+    /// let mutex = Mutex::new(1);
+    /// let data = MutexGuard::leak(mutex.lock());
+    /// *data += 1;
+    /// assert!(mutex.try_lock().is_err());
+    /// ```
+    #[inline]
+    pub fn leak(orig: Self) -> &'a mut T {
+        let data = orig.mutex.data.get();
+        mem::forget(orig);
+        unsafe { &mut *data }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for MutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "MutexGuard", self.mutex.addr(), &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for MutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for MutexGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for MutexGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.mutex.addr(), Mode::Exclusive);
+        unsafe { self.mutex.unlock_raw() };
+        held::check_released(owned);
+    }
+}
+
+/// An RAII guard for a component of the data protected by a `Mutex`.
+///
+/// This is created by `MutexGuard::map`, and unlocks the mutex when dropped.
+/// Guards created by `MutexGuard::split_map` unlock the mutex when the last
+/// of them is dropped.
+#[must_use]
+pub struct MappedMutexGuard<'a, T: ?Sized + 'a> {
+    raw: &'a RawMutex,
+    instrument: Option<&'a Instrument>,
+    data: *mut T,
+    // Shared by guards split from one guard.
+    split: Option<Arc<()>>,
+    _marker: PhantomData<(&'a mut T, GuardMarker)>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for MappedMutexGuard<'a, T> {}
+#[cfg(feature = "send_guard")]
+unsafe impl<'a, T: ?Sized + Send + 'a> Send for MappedMutexGuard<'a, T> {}
+
+impl<'a, T: ?Sized> MappedMutexGuard<'a, T> {
+    #[inline]
+    fn new(raw: &'a RawMutex,
+           instrument: Option<&'a Instrument>,
+           data: *mut T,
+           split: Option<Arc<()>>)
+           -> MappedMutexGuard<'a, T> {
+        MappedMutexGuard {
+            raw,
+            instrument,
+            data,
+            split,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Like `MutexGuard::map`.
+    #[inline]
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedMutexGuard<'a, U>
+        where F: FnOnce(&mut T) -> &mut U
+    {
+        let raw = orig.raw;
+        let instrument = orig.instrument;
+        let split = orig.split.take();
+        let data = f(unsafe { &mut *orig.data }) as *mut U;
+        mem::forget(orig);
+        MappedMutexGuard::new(raw, instrument, data, split)
+    }
+
+    /// Like `MutexGuard::try_map`.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(mut orig: Self, f: F) -> Result<MappedMutexGuard<'a, U>, Self>
+        where F: FnOnce(&mut T) -> Option<&mut U>
+    {
+        let raw = orig.raw;
+        let instrument = orig.instrument;
+        match f(unsafe { &mut *orig.data }) {
+            Some(data) => {
+                let data = data as *mut U;
+                let split = orig.split.take();
+                mem::forget(orig);
+                Ok(MappedMutexGuard::new(raw, instrument, data, split))
+            }
+            None => Err(orig),
+        }
+    }
+
+    /// Like `MutexGuard::split_map`.
+    #[inline]
+    pub fn split_map<A: ?Sized, B: ?Sized, F>(mut orig: Self,
+                                              f: F)
+                                              -> (MappedMutexGuard<'a, A>, MappedMutexGuard<'a, B>)
+        where F: FnOnce(&mut T) -> (&mut A, &mut B)
+    {
+        let raw = orig.raw;
+        let instrument = orig.instrument;
+        let split = orig.split.take().unwrap_or_default();
+        let (a, b) = f(unsafe { &mut *orig.data });
+        let (a, b) = (a as *mut A, b as *mut B);
+        mem::forget(orig);
+        (MappedMutexGuard::new(raw, instrument, a, Some(split.clone())),
+         MappedMutexGuard::new(raw, instrument, b, Some(split)))
+    }
+
+    /// Like `MutexGuard::map_pin`.
+    #[inline]
+    pub fn map_pin<U: ?Sized, F>(orig: Pin<Self>, f: F) -> Pin<MappedMutexGuard<'a, U>>
+        where F: FnOnce(Pin<&mut T>) -> Pin<&mut U>
+    {
+        unsafe {
+            let orig = Pin::into_inner_unchecked(orig);
+            let guard = MappedMutexGuard::map(orig, |data| Pin::get_unchecked_mut(f(Pin::new_unchecked(data))));
+            Pin::new_unchecked(guard)
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for MappedMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "MappedMutexGuard", self.raw as *const RawMutex as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for MappedMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for MappedMutexGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for MappedMutexGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for MappedMutexGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.split.take().is_some_and(|split| Arc::into_inner(split).is_none()) {
+            return;
+        }
+        let owned = held::released(self.raw as *const RawMutex as usize, Mode::Exclusive);
+        unsafe { unlock(self.raw, self.instrument) };
+        held::check_released(owned);
+    }
+}
+
+/// An RAII guard for a `Mutex` which keeps the mutex alive through an `Arc`.
+///
+/// Unlike `MutexGuard`, this is `'static` if `T` is, and is always `Send`.
+/// Since it may be released on a different thread than the one which
+/// acquired it, the debug-build deadlock detection doesn't record it as
+/// held. `lock_arc` still panics if the current thread holds a
+/// `MutexGuard` for the mutex.
+#[must_use]
+pub struct ArcMutexGuard<T: ?Sized> {
+    mutex: Arc<Mutex<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ?Sized> ArcMutexGuard<T> {
+    #[inline]
+    fn new(mutex: Arc<Mutex<T>>) -> ArcMutexGuard<T> {
+        ArcMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the mutex this guard locks.
+    #[inline]
+    pub fn mutex(this: &ArcMutexGuard<T>) -> &Arc<Mutex<T>> {
+        &this.mutex
+    }
+
+    /// Like `MutexGuard::map`, but the returned guard keeps holding a
+    /// reference count on the mutex.
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedArcMutexGuard<T, U>
+        where F: FnOnce(&mut T) -> &mut U
+    {
+        let data = f(unsafe { &mut *orig.mutex.data.get() }) as *mut U;
+        MappedArcMutexGuard::new(ArcMutexGuard::into_mutex(orig), data)
+    }
+
+    /// Like `MutexGuard::try_map`, but the returned guard keeps holding a
+    /// reference count on the mutex.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedArcMutexGuard<T, U>, Self>
+        where F: FnOnce(&mut T) -> Option<&mut U>
+    {
+        match f(unsafe { &mut *orig.mutex.data.get() }) {
+            Some(data) => {
+                let data = data as *mut U;
+                Ok(MappedArcMutexGuard::new(ArcMutexGuard::into_mutex(orig), data))
+            }
+            None => Err(orig),
+        }
+    }
+
+    // Takes the mutex out of the guard without unlocking it.
+    #[inline]
+    fn into_mutex(this: Self) -> Arc<Mutex<T>> {
+        let mutex = unsafe { ptr::read(&this.mutex) };
+        mem::forget(this);
+        mutex
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ArcMutexGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "ArcMutexGuard", self.mutex.addr(), &**self)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for ArcMutexGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized> Deref for ArcMutexGuard<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for ArcMutexGuard<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for ArcMutexGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.mutex.unlock_raw() }
+    }
+}
+
+/// An RAII guard for a component of the data protected by a `Mutex`, which
+/// keeps the mutex alive through an `Arc`.
+///
+/// This is created by `ArcMutexGuard::map`, and unlocks the mutex when
+/// dropped.
+#[must_use]
+pub struct MappedArcMutexGuard<T: ?Sized, U: ?Sized> {
+    mutex: Arc<Mutex<T>>,
+    data: *mut U,
+}
+
+unsafe impl<T: ?Sized + Send, U: ?Sized + Send> Send for MappedArcMutexGuard<T, U> {}
+unsafe impl<T: ?Sized + Send, U: ?Sized + Sync> Sync for MappedArcMutexGuard<T, U> {}
+
+impl<T: ?Sized, U: ?Sized> MappedArcMutexGuard<T, U> {
+    #[inline]
+    fn new(mutex: Arc<Mutex<T>>, data: *mut U) -> MappedArcMutexGuard<T, U> {
+        MappedArcMutexGuard { mutex, data }
+    }
+
+    /// Returns the mutex this guard locks.
+    #[inline]
+    pub fn mutex(this: &MappedArcMutexGuard<T, U>) -> &Arc<Mutex<T>> {
+        &this.mutex
+    }
+
+    /// Like `MutexGuard::map`.
+    #[inline]
+    pub fn map<V: ?Sized, F>(orig: Self, f: F) -> MappedArcMutexGuard<T, V>
+        where F: FnOnce(&mut U) -> &mut V
+    {
+        let data = f(unsafe { &mut *orig.data }) as *mut V;
+        MappedArcMutexGuard::new(MappedArcMutexGuard::into_mutex(orig), data)
+    }
+
+    /// Like `MutexGuard::try_map`.
+    #[inline]
+    pub fn try_map<V: ?Sized, F>(orig: Self, f: F) -> Result<MappedArcMutexGuard<T, V>, Self>
+        where F: FnOnce(&mut U) -> Option<&mut V>
+    {
+        match f(unsafe { &mut *orig.data }) {
+            Some(data) => {
+                let data = data as *mut V;
+                Ok(MappedArcMutexGuard::new(MappedArcMutexGuard::into_mutex(orig), data))
+            }
+            None => Err(orig),
+        }
+    }
+
+    #[inline]
+    fn into_mutex(this: Self) -> Arc<Mutex<T>> {
+        let mutex = unsafe { ptr::read(&this.mutex) };
+        mem::forget(this);
+        mutex
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Debug> fmt::Debug for MappedArcMutexGuard<T, U> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "MappedArcMutexGuard", self.mutex.addr(), &**self)
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Display> fmt::Display for MappedArcMutexGuard<T, U> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Deref for MappedArcMutexGuard<T, U> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        unsafe { &*self.data }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> DerefMut for MappedArcMutexGuard<T, U> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut U {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Drop for MappedArcMutexGuard<T, U> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.mutex.unlock_raw() }
+    }
+}
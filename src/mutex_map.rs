@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use held::{self, Mode};
+use raw::RawMutex;
+use {GuardMarker, Mutex, TryLockError, TryLockResult};
+
+/// A set of mutexes identified by key, for serializing operations per key.
+///
+/// Locking a key blocks while another thread holds the lock for an equal
+/// key, and operations on different keys proceed in parallel. The mutex for
+/// a key is created when it is first locked, and removed once no thread
+/// holds or is waiting for it, so the map only ever contains keys which are
+/// in use.
+pub struct MutexMap<K> {
+    entries: Mutex<HashMap<K, Arc<RawMutex>>>,
+}
+
+impl<K: Eq + Hash + fmt::Debug> fmt::Debug for MutexMap<K> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_set().entries(self.entries.lock().keys()).finish()
+    }
+}
+
+impl<K: Eq + Hash + Clone> MutexMap<K> {
+    /// Creates a new, empty `MutexMap`.
+    #[inline]
+    pub fn new() -> MutexMap<K> {
+        MutexMap { entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// Acquires the mutex for `key`, blocking the current thread until it is
+    /// able to do so.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// mutex for `key` rather than deadlocking.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock<'a>(&'a self, key: K) -> MutexMapGuard<'a, K> {
+        let entry = self.entry(&key);
+        let addr = &*entry as *const RawMutex as usize;
+        held::check("MutexMap entry", None, addr, Mode::Exclusive);
+        entry.lock();
+        held::acquired("MutexMap entry", None, addr, Mode::Exclusive);
+        MutexMapGuard::new(self, key, entry)
+    }
+
+    /// Attempts to acquire the mutex for `key`.
+    ///
+    /// This function does not block.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock<'a>(&'a self, key: K) -> TryLockResult<MutexMapGuard<'a, K>> {
+        let entry = self.entry(&key);
+        if entry.try_lock() {
+            held::acquired("MutexMap entry", None, &*entry as *const RawMutex as usize, Mode::Exclusive);
+            Ok(MutexMapGuard::new(self, key, entry))
+        } else {
+            self.release(&key, entry);
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Returns the number of keys which are currently locked or waited on.
+    pub fn len(&self) -> usize {
+        self.entries.lock().len()
+    }
+
+    /// Returns `true` if no keys are currently locked or waited on.
+    pub fn is_empty(&self) -> bool {
+        self.entries.lock().is_empty()
+    }
+
+    fn entry(&self, key: &K) -> Arc<RawMutex> {
+        self.entries
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(RawMutex::new()))
+            .clone()
+    }
+
+    // Drops a reference to the entry for `key`, removing it from the map if
+    // it was the last one outside of the map. References are only created
+    // with the map locked, so none can appear concurrently.
+    fn release(&self, key: &K, entry: Arc<RawMutex>) {
+        let mut entries = self.entries.lock();
+        drop(entry);
+        if entries.get(key).is_some_and(|e| Arc::strong_count(e) == 1) {
+            entries.remove(key);
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> Default for MutexMap<K> {
+    fn default() -> MutexMap<K> {
+        MutexMap::new()
+    }
+}
+
+/// An RAII guard for the mutex of a key in a `MutexMap`.
+#[must_use]
+pub struct MutexMapGuard<'a, K: Eq + Hash + Clone + 'a> {
+    map: &'a MutexMap<K>,
+    key: K,
+    entry: Option<Arc<RawMutex>>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, K: Eq + Hash + Clone + Sync + 'a> Sync for MutexMapGuard<'a, K> {}
+
+impl<'a, K: Eq + Hash + Clone + fmt::Debug> fmt::Debug for MutexMapGuard<'a, K> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("MutexMapGuard").field("key", &self.key).finish()
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone> MutexMapGuard<'a, K> {
+    #[inline]
+    fn new(map: &'a MutexMap<K>, key: K, entry: Arc<RawMutex>) -> MutexMapGuard<'a, K> {
+        MutexMapGuard {
+            map,
+            key,
+            entry: Some(entry),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the key whose mutex this guard holds.
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<'a, K: Eq + Hash + Clone> Drop for MutexMapGuard<'a, K> {
+    fn drop(&mut self) {
+        let entry = self.entry.take().unwrap();
+        let owned = held::released(&*entry as *const RawMutex as usize, Mode::Exclusive);
+        unsafe { entry.unlock() };
+        self.map.release(&self.key, entry);
+        held::check_released(owned);
+    }
+}
@@ -0,0 +1,60 @@
+//! Implementations of the `mutex-trait` crate's `Mutex` trait, so drivers
+//! written against it can share this crate's locks.
+//!
+//! The trait takes `&mut self`, so it is implemented for shared references
+//! to the locks, which can be copied into every driver using the lock.
+
+use mutex_trait;
+
+use {Mutex, RwLock, TicketMutex};
+
+/// Locks the mutex for the duration of the closure.
+///
+/// ```
+/// extern crate antidote;
+/// extern crate mutex_trait;
+///
+/// use antidote::Mutex;
+///
+/// fn increment<M: mutex_trait::Mutex<Data = u32>>(mut counter: M) {
+///     counter.lock(|n| *n += 1);
+/// }
+///
+/// # fn main() {
+/// let counter = Mutex::new(0);
+/// increment(&counter);
+/// increment(&counter);
+/// assert_eq!(*counter.lock(), 2);
+/// # }
+/// ```
+impl<T> mutex_trait::Mutex for &Mutex<T> {
+    type Data = T;
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn lock<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut Mutex::lock(self))
+    }
+}
+
+/// Locks the mutex for the duration of the closure.
+impl<T> mutex_trait::Mutex for &TicketMutex<T> {
+    type Data = T;
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn lock<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut TicketMutex::lock(self))
+    }
+}
+
+/// Write-locks the lock for the duration of the closure.
+impl<T> mutex_trait::Mutex for &RwLock<T> {
+    type Data = T;
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn lock<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut RwLock::write(self))
+    }
+}
@@ -0,0 +1,157 @@
+use std::ffi::CString;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+
+use libc;
+
+use {GuardMarker, TryLockError, TryLockResult};
+
+/// A mutex identified by a system-wide name, for coordinating between
+/// processes.
+///
+/// Every process which opens a `NamedMutex` with the same name shares the
+/// same lock. This is implemented with a POSIX named semaphore, so it is
+/// only available on Unix platforms. Names are limited to a single path
+/// component; a leading `/` is added if it is missing.
+///
+/// Since the mutex protects no data, its guard does not dereference to
+/// anything. The operating system does not track which process holds the
+/// lock, so a process which exits while holding it leaves it locked.
+pub struct NamedMutex {
+    sem: *mut libc::sem_t,
+    name: String,
+}
+
+unsafe impl Send for NamedMutex {}
+unsafe impl Sync for NamedMutex {}
+
+impl fmt::Debug for NamedMutex {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("NamedMutex").field("name", &self.name).finish()
+    }
+}
+
+fn c_name(name: &str) -> io::Result<CString> {
+    let name = if name.starts_with('/') {
+        name.to_string()
+    } else {
+        format!("/{}", name)
+    };
+    CString::new(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+impl NamedMutex {
+    /// Opens the mutex with the specified name, creating it if it does not
+    /// already exist.
+    pub fn open(name: &str) -> io::Result<NamedMutex> {
+        let c_name = c_name(name)?;
+        let sem = unsafe {
+            libc::sem_open(c_name.as_ptr(), libc::O_CREAT, 0o644 as libc::c_uint, 1 as libc::c_uint)
+        };
+        if sem == libc::SEM_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(NamedMutex {
+            sem,
+            name: c_name.into_string().unwrap(),
+        })
+    }
+
+    /// Removes the mutex with the specified name from the system.
+    ///
+    /// Processes which already have the mutex open can continue to use it,
+    /// but later calls to `open` will create a new, distinct mutex.
+    pub fn remove(name: &str) -> io::Result<()> {
+        let c_name = c_name(name)?;
+        if unsafe { libc::sem_unlink(c_name.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the mutex, including the leading `/`.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the POSIX semaphore behind the mutex, for sharing it with C
+    /// code.
+    ///
+    /// The semaphore's count is 1 while the mutex is unlocked, so C code can
+    /// lock it with `sem_wait` and unlock it with `sem_post`. The pointer is
+    /// valid for as long as this `NamedMutex` is alive, and must not be
+    /// passed to `sem_close`.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn as_raw_sem(&self) -> *mut libc::sem_t {
+        self.sem
+    }
+
+    /// Like `Mutex::lock`.
+    pub fn lock<'a>(&'a self) -> NamedMutexGuard<'a> {
+        while unsafe { libc::sem_wait(self.sem) } != 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EINTR) {
+                panic!("failed to lock named mutex: {}", err);
+            }
+        }
+        NamedMutexGuard::new(self)
+    }
+
+    /// Like `Mutex::try_lock`.
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<NamedMutexGuard<'a>> {
+        loop {
+            if unsafe { libc::sem_trywait(self.sem) } == 0 {
+                return Ok(NamedMutexGuard::new(self));
+            }
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => {}
+                Some(libc::EAGAIN) => return Err(TryLockError::WouldBlock),
+                _ => panic!("failed to lock named mutex: {}", err),
+            }
+        }
+    }
+}
+
+impl Drop for NamedMutex {
+    fn drop(&mut self) {
+        unsafe {
+            libc::sem_close(self.sem);
+        }
+    }
+}
+
+/// An RAII guard for a `NamedMutex`.
+#[must_use]
+pub struct NamedMutexGuard<'a> {
+    mutex: &'a NamedMutex,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a> Sync for NamedMutexGuard<'a> {}
+
+impl<'a> NamedMutexGuard<'a> {
+    fn new(mutex: &'a NamedMutex) -> NamedMutexGuard<'a> {
+        NamedMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a> fmt::Debug for NamedMutexGuard<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("NamedMutexGuard").field("mutex", self.mutex).finish()
+    }
+}
+
+impl<'a> Drop for NamedMutexGuard<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            libc::sem_post(self.mutex.sem);
+        }
+    }
+}
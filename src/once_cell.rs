@@ -0,0 +1,143 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use Mutex;
+
+/// A thread-safe cell which is written to at most once, like
+/// `std::sync::OnceLock` except that it does not poison itself.
+///
+/// If an initialization closure panics or fails, the cell is left empty and
+/// the next caller simply runs its own closure. While one thread is
+/// initializing the cell, other threads trying to initialize it block until
+/// it finishes.
+pub struct OnceCell<T> {
+    complete: AtomicBool,
+    // Held while a thread runs an initialization closure.
+    init: Mutex<()>,
+    value: UnsafeCell<Option<T>>,
+}
+
+unsafe impl<T: Send> Send for OnceCell<T> {}
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+
+impl<T: fmt::Debug> fmt::Debug for OnceCell<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(value) => fmt.debug_tuple("OnceCell").field(value).finish(),
+            None => fmt.write_str("OnceCell(<uninit>)"),
+        }
+    }
+}
+
+impl<T> OnceCell<T> {
+    /// Creates a new, empty cell.
+    #[inline]
+    pub fn new() -> OnceCell<T> {
+        OnceCell {
+            complete: AtomicBool::new(false),
+            init: Mutex::new(()),
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    /// Returns a reference to the value, if the cell has been initialized.
+    #[inline]
+    pub fn get(&self) -> Option<&T> {
+        if self.complete.load(Ordering::Acquire) {
+            unsafe { (*self.value.get()).as_ref() }
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the value, if the cell has been
+    /// initialized.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        unsafe { (*self.value.get()).as_mut() }
+    }
+
+    /// Initializes the cell with `value`.
+    ///
+    /// Returns `value` back if the cell was already initialized.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        match value {
+            Some(value) => Err(value),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the value, initializing the cell with `f` if it is empty.
+    ///
+    /// If `f` panics, the panic propagates and the cell remains empty.
+    ///
+    /// In debug builds, this panics if `f` tries to initialize the same cell
+    /// rather than deadlocking.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn get_or_init<F>(&self, f: F) -> &T
+        where F: FnOnce() -> T
+    {
+        enum Void {}
+
+        match self.get_or_try_init(|| Ok::<T, Void>(f())) {
+            Ok(value) => value,
+            Err(void) => match void {},
+        }
+    }
+
+    /// Returns the value, initializing the cell with `f` if it is empty.
+    ///
+    /// If `f` returns an error or panics, the cell remains empty and a later
+    /// call may try again.
+    ///
+    /// In debug builds, this panics if `f` tries to initialize the same cell
+    /// rather than deadlocking.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn get_or_try_init<F, E>(&self, f: F) -> Result<&T, E>
+        where F: FnOnce() -> Result<T, E>
+    {
+        if let Some(value) = self.get() {
+            return Ok(value);
+        }
+
+        let _guard = self.init.lock();
+        if !self.complete.load(Ordering::Acquire) {
+            let value = f()?;
+            unsafe { *self.value.get() = Some(value) };
+            self.complete.store(true, Ordering::Release);
+        }
+        Ok(unsafe { (*self.value.get()).as_ref().unwrap() })
+    }
+
+    /// Takes the value out of the cell, leaving it empty.
+    #[inline]
+    pub fn take(&mut self) -> Option<T> {
+        *self.complete.get_mut() = false;
+        unsafe { (*self.value.get()).take() }
+    }
+
+    /// Consumes the cell, returning its value if it has been initialized.
+    #[inline]
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> OnceCell<T> {
+        OnceCell::new()
+    }
+}
+
+impl<T> From<T> for OnceCell<T> {
+    fn from(value: T) -> OnceCell<T> {
+        OnceCell {
+            complete: AtomicBool::new(true),
+            init: Mutex::new(()),
+            value: UnsafeCell::new(Some(value)),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt;
+use std::iter::FusedIterator;
+
+/// An iterator over the contents of a locked collection which owns the guard
+/// keeping it locked.
+///
+/// This is created by `Mutex::lock_iter` and `RwLock::read_iter`. Because it
+/// doesn't borrow the lock it can be returned from a function or stored, and
+/// since its items can't outlive it they are passed through a closure which
+/// turns them into owned values, typically by cloning them. The lock is
+/// released when the iterator is dropped.
+///
+/// ```
+/// use antidote::Mutex;
+/// use std::collections::HashMap;
+/// use std::sync::Arc;
+///
+/// fn names(names: &Arc<Mutex<Vec<String>>>) -> impl Iterator<Item = String> {
+///     names.clone().lock_iter(|name| name.clone())
+/// }
+///
+/// let shared = Arc::new(Mutex::new(vec!["a".to_string(), "b".to_string()]));
+/// assert_eq!(names(&shared).collect::<Vec<_>>(), ["a", "b"]);
+///
+/// let ages = Arc::new(Mutex::new(HashMap::from([("alice".to_string(), 30)])));
+/// let ages = ages.lock_iter(|(name, age)| (name.clone(), *age)).collect::<Vec<_>>();
+/// assert_eq!(ages, [("alice".to_string(), 30)]);
+/// ```
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct OwnedIter<G, T: ?Sized + 'static, F>
+    where for<'a> &'a T: IntoIterator
+{
+    // Borrows from the data locked by `_guard`, so must be dropped first. The
+    // lifetime is a lie kept inside this type: items are only handed to `f`,
+    // which accepts them for any lifetime and so can't hold on to them.
+    iter: <&'static T as IntoIterator>::IntoIter,
+    f: F,
+    _guard: G,
+}
+
+impl<G, T: ?Sized, F> OwnedIter<G, T, F>
+    where for<'a> &'a T: IntoIterator
+{
+    // `data` must stay locked while `guard` is alive.
+    #[inline]
+    pub(crate) unsafe fn new(guard: G, data: *const T, f: F) -> OwnedIter<G, T, F> {
+        OwnedIter {
+            iter: (*data).into_iter(),
+            f,
+            _guard: guard,
+        }
+    }
+}
+
+impl<G, T: ?Sized, F> fmt::Debug for OwnedIter<G, T, F>
+    where for<'a> &'a T: IntoIterator
+{
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("OwnedIter").finish()
+    }
+}
+
+impl<G, T: ?Sized, F, U> Iterator for OwnedIter<G, T, F>
+    where for<'a> &'a T: IntoIterator,
+          F: for<'a> FnMut(<&'a T as IntoIterator>::Item) -> U
+{
+    type Item = U;
+
+    #[inline]
+    fn next(&mut self) -> Option<U> {
+        self.iter.next().map(&mut self.f)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<G, T: ?Sized, F, U> DoubleEndedIterator for OwnedIter<G, T, F>
+    where for<'a> &'a T: IntoIterator<IntoIter: DoubleEndedIterator>,
+          F: for<'a> FnMut(<&'a T as IntoIterator>::Item) -> U
+{
+    #[inline]
+    fn next_back(&mut self) -> Option<U> {
+        self.iter.next_back().map(&mut self.f)
+    }
+}
+
+impl<G, T: ?Sized, F, U> ExactSizeIterator for OwnedIter<G, T, F>
+    where for<'a> &'a T: IntoIterator<IntoIter: ExactSizeIterator>,
+          F: for<'a> FnMut(<&'a T as IntoIterator>::Item) -> U
+{
+}
+
+impl<G, T: ?Sized, F, U> FusedIterator for OwnedIter<G, T, F>
+    where for<'a> &'a T: IntoIterator<IntoIter: FusedIterator>,
+          F: for<'a> FnMut(<&'a T as IntoIterator>::Item) -> U
+{
+}
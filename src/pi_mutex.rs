@@ -0,0 +1,155 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use raw::RawPiMutex;
+use {fmt_guard, TryLockError, TryLockResult};
+
+/// A mutex which boosts the priority of its holder while higher priority
+/// threads are waiting for it.
+///
+/// This avoids priority inversion, where a low priority thread holding the
+/// lock is starved by medium priority threads while a high priority thread
+/// waits on it. On Linux this is implemented with priority-inheritance
+/// futexes. On other platforms it is a regular mutex.
+pub struct PiMutex<T: ?Sized> {
+    raw: RawPiMutex,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for PiMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for PiMutex<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PiMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Ok(guard) => fmt.debug_struct("PiMutex").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("PiMutex").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> PiMutex<T> {
+    /// Creates a new priority-inheriting mutex.
+    #[inline]
+    pub fn new(t: T) -> PiMutex<T> {
+        PiMutex {
+            raw: RawPiMutex::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> PiMutex<T> {
+    /// Like `Mutex::lock`.
+    #[inline]
+    pub fn lock<'a>(&'a self) -> PiMutexGuard<'a, T> {
+        self.raw.lock();
+        PiMutexGuard::new(self)
+    }
+
+    /// Like `Mutex::try_lock`.
+    #[inline]
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<PiMutexGuard<'a, T>> {
+        if self.raw.try_lock() {
+            Ok(PiMutexGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    /// Returns a pointer to the priority-inheritance futex word behind the
+    /// mutex, for sharing it with C code.
+    ///
+    /// The word follows the kernel's protocol for `FUTEX_LOCK_PI`: it is 0
+    /// while unlocked, and holds the owner's thread ID, as returned by
+    /// `gettid`, while locked. C code can lock it by compare-and-swapping 0
+    /// to its thread ID, falling back to `FUTEX_LOCK_PI`, and unlock it by
+    /// compare-and-swapping its thread ID back to 0, falling back to
+    /// `FUTEX_UNLOCK_PI`. The data may only be accessed while the lock is
+    /// held. The pointer is valid for as long as the mutex is alive.
+    ///
+    /// Only available on Linux and Android, where the mutex is implemented
+    /// with priority-inheritance futexes.
+    #[cfg(all(feature = "raw", any(target_os = "linux", target_os = "android")))]
+    #[inline]
+    pub fn as_raw_futex(&self) -> *mut u32 {
+        self.raw.futex_word()
+    }
+}
+
+impl<T: Default> Default for PiMutex<T> {
+    fn default() -> Self {
+        PiMutex::new(Default::default())
+    }
+}
+
+/// An RAII guard for a `PiMutex`.
+///
+/// The kernel tracks the owner of the lock by thread, so unlike the other
+/// guards this is never `Send`.
+#[must_use]
+pub struct PiMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a PiMutex<T>,
+    _marker: PhantomData<*mut ()>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for PiMutexGuard<'a, T> {}
+
+impl<'a, T: ?Sized> PiMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a PiMutex<T>) -> PiMutexGuard<'a, T> {
+        PiMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for PiMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "PiMutexGuard", self.mutex as *const PiMutex<T> as *const () as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for PiMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for PiMutexGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for PiMutexGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for PiMutexGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.mutex.raw.unlock() }
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+use std::pin::Pin;
+
+use {Mutex, MutexGuard, TryLockResult};
+
+/// A `Mutex` whose data is structurally pinned.
+///
+/// A plain `Mutex` can't hand out pinned references to its data even when it
+/// is itself pinned, since its guards give out `&mut T` and `Mutex::swap`
+/// moves the data between mutexes. A pinned `PinMutex` only gives out pinned
+/// guards, through which the data can be reached as a `Pin<&mut T>` with
+/// `Pin::as_mut`, and projected with `MutexGuard::map_pin`. The data is only
+/// reachable as a `&mut T` if it is `Unpin`.
+///
+/// ```
+/// use antidote::{MutexGuard, PinMutex};
+/// use std::marker::PhantomPinned;
+/// use std::pin::pin;
+///
+/// struct Node {
+///     value: u32,
+///     _pin: PhantomPinned,
+/// }
+///
+/// let node = pin!(PinMutex::new(Node { value: 1, _pin: PhantomPinned }));
+/// let guard = node.as_ref().lock();
+/// // `value` is `Unpin`, so the projected guard gives out `&mut u32`.
+/// let mut value = MutexGuard::map_pin(guard, |n| unsafe { n.map_unchecked_mut(|n| &mut n.value) });
+/// *value += 1;
+/// ```
+pub struct PinMutex<T: ?Sized> {
+    mutex: Mutex<T>,
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PinMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("PinMutex").field("mutex", &&self.mutex).finish()
+    }
+}
+
+impl<T: Default> Default for PinMutex<T> {
+    fn default() -> PinMutex<T> {
+        PinMutex::new(T::default())
+    }
+}
+
+impl<T> PinMutex<T> {
+    /// Creates a new `PinMutex`.
+    ///
+    /// The data is pinned once the mutex itself is, with `std::pin::pin!` or
+    /// `Box::pin`, for example.
+    #[inline]
+    pub fn new(t: T) -> PinMutex<T> {
+        PinMutex { mutex: Mutex::new(t) }
+    }
+
+    /// Like `Mutex::into_inner`.
+    ///
+    /// A pinned mutex can't be passed by value unless its data is `Unpin`, so
+    /// this never moves pinned data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.mutex.into_inner()
+    }
+}
+
+impl<T: ?Sized> PinMutex<T> {
+    /// Acquires the mutex, returning a pinned guard.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock<'a>(self: Pin<&'a Self>) -> Pin<MutexGuard<'a, T>> {
+        // The data is pinned whenever the mutex is, since nothing else can
+        // reach the inner mutex.
+        unsafe { Pin::new_unchecked(self.get_ref().mutex.lock()) }
+    }
+
+    /// Like `Mutex::try_lock`, but returning a pinned guard.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock<'a>(self: Pin<&'a Self>) -> TryLockResult<Pin<MutexGuard<'a, T>>> {
+        self.get_ref().mutex.try_lock().map(|guard| unsafe { Pin::new_unchecked(guard) })
+    }
+
+    /// Returns a pinned reference to the data, which needs no locking since
+    /// the mutex is borrowed mutably.
+    #[inline]
+    pub fn get_mut(self: Pin<&mut Self>) -> Pin<&mut T> {
+        unsafe { self.map_unchecked_mut(|m| m.mutex.get_mut()) }
+    }
+}
+
+impl<T> From<T> for PinMutex<T> {
+    fn from(t: T) -> PinMutex<T> {
+        PinMutex::new(t)
+    }
+}
@@ -0,0 +1,211 @@
+//! A sampling profiler for blocked lock acquisitions.
+//!
+//! While the profiler is running, a sample of the acquisitions of `Mutex`es
+//! and `RwLock`s which have to block record the call stack they blocked at,
+//! the lock they blocked on, and how long they waited. The result can be
+//! written out as folded stacks, the input format of `flamegraph.pl` and
+//! `inferno-flamegraph`:
+//!
+//! ```no_run
+//! use antidote::profiler;
+//! use std::fs::File;
+//!
+//! profiler::start(10);
+//! // run the workload...
+//! let profile = profiler::stop();
+//! profile.write_folded(File::create("locks.folded").unwrap()).unwrap();
+//! ```
+//!
+//! ```text
+//! $ flamegraph.pl --countname=us locks.folded > locks.svg
+//! ```
+//!
+//! The lock appears as the leaf frame of each stack, identified by its
+//! address and, if it has one, its name.
+//!
+//! Capturing a call stack is expensive, so only blocked acquisitions are
+//! considered at all, and only every `n`th of those is sampled. Stack frames
+//! are resolved to symbols with `std::backtrace`, so the program needs debug
+//! symbols for the output to be useful.
+
+use std::backtrace::Backtrace;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+
+// The profiler records samples into a standard library mutex, as blocking on
+// one of this crate's locks would recursively take a sample.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+static SAMPLE_EVERY: AtomicU32 = AtomicU32::new(1);
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+static SAMPLES: Mutex<Option<HashMap<Key, Totals>>> = Mutex::new(None);
+static NAMES: Mutex<Option<HashMap<usize, String>>> = Mutex::new(None);
+
+#[derive(PartialEq, Eq, Hash)]
+struct Key {
+    stack: Vec<String>,
+    kind: &'static str,
+    lock: usize,
+}
+
+#[derive(Default, Copy, Clone)]
+struct Totals {
+    samples: u64,
+    wait_micros: u64,
+}
+
+/// Starts the profiler, sampling every `n`th blocked acquisition.
+///
+/// Samples are accumulated until `stop` is called. `n` is clamped to at
+/// least 1.
+pub fn start(n: u32) {
+    SAMPLE_EVERY.store(n.max(1), Ordering::Relaxed);
+    lock(&SAMPLES).get_or_insert_with(HashMap::new);
+    RUNNING.store(true, Ordering::Relaxed);
+}
+
+/// Stops the profiler, returning the samples taken since it was started.
+pub fn stop() -> Profile {
+    RUNNING.store(false, Ordering::Relaxed);
+    let samples = lock(&SAMPLES).take().unwrap_or_default();
+    let names = lock(&NAMES).take().unwrap_or_default();
+    Profile { samples, names }
+}
+
+/// Returns whether the profiler is running.
+#[inline]
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::Relaxed)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Associates a name with a lock's address, for locks which have one.
+#[cold]
+pub(crate) fn name(addr: usize, name: &str) {
+    lock(&NAMES).get_or_insert_with(HashMap::new).entry(addr).or_insert_with(|| name.to_string());
+}
+
+/// A blocked acquisition being sampled, recorded when dropped.
+pub(crate) struct Sample {
+    key: Option<Key>,
+    start: Instant,
+}
+
+/// Called before a lock acquisition blocks. Returns `None` if the
+/// acquisition is not sampled.
+#[inline]
+pub(crate) fn sample(kind: &'static str, lock: usize) -> Option<Sample> {
+    if !is_running() {
+        return None;
+    }
+    sample_slow(kind, lock)
+}
+
+#[cold]
+fn sample_slow(kind: &'static str, lock: usize) -> Option<Sample> {
+    let every = SAMPLE_EVERY.load(Ordering::Relaxed) as u64;
+    if !COUNTER.fetch_add(1, Ordering::Relaxed).is_multiple_of(every) {
+        return None;
+    }
+    Some(Sample {
+        key: Some(Key {
+            stack: stack(),
+            kind,
+            lock,
+        }),
+        start: Instant::now(),
+    })
+}
+
+impl Drop for Sample {
+    fn drop(&mut self) {
+        let wait_micros = self.start.elapsed().as_micros().min(u64::MAX as u128) as u64;
+        let key = self.key.take().unwrap();
+        if let Some(ref mut samples) = *lock(&SAMPLES) {
+            let totals = samples.entry(key).or_default();
+            totals.samples += 1;
+            totals.wait_micros += wait_micros;
+        }
+    }
+}
+
+// Captures the current call stack, outermost frame first, leaving out the
+// profiler and lock internals and the runtime's frames below `main` or the
+// thread's entry point.
+fn stack() -> Vec<String> {
+    let backtrace = Backtrace::force_capture().to_string();
+    let mut frames = backtrace.lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            let colon = line.find(": ")?;
+            if line[..colon].bytes().all(|b| b.is_ascii_digit()) {
+                Some(&line[colon + 2..])
+            } else {
+                None
+            }
+        })
+        .skip_while(|frame| frame.starts_with("std::backtrace") || frame.starts_with("antidote::"))
+        .take_while(|frame| !frame.starts_with("std::sys::backtrace::__rust_begin_short_backtrace"))
+        // Semicolons separate frames in the folded format.
+        .map(|frame| frame.replace(';', ":"))
+        .collect::<Vec<_>>();
+    frames.reverse();
+    frames
+}
+
+/// The samples collected by the profiler.
+pub struct Profile {
+    samples: HashMap<Key, Totals>,
+    names: HashMap<usize, String>,
+}
+
+impl fmt::Debug for Profile {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Profile").field("stacks", &self.samples.len()).finish()
+    }
+}
+
+impl Profile {
+    /// Returns whether no samples were taken.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the total number of samples taken.
+    pub fn samples(&self) -> u64 {
+        self.samples.values().map(|t| t.samples).sum()
+    }
+
+    /// Writes the samples in the folded stacks format.
+    ///
+    /// Each line holds the frames of a stack separated by semicolons,
+    /// followed by the total number of microseconds the samples with that
+    /// stack waited for the lock.
+    pub fn write_folded<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let mut lines = self.samples
+            .iter()
+            .map(|(key, totals)| {
+                let mut line = key.stack.join(";");
+                if !line.is_empty() {
+                    line.push(';');
+                }
+                match self.names.get(&key.lock) {
+                    Some(name) => line.push_str(&format!("{} {:?} ({:#x})", key.kind, name, key.lock)),
+                    None => line.push_str(&format!("{} {:#x}", key.kind, key.lock)),
+                }
+                (line, totals.wait_micros)
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+        for (line, wait_micros) in lines {
+            writeln!(w, "{} {}", line, wait_micros)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use mutex::{MappedMutexGuard, MutexGuard};
+use rwlock::{MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLockReadGuard, RwLockWriteGuard};
+
+/// Projects a lock guard onto one of the fields of the locked data.
+///
+/// `guard_project!(guard.field)` consumes `guard` and returns a mapped guard
+/// for `field`, keeping the lock held until the mapped guard is dropped. Any
+/// number of fields, including tuple indices, may be chained. Read guards
+/// project to read guards, and write guards to write guards.
+///
+/// ```
+/// #[macro_use]
+/// extern crate antidote;
+///
+/// use antidote::Mutex;
+///
+/// struct Config {
+///     limits: Limits,
+/// }
+///
+/// struct Limits {
+///     max_connections: usize,
+/// }
+///
+/// # fn main() {
+/// let config = Mutex::new(Config { limits: Limits { max_connections: 10 } });
+///
+/// let guard = config.lock();
+/// let mut max_connections = guard_project!(guard.limits.max_connections);
+/// *max_connections += 1;
+/// # }
+/// ```
+#[macro_export]
+macro_rules! guard_project {
+    ($guard:ident $(. $field:tt)+) => {
+        $crate::Project::project($guard, |v| &v $(. $field)+, |v| &mut v $(. $field)+)
+    };
+}
+
+/// The implementation of `guard_project!`.
+///
+/// Shared guards use the first closure and exclusive guards the second.
+#[doc(hidden)]
+pub trait Project<'a>: Sized {
+    type Target: ?Sized;
+    type Output<U: ?Sized + 'a>;
+
+    fn project<U: ?Sized, F, G>(self, shared: F, unique: G) -> Self::Output<U>
+        where F: FnOnce(&Self::Target) -> &U,
+              G: FnOnce(&mut Self::Target) -> &mut U;
+}
+
+impl<'a, T: ?Sized> Project<'a> for MutexGuard<'a, T> {
+    type Target = T;
+    type Output<U: ?Sized + 'a> = MappedMutexGuard<'a, U>;
+
+    #[inline]
+    fn project<U: ?Sized, F, G>(self, _: F, unique: G) -> MappedMutexGuard<'a, U>
+        where F: FnOnce(&T) -> &U,
+              G: FnOnce(&mut T) -> &mut U
+    {
+        MutexGuard::map(self, unique)
+    }
+}
+
+impl<'a, T: ?Sized> Project<'a> for MappedMutexGuard<'a, T> {
+    type Target = T;
+    type Output<U: ?Sized + 'a> = MappedMutexGuard<'a, U>;
+
+    #[inline]
+    fn project<U: ?Sized, F, G>(self, _: F, unique: G) -> MappedMutexGuard<'a, U>
+        where F: FnOnce(&T) -> &U,
+              G: FnOnce(&mut T) -> &mut U
+    {
+        MappedMutexGuard::map(self, unique)
+    }
+}
+
+impl<'a, T: ?Sized> Project<'a> for RwLockReadGuard<'a, T> {
+    type Target = T;
+    type Output<U: ?Sized + 'a> = MappedRwLockReadGuard<'a, U>;
+
+    #[inline]
+    fn project<U: ?Sized, F, G>(self, shared: F, _: G) -> MappedRwLockReadGuard<'a, U>
+        where F: FnOnce(&T) -> &U,
+              G: FnOnce(&mut T) -> &mut U
+    {
+        RwLockReadGuard::map(self, shared)
+    }
+}
+
+impl<'a, T: ?Sized> Project<'a> for MappedRwLockReadGuard<'a, T> {
+    type Target = T;
+    type Output<U: ?Sized + 'a> = MappedRwLockReadGuard<'a, U>;
+
+    #[inline]
+    fn project<U: ?Sized, F, G>(self, shared: F, _: G) -> MappedRwLockReadGuard<'a, U>
+        where F: FnOnce(&T) -> &U,
+              G: FnOnce(&mut T) -> &mut U
+    {
+        MappedRwLockReadGuard::map(self, shared)
+    }
+}
+
+impl<'a, T: ?Sized> Project<'a> for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    type Output<U: ?Sized + 'a> = MappedRwLockWriteGuard<'a, U>;
+
+    #[inline]
+    fn project<U: ?Sized, F, G>(self, _: F, unique: G) -> MappedRwLockWriteGuard<'a, U>
+        where F: FnOnce(&T) -> &U,
+              G: FnOnce(&mut T) -> &mut U
+    {
+        RwLockWriteGuard::map(self, unique)
+    }
+}
+
+impl<'a, T: ?Sized> Project<'a> for MappedRwLockWriteGuard<'a, T> {
+    type Target = T;
+    type Output<U: ?Sized + 'a> = MappedRwLockWriteGuard<'a, U>;
+
+    #[inline]
+    fn project<U: ?Sized, F, G>(self, _: F, unique: G) -> MappedRwLockWriteGuard<'a, U>
+        where F: FnOnce(&T) -> &U,
+              G: FnOnce(&mut T) -> &mut U
+    {
+        MappedRwLockWriteGuard::map(self, unique)
+    }
+}
@@ -0,0 +1,150 @@
+//! Lock statistics in the Prometheus text exposition format.
+//!
+//! Every `Mutex` and `RwLock` which collects statistics, whether enabled by
+//! its builder or by `config::Defaults::stats`, is registered for as long as
+//! it exists. `gather` returns their statistics as text to be served from a
+//! `/metrics` endpoint:
+//!
+//! ```
+//! use antidote::{prometheus, MutexBuilder};
+//!
+//! let pool = MutexBuilder::new().name("connection pool").stats(true).build(vec![1, 2, 3]);
+//! pool.lock().pop();
+//!
+//! let metrics = prometheus::gather();
+//! assert!(metrics.contains(r#"antidote_lock_acquisitions_total{kind="Mutex",lock="connection pool"} 1"#));
+//! ```
+//!
+//! Each series is labelled with the kind of lock and its name, or an empty
+//! name for unnamed locks, and sums the statistics of every lock with that
+//! kind and name. Dropped locks remain counted, so the counters never
+//! decrease.
+//!
+//! This module is only available with the `prometheus` feature.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use instrument::Counters;
+use LockStats;
+
+// The registry is a standard library mutex, as locking one of this crate's
+// locks while creating another would be recursive.
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+type Key = (&'static str, String);
+
+#[derive(Default)]
+struct Registry {
+    live: HashMap<usize, (Key, Arc<Counters>)>,
+    // The totals of dropped locks.
+    retired: HashMap<Key, LockStats>,
+}
+
+fn registry() -> MutexGuard<'static, Option<Registry>> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn id(counters: &Arc<Counters>) -> usize {
+    Arc::as_ptr(counters) as usize
+}
+
+pub(crate) fn register(kind: &'static str, name: Option<&str>, counters: &Arc<Counters>) {
+    let key = (kind, name.unwrap_or("").to_string());
+    registry().get_or_insert_with(Registry::default).live.insert(id(counters), (key, counters.clone()));
+}
+
+pub(crate) fn unregister(counters: &Arc<Counters>) {
+    let mut registry = registry();
+    let registry = registry.get_or_insert_with(Registry::default);
+    if let Some((key, counters)) = registry.live.remove(&id(counters)) {
+        registry.retired.entry(key).or_default().merge(&counters.stats());
+    }
+}
+
+/// Returns the statistics of every lock in the Prometheus text exposition
+/// format.
+///
+/// The metrics are:
+///
+/// * `antidote_lock_acquisitions_total` - a counter of acquisitions.
+/// * `antidote_lock_contended_acquisitions_total` - a counter of
+///   acquisitions which had to wait.
+/// * `antidote_lock_wait_seconds_total` - a counter of the time spent
+///   waiting.
+/// * `antidote_lock_max_wait_seconds` - a gauge of the longest time a single
+///   acquisition waited.
+/// * `antidote_locks` - a gauge of the number of locks which currently
+///   exist.
+pub fn gather() -> String {
+    let mut series = BTreeMap::<Key, (LockStats, u64)>::new();
+    if let Some(ref registry) = *registry() {
+        for (key, stats) in &registry.retired {
+            series.entry(key.clone()).or_default().0.merge(stats);
+        }
+        for (key, counters) in registry.live.values() {
+            let entry = series.entry(key.clone()).or_default();
+            entry.0.merge(&counters.stats());
+            entry.1 += 1;
+        }
+    }
+
+    let mut out = String::new();
+    metric(&mut out,
+           "antidote_lock_acquisitions_total",
+           "counter",
+           "Number of times the lock has been acquired.",
+           &series,
+           |s, _| s.acquisitions().to_string());
+    metric(&mut out,
+           "antidote_lock_contended_acquisitions_total",
+           "counter",
+           "Number of acquisitions which had to wait for the lock to be released.",
+           &series,
+           |s, _| s.contended_acquisitions().to_string());
+    metric(&mut out,
+           "antidote_lock_wait_seconds_total",
+           "counter",
+           "Total time spent waiting for the lock.",
+           &series,
+           |s, _| s.total_wait().as_secs_f64().to_string());
+    metric(&mut out,
+           "antidote_lock_max_wait_seconds",
+           "gauge",
+           "Longest time a single acquisition waited for the lock.",
+           &series,
+           |s, _| s.max_wait().as_secs_f64().to_string());
+    metric(&mut out,
+           "antidote_locks",
+           "gauge",
+           "Number of locks which currently exist.",
+           &series,
+           |_, live| live.to_string());
+    out
+}
+
+fn metric<F>(out: &mut String,
+             name: &str,
+             kind: &str,
+             help: &str,
+             series: &BTreeMap<Key, (LockStats, u64)>,
+             value: F)
+    where F: Fn(&LockStats, u64) -> String
+{
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (&(lock_kind, ref lock), &(ref stats, live)) in series {
+        let _ = writeln!(out,
+                         "{}{{kind=\"{}\",lock=\"{}\"}} {}",
+                         name,
+                         lock_kind,
+                         escape(lock),
+                         value(stats, live));
+    }
+}
+
+// Escapes a label value.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
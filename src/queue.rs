@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use {Condvar, Mutex, MutexGuard};
+
+/// An unbounded multi-producer, multi-consumer FIFO queue, whose consumers
+/// block while it's empty.
+///
+/// ```
+/// use antidote::BlockingQueue;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let jobs = Arc::new(BlockingQueue::new());
+/// let worker = {
+///     let jobs = jobs.clone();
+///     thread::spawn(move || jobs.pop() * 2)
+/// };
+/// jobs.push(21);
+/// assert_eq!(worker.join().unwrap(), 42);
+/// ```
+pub struct BlockingQueue<T> {
+    items: Mutex<VecDeque<T>>,
+    not_empty: Condvar,
+}
+
+impl<T: fmt::Debug> fmt::Debug for BlockingQueue<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BlockingQueue").field("items", &self.items).finish()
+    }
+}
+
+impl<T> Default for BlockingQueue<T> {
+    fn default() -> BlockingQueue<T> {
+        BlockingQueue::new()
+    }
+}
+
+impl<T> BlockingQueue<T> {
+    /// Creates a new, empty queue.
+    #[inline]
+    pub fn new() -> BlockingQueue<T> {
+        BlockingQueue {
+            items: Mutex::new(VecDeque::new()),
+            not_empty: Condvar::new(),
+        }
+    }
+
+    /// Adds an item to the back of the queue, waking a consumer waiting for
+    /// one.
+    pub fn push(&self, item: T) {
+        self.items.lock().push_back(item);
+        // Notifying only when the queue was empty would leave consumers
+        // asleep when several items are pushed before the first one wakes.
+        self.not_empty.notify_one();
+    }
+
+    /// Removes the item at the front of the queue, if there is one.
+    pub fn try_pop(&self) -> Option<T> {
+        self.items.lock().pop_front()
+    }
+
+    /// Removes the item at the front of the queue, blocking the current
+    /// thread until there is one.
+    pub fn pop(&self) -> T {
+        let mut items = self.not_empty.wait_while(self.items.lock(), |items| items.is_empty());
+        items.pop_front().unwrap()
+    }
+
+    /// Like `pop`, but gives up after `timeout`, returning `None`.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        let (mut items, _) = self.not_empty.wait_timeout_while(self.items.lock(), timeout, |items| items.is_empty());
+        items.pop_front()
+    }
+
+    /// Like `pop`, but gives up once `deadline` passes, returning `None`.
+    pub fn pop_until(&self, deadline: Instant) -> Option<T> {
+        let (mut items, _) = self.not_empty.wait_while_until(self.items.lock(), deadline, |items| items.is_empty());
+        items.pop_front()
+    }
+
+    /// Returns the number of items in the queue.
+    pub fn len(&self) -> usize {
+        self.items.lock().len()
+    }
+
+    /// Returns `true` if the queue contains no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.lock().is_empty()
+    }
+
+    /// Consumes the queue, returning its items.
+    #[inline]
+    pub fn into_inner(self) -> VecDeque<T> {
+        self.items.into_inner()
+    }
+}
+
+/// A multi-producer, multi-consumer FIFO queue with a fixed capacity, whose
+/// producers block while it's full and consumers block while it's empty.
+///
+/// This provides backpressure between stages of a pipeline: a producer
+/// which outpaces its consumers is slowed down to their rate rather than
+/// queueing work without limit.
+///
+/// ```
+/// use antidote::BoundedQueue;
+/// use std::time::Duration;
+///
+/// let queue = BoundedQueue::new(1);
+/// queue.push(1);
+/// assert_eq!(queue.push_timeout(2, Duration::from_millis(1)), Err(2));
+/// assert_eq!(queue.pop(), 1);
+/// ```
+pub struct BoundedQueue<T> {
+    items: Mutex<VecDeque<T>>,
+    capacity: usize,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl<T: fmt::Debug> fmt::Debug for BoundedQueue<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("BoundedQueue")
+            .field("items", &self.items)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl<T> BoundedQueue<T> {
+    /// Creates a new, empty queue which holds at most `capacity` items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> BoundedQueue<T> {
+        assert!(capacity != 0, "BoundedQueue capacity must be nonzero");
+        BoundedQueue {
+            items: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+        }
+    }
+
+    /// Adds an item to the back of the queue, blocking the current thread
+    /// until there is space for it.
+    pub fn push(&self, item: T) {
+        let items = self.not_full.wait_while(self.items.lock(), |items| items.len() == self.capacity);
+        self.push_locked(items, item);
+    }
+
+    /// Adds an item to the back of the queue if there is space for it,
+    /// returning it otherwise.
+    pub fn try_push(&self, item: T) -> Result<(), T> {
+        let items = self.items.lock();
+        if items.len() == self.capacity {
+            return Err(item);
+        }
+        self.push_locked(items, item);
+        Ok(())
+    }
+
+    /// Like `push`, but gives up after `timeout`, returning the item.
+    pub fn push_timeout(&self, item: T, timeout: Duration) -> Result<(), T> {
+        let (items, result) =
+            self.not_full.wait_timeout_while(self.items.lock(), timeout, |items| items.len() == self.capacity);
+        if result.timed_out() {
+            return Err(item);
+        }
+        self.push_locked(items, item);
+        Ok(())
+    }
+
+    /// Like `push`, but gives up once `deadline` passes, returning the item.
+    pub fn push_until(&self, item: T, deadline: Instant) -> Result<(), T> {
+        let (items, result) =
+            self.not_full.wait_while_until(self.items.lock(), deadline, |items| items.len() == self.capacity);
+        if result.timed_out() {
+            return Err(item);
+        }
+        self.push_locked(items, item);
+        Ok(())
+    }
+
+    fn push_locked(&self, mut items: MutexGuard<VecDeque<T>>, item: T) {
+        items.push_back(item);
+        drop(items);
+        self.not_empty.notify_one();
+    }
+
+    /// Removes the item at the front of the queue, blocking the current
+    /// thread until there is one.
+    pub fn pop(&self) -> T {
+        let items = self.not_empty.wait_while(self.items.lock(), |items| items.is_empty());
+        self.pop_locked(items).unwrap()
+    }
+
+    /// Removes the item at the front of the queue, if there is one.
+    pub fn try_pop(&self) -> Option<T> {
+        self.pop_locked(self.items.lock())
+    }
+
+    /// Like `pop`, but gives up after `timeout`, returning `None`.
+    pub fn pop_timeout(&self, timeout: Duration) -> Option<T> {
+        let (items, _) = self.not_empty.wait_timeout_while(self.items.lock(), timeout, |items| items.is_empty());
+        self.pop_locked(items)
+    }
+
+    /// Like `pop`, but gives up once `deadline` passes, returning `None`.
+    pub fn pop_until(&self, deadline: Instant) -> Option<T> {
+        let (items, _) = self.not_empty.wait_while_until(self.items.lock(), deadline, |items| items.is_empty());
+        self.pop_locked(items)
+    }
+
+    fn pop_locked(&self, mut items: MutexGuard<VecDeque<T>>) -> Option<T> {
+        let item = items.pop_front();
+        drop(items);
+        if item.is_some() {
+            self.not_full.notify_one();
+        }
+        item
+    }
+
+    /// Returns the maximum number of items the queue holds.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of items in the queue.
+    pub fn len(&self) -> usize {
+        self.items.lock().len()
+    }
+
+    /// Returns `true` if the queue contains no items.
+    pub fn is_empty(&self) -> bool {
+        self.items.lock().is_empty()
+    }
+
+    /// Consumes the queue, returning its items.
+    #[inline]
+    pub fn into_inner(self) -> VecDeque<T> {
+        self.items.into_inner()
+    }
+}
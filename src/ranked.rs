@@ -0,0 +1,181 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use {Mutex, MutexGuard};
+
+/// Evidence of the highest rank of `RankedMutex` held by the current thread.
+///
+/// Ranked mutexes must be acquired in strictly increasing order of rank,
+/// which rules out deadlocks between them. Locking a `RankedMutex` requires a
+/// token for a lower rank, which is borrowed for as long as the guard is
+/// alive, and the guard provides a token of the mutex's own rank for locking
+/// higher-ranked mutexes. Acquiring a mutex with a token of an equal or
+/// higher rank fails to compile:
+///
+/// ```compile_fail
+/// use antidote::{LockToken, RankedMutex, RankedMutexGuard};
+///
+/// let accounts = RankedMutex::<_, 1>::new(vec![100]);
+/// let ledger = RankedMutex::<_, 2>::new(vec![]);
+///
+/// let mut token = LockToken::new();
+/// let mut entries = ledger.lock(&mut token);
+/// // Acquiring rank 1 while holding rank 2 is rejected.
+/// let balances = accounts.lock(RankedMutexGuard::token(&mut entries));
+/// ```
+///
+/// This only orders ranked mutexes against each other, and relies on each
+/// thread creating a single token with `LockToken::new`, at the top of its
+/// call stack, and passing it down.
+pub struct LockToken<const RANK: u32> {
+    // Tokens are created by `new` or handed out by guards, and are tied to
+    // the current thread.
+    _marker: PhantomData<*mut ()>,
+}
+
+impl<const RANK: u32> fmt::Debug for LockToken<RANK> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("LockToken").field("rank", &RANK).finish()
+    }
+}
+
+impl LockToken<0> {
+    /// Creates the token for a thread which holds no ranked mutexes.
+    #[inline]
+    pub fn new() -> LockToken<0> {
+        LockToken { _marker: PhantomData }
+    }
+}
+
+impl Default for LockToken<0> {
+    fn default() -> LockToken<0> {
+        LockToken::new()
+    }
+}
+
+/// A `Mutex` with a rank, which determines the order it may be acquired in
+/// relative to other ranked mutexes.
+///
+/// See `LockToken`.
+///
+/// ```
+/// use antidote::{LockToken, RankedMutex, RankedMutexGuard};
+///
+/// let accounts = RankedMutex::<_, 1>::new(vec![100]);
+/// let ledger = RankedMutex::<_, 2>::new(vec![]);
+///
+/// let mut token = LockToken::new();
+/// let mut balances = accounts.lock(&mut token);
+/// let mut entries = ledger.lock(RankedMutexGuard::token(&mut balances));
+/// entries.push(-10);
+/// drop(entries);
+/// balances[0] -= 10;
+/// ```
+pub struct RankedMutex<T: ?Sized, const RANK: u32> {
+    mutex: Mutex<T>,
+}
+
+impl<T: ?Sized + fmt::Debug, const RANK: u32> fmt::Debug for RankedMutex<T, RANK> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RankedMutex").field("rank", &RANK).field("mutex", &&self.mutex).finish()
+    }
+}
+
+impl<T: Default, const RANK: u32> Default for RankedMutex<T, RANK> {
+    fn default() -> RankedMutex<T, RANK> {
+        RankedMutex::new(T::default())
+    }
+}
+
+impl<T, const RANK: u32> RankedMutex<T, RANK> {
+    /// Creates a new `RankedMutex`.
+    #[inline]
+    pub fn new(t: T) -> RankedMutex<T, RANK> {
+        RankedMutex { mutex: Mutex::new(t) }
+    }
+
+    /// Like `Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.mutex.into_inner()
+    }
+}
+
+impl<T: ?Sized, const RANK: u32> RankedMutex<T, RANK> {
+    /// Acquires the mutex, given a token showing that the current thread
+    /// holds only ranked mutexes of lower ranks.
+    ///
+    /// Fails to compile if `HELD` is not less than the mutex's rank.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock<'a, 't, const HELD: u32>(&'a self,
+                                         _token: &'t mut LockToken<HELD>)
+                                         -> RankedMutexGuard<'a, 't, T, RANK> {
+        const {
+            assert!(HELD < RANK, "RankedMutex acquired out of order");
+        }
+        RankedMutexGuard {
+            guard: self.mutex.lock(),
+            token: LockToken { _marker: PhantomData },
+            _parent: PhantomData,
+        }
+    }
+
+    /// Like `Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.mutex.get_mut()
+    }
+}
+
+/// An RAII guard for a `RankedMutex`.
+///
+/// This is created by `RankedMutex::lock`, and holds on to the token it was
+/// given.
+#[must_use]
+pub struct RankedMutexGuard<'a, 't, T: ?Sized + 'a, const RANK: u32> {
+    guard: MutexGuard<'a, T>,
+    token: LockToken<RANK>,
+    _parent: PhantomData<&'t mut ()>,
+}
+
+impl<'a, 't, T: ?Sized, const RANK: u32> RankedMutexGuard<'a, 't, T, RANK> {
+    /// Returns a token for acquiring mutexes of higher ranks while this guard
+    /// is held.
+    ///
+    /// This is an associated function rather than a method to avoid
+    /// conflicting with methods of the protected value.
+    #[inline]
+    pub fn token(this: &mut Self) -> &mut LockToken<RANK> {
+        &mut this.token
+    }
+}
+
+impl<'a, 't, T: ?Sized + fmt::Debug, const RANK: u32> fmt::Debug for RankedMutexGuard<'a, 't, T, RANK> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.guard.fmt(fmt)
+    }
+}
+
+impl<'a, 't, T: ?Sized + fmt::Display, const RANK: u32> fmt::Display for RankedMutexGuard<'a, 't, T, RANK> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, 't, T: ?Sized, const RANK: u32> Deref for RankedMutexGuard<'a, 't, T, RANK> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, 't, T: ?Sized, const RANK: u32> DerefMut for RankedMutexGuard<'a, 't, T, RANK> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
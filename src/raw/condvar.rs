@@ -0,0 +1,137 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::Instant;
+
+use futex;
+#[cfg(feature = "testing")]
+use testing::deterministic;
+
+// The state counts the threads waiting in the low half, and the
+// notifications sent to them which they have yet to consume in the high
+// half. A notification is only sent if there are more waiters than pending
+// notifications, so the notify methods know exactly how many threads they
+// woke, whatever the platform's futex reports.
+const WAITER: u64 = 1;
+const SIGNAL: u64 = 1 << 32;
+
+fn waiters(state: u64) -> u64 {
+    state & (SIGNAL - 1)
+}
+
+fn signals(state: u64) -> u64 {
+    state >> 32
+}
+
+pub struct RawCondvar {
+    state: AtomicU64,
+    // Incremented on every notification. Threads block on this rather than
+    // the state, which is too wide for a futex.
+    seq: AtomicU32,
+}
+
+impl RawCondvar {
+    #[inline]
+    pub fn new() -> RawCondvar {
+        RawCondvar {
+            state: AtomicU64::new(0),
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    /// Starts a wait, returning the token to pass to `park`.
+    ///
+    /// This must be called before the caller's mutex is released, so that a
+    /// notification sent after the release will be seen by `park`.
+    #[inline]
+    pub fn prepare_wait(&self) -> u32 {
+        let seq = self.seq.load(Ordering::SeqCst);
+        self.state.fetch_add(WAITER, Ordering::SeqCst);
+        seq
+    }
+
+    /// Blocks until notified, with the caller's mutex released.
+    ///
+    /// Returns `false` if the deadline passed before a notification arrived.
+    pub fn park(&self, mut seq: u32, deadline: Option<Instant>) -> bool {
+        // Under the deterministic scheduler, waiting is a lock point which
+        // wakes spuriously once the lock is reacquired.
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                let notified = self.finish_wait(true) == Some(true);
+                return notified || deadline.is_none_or(|d| Instant::now() < d);
+            }
+        }
+
+        loop {
+            let woken = futex::wait(&self.seq, seq, deadline);
+            // The sequence number is read before the state, so that a
+            // notification sent after the state is checked changes it and
+            // the next wait returns immediately.
+            seq = self.seq.load(Ordering::SeqCst);
+            let timed_out = !woken && deadline.is_some_and(|d| Instant::now() >= d);
+            if let Some(notified) = self.finish_wait(timed_out) {
+                return notified;
+            }
+        }
+    }
+
+    // Consumes a pending notification if there is one, returning
+    // `Some(true)`. Otherwise, the wait ends with `Some(false)` if `give_up`
+    // is set, and continues with `None` if not.
+    fn finish_wait(&self, give_up: bool) -> Option<bool> {
+        let mut state = self.state.load(Ordering::SeqCst);
+        loop {
+            let (new, notified) = if signals(state) > 0 {
+                (state - WAITER - SIGNAL, true)
+            } else if give_up {
+                (state - WAITER, false)
+            } else {
+                return None;
+            };
+            match self.state.compare_exchange_weak(state, new, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => return Some(notified),
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    /// Notifies one waiting thread, returning whether there was one.
+    #[inline]
+    pub fn notify_one(&self) -> bool {
+        let mut state = self.state.load(Ordering::SeqCst);
+        loop {
+            if waiters(state) == signals(state) {
+                return false;
+            }
+            match self.state.compare_exchange_weak(state, state + SIGNAL, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => break,
+                Err(s) => state = s,
+            }
+        }
+        self.seq.fetch_add(1, Ordering::SeqCst);
+        futex::wake_one(&self.seq);
+        true
+    }
+
+    /// Notifies every waiting thread, returning how many there were.
+    #[inline]
+    pub fn notify_all(&self) -> usize {
+        let mut state = self.state.load(Ordering::SeqCst);
+        let notified = loop {
+            let notified = waiters(state) - signals(state);
+            if notified == 0 {
+                return 0;
+            }
+            match self.state.compare_exchange_weak(state,
+                                                   state + notified * SIGNAL,
+                                                   Ordering::SeqCst,
+                                                   Ordering::SeqCst) {
+                Ok(_) => break notified,
+                Err(s) => state = s,
+            }
+        };
+        self.seq.fetch_add(1, Ordering::SeqCst);
+        futex::wake_all(&self.seq);
+        notified as usize
+    }
+}
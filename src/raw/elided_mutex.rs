@@ -0,0 +1,145 @@
+use raw::RawMutex;
+
+/// A mutex whose critical sections are first attempted as hardware
+/// transactions, with Intel's Restricted Transactional Memory.
+///
+/// A transaction reads the lock word without writing it, so transactions on
+/// the same lock run in parallel as long as their data accesses don't
+/// conflict. If a transaction aborts, for a conflict or for any instruction
+/// which can't be executed transactionally, the processor rolls it back to
+/// the point where it began and the lock is acquired for real.
+pub struct RawElidedMutex {
+    raw: RawMutex,
+}
+
+impl RawElidedMutex {
+    #[inline]
+    pub fn new() -> RawElidedMutex {
+        RawElidedMutex { raw: RawMutex::new() }
+    }
+
+    /// Returns `true` if the lock was elided, in which case the caller is
+    /// running in a transaction which `unlock` commits.
+    #[inline]
+    pub fn lock(&self) -> bool {
+        #[cfg(all(target_arch = "x86_64", not(miri)))]
+        {
+            if rtm::supported() {
+                for _ in 0..rtm::MAX_ATTEMPTS {
+                    let status = unsafe { rtm::begin() };
+                    if status == rtm::STARTED {
+                        // Reading the lock word adds it to the transaction, so
+                        // a thread acquiring it for real aborts us.
+                        if !self.raw.is_locked() {
+                            return true;
+                        }
+                        unsafe { rtm::abort_lock_held() }
+                    }
+                    if status & rtm::ABORT_RETRY == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+
+        self.raw.lock();
+        false
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.raw.try_lock()
+    }
+
+    /// # Safety
+    ///
+    /// The mutex must be locked by the caller, which must pass on the value
+    /// returned by `lock`. If the lock was elided, this must be called on the
+    /// same thread.
+    #[inline]
+    pub unsafe fn unlock(&self, elided: bool) {
+        if elided {
+            #[cfg(all(target_arch = "x86_64", not(miri)))]
+            rtm::commit();
+        } else {
+            self.raw.unlock();
+        }
+    }
+}
+
+/// Returns whether critical sections can be run as hardware transactions.
+#[inline]
+pub fn elision_supported() -> bool {
+    #[cfg(all(target_arch = "x86_64", not(miri)))]
+    {
+        rtm::supported()
+    }
+    #[cfg(not(all(target_arch = "x86_64", not(miri))))]
+    {
+        false
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(miri)))]
+mod rtm {
+    use std::arch::asm;
+    use std::arch::x86_64::__cpuid_count;
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    // The status `begin` returns when the transaction has started.
+    pub const STARTED: u32 = !0;
+    // Set in an abort status when the transaction may succeed if retried.
+    pub const ABORT_RETRY: u32 = 1 << 1;
+    // Transactions are retried only while the processor suggests they may
+    // succeed, and at most this many times.
+    pub const MAX_ATTEMPTS: u32 = 3;
+
+    const UNKNOWN: u8 = 0;
+    const UNSUPPORTED: u8 = 1;
+    const SUPPORTED: u8 = 2;
+
+    static SUPPORT: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+    #[inline]
+    pub fn supported() -> bool {
+        match SUPPORT.load(Ordering::Relaxed) {
+            UNKNOWN => detect(),
+            support => support == SUPPORTED,
+        }
+    }
+
+    #[cold]
+    fn detect() -> bool {
+        // RTM is reported in bit 11 of EBX for leaf 7. Many processors which
+        // report it have had it disabled by microcode, in which case every
+        // transaction aborts and we fall back to locking.
+        let supported = __cpuid_count(0, 0).eax >= 7 && __cpuid_count(7, 0).ebx & (1 << 11) != 0;
+        SUPPORT.store(if supported { SUPPORTED } else { UNSUPPORTED }, Ordering::Relaxed);
+        supported
+    }
+
+    /// Starts a transaction, returning `STARTED`, or the abort status once
+    /// the transaction has been rolled back.
+    ///
+    /// On abort, every register but EAX and all memory written within the
+    /// transaction are restored, and execution resumes right after the
+    /// `xbegin`, so this appears to return a second time.
+    #[inline(always)]
+    pub unsafe fn begin() -> u32 {
+        let status: u32;
+        asm!("xbegin 2f", "2:", inout("eax") STARTED => status, options(nostack));
+        status
+    }
+
+    /// Commits the current transaction.
+    #[inline(always)]
+    pub unsafe fn commit() {
+        asm!("xend", options(nostack));
+    }
+
+    /// Aborts the current transaction because the lock is held.
+    #[inline(always)]
+    pub unsafe fn abort_lock_held() {
+        asm!("xabort 0xff", options(nostack));
+    }
+}
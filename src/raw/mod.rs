@@ -0,0 +1,30 @@
+//! The lock algorithms underlying the public types.
+//!
+//! These operate on plain atomic words and know nothing about the data they
+//! protect. Poisoning simply does not exist at this level.
+
+pub use self::condvar::RawCondvar;
+pub use self::elided_mutex::{elision_supported, RawElidedMutex};
+pub use self::mutex::RawMutex;
+pub use self::rwlock::RawRwLock;
+pub use self::spin::SpinWait;
+pub use self::ticket_mutex::RawTicketMutex;
+#[cfg(all(feature = "pi_mutex", any(target_os = "linux", target_os = "android")))]
+pub use self::pi_mutex::RawPiMutex;
+// Elsewhere priority inheritance is not available, and a regular mutex is
+// the best we can do.
+#[cfg(all(feature = "pi_mutex", not(any(target_os = "linux", target_os = "android"))))]
+pub use self::mutex::RawMutex as RawPiMutex;
+#[cfg(all(feature = "shm", any(target_os = "linux", target_os = "android")))]
+pub use self::shm_mutex::RawShmMutex;
+
+mod condvar;
+mod elided_mutex;
+mod mutex;
+#[cfg(all(feature = "pi_mutex", any(target_os = "linux", target_os = "android")))]
+mod pi_mutex;
+mod rwlock;
+#[cfg(all(feature = "shm", any(target_os = "linux", target_os = "android")))]
+mod shm_mutex;
+mod spin;
+mod ticket_mutex;
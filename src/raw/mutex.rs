@@ -0,0 +1,461 @@
+use std::hint;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+use backoff::Backoff;
+use futex;
+use profiler;
+use raw::spin::SpinWait;
+#[cfg(feature = "testing")]
+use testing::deterministic;
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+// Locked, and there may be threads blocked on the futex.
+const CONTENDED: u32 = 2;
+// Released by its holder, but reserved for the waiter it woke rather than
+// free for anyone to take.
+const HANDOFF: u32 = 3;
+
+// Barging, where a running thread takes the lock ahead of the threads parked
+// on it, is much faster than handing the lock over, but can starve a waiter
+// indefinitely. So every so often an unlock hands the lock to the thread it
+// wakes instead, as `parking_lot` does.
+const FAIR_INTERVAL_MICROS: u32 = 500;
+
+const FAIR_BUCKET_BITS: u32 = 8;
+
+// When unlocks should next hand the lock off fairly, from `now_micros`. This
+// is kept per bucket of mutexes with the same address hash rather than in
+// each mutex, much as `parking_lot` keeps it in its parking table. Mutexes
+// sharing a bucket share the schedule, which only makes the handoffs of each
+// less regular.
+static FAIR_AT: [AtomicU32; 1 << FAIR_BUCKET_BITS] = [const { AtomicU32::new(0) }; 1 << FAIR_BUCKET_BITS];
+
+// The current time in microseconds, wrapping around every 71 minutes.
+fn now_micros() -> u32 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_micros() as u32
+}
+
+pub struct RawMutex {
+    state: AtomicU32,
+}
+
+impl RawMutex {
+    #[inline]
+    pub fn new() -> RawMutex {
+        RawMutex {
+            state: AtomicU32::new(UNLOCKED),
+        }
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    /// Like `try_lock`, but busy-waits for up to `max_spins` rounds while the
+    /// mutex is held. Never parks or yields the thread.
+    #[inline]
+    pub fn try_lock_spin(&self, max_spins: u32) -> bool {
+        if self.try_lock() {
+            return true;
+        }
+        for _ in 0..max_spins {
+            hint::spin_loop();
+            if self.state.load(Ordering::Relaxed) == UNLOCKED && self.try_lock() {
+                return true;
+            }
+        }
+        false
+    }
+
+    #[inline]
+    pub fn lock(&self) {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                return deterministic::acquire(|| self.try_lock());
+            }
+        }
+
+        if !self.try_lock() {
+            self.lock_contended(None, None, None);
+        }
+    }
+
+    /// Returns `false` if the deadline passed before the mutex was acquired.
+    #[inline]
+    pub fn lock_until(&self, deadline: Option<Instant>) -> bool {
+        self.lock_with_backoff(deadline, None)
+    }
+
+    /// Like `lock_until`, but backs off with the specified strategy rather
+    /// than the process-wide one if the mutex is contended.
+    #[inline]
+    pub fn lock_with_backoff(&self, deadline: Option<Instant>, backoff: Option<&'static dyn Backoff>) -> bool {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                return deterministic::acquire_until(deadline, || self.try_lock());
+            }
+        }
+
+        self.try_lock() || self.lock_contended(deadline, backoff, None)
+    }
+
+    /// Like `lock_with_backoff`, but also gives up once `interrupted` returns
+    /// `true`. It is checked whenever the thread is about to park, so
+    /// whatever makes it return `true` has to call `wake_all` afterwards.
+    #[inline]
+    pub fn lock_interruptible(&self,
+                              deadline: Option<Instant>,
+                              backoff: Option<&'static dyn Backoff>,
+                              interrupted: &dyn Fn() -> bool)
+                              -> bool {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                let mut locked = false;
+                deterministic::acquire_until(deadline, || {
+                    locked = self.try_lock();
+                    locked || interrupted()
+                });
+                return locked;
+            }
+        }
+
+        self.try_lock() || self.lock_contended(deadline, backoff, Some(interrupted))
+    }
+
+    /// Wakes every thread parked on the mutex. Each one takes the lock if it
+    /// is free, and otherwise parks again unless it has been interrupted.
+    #[inline]
+    pub fn wake_all(&self) {
+        futex::wake_all(&self.state);
+    }
+
+    #[cold]
+    fn lock_contended(&self,
+                      deadline: Option<Instant>,
+                      backoff: Option<&'static dyn Backoff>,
+                      interrupted: Option<&dyn Fn() -> bool>)
+                      -> bool {
+        let _sample = profiler::sample("Mutex", self as *const RawMutex as usize);
+        let mut spin = SpinWait::with_backoff(backoff);
+        let mut state = self.state.load(Ordering::Relaxed);
+
+        // Spin while the lock is held but nobody is parked on it. Once there
+        // are parked threads the lock will be handed to one of them, so
+        // spinning would only delay our own parking.
+        loop {
+            if state == UNLOCKED {
+                match self.state.compare_exchange_weak(UNLOCKED,
+                                                       LOCKED,
+                                                       Ordering::Acquire,
+                                                       Ordering::Relaxed) {
+                    Ok(_) => return true,
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            if state != LOCKED || !spin.spin() {
+                break;
+            }
+            state = self.state.load(Ordering::Relaxed);
+        }
+
+        // Whether this thread has been parked, and so may have been woken to
+        // take a lock which was handed off.
+        let mut parked = false;
+        loop {
+            if state == HANDOFF && parked {
+                match self.state.compare_exchange(HANDOFF, CONTENDED, Ordering::Acquire, Ordering::Relaxed) {
+                    Ok(_) => return true,
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            // Once we have gone through the slow path we can't know whether
+            // other threads are still waiting, so we have to take the lock
+            // in the contended state to make sure they get woken up. A lock
+            // which was handed off in the meantime is ours as well.
+            if state != CONTENDED && state != HANDOFF {
+                match self.state.swap(CONTENDED, Ordering::Acquire) {
+                    UNLOCKED | HANDOFF => return true,
+                    _ => state = CONTENDED,
+                }
+            }
+
+            if interrupted.is_some_and(|interrupted| interrupted()) {
+                self.abandon_wait();
+                return false;
+            }
+
+            // A lock being handed off belongs to the threads which were
+            // already parked, so queue up behind them.
+            if !futex::wait(&self.state, state, deadline) && deadline.is_some_and(|d| Instant::now() >= d) {
+                self.abandon_wait();
+                return false;
+            }
+            parked = true;
+            state = self.state.load(Ordering::Relaxed);
+        }
+    }
+
+    // Called when a timed or interrupted acquisition gives up. An unlock may have woken this
+    // thread and cleared the contended state, so the wakeup has to be passed
+    // on to any other parked threads.
+    #[cold]
+    fn abandon_wait(&self) {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            match state {
+                UNLOCKED => {
+                    futex::wake_one(&self.state);
+                    return;
+                }
+                LOCKED => {
+                    match self.state.compare_exchange_weak(LOCKED, CONTENDED, Ordering::Relaxed, Ordering::Relaxed) {
+                        Ok(_) => return,
+                        Err(s) => state = s,
+                    }
+                }
+                // The lock may have been handed to this thread, so pass it on
+                // to the next waiter the same way.
+                HANDOFF => {
+                    match self.state.compare_exchange_weak(HANDOFF, CONTENDED, Ordering::Acquire, Ordering::Relaxed) {
+                        Ok(_) => {
+                            unsafe { self.unlock_contended(true) };
+                            return;
+                        }
+                        Err(s) => state = s,
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) != UNLOCKED
+    }
+
+    #[inline]
+    pub fn is_contended(&self) -> bool {
+        matches!(self.state.load(Ordering::Relaxed), CONTENDED | HANDOFF)
+    }
+
+    /// # Safety
+    ///
+    /// The mutex must be locked by the caller.
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        if self.state.compare_exchange(LOCKED, UNLOCKED, Ordering::Release, Ordering::Relaxed).is_err() {
+            self.unlock_contended(false);
+        }
+    }
+
+    /// Like `unlock`, but if there are waiters the lock is always handed to
+    /// the one which is woken, rather than only when fairness is due.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must be locked by the caller.
+    #[inline]
+    pub unsafe fn unlock_fair(&self) {
+        if self.state.compare_exchange(LOCKED, UNLOCKED, Ordering::Release, Ordering::Relaxed).is_err() {
+            self.unlock_contended(true);
+        }
+    }
+
+    // The mutex is locked in the contended state.
+    #[cold]
+    unsafe fn unlock_contended(&self, fair: bool) {
+        if fair || self.fairness_due() {
+            self.state.store(HANDOFF, Ordering::Release);
+            if futex::wake_one(&self.state) {
+                return;
+            }
+            // Nobody was woken to take the lock, or the platform doesn't say,
+            // so take it back unless someone already has.
+            if self.state.compare_exchange(HANDOFF, CONTENDED, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+                return;
+            }
+        }
+        self.state.store(UNLOCKED, Ordering::Release);
+        futex::wake_one(&self.state);
+    }
+
+    fn fairness_due(&self) -> bool {
+        let fair_at = self.fair_at();
+        let now = now_micros();
+        if (now.wrapping_sub(fair_at.load(Ordering::Relaxed)) as i32) < 0 {
+            return false;
+        }
+        fair_at.store(now.wrapping_add(FAIR_INTERVAL_MICROS), Ordering::Relaxed);
+        true
+    }
+
+    fn fair_at(&self) -> &'static AtomicU32 {
+        let addr = self as *const RawMutex as usize as u64;
+        &FAIR_AT[(addr.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - FAIR_BUCKET_BITS)) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn state(mutex: &RawMutex) -> u32 {
+        mutex.state.load(Ordering::Relaxed)
+    }
+
+    // Waits for a thread blocking on the mutex to mark it contended, and then
+    // gives it time to park.
+    fn wait_contended(mutex: &RawMutex) {
+        while state(mutex) != CONTENDED {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    // Makes the next contended unlock of the mutex hand it off.
+    fn force_fairness(mutex: &RawMutex) {
+        mutex.fair_at().store(now_micros().wrapping_sub(1), Ordering::Relaxed);
+    }
+
+    #[test]
+    fn uncontended() {
+        let mutex = RawMutex::new();
+        assert!(mutex.try_lock());
+        assert_eq!(state(&mutex), LOCKED);
+        assert!(!mutex.try_lock());
+        unsafe { mutex.unlock() };
+        assert_eq!(state(&mutex), UNLOCKED);
+    }
+
+    #[test]
+    fn contended_unlock_wakes_waiter() {
+        let mutex = RawMutex::new();
+        mutex.lock();
+        thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                mutex.lock();
+                unsafe { mutex.unlock() };
+            });
+            wait_contended(&mutex);
+            unsafe { mutex.unlock() };
+            waiter.join().unwrap();
+        });
+        // The waiter took the lock contended, as it couldn't know whether
+        // anyone else was still parked.
+        assert_eq!(state(&mutex), UNLOCKED);
+    }
+
+    // A wakeup consumed by a thread which then gives up must be passed on
+    // to the other parked threads.
+    #[test]
+    fn timed_out_waiter_passes_wakeup_on() {
+        let mutex = RawMutex::new();
+        mutex.lock();
+        thread::scope(|s| {
+            let timed = s.spawn(|| mutex.lock_until(Some(Instant::now() + Duration::from_millis(50))));
+            let patient = s.spawn(|| {
+                let locked = mutex.lock_until(Some(Instant::now() + Duration::from_secs(10)));
+                if locked {
+                    unsafe { mutex.unlock() };
+                }
+                locked
+            });
+            wait_contended(&mutex);
+            assert!(!timed.join().unwrap());
+            assert_ne!(state(&mutex), UNLOCKED);
+            unsafe { mutex.unlock() };
+            assert!(patient.join().unwrap());
+        });
+        assert_eq!(state(&mutex), UNLOCKED);
+    }
+
+    #[test]
+    fn abandon_wait_marks_held_lock_contended() {
+        let mutex = RawMutex::new();
+        assert!(mutex.try_lock());
+        mutex.abandon_wait();
+        assert_eq!(state(&mutex), CONTENDED);
+        unsafe { mutex.unlock() };
+        assert_eq!(state(&mutex), UNLOCKED);
+    }
+
+    // A lock handed off is reserved for a thread which was already parked,
+    // so a newly arriving thread queues up instead, and hands it on if it
+    // gives up.
+    #[test]
+    fn handoff_is_reserved_for_parked_threads() {
+        let mutex = RawMutex::new();
+        mutex.state.store(HANDOFF, Ordering::Relaxed);
+        assert!(!mutex.try_lock());
+        assert!(!mutex.try_lock_spin(100));
+        assert!(!mutex.lock_until(Some(Instant::now() + Duration::from_millis(10))));
+        assert_eq!(state(&mutex), UNLOCKED);
+        assert!(mutex.try_lock());
+        unsafe { mutex.unlock() };
+    }
+
+    // Platforms whose wakeups don't report whether a thread was woken take
+    // the lock back rather than handing it off.
+    #[cfg(any(not(feature = "futex"), target_os = "linux", target_os = "android"))]
+    #[test]
+    fn fair_unlock_hands_off_to_parked_waiter() {
+        let mutex = RawMutex::new();
+        mutex.lock();
+        thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                mutex.lock();
+                thread::sleep(Duration::from_millis(20));
+                unsafe { mutex.unlock() };
+            });
+            wait_contended(&mutex);
+            force_fairness(&mutex);
+            unsafe { mutex.unlock() };
+            // The lock went straight to the waiter, so it can't be barged.
+            assert_ne!(state(&mutex), UNLOCKED);
+            assert!(!mutex.try_lock());
+            waiter.join().unwrap();
+        });
+        assert_eq!(state(&mutex), UNLOCKED);
+    }
+
+    #[cfg(any(not(feature = "futex"), target_os = "linux", target_os = "android"))]
+    #[test]
+    fn unlock_fair_ignores_schedule() {
+        let mutex = RawMutex::new();
+        mutex.lock();
+        thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                mutex.lock();
+                thread::sleep(Duration::from_millis(20));
+                unsafe { mutex.unlock() };
+            });
+            wait_contended(&mutex);
+            mutex.fair_at().store(now_micros().wrapping_add(1 << 30), Ordering::Relaxed);
+            unsafe { mutex.unlock_fair() };
+            assert!(!mutex.try_lock());
+            waiter.join().unwrap();
+        });
+        assert_eq!(state(&mutex), UNLOCKED);
+    }
+}
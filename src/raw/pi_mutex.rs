@@ -0,0 +1,99 @@
+//! A mutex using priority-inheritance futexes on Linux.
+//!
+//! The lock word holds the thread ID of the owner, which lets the kernel
+//! boost the owner's priority while higher priority threads wait for it.
+
+use std::cell::Cell;
+use std::io;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use libc;
+
+thread_local! {
+    static TID: Cell<u32> = const { Cell::new(0) };
+}
+
+fn current_tid() -> u32 {
+    TID.with(|tid| {
+        if tid.get() == 0 {
+            tid.set(unsafe { libc::syscall(libc::SYS_gettid) } as u32);
+        }
+        tid.get()
+    })
+}
+
+fn futex_pi(futex: &AtomicU32, op: libc::c_int) -> io::Result<()> {
+    let r = unsafe {
+        libc::syscall(libc::SYS_futex,
+                      futex as *const AtomicU32,
+                      op | libc::FUTEX_PRIVATE_FLAG,
+                      0,
+                      ptr::null::<libc::timespec>())
+    };
+    if r < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+pub struct RawPiMutex {
+    state: AtomicU32,
+}
+
+impl RawPiMutex {
+    #[inline]
+    pub fn new() -> RawPiMutex {
+        RawPiMutex { state: AtomicU32::new(0) }
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.state
+            .compare_exchange(0, current_tid(), Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    #[inline]
+    pub fn lock(&self) {
+        if !self.try_lock() {
+            self.lock_contended();
+        }
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        // The kernel queues us by priority and boosts the owner.
+        loop {
+            match futex_pi(&self.state, libc::FUTEX_LOCK_PI) {
+                Ok(()) => return,
+                Err(ref e) if e.raw_os_error() == Some(libc::EINTR) ||
+                              e.raw_os_error() == Some(libc::EAGAIN) => {}
+                Err(e) => panic!("failed to lock priority-inheritance mutex: {}", e),
+            }
+        }
+    }
+
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn futex_word(&self) -> *mut u32 {
+        self.state.as_ptr()
+    }
+
+    /// # Safety
+    ///
+    /// The mutex must be locked by the calling thread.
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        // If there are waiters the kernel has set the `FUTEX_WAITERS` bit and
+        // must hand the lock over itself.
+        if self.state
+            .compare_exchange(current_tid(), 0, Ordering::Release, Ordering::Relaxed)
+            .is_err() {
+            if let Err(e) = futex_pi(&self.state, libc::FUTEX_UNLOCK_PI) {
+                panic!("failed to unlock priority-inheritance mutex: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,1049 @@
+use std::sync::atomic::{self, AtomicU32, AtomicU64, Ordering};
+use std::time::Instant;
+
+use backoff::Backoff;
+use futex;
+use profiler;
+use raw::spin::SpinWait;
+#[cfg(feature = "testing")]
+use testing::deterministic;
+use RwLockPolicy;
+
+// The lower 28 bits of the state hold the number of active readers, or
+// `WRITE_LOCKED` if a writer holds the lock. The next bit records whether one
+// of the readers is the upgradable reader, and the one after whether it is
+// waiting for the other readers to leave. The top two bits record whether
+// readers and writers are blocked waiting for the lock; threads waiting to
+// become the upgradable reader block alongside writers.
+const READ_LOCKED: u32 = 1;
+const MASK: u32 = (1 << 28) - 1;
+const WRITE_LOCKED: u32 = MASK;
+const MAX_READERS: u32 = MASK - 1;
+const UPGRADABLE: u32 = 1 << 28;
+const UPGRADING: u32 = 1 << 29;
+const READERS_WAITING: u32 = 1 << 30;
+const WRITERS_WAITING: u32 = 1 << 31;
+
+#[inline]
+fn is_unlocked(state: u32) -> bool {
+    state & MASK == 0
+}
+
+#[inline]
+fn has_readers_waiting(state: u32) -> bool {
+    state & READERS_WAITING != 0
+}
+
+#[inline]
+fn has_writers_waiting(state: u32) -> bool {
+    state & WRITERS_WAITING != 0
+}
+
+#[inline]
+fn has_upgradable(state: u32) -> bool {
+    state & UPGRADABLE != 0
+}
+
+#[inline]
+fn is_upgrading(state: u32) -> bool {
+    state & UPGRADING != 0
+}
+
+#[inline]
+fn is_write_locked(state: u32) -> bool {
+    state & MASK == WRITE_LOCKED
+}
+
+#[inline]
+fn has_reached_max_readers(state: u32) -> bool {
+    state & MASK == MAX_READERS
+}
+
+pub struct RawRwLock {
+    state: AtomicU32,
+    // Incremented each time a writer is woken, so writers block on this
+    // rather than on the state itself.
+    writer_notify: AtomicU32,
+    // Incremented each time a write lock is released, to validate optimistic
+    // reads.
+    version: AtomicU64,
+    policy: RwLockPolicy,
+    // Whether a write unlock hands the lock to the readers waiting for it,
+    // overriding the policy.
+    fair: bool,
+}
+
+impl RawRwLock {
+    #[inline]
+    pub fn new(policy: RwLockPolicy) -> RawRwLock {
+        RawRwLock::with_fairness(policy, false)
+    }
+
+    /// Creates a lock which alternates between the readers and writers
+    /// waiting for it if `fair` is set: new readers wait behind blocked
+    /// writers, as if writers were preferred, but a write unlock wakes the
+    /// blocked readers rather than the next writer, and lets them in ahead
+    /// of it. Neither side can then be starved.
+    #[inline]
+    pub fn with_fairness(policy: RwLockPolicy, fair: bool) -> RawRwLock {
+        RawRwLock {
+            state: AtomicU32::new(0),
+            writer_notify: AtomicU32::new(0),
+            version: AtomicU64::new(0),
+            policy,
+            fair,
+        }
+    }
+
+    #[inline]
+    fn prefers_writers(&self) -> bool {
+        self.fair || self.policy == RwLockPolicy::PreferWriters
+    }
+
+    #[inline]
+    fn is_read_lockable(&self, state: u32) -> bool {
+        // Readers never barge past other blocked readers. When writers are
+        // preferred they also wait behind blocked writers and upgrades, so
+        // that a steady stream of readers can't starve a writer.
+        state & MASK < MAX_READERS && !has_readers_waiting(state) &&
+        (!self.prefers_writers() || !has_writers_waiting(state) && !is_upgrading(state))
+    }
+
+    #[inline]
+    fn is_read_lockable_woken(&self, state: u32) -> bool {
+        // With fairness, readers woken by a write unlock take the lock ahead
+        // of the writers left waiting, and of readers which blocked since.
+        if self.fair {
+            state & MASK < MAX_READERS && !is_upgrading(state)
+        } else {
+            self.is_read_lockable(state)
+        }
+    }
+
+    #[inline]
+    fn is_upgradable_lockable(&self, state: u32, woken: bool) -> bool {
+        // A thread which has already blocked takes the lock regardless of
+        // waiting threads, like a woken writer.
+        !has_upgradable(state) && !is_write_locked(state) && state & MASK < MAX_READERS &&
+        (woken || self.is_read_lockable(state))
+    }
+
+    #[inline]
+    pub fn try_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while self.is_read_lockable(state) {
+            match self.state.compare_exchange_weak(state,
+                                                   state + READ_LOCKED,
+                                                   Ordering::Acquire,
+                                                   Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+        false
+    }
+
+    /// Acquires a read lock if the lock is already read-locked, without
+    /// deferring to blocked readers, writers or upgrades. A thread which
+    /// holds a read lock can always take another this way.
+    #[inline]
+    pub fn try_read_recursive(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while state & MASK != 0 && state & MASK < MAX_READERS {
+            match self.state.compare_exchange_weak(state,
+                                                   state + READ_LOCKED,
+                                                   Ordering::Acquire,
+                                                   Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+        false
+    }
+
+    /// Acquires another read lock for a caller which already holds one.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be read-locked by the caller.
+    #[inline]
+    pub unsafe fn read_again(&self) {
+        if !self.try_read_recursive() {
+            panic!("too many active read locks on RwLock");
+        }
+    }
+
+    #[inline]
+    pub fn read(&self) {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                return deterministic::acquire(|| self.try_read());
+            }
+        }
+
+        let state = self.state.load(Ordering::Relaxed);
+        if !self.is_read_lockable(state) ||
+           self.state
+            .compare_exchange_weak(state, state + READ_LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err() {
+            self.read_contended(None, None);
+        }
+    }
+
+    /// Returns `false` if the deadline passed before the lock was acquired.
+    #[inline]
+    pub fn read_until(&self, deadline: Option<Instant>) -> bool {
+        self.read_with_backoff(deadline, None)
+    }
+
+    /// Like `read_until`, but backs off with the specified strategy rather
+    /// than the process-wide one if the lock is contended.
+    #[inline]
+    pub fn read_with_backoff(&self, deadline: Option<Instant>, backoff: Option<&'static dyn Backoff>) -> bool {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                return deterministic::acquire_until(deadline, || self.try_read());
+            }
+        }
+
+        self.try_read() || self.read_contended(deadline, backoff)
+    }
+
+    #[cold]
+    fn read_contended(&self, deadline: Option<Instant>, backoff: Option<&'static dyn Backoff>) -> bool {
+        let _sample = profiler::sample("RwLock (read)", self as *const RawRwLock as usize);
+        let mut state = self.spin_read(backoff);
+        let mut woken = false;
+        loop {
+            if if woken { self.is_read_lockable_woken(state) } else { self.is_read_lockable(state) } {
+                match self.state.compare_exchange_weak(state,
+                                                       state + READ_LOCKED,
+                                                       Ordering::Acquire,
+                                                       Ordering::Relaxed) {
+                    Ok(_) => return true,
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            if has_reached_max_readers(state) {
+                panic!("too many active read locks on RwLock");
+            }
+
+            if !has_readers_waiting(state) {
+                if let Err(s) = self.state.compare_exchange(state,
+                                                            state | READERS_WAITING,
+                                                            Ordering::Relaxed,
+                                                            Ordering::Relaxed) {
+                    state = s;
+                    continue;
+                }
+            }
+
+            if !futex::wait(&self.state, state | READERS_WAITING, deadline) &&
+               deadline.is_some_and(|d| Instant::now() >= d) {
+                self.abandon_read_wait();
+                return false;
+            }
+            woken = true;
+            state = self.spin_read(backoff);
+        }
+    }
+
+    // Called when a timed read acquisition gives up. Waking readers can
+    // leave writers waiting for the readers to unlock, so if none of them
+    // took the lock a writer has to be woken instead.
+    #[cold]
+    fn abandon_read_wait(&self) {
+        let state = self.state.load(Ordering::Relaxed);
+        if is_unlocked(state) && has_writers_waiting(state) {
+            self.wake_writer_or_readers(state);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The lock must be read-locked by the caller.
+    #[inline]
+    pub unsafe fn read_unlock(&self) {
+        self.release_read(READ_LOCKED);
+    }
+
+    #[inline]
+    unsafe fn release_read(&self, held: u32) {
+        let state = self.state.fetch_sub(held, Ordering::Release) - held;
+
+        if is_unlocked(state) && (has_writers_waiting(state) || has_readers_waiting(state)) {
+            self.wake_writer_or_readers(state);
+        } else if is_upgrading(state) && state & MASK == READ_LOCKED {
+            // Only the upgrading reader is left. Readers blocked on the state
+            // may be woken too, but will simply wait again.
+            futex::wake_all(&self.state);
+        }
+    }
+
+    #[inline]
+    pub fn try_upgradable_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while self.is_upgradable_lockable(state, false) {
+            match self.state.compare_exchange_weak(state,
+                                                   state + READ_LOCKED + UPGRADABLE,
+                                                   Ordering::Acquire,
+                                                   Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+        false
+    }
+
+    #[inline]
+    pub fn upgradable_read(&self, backoff: Option<&'static dyn Backoff>) {
+        self.upgradable_read_with_backoff(None, backoff);
+    }
+
+    /// Returns `false` if the deadline passed before the lock was acquired.
+    #[inline]
+    pub fn upgradable_read_with_backoff(&self,
+                                        deadline: Option<Instant>,
+                                        backoff: Option<&'static dyn Backoff>)
+                                        -> bool {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                return deterministic::acquire_until(deadline, || self.try_upgradable_read());
+            }
+        }
+
+        self.try_upgradable_read() || self.upgradable_read_contended(deadline, backoff)
+    }
+
+    #[cold]
+    fn upgradable_read_contended(&self, deadline: Option<Instant>, backoff: Option<&'static dyn Backoff>) -> bool {
+        let _sample = profiler::sample("RwLock (upgradable read)", self as *const RawRwLock as usize);
+        let mut state = self.spin_upgradable_read(backoff);
+        let mut other_writers_waiting = 0;
+
+        loop {
+            if self.is_upgradable_lockable(state, other_writers_waiting != 0) {
+                match self.state.compare_exchange_weak(state,
+                                                       (state + READ_LOCKED + UPGRADABLE) | other_writers_waiting,
+                                                       Ordering::Acquire,
+                                                       Ordering::Relaxed) {
+                    Ok(_) => return true,
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            // Block with the writers, since it is the upgradable reader or a
+            // writer leaving that lets this thread in.
+            if !has_writers_waiting(state) {
+                if let Err(s) = self.state.compare_exchange(state,
+                                                            state | WRITERS_WAITING,
+                                                            Ordering::Relaxed,
+                                                            Ordering::Relaxed) {
+                    state = s;
+                    continue;
+                }
+            }
+
+            other_writers_waiting = WRITERS_WAITING;
+
+            let seq = self.writer_notify.load(Ordering::Acquire);
+            state = self.state.load(Ordering::Relaxed);
+            if self.is_upgradable_lockable(state, true) || !has_writers_waiting(state) {
+                continue;
+            }
+
+            // Giving up passes a wakeup on like a writer does, since this
+            // thread was woken like one.
+            if !futex::wait(&self.writer_notify, seq, deadline) && deadline.is_some_and(|d| Instant::now() >= d) {
+                self.abandon_write_wait();
+                return false;
+            }
+            state = self.spin_upgradable_read(backoff);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The lock must be upgradably read-locked by the caller.
+    #[inline]
+    pub unsafe fn upgradable_read_unlock(&self) {
+        self.release_read(READ_LOCKED + UPGRADABLE);
+    }
+
+    /// Converts the caller's upgradable read lock into a write lock if it is
+    /// the only reader.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be upgradably read-locked by the caller.
+    #[inline]
+    pub unsafe fn try_upgrade(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while state & MASK == READ_LOCKED {
+            match self.state.compare_exchange_weak(state,
+                                                   (state & !UPGRADABLE) - READ_LOCKED + WRITE_LOCKED,
+                                                   Ordering::Acquire,
+                                                   Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+        false
+    }
+
+    /// Converts the caller's upgradable read lock into a write lock, waiting
+    /// for the other readers to leave.
+    ///
+    /// Returns `false`, leaving the lock upgradably read-locked, if the
+    /// deadline passed first.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be upgradably read-locked by the caller.
+    pub unsafe fn upgrade(&self, deadline: Option<Instant>, backoff: Option<&'static dyn Backoff>) -> bool {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                loop {
+                    deterministic::yield_now();
+                    if self.try_upgrade() {
+                        return true;
+                    }
+                    if deadline.is_some_and(|d| Instant::now() >= d) {
+                        return false;
+                    }
+                }
+            }
+        }
+
+        if self.try_upgrade() {
+            return true;
+        }
+        self.upgrade_contended(deadline, backoff)
+    }
+
+    #[cold]
+    unsafe fn upgrade_contended(&self, deadline: Option<Instant>, backoff: Option<&'static dyn Backoff>) -> bool {
+        let mut state = self.spin_until(backoff, |state| state & MASK == READ_LOCKED);
+        loop {
+            // Waiting readers and writers stay waiting for the write unlock.
+            if state & MASK == READ_LOCKED {
+                match self.state.compare_exchange_weak(state,
+                                                       (state & !(UPGRADABLE | UPGRADING)) - READ_LOCKED + WRITE_LOCKED,
+                                                       Ordering::Acquire,
+                                                       Ordering::Relaxed) {
+                    Ok(_) => return true,
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            if !is_upgrading(state) {
+                if let Err(s) = self.state.compare_exchange(state,
+                                                            state | UPGRADING,
+                                                            Ordering::Relaxed,
+                                                            Ordering::Relaxed) {
+                    state = s;
+                    continue;
+                }
+            }
+
+            if !futex::wait(&self.state, state | UPGRADING, deadline) &&
+               deadline.is_some_and(|d| Instant::now() >= d) {
+                self.cancel_upgrade();
+                return false;
+            }
+            state = self.state.load(Ordering::Relaxed);
+        }
+    }
+
+    #[cold]
+    fn cancel_upgrade(&self) {
+        // Readers may have blocked because of the upgrade. Wake them all to
+        // reevaluate the state; any that still can't proceed will wait again.
+        let state = self.state.fetch_and(!(UPGRADING | READERS_WAITING), Ordering::Relaxed);
+        if has_readers_waiting(state) {
+            futex::wake_all(&self.state);
+        }
+    }
+
+    #[inline]
+    pub fn try_write(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        while is_unlocked(state) {
+            match self.state.compare_exchange_weak(state,
+                                                   state + WRITE_LOCKED,
+                                                   Ordering::Acquire,
+                                                   Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+        false
+    }
+
+    #[inline]
+    pub fn write(&self) {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                return deterministic::acquire(|| self.try_write());
+            }
+        }
+
+        if self.state.compare_exchange_weak(0, WRITE_LOCKED, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            self.write_contended(None, None);
+        }
+    }
+
+    /// Returns `false` if the deadline passed before the lock was acquired.
+    #[inline]
+    pub fn write_until(&self, deadline: Option<Instant>) -> bool {
+        self.write_with_backoff(deadline, None)
+    }
+
+    /// Like `write_until`, but backs off with the specified strategy rather
+    /// than the process-wide one if the lock is contended.
+    #[inline]
+    pub fn write_with_backoff(&self, deadline: Option<Instant>, backoff: Option<&'static dyn Backoff>) -> bool {
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                return deterministic::acquire_until(deadline, || self.try_write());
+            }
+        }
+
+        if self.try_write() {
+            return true;
+        }
+        self.write_contended(deadline, backoff)
+    }
+
+    #[cold]
+    fn write_contended(&self, deadline: Option<Instant>, backoff: Option<&'static dyn Backoff>) -> bool {
+        let _sample = profiler::sample("RwLock (write)", self as *const RawRwLock as usize);
+        let mut state = self.spin_write(backoff);
+        let mut other_writers_waiting = 0;
+
+        loop {
+            // If we've been woken there may be other writers waiting too, so
+            // we have to keep the waiting bit set when we take the lock.
+            if is_unlocked(state) {
+                match self.state.compare_exchange_weak(state,
+                                                       state | WRITE_LOCKED | other_writers_waiting,
+                                                       Ordering::Acquire,
+                                                       Ordering::Relaxed) {
+                    Ok(_) => return true,
+                    Err(s) => {
+                        state = s;
+                        continue;
+                    }
+                }
+            }
+
+            if !has_writers_waiting(state) {
+                if let Err(s) = self.state.compare_exchange(state,
+                                                            state | WRITERS_WAITING,
+                                                            Ordering::Relaxed,
+                                                            Ordering::Relaxed) {
+                    state = s;
+                    continue;
+                }
+            }
+
+            other_writers_waiting = WRITERS_WAITING;
+
+            // Sample the notification counter before rechecking the state so
+            // that an unlock between the two can't be missed.
+            let seq = self.writer_notify.load(Ordering::Acquire);
+            state = self.state.load(Ordering::Relaxed);
+            if is_unlocked(state) || !has_writers_waiting(state) {
+                continue;
+            }
+
+            if !futex::wait(&self.writer_notify, seq, deadline) && deadline.is_some_and(|d| Instant::now() >= d) {
+                self.abandon_write_wait();
+                return false;
+            }
+            state = self.spin_write(backoff);
+        }
+    }
+
+    // Called when a timed write acquisition gives up. An unlock may have
+    // woken this thread and cleared the waiting bit, so the wakeup has to be
+    // passed on: to another writer, or to readers which were left waiting
+    // for it.
+    #[cold]
+    fn abandon_write_wait(&self) {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if is_unlocked(state) {
+                if !self.wake_writer() && has_readers_waiting(state) &&
+                   self.state
+                    .compare_exchange(state, state - READERS_WAITING, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok() {
+                    futex::wake_all(&self.state);
+                }
+                return;
+            }
+
+            // Make sure whoever holds the lock wakes the next writer.
+            if has_writers_waiting(state) {
+                return;
+            }
+            match self.state.compare_exchange_weak(state,
+                                                   state | WRITERS_WAITING,
+                                                   Ordering::Relaxed,
+                                                   Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The lock must be write-locked by the caller.
+    #[inline]
+    pub unsafe fn write_unlock(&self) {
+        self.version.fetch_add(1, Ordering::Release);
+        let state = self.state.fetch_sub(WRITE_LOCKED, Ordering::Release) - WRITE_LOCKED;
+
+        if has_writers_waiting(state) || has_readers_waiting(state) {
+            if self.fair {
+                self.wake_preferring_readers(state);
+            } else {
+                self.wake_writer_or_readers(state);
+            }
+        }
+    }
+
+    /// Converts the caller's write lock into an upgradable read lock.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be write-locked by the caller.
+    pub unsafe fn downgrade_to_upgradable(&self) {
+        // Threads waiting to become the upgradable reader stay blocked, as
+        // the caller keeps that role.
+        self.version.fetch_add(1, Ordering::Release);
+        const DOWNGRADE: u32 = READ_LOCKED + UPGRADABLE - WRITE_LOCKED;
+        let state = self.state.fetch_add(DOWNGRADE, Ordering::Release) + DOWNGRADE;
+
+        if has_readers_waiting(state) {
+            // Blocked readers may be able to share the lock now. Any that
+            // can't will wait again.
+            self.state.fetch_and(!READERS_WAITING, Ordering::Relaxed);
+            futex::wake_all(&self.state);
+        }
+    }
+
+    #[inline]
+    pub fn has_waiters(&self) -> bool {
+        let state = self.state.load(Ordering::Relaxed);
+        has_writers_waiting(state) || has_readers_waiting(state)
+    }
+
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        !is_unlocked(self.state.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn is_write_locked(&self) -> bool {
+        is_write_locked(self.state.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    pub fn reader_count(&self) -> usize {
+        let state = self.state.load(Ordering::Relaxed);
+        if is_write_locked(state) {
+            0
+        } else {
+            (state & MASK) as usize
+        }
+    }
+
+    /// Starts an optimistic read, returning the version to validate it
+    /// against, or `None` if the lock is write-locked.
+    #[inline]
+    pub fn optimistic_version(&self) -> Option<u64> {
+        let version = self.version.load(Ordering::Acquire);
+        if is_write_locked(self.state.load(Ordering::Acquire)) {
+            None
+        } else {
+            Some(version)
+        }
+    }
+
+    /// Returns whether no write lock has been acquired since the optimistic
+    /// read with the given version started.
+    #[inline]
+    pub fn validate(&self, version: u64) -> bool {
+        atomic::fence(Ordering::Acquire);
+        !is_write_locked(self.state.load(Ordering::Relaxed)) && self.version.load(Ordering::Relaxed) == version
+    }
+
+    #[cold]
+    fn wake_writer_or_readers(&self, state: u32) {
+        debug_assert!(is_unlocked(state));
+
+        if self.prefers_writers() {
+            self.wake_preferring_writers(state);
+        } else {
+            self.wake_preferring_readers(state);
+        }
+    }
+
+    fn wake_preferring_writers(&self, mut state: u32) {
+        // Writers are woken in preference to readers. Only one writer is
+        // woken; the waiting bit is cleared and the woken writer sets it
+        // again when it takes the lock if others are still waiting.
+        if state == WRITERS_WAITING {
+            match self.state.compare_exchange(state, 0, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => {
+                    self.wake_writer();
+                    return;
+                }
+                Err(s) => state = s,
+            }
+        }
+
+        // If both readers and writers are waiting, leave the readers waiting
+        // and wake a writer. If no writer was actually blocked, fall through
+        // and wake the readers instead.
+        if state == READERS_WAITING + WRITERS_WAITING {
+            if self.state
+                .compare_exchange(state, READERS_WAITING, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err() {
+                // Someone else took the lock and will take care of waking.
+                return;
+            }
+            if self.wake_writer() {
+                return;
+            }
+            state = READERS_WAITING;
+        }
+
+        if state == READERS_WAITING &&
+           self.state.compare_exchange(state, 0, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            futex::wake_all(&self.state);
+        }
+    }
+
+    fn wake_preferring_readers(&self, mut state: u32) {
+        // All waiting readers are woken in preference to writers. Writers are
+        // left waiting, and the last of the readers will wake one of them
+        // when it unlocks. If no reader was actually blocked, fall through
+        // and wake a writer instead.
+        if has_readers_waiting(state) {
+            if self.state
+                .compare_exchange(state, state - READERS_WAITING, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err() {
+                // Someone else took the lock and will take care of waking.
+                return;
+            }
+            if futex::wake_all(&self.state) > 0 || !has_writers_waiting(state) {
+                return;
+            }
+            state -= READERS_WAITING;
+        }
+
+        if state == WRITERS_WAITING &&
+           self.state.compare_exchange(state, 0, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+            self.wake_writer();
+        }
+    }
+
+    /// Spins until a reader could make progress, or parked threads exist.
+    fn spin_read(&self, backoff: Option<&'static dyn Backoff>) -> u32 {
+        self.spin_until(backoff, |state| {
+            !is_write_locked(state) || has_readers_waiting(state) || has_writers_waiting(state)
+        })
+    }
+
+    /// Spins until an upgradable reader could make progress, or parked
+    /// writers exist.
+    fn spin_upgradable_read(&self, backoff: Option<&'static dyn Backoff>) -> u32 {
+        self.spin_until(backoff, |state| {
+            !has_upgradable(state) && !is_write_locked(state) || has_writers_waiting(state)
+        })
+    }
+
+    /// Spins until a writer could make progress, or parked writers exist.
+    fn spin_write(&self, backoff: Option<&'static dyn Backoff>) -> u32 {
+        self.spin_until(backoff, |state| is_unlocked(state) || has_writers_waiting(state))
+    }
+
+    fn spin_until<F>(&self, backoff: Option<&'static dyn Backoff>, f: F) -> u32
+        where F: Fn(u32) -> bool
+    {
+        let mut spin = SpinWait::with_backoff(backoff);
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+            if f(state) || !spin.spin() {
+                return state;
+            }
+        }
+    }
+
+    fn wake_writer(&self) -> bool {
+        self.writer_notify.fetch_add(1, Ordering::Release);
+        futex::wake_one(&self.writer_notify)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn state(lock: &RawRwLock) -> u32 {
+        lock.state.load(Ordering::Relaxed)
+    }
+
+    // Waits for a blocked thread to set `bit`, and then gives it time to
+    // park.
+    fn wait_for_bit(lock: &RawRwLock, bit: u32) {
+        while state(lock) & bit == 0 {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    fn deadline(millis: u64) -> Option<Instant> {
+        Some(Instant::now() + Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn uncontended() {
+        let lock = RawRwLock::new(RwLockPolicy::PreferWriters);
+        assert!(lock.try_read());
+        assert!(lock.try_read());
+        assert_eq!(state(&lock), 2 * READ_LOCKED);
+        assert!(!lock.try_write());
+        unsafe {
+            lock.read_unlock();
+            lock.read_unlock();
+        }
+        assert!(lock.try_write());
+        assert_eq!(state(&lock), WRITE_LOCKED);
+        assert!(!lock.try_read());
+        unsafe { lock.write_unlock() };
+        assert_eq!(state(&lock), 0);
+    }
+
+    // Readers blocked behind a writer are all woken when it unlocks, and the
+    // waiting bit is cleared.
+    #[test]
+    fn readers_waiting() {
+        let lock = RawRwLock::new(RwLockPolicy::PreferWriters);
+        lock.write();
+        thread::scope(|s| {
+            let readers = (0..2)
+                .map(|_| {
+                    s.spawn(|| {
+                        lock.read();
+                        thread::sleep(Duration::from_millis(20));
+                        unsafe { lock.read_unlock() };
+                    })
+                })
+                .collect::<Vec<_>>();
+            wait_for_bit(&lock, READERS_WAITING);
+            unsafe { lock.write_unlock() };
+            for reader in readers {
+                reader.join().unwrap();
+            }
+        });
+        assert_eq!(state(&lock), 0);
+    }
+
+    // When writers are preferred, a blocked writer keeps new readers out.
+    #[test]
+    fn writers_waiting() {
+        let lock = RawRwLock::new(RwLockPolicy::PreferWriters);
+        lock.read();
+        thread::scope(|s| {
+            let writer = s.spawn(|| {
+                lock.write();
+                unsafe { lock.write_unlock() };
+            });
+            wait_for_bit(&lock, WRITERS_WAITING);
+            assert!(!lock.try_read());
+            // A thread which already holds a read lock can still take
+            // another, or it would deadlock against the writer.
+            assert!(lock.try_read_recursive());
+            unsafe {
+                lock.read_unlock();
+                lock.read_unlock();
+            }
+            writer.join().unwrap();
+        });
+        assert_eq!(state(&lock), 0);
+    }
+
+    #[test]
+    fn readers_preferred_over_waiting_writer() {
+        let lock = RawRwLock::new(RwLockPolicy::PreferReaders);
+        lock.read();
+        thread::scope(|s| {
+            let writer = s.spawn(|| {
+                lock.write();
+                unsafe { lock.write_unlock() };
+            });
+            wait_for_bit(&lock, WRITERS_WAITING);
+            assert!(lock.try_read());
+            unsafe {
+                lock.read_unlock();
+                lock.read_unlock();
+            }
+            writer.join().unwrap();
+        });
+        assert_eq!(state(&lock), 0);
+    }
+
+    // A writer which gives up must pass its wakeup on to the next writer.
+    #[test]
+    fn timed_out_writer_passes_wakeup_on() {
+        let lock = RawRwLock::new(RwLockPolicy::PreferWriters);
+        lock.write();
+        thread::scope(|s| {
+            let timed = s.spawn(|| lock.write_until(deadline(50)));
+            let patient = s.spawn(|| {
+                let locked = lock.write_until(deadline(10_000));
+                if locked {
+                    unsafe { lock.write_unlock() };
+                }
+                locked
+            });
+            wait_for_bit(&lock, WRITERS_WAITING);
+            assert!(!timed.join().unwrap());
+            unsafe { lock.write_unlock() };
+            assert!(patient.join().unwrap());
+        });
+        assert_eq!(state(&lock), 0);
+    }
+
+    // A reader which gives up while a writer waits behind it must not leave
+    // the writer parked on an unlocked lock.
+    #[test]
+    fn timed_out_reader_wakes_writer() {
+        let lock = RawRwLock::new(RwLockPolicy::PreferWriters);
+        lock.write();
+        thread::scope(|s| {
+            let reader = s.spawn(|| lock.read_until(deadline(50)));
+            wait_for_bit(&lock, READERS_WAITING);
+            let writer = s.spawn(|| {
+                let locked = lock.write_until(deadline(10_000));
+                if locked {
+                    unsafe { lock.write_unlock() };
+                }
+                locked
+            });
+            wait_for_bit(&lock, WRITERS_WAITING);
+            assert!(!reader.join().unwrap());
+            unsafe { lock.write_unlock() };
+            assert!(writer.join().unwrap());
+        });
+        assert_eq!(state(&lock), 0);
+    }
+
+    #[test]
+    fn upgrade() {
+        let lock = RawRwLock::new(RwLockPolicy::PreferWriters);
+        lock.upgradable_read(None);
+        assert_eq!(state(&lock), READ_LOCKED + UPGRADABLE);
+        assert!(!lock.try_upgradable_read());
+        assert!(lock.try_read());
+        assert!(!unsafe { lock.try_upgrade() });
+
+        thread::scope(|s| {
+            let upgrader = s.spawn(|| unsafe {
+                lock.upgrade(None, None);
+                lock.write_unlock();
+            });
+            wait_for_bit(&lock, UPGRADING);
+            // New readers wait for the upgrade, like they wait for writers.
+            assert!(!lock.try_read());
+            unsafe { lock.read_unlock() };
+            upgrader.join().unwrap();
+        });
+        assert_eq!(state(&lock), 0);
+    }
+
+    // An upgrade which times out leaves the upgradable read lock in place,
+    // and lets the readers it was holding back in.
+    #[test]
+    fn upgrade_timeout() {
+        let lock = RawRwLock::new(RwLockPolicy::PreferWriters);
+        lock.upgradable_read(None);
+        lock.read();
+        thread::scope(|s| {
+            let reader = s.spawn(|| {
+                wait_for_bit(&lock, UPGRADING);
+                lock.read();
+                unsafe { lock.read_unlock() };
+            });
+            assert!(!unsafe { lock.upgrade(deadline(100), None) });
+            reader.join().unwrap();
+        });
+        assert_eq!(state(&lock) & (UPGRADING | READERS_WAITING), 0);
+        assert_eq!(state(&lock), 2 * READ_LOCKED + UPGRADABLE);
+
+        unsafe { lock.read_unlock() };
+        assert!(unsafe { lock.try_upgrade() });
+        assert_eq!(state(&lock), WRITE_LOCKED);
+        unsafe { lock.downgrade_to_upgradable() };
+        assert_eq!(state(&lock), READ_LOCKED + UPGRADABLE);
+        unsafe { lock.upgradable_read_unlock() };
+        assert_eq!(state(&lock), 0);
+    }
+
+    // A fair write unlock lets the blocked readers in ahead of the blocked
+    // writer, which goes next.
+    #[test]
+    fn fair_write_unlock_wakes_readers_first() {
+        use std::sync::atomic::AtomicBool;
+
+        let lock = RawRwLock::with_fairness(RwLockPolicy::PreferWriters, true);
+        let written = AtomicBool::new(false);
+        lock.write();
+        thread::scope(|s| {
+            let reader = s.spawn(|| {
+                lock.read();
+                thread::sleep(Duration::from_millis(20));
+                let written = written.load(Ordering::SeqCst);
+                unsafe { lock.read_unlock() };
+                written
+            });
+            wait_for_bit(&lock, READERS_WAITING);
+            let writer = s.spawn(|| {
+                lock.write();
+                written.store(true, Ordering::SeqCst);
+                unsafe { lock.write_unlock() };
+            });
+            wait_for_bit(&lock, WRITERS_WAITING);
+            // New readers wait behind the writer.
+            assert!(!lock.try_read());
+            unsafe { lock.write_unlock() };
+            assert!(!reader.join().unwrap());
+            writer.join().unwrap();
+        });
+        assert_eq!(state(&lock), 0);
+    }
+}
@@ -0,0 +1,72 @@
+//! A mutex usable from multiple processes through shared memory.
+//!
+//! This uses the same algorithm as `RawMutex`, but with shared rather than
+//! process-private futexes, which the kernel keys by physical page so that
+//! processes mapping the lock at different addresses still meet.
+
+use std::io;
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use libc;
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+const CONTENDED: u32 = 2;
+
+fn futex(futex: &AtomicU32, op: libc::c_int, val: u32) -> libc::c_long {
+    unsafe {
+        libc::syscall(libc::SYS_futex,
+                      futex as *const AtomicU32,
+                      op,
+                      val,
+                      ptr::null::<libc::timespec>())
+    }
+}
+
+#[repr(C)]
+pub struct RawShmMutex {
+    state: AtomicU32,
+}
+
+impl RawShmMutex {
+    #[inline]
+    pub const fn new() -> RawShmMutex {
+        RawShmMutex { state: AtomicU32::new(UNLOCKED) }
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        self.state.compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    #[inline]
+    pub fn lock(&self) {
+        if !self.try_lock() {
+            self.lock_contended();
+        }
+    }
+
+    #[cold]
+    fn lock_contended(&self) {
+        while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            if futex(&self.state, libc::FUTEX_WAIT, CONTENDED) < 0 {
+                let err = io::Error::last_os_error();
+                match err.raw_os_error() {
+                    Some(libc::EAGAIN) | Some(libc::EINTR) => {}
+                    _ => panic!("failed to wait on shared mutex: {}", err),
+                }
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The mutex must be locked by the caller.
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex(&self.state, libc::FUTEX_WAKE, 1);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use backoff::{self, Backoff};
+
+/// Backoff for the contended path of the locks.
+///
+/// Parking and unparking a thread costs far more than a short critical
+/// section, so a thread that fails to acquire a lock first waits briefly for
+/// the holder to release it. How it waits is up to the lock's `Backoff`
+/// strategy, or the process-wide one.
+pub struct SpinWait {
+    counter: u32,
+    backoff: &'static dyn Backoff,
+}
+
+impl SpinWait {
+    #[inline]
+    pub fn new() -> SpinWait {
+        SpinWait::with_backoff(None)
+    }
+
+    #[inline]
+    pub fn with_backoff(backoff: Option<&'static dyn Backoff>) -> SpinWait {
+        SpinWait {
+            counter: 0,
+            backoff: backoff.unwrap_or_else(backoff::default),
+        }
+    }
+
+    /// Backs off once, returning `false` when the thread should park instead.
+    #[inline]
+    pub fn spin(&mut self) -> bool {
+        if !self.backoff.backoff(self.counter) {
+            return false;
+        }
+        self.counter = self.counter.saturating_add(1);
+        true
+    }
+}
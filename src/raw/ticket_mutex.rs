@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use futex;
+use raw::spin::SpinWait;
+#[cfg(feature = "testing")]
+use testing::deterministic;
+
+pub struct RawTicketMutex {
+    next_ticket: AtomicU32,
+    now_serving: AtomicU32,
+    // The number of threads blocked on `now_serving`, so that unlocking can
+    // skip the wake when there are none.
+    sleepers: AtomicU32,
+}
+
+impl RawTicketMutex {
+    #[inline]
+    pub fn new() -> RawTicketMutex {
+        RawTicketMutex {
+            next_ticket: AtomicU32::new(0),
+            now_serving: AtomicU32::new(0),
+            sleepers: AtomicU32::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn try_lock(&self) -> bool {
+        // Taking a ticket only succeeds if it would be served immediately.
+        let serving = self.now_serving.load(Ordering::Relaxed);
+        self.next_ticket
+            .compare_exchange(serving, serving.wrapping_add(1), Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    #[inline]
+    pub fn lock(&self) {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+
+        #[cfg(feature = "testing")]
+        {
+            if deterministic::is_managed() {
+                return deterministic::acquire(|| self.now_serving.load(Ordering::Acquire) == ticket);
+            }
+        }
+
+        if self.now_serving.load(Ordering::Acquire) != ticket {
+            self.lock_contended(ticket);
+        }
+    }
+
+    #[cold]
+    fn lock_contended(&self, ticket: u32) {
+        let mut spin = SpinWait::new();
+        loop {
+            let serving = self.now_serving.load(Ordering::Acquire);
+            if serving == ticket {
+                return;
+            }
+            if spin.spin() {
+                continue;
+            }
+
+            // Every waiter is woken on unlock, since only the one holding the
+            // next ticket can proceed and the futex can't pick it out.
+            self.sleepers.fetch_add(1, Ordering::SeqCst);
+            if self.now_serving.load(Ordering::SeqCst) == serving {
+                futex::wait(&self.now_serving, serving, None);
+            }
+            self.sleepers.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The mutex must be locked by the caller.
+    #[inline]
+    pub unsafe fn unlock(&self) {
+        self.now_serving.fetch_add(1, Ordering::SeqCst);
+        if self.sleepers.load(Ordering::SeqCst) != 0 {
+            futex::wake_all(&self.now_serving);
+        }
+    }
+}
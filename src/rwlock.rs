@@ -0,0 +1,1829 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::ptr;
+use std::sync::{self, Arc, PoisonError};
+use std::time::{Duration, Instant};
+
+use backoff::Backoff;
+use clock::{self, Clock};
+use held::{self, Mode};
+use instrument::{Hold, HoldStart, Instrument, LockStats};
+use owned_iter::OwnedIter;
+use raw::RawRwLock;
+use {deadline_after, fmt_guard, GuardMarker, RwLockBuilder, TryLockError, TryLockResult};
+
+/// An `RwLock` shared through an `Arc`, which `RwLock::read_arc` and
+/// `RwLock::write_arc` lock with guards of their own.
+pub type ArcRwLock<T> = Arc<RwLock<T>>;
+
+/// The policy an `RwLock` uses to arbitrate between readers and writers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum RwLockPolicy {
+    /// New readers wait while a writer is waiting, so a steady stream of
+    /// readers can't starve writers.
+    ///
+    /// This is the default.
+    #[default]
+    PreferWriters,
+    /// New readers acquire the lock whenever no writer holds it, and waiting
+    /// readers are woken before waiting writers. This maximizes read
+    /// throughput, but writers may be starved by a steady stream of readers.
+    PreferReaders,
+}
+
+/// Like `std::sync::RwLock` except that it does not poison itself.
+pub struct RwLock<T: ?Sized> {
+    raw: RawRwLock,
+    instrument: Option<Box<Instrument>>,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+// As with `Mutex`, these match the standard library.
+impl<T: ?Sized> UnwindSafe for RwLock<T> {}
+impl<T: ?Sized> RefUnwindSafe for RwLock<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Ok(guard) => fmt.debug_struct("RwLock").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("RwLock").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> RwLock<T> {
+    /// Like `std::sync::RwLock::new`.
+    #[inline]
+    pub fn new(t: T) -> RwLock<T> {
+        RwLockBuilder::new().build(t)
+    }
+
+    /// Creates a new `RwLock` which arbitrates between readers and writers
+    /// according to the specified policy.
+    #[inline]
+    pub fn new_with_policy(t: T, policy: RwLockPolicy) -> RwLock<T> {
+        RwLockBuilder::new().policy(policy).build(t)
+    }
+
+    /// Creates a new `RwLock` which uses the specified strategy, rather than
+    /// the process-wide one, when it is contended.
+    #[inline]
+    pub fn new_with_backoff(t: T, backoff: &'static dyn Backoff) -> RwLock<T> {
+        RwLockBuilder::new().backoff(backoff).build(t)
+    }
+
+    #[inline]
+    pub(crate) fn from_parts(t: T, raw: RawRwLock, instrument: Option<Box<Instrument>>) -> RwLock<T> {
+        RwLock {
+            raw,
+            instrument,
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Creates a new `RwLock` in an `Arc`.
+    #[inline]
+    pub fn new_arc(t: T) -> ArcRwLock<T> {
+        Arc::new(RwLock::new(t))
+    }
+
+    /// Like `std::sync::RwLock::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T where T: Sized {
+        self.data.into_inner()
+    }
+
+    /// Returns a copy of the protected value.
+    ///
+    /// The read lock is held only while the value is cloned, so readers can
+    /// work with a consistent snapshot for as long as they like without
+    /// stalling writers. Each call makes a new clone.
+    ///
+    /// ```
+    /// use antidote::RwLock;
+    ///
+    /// let rows = RwLock::new(vec![1, 2, 3]);
+    /// let snapshot = rows.snapshot();
+    /// rows.write().push(4);
+    /// assert_eq!(*snapshot, [1, 2, 3]);
+    /// ```
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn snapshot(&self) -> Arc<T>
+        where T: Clone
+    {
+        Arc::new(self.read().clone())
+    }
+
+    /// Returns a clone of the protected value, taking the read lock only
+    /// for the duration of the clone.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read_cloned(&self) -> T
+        where T: Clone
+    {
+        self.read().clone()
+    }
+
+    /// Replaces the protected value.
+    ///
+    /// The old value is dropped after the write lock is released.
+    ///
+    /// ```
+    /// use antidote::RwLock;
+    ///
+    /// let endpoint = RwLock::new("primary".to_string());
+    /// endpoint.set("replica".to_string());
+    /// assert_eq!(endpoint.read_cloned(), "replica");
+    /// ```
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn set(&self, t: T) {
+        let old = mem::replace(&mut *self.write(), t);
+        drop(old);
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Like `std::sync::RwLock::read`.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// lock for writing rather than deadlocking.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read<'a>(&'a self) -> RwLockReadGuard<'a, T> {
+        held::check("RwLock", self.name(), self.addr(), Mode::Shared);
+        self.read_raw();
+        held::acquired("RwLock", self.name(), self.addr(), Mode::Shared);
+        RwLockReadGuard::new(self)
+    }
+
+    /// Locks this rwlock with shared read access, even if the current
+    /// thread already holds a read lock.
+    ///
+    /// With `RwLockPolicy::PreferWriters`, a second `read` on a thread which
+    /// already holds a read lock deadlocks if a writer has started waiting in
+    /// between. This instead joins the existing readers whenever the lock is
+    /// read-locked, at the cost of letting readers overtake waiting writers.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read_recursive<'a>(&'a self) -> RwLockReadGuard<'a, T> {
+        held::check("RwLock", self.name(), self.addr(), Mode::Shared);
+        if self.raw.try_read_recursive() {
+            self.record_acquired(false);
+        } else {
+            self.read_raw();
+        }
+        held::acquired("RwLock", self.name(), self.addr(), Mode::Shared);
+        RwLockReadGuard::new(self)
+    }
+
+    /// Like `std::sync::RwLock::try_read`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_read<'a>(&'a self) -> TryLockResult<RwLockReadGuard<'a, T>> {
+        if self.raw.try_read() {
+            self.record_acquired(false);
+            held::acquired("RwLock", self.name(), self.addr(), Mode::Shared);
+            Ok(RwLockReadGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Attempts to acquire shared read access, blocking for at most
+    /// `timeout`.
+    ///
+    /// Returns `TryLockError::Timeout` if the lock could not be acquired in
+    /// time.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_read_for<'a>(&'a self, timeout: Duration) -> TryLockResult<RwLockReadGuard<'a, T>> {
+        self.try_read_until_inner(deadline_after(timeout))
+    }
+
+    /// Attempts to acquire shared read access, blocking until `deadline` at
+    /// the latest.
+    ///
+    /// Returns `TryLockError::Timeout` if the lock could not be acquired in
+    /// time.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_read_until<'a>(&'a self, deadline: Instant) -> TryLockResult<RwLockReadGuard<'a, T>> {
+        self.try_read_until_inner(Some(deadline))
+    }
+
+    /// Like `try_read_until`, but with a deadline measured by `clock`.
+    ///
+    /// See the `clock` module for details.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_read_until_clock<'a, C>(&'a self,
+                                       clock: &C,
+                                       deadline: C::Instant)
+                                       -> TryLockResult<RwLockReadGuard<'a, T>>
+        where C: ?Sized + Clock
+    {
+        held::check("RwLock", self.name(), self.addr(), Mode::Shared);
+        if clock::lock_until(clock, deadline, |d| self.read_raw_until(d)) {
+            held::acquired("RwLock", self.name(), self.addr(), Mode::Shared);
+            Ok(RwLockReadGuard::new(self))
+        } else {
+            Err(TryLockError::Timeout)
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn try_read_until_inner<'a>(&'a self, deadline: Option<Instant>) -> TryLockResult<RwLockReadGuard<'a, T>> {
+        held::check("RwLock", self.name(), self.addr(), Mode::Shared);
+        if self.read_raw_until(deadline) {
+            held::acquired("RwLock", self.name(), self.addr(), Mode::Shared);
+            Ok(RwLockReadGuard::new(self))
+        } else {
+            Err(TryLockError::Timeout)
+        }
+    }
+
+    /// Like `std::sync::RwLock::write`.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// lock rather than deadlocking.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn write<'a>(&'a self) -> RwLockWriteGuard<'a, T> {
+        held::check("RwLock", self.name(), self.addr(), Mode::Exclusive);
+        self.write_raw();
+        held::acquired("RwLock", self.name(), self.addr(), Mode::Exclusive);
+        RwLockWriteGuard::new(self)
+    }
+
+    /// Like `std::sync::RwLock::try_write`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_write<'a>(&'a self) -> TryLockResult<RwLockWriteGuard<'a, T>> {
+        if self.raw.try_write() {
+            self.record_acquired(true);
+            held::acquired("RwLock", self.name(), self.addr(), Mode::Exclusive);
+            Ok(RwLockWriteGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `read`, but returns a guard which holds a reference count on the
+    /// lock rather than borrowing it.
+    #[inline]
+    pub fn read_arc(self: &Arc<Self>) -> ArcRwLockReadGuard<T> {
+        held::check("RwLock", self.name(), self.addr(), Mode::Shared);
+        self.read_raw();
+        ArcRwLockReadGuard::new(self.clone())
+    }
+
+    /// Like `try_read`, but returns a guard which holds a reference count on
+    /// the lock rather than borrowing it.
+    #[inline]
+    pub fn try_read_arc(self: &Arc<Self>) -> TryLockResult<ArcRwLockReadGuard<T>> {
+        if self.raw.try_read() {
+            self.record_acquired(false);
+            Ok(ArcRwLockReadGuard::new(self.clone()))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `write`, but returns a guard which holds a reference count on the
+    /// lock rather than borrowing it.
+    #[inline]
+    pub fn write_arc(self: &Arc<Self>) -> ArcRwLockWriteGuard<T> {
+        held::check("RwLock", self.name(), self.addr(), Mode::Exclusive);
+        self.write_raw();
+        ArcRwLockWriteGuard::new(self.clone())
+    }
+
+    /// Like `try_write`, but returns a guard which holds a reference count on
+    /// the lock rather than borrowing it.
+    #[inline]
+    pub fn try_write_arc(self: &Arc<Self>) -> TryLockResult<ArcRwLockWriteGuard<T>> {
+        if self.raw.try_write() {
+            self.record_acquired(true);
+            Ok(ArcRwLockWriteGuard::new(self.clone()))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Attempts to acquire exclusive write access, blocking for at most
+    /// `timeout`.
+    ///
+    /// Returns `TryLockError::Timeout` if the lock could not be acquired in
+    /// time.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_write_for<'a>(&'a self, timeout: Duration) -> TryLockResult<RwLockWriteGuard<'a, T>> {
+        self.try_write_until_inner(deadline_after(timeout))
+    }
+
+    /// Attempts to acquire exclusive write access, blocking until `deadline`
+    /// at the latest.
+    ///
+    /// Returns `TryLockError::Timeout` if the lock could not be acquired in
+    /// time.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_write_until<'a>(&'a self, deadline: Instant) -> TryLockResult<RwLockWriteGuard<'a, T>> {
+        self.try_write_until_inner(Some(deadline))
+    }
+
+    /// Like `try_write_until`, but with a deadline measured by `clock`.
+    ///
+    /// See the `clock` module for details.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_write_until_clock<'a, C>(&'a self,
+                                        clock: &C,
+                                        deadline: C::Instant)
+                                        -> TryLockResult<RwLockWriteGuard<'a, T>>
+        where C: ?Sized + Clock
+    {
+        held::check("RwLock", self.name(), self.addr(), Mode::Exclusive);
+        if clock::lock_until(clock, deadline, |d| self.write_raw_until(d)) {
+            held::acquired("RwLock", self.name(), self.addr(), Mode::Exclusive);
+            Ok(RwLockWriteGuard::new(self))
+        } else {
+            Err(TryLockError::Timeout)
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn try_write_until_inner<'a>(&'a self, deadline: Option<Instant>) -> TryLockResult<RwLockWriteGuard<'a, T>> {
+        held::check("RwLock", self.name(), self.addr(), Mode::Exclusive);
+        if self.write_raw_until(deadline) {
+            held::acquired("RwLock", self.name(), self.addr(), Mode::Exclusive);
+            Ok(RwLockWriteGuard::new(self))
+        } else {
+            Err(TryLockError::Timeout)
+        }
+    }
+
+    /// Starts an optimistic read of the data.
+    ///
+    /// The returned snapshot records the lock's version without acquiring
+    /// it, so checking whether a writer has intervened since is lock-free.
+    #[inline]
+    pub fn optimistic_read<'a>(&'a self) -> OptimisticRead<'a, T> {
+        OptimisticRead {
+            rwlock: self,
+            version: self.raw.optimistic_version(),
+        }
+    }
+
+    /// Locks this rwlock with upgradable read access, blocking the current
+    /// thread until it can be acquired.
+    ///
+    /// An upgradable read lock coexists with other readers, but excludes
+    /// writers and other upgradable readers. It can later be atomically
+    /// upgraded to a write lock with `RwLockUpgradableReadGuard::upgrade`.
+    ///
+    /// In debug builds, this panics if the current thread already holds the
+    /// lock for writing or upgradable reading rather than deadlocking.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn upgradable_read<'a>(&'a self) -> RwLockUpgradableReadGuard<'a, T> {
+        held::check("RwLock", self.name(), self.addr(), Mode::Upgradable);
+        self.upgradable_read_raw();
+        held::acquired("RwLock", self.name(), self.addr(), Mode::Upgradable);
+        RwLockUpgradableReadGuard::new(self)
+    }
+
+    /// Attempts to acquire this rwlock with upgradable read access.
+    ///
+    /// This function does not block.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_upgradable_read<'a>(&'a self) -> TryLockResult<RwLockUpgradableReadGuard<'a, T>> {
+        if self.raw.try_upgradable_read() {
+            self.record_acquired(false);
+            held::acquired("RwLock", self.name(), self.addr(), Mode::Upgradable);
+            Ok(RwLockUpgradableReadGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `std::sync::RwLock::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    /// Returns whether any threads are blocked waiting to acquire the lock,
+    /// for reading or for writing.
+    ///
+    /// This is a single atomic load, cheap enough for hot paths to check
+    /// before choosing a strategy which avoids the lock while it is busy. It
+    /// is only a hint, and may be out of date by the time it is returned.
+    #[inline]
+    pub fn has_waiters(&self) -> bool {
+        self.raw.has_waiters()
+    }
+
+    /// Returns the number of readers currently holding the lock.
+    ///
+    /// This is a best-effort snapshot intended for monitoring, and may be
+    /// out of date by the time it is returned.
+    #[inline]
+    pub fn reader_count(&self) -> usize {
+        self.raw.reader_count()
+    }
+
+    /// Panics if the lock is held, whether by readers or a writer.
+    ///
+    /// This is intended for tests which check that nothing is still holding
+    /// the lock.
+    #[track_caller]
+    pub fn assert_unlocked(&self) {
+        if self.raw.is_locked() {
+            panic!("{} is locked", self.describe());
+        }
+    }
+
+    /// Panics if a writer holds the lock.
+    ///
+    /// Readers may still hold it.
+    #[track_caller]
+    pub fn assert_no_writers(&self) {
+        if self.raw.is_write_locked() {
+            panic!("{} is write-locked", self.describe());
+        }
+    }
+
+    // Describes the lock for panic messages.
+    fn describe(&self) -> String {
+        match self.name() {
+            Some(name) => format!("RwLock {:?}", name),
+            None => "RwLock".to_string(),
+        }
+    }
+
+    /// Acquires shared read access without creating a guard.
+    ///
+    /// The lock stays read-locked until it is released with
+    /// `force_unlock_read`. This is equivalent to leaking the guard returned
+    /// by `read`.
+    ///
+    /// Like `Mutex`, the lock has no native handle, such as a
+    /// `pthread_rwlock_t` or `PSRWLOCK`, which C code could lock in its place.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn raw_read(&self) {
+        self.read_raw();
+    }
+
+    /// Attempts to acquire shared read access without creating a guard,
+    /// returning whether it was acquired.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn raw_try_read(&self) -> bool {
+        let locked = self.raw.try_read();
+        if locked {
+            self.record_acquired(false);
+        }
+        locked
+    }
+
+    /// Acquires exclusive write access without creating a guard.
+    ///
+    /// The lock stays write-locked until it is released with
+    /// `force_unlock_write`. This is equivalent to leaking the guard returned
+    /// by `write`.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn raw_write(&self) {
+        self.write_raw();
+    }
+
+    /// Attempts to acquire exclusive write access without creating a guard,
+    /// returning whether it was acquired.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn raw_try_write(&self) -> bool {
+        let locked = self.raw.try_write();
+        if locked {
+            self.record_acquired(true);
+        }
+        locked
+    }
+
+    /// Releases shared read access without a guard.
+    ///
+    /// The release is not reported to the unlock hook, if there is one, since
+    /// when the read lock was acquired is unknown.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be read-locked, and one of the read locks must be
+    /// logically owned by the caller: it was acquired with `raw_read`, or
+    /// with a guard which has since been leaked.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub unsafe fn force_unlock_read(&self) {
+        held::force_released(self.addr(), Mode::Shared);
+        self.raw.read_unlock();
+    }
+
+    /// Releases exclusive write access without a guard.
+    ///
+    /// # Safety
+    ///
+    /// The lock must be write-locked, and the lock must be logically owned by
+    /// the caller: it was acquired with `raw_write`, or with a guard which
+    /// has since been leaked.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub unsafe fn force_unlock_write(&self) {
+        held::force_released(self.addr(), Mode::Exclusive);
+        self.write_unlock_raw();
+    }
+
+    /// Returns a raw pointer to the protected data.
+    ///
+    /// The pointer is always valid to create, but may only be dereferenced
+    /// while the caller logically holds an appropriate lock.
+    #[cfg(feature = "raw")]
+    #[inline]
+    pub fn data_ptr(&self) -> *mut T {
+        self.data.get()
+    }
+
+    /// Returns the name given to the lock by `RwLockBuilder::name`, if any.
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.instrument.as_ref().and_then(|i| i.name())
+    }
+
+    /// Returns statistics about the lock's read and write acquisitions, if
+    /// they were enabled by `RwLockBuilder::stats`.
+    #[inline]
+    pub fn stats(&self) -> Option<LockStats> {
+        self.instrument.as_ref().and_then(|i| i.stats())
+    }
+
+    /// Acquires shared read access, returning an iterator over the locked
+    /// collection which keeps it read-locked until the iterator is dropped.
+    ///
+    /// Each item is passed through `f`, which turns it into an owned value.
+    /// See `OwnedIter`.
+    #[inline]
+    pub fn read_iter<F, U>(self: Arc<Self>, f: F) -> OwnedIter<ArcRwLockReadGuard<T>, T, F>
+        where T: 'static,
+              for<'a> &'a T: IntoIterator,
+              F: for<'a> FnMut(<&'a T as IntoIterator>::Item) -> U
+    {
+        self.read_raw();
+        let guard = ArcRwLockReadGuard::new(self);
+        // The guard keeps both the lock and the read lock alive for as long
+        // as the iterator exists.
+        let data = guard.rwlock.data.get();
+        unsafe { OwnedIter::new(guard, data, f) }
+    }
+
+    #[inline]
+    fn read_raw(&self) {
+        if self.instrument.is_none() {
+            self.raw.read();
+        } else {
+            self.read_raw_until(None);
+        }
+    }
+
+    #[inline]
+    fn read_raw_until(&self, deadline: Option<Instant>) -> bool {
+        match self.instrument {
+            None => self.raw.read_until(deadline),
+            Some(ref instrument) => {
+                if self.raw.try_read() {
+                    instrument.acquired(false);
+                    true
+                } else {
+                    instrument.acquire_contended(self.addr(), false, deadline, |d| {
+                        self.raw.read_with_backoff(d, instrument.backoff())
+                    })
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn write_raw(&self) {
+        if self.instrument.is_none() {
+            self.raw.write();
+        } else {
+            self.write_raw_until(None);
+        }
+    }
+
+    #[inline]
+    fn write_raw_until(&self, deadline: Option<Instant>) -> bool {
+        match self.instrument {
+            None => self.raw.write_until(deadline),
+            Some(ref instrument) => {
+                if self.raw.try_write() {
+                    instrument.acquired(true);
+                    true
+                } else {
+                    instrument.acquire_contended(self.addr(), true, deadline, |d| {
+                        self.raw.write_with_backoff(d, instrument.backoff())
+                    })
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn upgradable_read_raw(&self) {
+        match self.instrument {
+            None => self.raw.upgradable_read(None),
+            Some(ref instrument) => {
+                if self.raw.try_upgradable_read() {
+                    instrument.acquired(false);
+                } else {
+                    instrument.acquire_contended(self.addr(), false, None, |d| {
+                        self.raw.upgradable_read_with_backoff(d, instrument.backoff())
+                    });
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn record_acquired(&self, exclusive: bool) {
+        if let Some(ref instrument) = self.instrument {
+            instrument.acquired(exclusive);
+        }
+    }
+
+    #[inline]
+    unsafe fn write_unlock_raw(&self) {
+        write_unlock(&self.raw, self.instrument.as_deref());
+    }
+
+    #[inline]
+    fn start_shared_hold(&self) -> HoldStart {
+        match self.instrument {
+            None => HoldStart::NONE,
+            Some(ref instrument) => instrument.start_shared_hold(),
+        }
+    }
+
+    // Identifies the lock to the held lock tracking. The raw lock's address
+    // is used so that mapped guards, which only know the raw lock, agree.
+    #[inline]
+    fn addr(&self) -> usize {
+        &self.raw as *const RawRwLock as usize
+    }
+}
+
+// Releases a raw write lock, invoking the lock's unlock hook if it has one.
+#[inline]
+unsafe fn write_unlock(raw: &RawRwLock, instrument: Option<&Instrument>) {
+    match instrument {
+        None => raw.write_unlock(),
+        Some(instrument) => instrument.release(Hold::Exclusive, || raw.write_unlock()),
+    }
+}
+
+// Releases a raw read lock held since `since`, invoking the lock's unlock
+// hook if it has one.
+#[inline]
+unsafe fn read_unlock(raw: &RawRwLock, instrument: Option<&Instrument>, since: HoldStart) {
+    match instrument {
+        None => raw.read_unlock(),
+        Some(instrument) => instrument.release(Hold::Shared(since), || raw.read_unlock()),
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        RwLock::new(Default::default())
+    }
+}
+
+/// Adopts a standard library reader-writer lock, ignoring any poisoning.
+impl<T> From<sync::RwLock<T>> for RwLock<T> {
+    #[inline]
+    fn from(rwlock: sync::RwLock<T>) -> RwLock<T> {
+        RwLock::new(rwlock.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl<T> From<RwLock<T>> for sync::RwLock<T> {
+    #[inline]
+    fn from(rwlock: RwLock<T>) -> sync::RwLock<T> {
+        sync::RwLock::new(rwlock.into_inner())
+    }
+}
+
+/// Like `std::sync::RwLockReadGuard`.
+///
+/// Unlike the standard library's guard, this implements `Send` when the
+/// `send_guard` feature is enabled.
+#[must_use]
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    rwlock: &'a RwLock<T>,
+    since: HoldStart,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for RwLockReadGuard<'a, T> {}
+
+impl<'a, T: ?Sized> RwLockReadGuard<'a, T> {
+    #[inline]
+    fn new(rwlock: &'a RwLock<T>) -> RwLockReadGuard<'a, T> {
+        RwLockReadGuard {
+            rwlock,
+            since: rwlock.start_shared_hold(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes another guard for the same read lock.
+    ///
+    /// This takes an additional read lock, which never blocks since the lock
+    /// is already read-locked, so the guards can be dropped independently.
+    /// This is an associated function rather than a method to avoid
+    /// conflicting with methods of the protected value.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn duplicate(orig: &Self) -> RwLockReadGuard<'a, T> {
+        unsafe { orig.rwlock.raw.read_again() };
+        held::acquired("RwLock", orig.rwlock.name(), orig.rwlock.addr(), Mode::Shared);
+        RwLockReadGuard::new(orig.rwlock)
+    }
+
+    /// Makes a new guard for a component of the locked data.
+    ///
+    /// The lock stays read-locked until the returned guard is dropped. This
+    /// is an associated function rather than a method to avoid conflicting
+    /// with methods of the protected value.
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedRwLockReadGuard<'a, U>
+        where F: FnOnce(&T) -> &U
+    {
+        let (raw, instrument, since) = (&orig.rwlock.raw, orig.rwlock.instrument.as_deref(), orig.since);
+        let data = f(unsafe { &*orig.rwlock.data.get() }) as *const U;
+        mem::forget(orig);
+        MappedRwLockReadGuard::new(raw, instrument, data, since)
+    }
+
+    /// Attempts to make a new guard for a component of the locked data.
+    ///
+    /// The original guard is returned if the closure returns `None`.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedRwLockReadGuard<'a, U>, Self>
+        where F: FnOnce(&T) -> Option<&U>
+    {
+        let (raw, instrument, since) = (&orig.rwlock.raw, orig.rwlock.instrument.as_deref(), orig.since);
+        match f(unsafe { &*orig.rwlock.data.get() }) {
+            Some(data) => {
+                let data = data as *const U;
+                mem::forget(orig);
+                Ok(MappedRwLockReadGuard::new(raw, instrument, data, since))
+            }
+            None => Err(orig),
+        }
+    }
+
+    /// Leaks the guard, returning a reference to the locked data which lasts
+    /// as long as the lock's borrow.
+    ///
+    /// The lock stays read-locked. With the `raw` feature, the read lock can
+    /// be released with `RwLock::force_unlock_read` once the reference is no
+    /// longer used.
+    #[inline]
+    pub fn leak(orig: Self) -> &'a T {
+        let data = orig.rwlock.data.get();
+        mem::forget(orig);
+        unsafe { &*data }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "RwLockReadGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for RwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.rwlock.addr(), Mode::Shared);
+        unsafe { read_unlock(&self.rwlock.raw, self.rwlock.instrument.as_deref(), self.since) };
+        held::check_released(owned);
+    }
+}
+
+/// An RAII guard providing shared read access to an `RwLock` which keeps
+/// the lock alive through an `Arc`.
+///
+/// Unlike `RwLockReadGuard`, this is `'static` if `T` is, and is always
+/// `Send`. Since it may be released on a different thread than the one which
+/// acquired it, the debug-build deadlock detection doesn't record it as held.
+/// `read_arc` still panics if the current thread holds a write guard for the
+/// lock.
+#[must_use]
+pub struct ArcRwLockReadGuard<T: ?Sized> {
+    rwlock: Arc<RwLock<T>>,
+    since: HoldStart,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ?Sized> ArcRwLockReadGuard<T> {
+    #[inline]
+    fn new(rwlock: Arc<RwLock<T>>) -> ArcRwLockReadGuard<T> {
+        ArcRwLockReadGuard {
+            since: rwlock.start_shared_hold(),
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(this: &ArcRwLockReadGuard<T>) -> &Arc<RwLock<T>> {
+        &this.rwlock
+    }
+
+    /// Like `RwLockReadGuard::duplicate`.
+    #[inline]
+    pub fn duplicate(orig: &Self) -> ArcRwLockReadGuard<T> {
+        unsafe { orig.rwlock.raw.read_again() };
+        ArcRwLockReadGuard::new(orig.rwlock.clone())
+    }
+
+    /// Like `RwLockReadGuard::map`, but the returned guard keeps holding a
+    /// reference count on the lock.
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedArcRwLockReadGuard<T, U>
+        where F: FnOnce(&T) -> &U
+    {
+        let data = f(unsafe { &*orig.rwlock.data.get() }) as *const U;
+        let since = orig.since;
+        MappedArcRwLockReadGuard::new(ArcRwLockReadGuard::into_rwlock(orig), data, since)
+    }
+
+    /// Like `RwLockReadGuard::try_map`, but the returned guard keeps holding
+    /// a reference count on the lock.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedArcRwLockReadGuard<T, U>, Self>
+        where F: FnOnce(&T) -> Option<&U>
+    {
+        match f(unsafe { &*orig.rwlock.data.get() }) {
+            Some(data) => {
+                let data = data as *const U;
+                let since = orig.since;
+                Ok(MappedArcRwLockReadGuard::new(ArcRwLockReadGuard::into_rwlock(orig), data, since))
+            }
+            None => Err(orig),
+        }
+    }
+
+    // Takes the lock out of the guard without unlocking it.
+    #[inline]
+    fn into_rwlock(this: Self) -> Arc<RwLock<T>> {
+        let rwlock = unsafe { ptr::read(&this.rwlock) };
+        mem::forget(this);
+        rwlock
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ArcRwLockReadGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "ArcRwLockReadGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for ArcRwLockReadGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized> Deref for ArcRwLockReadGuard<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for ArcRwLockReadGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { read_unlock(&self.rwlock.raw, self.rwlock.instrument.as_deref(), self.since) }
+    }
+}
+
+/// Like `std::sync::RwLockWriteGuard`.
+///
+/// Unlike the standard library's guard, this implements `Send` when the
+/// `send_guard` feature is enabled. It also implements `io::Read`,
+/// `io::Write`, `io::Seek` and `fmt::Write` when the locked data does.
+#[must_use]
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    rwlock: &'a RwLock<T>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for RwLockWriteGuard<'a, T> {}
+
+impl<'a, T: ?Sized> RwLockWriteGuard<'a, T> {
+    #[inline]
+    fn new(rwlock: &'a RwLock<T>) -> RwLockWriteGuard<'a, T> {
+        RwLockWriteGuard {
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Makes a new guard for a component of the locked data.
+    ///
+    /// The lock stays write-locked until the returned guard is dropped. This
+    /// is an associated function rather than a method to avoid conflicting
+    /// with methods of the protected value.
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedRwLockWriteGuard<'a, U>
+        where F: FnOnce(&mut T) -> &mut U
+    {
+        let raw = &orig.rwlock.raw;
+        let instrument = orig.rwlock.instrument.as_deref();
+        let data = f(unsafe { &mut *orig.rwlock.data.get() }) as *mut U;
+        mem::forget(orig);
+        MappedRwLockWriteGuard::new(raw, instrument, data, None)
+    }
+
+    /// Attempts to make a new guard for a component of the locked data.
+    ///
+    /// The original guard is returned if the closure returns `None`.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedRwLockWriteGuard<'a, U>, Self>
+        where F: FnOnce(&mut T) -> Option<&mut U>
+    {
+        let raw = &orig.rwlock.raw;
+        let instrument = orig.rwlock.instrument.as_deref();
+        match f(unsafe { &mut *orig.rwlock.data.get() }) {
+            Some(data) => {
+                let data = data as *mut U;
+                mem::forget(orig);
+                Ok(MappedRwLockWriteGuard::new(raw, instrument, data, None))
+            }
+            None => Err(orig),
+        }
+    }
+
+    /// Splits the guard into guards for two disjoint components of the
+    /// locked data.
+    ///
+    /// The lock stays write-locked until both returned guards are dropped.
+    #[inline]
+    pub fn split_map<A: ?Sized, B: ?Sized, F>(orig: Self,
+                                              f: F)
+                                              -> (MappedRwLockWriteGuard<'a, A>, MappedRwLockWriteGuard<'a, B>)
+        where F: FnOnce(&mut T) -> (&mut A, &mut B)
+    {
+        let raw = &orig.rwlock.raw;
+        let instrument = orig.rwlock.instrument.as_deref();
+        let (a, b) = f(unsafe { &mut *orig.rwlock.data.get() });
+        let (a, b) = (a as *mut A, b as *mut B);
+        mem::forget(orig);
+        let split = Arc::new(());
+        (MappedRwLockWriteGuard::new(raw, instrument, a, Some(split.clone())),
+         MappedRwLockWriteGuard::new(raw, instrument, b, Some(split)))
+    }
+
+    /// Atomically downgrades the write lock to an upgradable read lock.
+    ///
+    /// No writer can acquire the lock in between, and the returned guard can
+    /// be upgraded again later. Other readers may share the lock in the
+    /// meantime.
+    ///
+    /// ```
+    /// use antidote::{RwLock, RwLockUpgradableReadGuard, RwLockWriteGuard};
+    ///
+    /// let lock = RwLock::new(vec![3, 1, 2]);
+    /// let mut items = lock.write();
+    /// items.sort();
+    /// let items = RwLockWriteGuard::downgrade_to_upgradable(items);
+    /// assert_eq!(*lock.read(), [1, 2, 3]);
+    /// let mut items = RwLockUpgradableReadGuard::upgrade(items);
+    /// items.push(4);
+    /// ```
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn downgrade_to_upgradable(orig: Self) -> RwLockUpgradableReadGuard<'a, T> {
+        let rwlock = orig.rwlock;
+        mem::forget(orig);
+        match rwlock.instrument {
+            None => unsafe { rwlock.raw.downgrade_to_upgradable() },
+            Some(ref instrument) => {
+                instrument.release(Hold::Exclusive, || unsafe { rwlock.raw.downgrade_to_upgradable() })
+            }
+        }
+        let owned = held::released(rwlock.addr(), Mode::Exclusive);
+        held::acquired("RwLock", rwlock.name(), rwlock.addr(), Mode::Upgradable);
+        let guard = RwLockUpgradableReadGuard::new(rwlock);
+        held::check_released(owned);
+        guard
+    }
+
+    /// Leaks the guard, returning a reference to the locked data which lasts
+    /// as long as the lock's borrow.
+    ///
+    /// The lock stays write-locked. With the `raw` feature, it can be
+    /// released with `RwLock::force_unlock_write` once the reference is no
+    /// longer used.
+    #[inline]
+    pub fn leak(orig: Self) -> &'a mut T {
+        let data = orig.rwlock.data.get();
+        mem::forget(orig);
+        unsafe { &mut *data }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "RwLockWriteGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for RwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.rwlock.addr(), Mode::Exclusive);
+        unsafe { self.rwlock.write_unlock_raw() };
+        held::check_released(owned);
+    }
+}
+
+/// An RAII guard providing exclusive write access to an `RwLock` which keeps
+/// the lock alive through an `Arc`.
+///
+/// Like `ArcRwLockReadGuard`, this is `'static` if `T` is, is always `Send`,
+/// and is not recorded as held by the debug-build deadlock detection.
+#[must_use]
+pub struct ArcRwLockWriteGuard<T: ?Sized> {
+    rwlock: Arc<RwLock<T>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ?Sized> ArcRwLockWriteGuard<T> {
+    #[inline]
+    fn new(rwlock: Arc<RwLock<T>>) -> ArcRwLockWriteGuard<T> {
+        ArcRwLockWriteGuard {
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(this: &ArcRwLockWriteGuard<T>) -> &Arc<RwLock<T>> {
+        &this.rwlock
+    }
+
+    /// Like `RwLockWriteGuard::map`, but the returned guard keeps holding a
+    /// reference count on the lock.
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedArcRwLockWriteGuard<T, U>
+        where F: FnOnce(&mut T) -> &mut U
+    {
+        let data = f(unsafe { &mut *orig.rwlock.data.get() }) as *mut U;
+        MappedArcRwLockWriteGuard::new(ArcRwLockWriteGuard::into_rwlock(orig), data)
+    }
+
+    /// Like `RwLockWriteGuard::try_map`, but the returned guard keeps holding
+    /// a reference count on the lock.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedArcRwLockWriteGuard<T, U>, Self>
+        where F: FnOnce(&mut T) -> Option<&mut U>
+    {
+        match f(unsafe { &mut *orig.rwlock.data.get() }) {
+            Some(data) => {
+                let data = data as *mut U;
+                Ok(MappedArcRwLockWriteGuard::new(ArcRwLockWriteGuard::into_rwlock(orig), data))
+            }
+            None => Err(orig),
+        }
+    }
+
+    #[inline]
+    fn into_rwlock(this: Self) -> Arc<RwLock<T>> {
+        let rwlock = unsafe { ptr::read(&this.rwlock) };
+        mem::forget(this);
+        rwlock
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ArcRwLockWriteGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "ArcRwLockWriteGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<T: ?Sized + fmt::Display> fmt::Display for ArcRwLockWriteGuard<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized> Deref for ArcRwLockWriteGuard<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for ArcRwLockWriteGuard<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.rwlock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for ArcRwLockWriteGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.rwlock.write_unlock_raw() }
+    }
+}
+
+/// An optimistic read of an `RwLock`, in the style of Java's `StampedLock`.
+///
+/// This is created by `RwLock::optimistic_read`. It does not hold the lock,
+/// and is invalidated as soon as a writer acquires it. Writers never wait for
+/// optimistic readers.
+///
+/// Checking the snapshot is lock-free, which makes it cheap to tell whether
+/// something computed from the data earlier is still up to date. Reading the
+/// data itself still takes a read lock.
+///
+/// Versions are 64 bits wide, so a snapshot can't be mistaken for a valid one
+/// after the version wraps around.
+///
+/// ```
+/// use antidote::RwLock;
+///
+/// let bounds = RwLock::new([0u32, 10]);
+/// let read = bounds.optimistic_read();
+/// let width = read.read(|b| b[1] - b[0]);
+/// assert!(read.validate());
+///
+/// *bounds.write() = [5, 10];
+/// assert!(!read.validate());
+/// assert_ne!(read.read(|b| b[1] - b[0]), width);
+/// ```
+pub struct OptimisticRead<'a, T: ?Sized + 'a> {
+    rwlock: &'a RwLock<T>,
+    version: Option<u64>,
+}
+
+impl<'a, T: ?Sized> Clone for OptimisticRead<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: ?Sized> Copy for OptimisticRead<'a, T> {}
+
+impl<'a, T: ?Sized> fmt::Debug for OptimisticRead<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("OptimisticRead").field("valid", &self.validate()).finish()
+    }
+}
+
+impl<'a, T: ?Sized> OptimisticRead<'a, T> {
+    /// Returns whether no writer has acquired the lock since the optimistic
+    /// read started.
+    #[inline]
+    pub fn validate(&self) -> bool {
+        match self.version {
+            Some(version) => self.rwlock.raw.validate(version),
+            None => false,
+        }
+    }
+
+    /// Passes the current data to `f` under a read lock.
+    ///
+    /// The data is never read without the lock, since a writer may be
+    /// modifying it. Whether it has changed since the optimistic read started
+    /// can be checked with `validate`.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read<R, F>(&self, f: F) -> R
+        where F: FnOnce(&T) -> R
+    {
+        f(&self.rwlock.read())
+    }
+}
+
+/// An RAII guard providing upgradable read access to an `RwLock`.
+///
+/// This is created by `RwLock::upgradable_read`.
+#[must_use]
+pub struct RwLockUpgradableReadGuard<'a, T: ?Sized + 'a> {
+    rwlock: &'a RwLock<T>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for RwLockUpgradableReadGuard<'a, T> {}
+
+impl<'a, T: ?Sized> RwLockUpgradableReadGuard<'a, T> {
+    #[inline]
+    fn new(rwlock: &'a RwLock<T>) -> RwLockUpgradableReadGuard<'a, T> {
+        if let Some(ref instrument) = rwlock.instrument {
+            instrument.start_upgradable_hold();
+        }
+        RwLockUpgradableReadGuard {
+            rwlock,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Atomically upgrades the read lock to a write lock, blocking the
+    /// current thread until all other readers have released the lock.
+    ///
+    /// No writer can acquire the lock in between, so anything observed
+    /// through the upgradable guard remains valid.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn upgrade(orig: Self) -> RwLockWriteGuard<'a, T> {
+        match RwLockUpgradableReadGuard::upgrade_until_inner(orig, None) {
+            Ok(guard) => guard,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Attempts to atomically upgrade the read lock to a write lock.
+    ///
+    /// This function does not block. If other readers hold the lock, the
+    /// original guard is returned.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_upgrade(orig: Self) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        if unsafe { orig.rwlock.raw.try_upgrade() } {
+            orig.rwlock.record_acquired(true);
+            Ok(RwLockUpgradableReadGuard::upgraded(orig))
+        } else {
+            Err(orig)
+        }
+    }
+
+    /// Attempts to atomically upgrade the read lock to a write lock, blocking
+    /// the current thread for at most `timeout` while other readers hold it.
+    ///
+    /// If the timeout expires, the original guard is returned.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_upgrade_for(orig: Self, timeout: Duration) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        RwLockUpgradableReadGuard::upgrade_until_inner(orig, deadline_after(timeout))
+    }
+
+    /// Attempts to atomically upgrade the read lock to a write lock, blocking
+    /// the current thread until at most `deadline` while other readers hold
+    /// it.
+    ///
+    /// If the deadline passes, the original guard is returned.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_upgrade_until(orig: Self, deadline: Instant) -> Result<RwLockWriteGuard<'a, T>, Self> {
+        RwLockUpgradableReadGuard::upgrade_until_inner(orig, Some(deadline))
+    }
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn upgrade_until_inner(orig: Self,
+                           deadline: Option<Instant>)
+                           -> Result<RwLockWriteGuard<'a, T>, Self> {
+        let rwlock = orig.rwlock;
+        let upgraded = match rwlock.instrument {
+            None => unsafe { rwlock.raw.upgrade(deadline, None) },
+            Some(ref instrument) => {
+                if unsafe { rwlock.raw.try_upgrade() } {
+                    instrument.acquired(true);
+                    true
+                } else {
+                    instrument.acquire_contended(rwlock.addr(), true, deadline, |d| unsafe {
+                        rwlock.raw.upgrade(d, instrument.backoff())
+                    })
+                }
+            }
+        };
+        if upgraded {
+            Ok(RwLockUpgradableReadGuard::upgraded(orig))
+        } else {
+            Err(orig)
+        }
+    }
+
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    fn upgraded(orig: Self) -> RwLockWriteGuard<'a, T> {
+        let rwlock = orig.rwlock;
+        mem::forget(orig);
+        let owned = held::released(rwlock.addr(), Mode::Upgradable);
+        held::acquired("RwLock", rwlock.name(), rwlock.addr(), Mode::Exclusive);
+        let guard = RwLockWriteGuard::new(rwlock);
+        held::check_released(owned);
+        guard
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockUpgradableReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "RwLockUpgradableReadGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for RwLockUpgradableReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockUpgradableReadGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockUpgradableReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.rwlock.addr(), Mode::Upgradable);
+        let raw = &self.rwlock.raw;
+        match self.rwlock.instrument {
+            None => unsafe { raw.upgradable_read_unlock() },
+            Some(ref instrument) => instrument.release(Hold::Upgradable, || unsafe { raw.upgradable_read_unlock() }),
+        }
+        held::check_released(owned);
+    }
+}
+
+/// An RAII guard for a component of the data protected by an `RwLock`,
+/// giving shared access.
+///
+/// This is created by `RwLockReadGuard::map`, and releases the read lock when
+/// dropped.
+#[must_use]
+pub struct MappedRwLockReadGuard<'a, T: ?Sized + 'a> {
+    raw: &'a RawRwLock,
+    instrument: Option<&'a Instrument>,
+    data: *const T,
+    since: HoldStart,
+    _marker: PhantomData<(&'a T, GuardMarker)>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for MappedRwLockReadGuard<'a, T> {}
+#[cfg(feature = "send_guard")]
+unsafe impl<'a, T: ?Sized + Sync + 'a> Send for MappedRwLockReadGuard<'a, T> {}
+
+impl<'a, T: ?Sized> MappedRwLockReadGuard<'a, T> {
+    #[inline]
+    fn new(raw: &'a RawRwLock,
+           instrument: Option<&'a Instrument>,
+           data: *const T,
+           since: HoldStart)
+           -> MappedRwLockReadGuard<'a, T> {
+        MappedRwLockReadGuard {
+            raw,
+            instrument,
+            data,
+            since,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Like `RwLockReadGuard::duplicate`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn duplicate(orig: &Self) -> MappedRwLockReadGuard<'a, T> {
+        unsafe { orig.raw.read_again() };
+        held::acquired("RwLock", None, orig.raw as *const RawRwLock as usize, Mode::Shared);
+        let since = orig.instrument.map_or(HoldStart::NONE, |instrument| instrument.start_shared_hold());
+        MappedRwLockReadGuard::new(orig.raw, orig.instrument, orig.data, since)
+    }
+
+    /// Like `RwLockReadGuard::map`.
+    #[inline]
+    pub fn map<U: ?Sized, F>(orig: Self, f: F) -> MappedRwLockReadGuard<'a, U>
+        where F: FnOnce(&T) -> &U
+    {
+        let (raw, instrument, since) = (orig.raw, orig.instrument, orig.since);
+        let data = f(unsafe { &*orig.data }) as *const U;
+        mem::forget(orig);
+        MappedRwLockReadGuard::new(raw, instrument, data, since)
+    }
+
+    /// Like `RwLockReadGuard::try_map`.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(orig: Self, f: F) -> Result<MappedRwLockReadGuard<'a, U>, Self>
+        where F: FnOnce(&T) -> Option<&U>
+    {
+        let (raw, instrument, since) = (orig.raw, orig.instrument, orig.since);
+        match f(unsafe { &*orig.data }) {
+            Some(data) => {
+                let data = data as *const U;
+                mem::forget(orig);
+                Ok(MappedRwLockReadGuard::new(raw, instrument, data, since))
+            }
+            None => Err(orig),
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for MappedRwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "MappedRwLockReadGuard", self.raw as *const RawRwLock as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for MappedRwLockReadGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for MappedRwLockReadGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for MappedRwLockReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.raw as *const RawRwLock as usize, Mode::Shared);
+        unsafe { read_unlock(self.raw, self.instrument, self.since) };
+        held::check_released(owned);
+    }
+}
+
+/// An RAII guard for a component of the data protected by an `RwLock`,
+/// giving exclusive access.
+///
+/// This is created by `RwLockWriteGuard::map`, and releases the write lock
+/// when dropped. Guards created by `RwLockWriteGuard::split_map` release it
+/// when the last of them is dropped.
+#[must_use]
+pub struct MappedRwLockWriteGuard<'a, T: ?Sized + 'a> {
+    raw: &'a RawRwLock,
+    instrument: Option<&'a Instrument>,
+    data: *mut T,
+    // Shared by guards split from one guard.
+    split: Option<Arc<()>>,
+    _marker: PhantomData<(&'a mut T, GuardMarker)>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for MappedRwLockWriteGuard<'a, T> {}
+#[cfg(feature = "send_guard")]
+unsafe impl<'a, T: ?Sized + Send + 'a> Send for MappedRwLockWriteGuard<'a, T> {}
+
+impl<'a, T: ?Sized> MappedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn new(raw: &'a RawRwLock,
+           instrument: Option<&'a Instrument>,
+           data: *mut T,
+           split: Option<Arc<()>>)
+           -> MappedRwLockWriteGuard<'a, T> {
+        MappedRwLockWriteGuard {
+            raw,
+            instrument,
+            data,
+            split,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Like `RwLockWriteGuard::map`.
+    #[inline]
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedRwLockWriteGuard<'a, U>
+        where F: FnOnce(&mut T) -> &mut U
+    {
+        let raw = orig.raw;
+        let instrument = orig.instrument;
+        let split = orig.split.take();
+        let data = f(unsafe { &mut *orig.data }) as *mut U;
+        mem::forget(orig);
+        MappedRwLockWriteGuard::new(raw, instrument, data, split)
+    }
+
+    /// Like `RwLockWriteGuard::try_map`.
+    #[inline]
+    pub fn try_map<U: ?Sized, F>(mut orig: Self, f: F) -> Result<MappedRwLockWriteGuard<'a, U>, Self>
+        where F: FnOnce(&mut T) -> Option<&mut U>
+    {
+        let raw = orig.raw;
+        let instrument = orig.instrument;
+        match f(unsafe { &mut *orig.data }) {
+            Some(data) => {
+                let data = data as *mut U;
+                let split = orig.split.take();
+                mem::forget(orig);
+                Ok(MappedRwLockWriteGuard::new(raw, instrument, data, split))
+            }
+            None => Err(orig),
+        }
+    }
+
+    /// Like `RwLockWriteGuard::split_map`.
+    #[inline]
+    pub fn split_map<A: ?Sized, B: ?Sized, F>(mut orig: Self,
+                                              f: F)
+                                              -> (MappedRwLockWriteGuard<'a, A>, MappedRwLockWriteGuard<'a, B>)
+        where F: FnOnce(&mut T) -> (&mut A, &mut B)
+    {
+        let raw = orig.raw;
+        let instrument = orig.instrument;
+        let split = orig.split.take().unwrap_or_default();
+        let (a, b) = f(unsafe { &mut *orig.data });
+        let (a, b) = (a as *mut A, b as *mut B);
+        mem::forget(orig);
+        (MappedRwLockWriteGuard::new(raw, instrument, a, Some(split.clone())),
+         MappedRwLockWriteGuard::new(raw, instrument, b, Some(split)))
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for MappedRwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "MappedRwLockWriteGuard", self.raw as *const RawRwLock as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for MappedRwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for MappedRwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for MappedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for MappedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.split.take().is_some_and(|split| Arc::into_inner(split).is_none()) {
+            return;
+        }
+        let owned = held::released(self.raw as *const RawRwLock as usize, Mode::Exclusive);
+        unsafe { write_unlock(self.raw, self.instrument) };
+        held::check_released(owned);
+    }
+}
+
+/// An RAII guard providing shared read access to a component of the data
+/// protected by an `RwLock`, which keeps the lock alive through an `Arc`.
+///
+/// This is created by `ArcRwLockReadGuard::map`, and unlocks the lock when
+/// dropped.
+#[must_use]
+pub struct MappedArcRwLockReadGuard<T: ?Sized, U: ?Sized> {
+    rwlock: Arc<RwLock<T>>,
+    data: *const U,
+    since: HoldStart,
+}
+
+unsafe impl<T: ?Sized + Send + Sync, U: ?Sized + Sync> Send for MappedArcRwLockReadGuard<T, U> {}
+unsafe impl<T: ?Sized + Send + Sync, U: ?Sized + Sync> Sync for MappedArcRwLockReadGuard<T, U> {}
+
+impl<T: ?Sized, U: ?Sized> MappedArcRwLockReadGuard<T, U> {
+    #[inline]
+    fn new(rwlock: Arc<RwLock<T>>, data: *const U, since: HoldStart) -> MappedArcRwLockReadGuard<T, U> {
+        MappedArcRwLockReadGuard { rwlock, data, since }
+    }
+
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(this: &MappedArcRwLockReadGuard<T, U>) -> &Arc<RwLock<T>> {
+        &this.rwlock
+    }
+
+    /// Like `RwLockReadGuard::duplicate`.
+    #[inline]
+    pub fn duplicate(orig: &Self) -> MappedArcRwLockReadGuard<T, U> {
+        unsafe { orig.rwlock.raw.read_again() };
+        MappedArcRwLockReadGuard::new(orig.rwlock.clone(), orig.data, orig.rwlock.start_shared_hold())
+    }
+
+    /// Like `RwLockReadGuard::map`.
+    #[inline]
+    pub fn map<V: ?Sized, F>(orig: Self, f: F) -> MappedArcRwLockReadGuard<T, V>
+        where F: FnOnce(&U) -> &V
+    {
+        let data = f(unsafe { &*orig.data }) as *const V;
+        let since = orig.since;
+        MappedArcRwLockReadGuard::new(MappedArcRwLockReadGuard::into_rwlock(orig), data, since)
+    }
+
+    /// Like `RwLockReadGuard::try_map`.
+    #[inline]
+    pub fn try_map<V: ?Sized, F>(orig: Self, f: F) -> Result<MappedArcRwLockReadGuard<T, V>, Self>
+        where F: FnOnce(&U) -> Option<&V>
+    {
+        match f(unsafe { &*orig.data }) {
+            Some(data) => {
+                let data = data as *const V;
+                let since = orig.since;
+                Ok(MappedArcRwLockReadGuard::new(MappedArcRwLockReadGuard::into_rwlock(orig), data, since))
+            }
+            None => Err(orig),
+        }
+    }
+
+    #[inline]
+    fn into_rwlock(this: Self) -> Arc<RwLock<T>> {
+        let rwlock = unsafe { ptr::read(&this.rwlock) };
+        mem::forget(this);
+        rwlock
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Debug> fmt::Debug for MappedArcRwLockReadGuard<T, U> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "MappedArcRwLockReadGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Display> fmt::Display for MappedArcRwLockReadGuard<T, U> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Deref for MappedArcRwLockReadGuard<T, U> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        unsafe { &*self.data }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Drop for MappedArcRwLockReadGuard<T, U> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { read_unlock(&self.rwlock.raw, self.rwlock.instrument.as_deref(), self.since) }
+    }
+}
+
+/// An RAII guard providing exclusive write access to a component of the data
+/// protected by an `RwLock`, which keeps the lock alive through an `Arc`.
+///
+/// This is created by `ArcRwLockWriteGuard::map`, and unlocks the lock when
+/// dropped.
+#[must_use]
+pub struct MappedArcRwLockWriteGuard<T: ?Sized, U: ?Sized> {
+    rwlock: Arc<RwLock<T>>,
+    data: *mut U,
+}
+
+unsafe impl<T: ?Sized + Send + Sync, U: ?Sized + Send> Send for MappedArcRwLockWriteGuard<T, U> {}
+unsafe impl<T: ?Sized + Send + Sync, U: ?Sized + Sync> Sync for MappedArcRwLockWriteGuard<T, U> {}
+
+impl<T: ?Sized, U: ?Sized> MappedArcRwLockWriteGuard<T, U> {
+    #[inline]
+    fn new(rwlock: Arc<RwLock<T>>, data: *mut U) -> MappedArcRwLockWriteGuard<T, U> {
+        MappedArcRwLockWriteGuard { rwlock, data }
+    }
+
+    /// Returns the lock this guard holds.
+    #[inline]
+    pub fn rwlock(this: &MappedArcRwLockWriteGuard<T, U>) -> &Arc<RwLock<T>> {
+        &this.rwlock
+    }
+
+    /// Like `RwLockWriteGuard::map`.
+    #[inline]
+    pub fn map<V: ?Sized, F>(orig: Self, f: F) -> MappedArcRwLockWriteGuard<T, V>
+        where F: FnOnce(&mut U) -> &mut V
+    {
+        let data = f(unsafe { &mut *orig.data }) as *mut V;
+        MappedArcRwLockWriteGuard::new(MappedArcRwLockWriteGuard::into_rwlock(orig), data)
+    }
+
+    /// Like `RwLockWriteGuard::try_map`.
+    #[inline]
+    pub fn try_map<V: ?Sized, F>(orig: Self, f: F) -> Result<MappedArcRwLockWriteGuard<T, V>, Self>
+        where F: FnOnce(&mut U) -> Option<&mut V>
+    {
+        match f(unsafe { &mut *orig.data }) {
+            Some(data) => {
+                let data = data as *mut V;
+                Ok(MappedArcRwLockWriteGuard::new(MappedArcRwLockWriteGuard::into_rwlock(orig), data))
+            }
+            None => Err(orig),
+        }
+    }
+
+    #[inline]
+    fn into_rwlock(this: Self) -> Arc<RwLock<T>> {
+        let rwlock = unsafe { ptr::read(&this.rwlock) };
+        mem::forget(this);
+        rwlock
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Debug> fmt::Debug for MappedArcRwLockWriteGuard<T, U> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "MappedArcRwLockWriteGuard", self.rwlock.addr(), &**self)
+    }
+}
+
+impl<T: ?Sized, U: ?Sized + fmt::Display> fmt::Display for MappedArcRwLockWriteGuard<T, U> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Deref for MappedArcRwLockWriteGuard<T, U> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &U {
+        unsafe { &*self.data }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> DerefMut for MappedArcRwLockWriteGuard<T, U> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut U {
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<T: ?Sized, U: ?Sized> Drop for MappedArcRwLockWriteGuard<T, U> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.rwlock.write_unlock_raw() }
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use std::mem;
+
+use {Condvar, Mutex, TryLockError, TryLockResult};
+
+/// A counting semaphore, which limits the number of permits held at once.
+///
+/// Permits can be acquired in any number at a time, so the semaphore can
+/// bound a weighted quantity, such as the memory used by work in progress,
+/// as well as a number of holders. Acquisitions are served in FIFO order,
+/// so a large acquisition is not starved by a stream of small ones.
+///
+/// ```
+/// use antidote::Semaphore;
+///
+/// // Allow at most 64 MiB of buffers at a time.
+/// let memory = Semaphore::new(64 << 20);
+///
+/// let permit = memory.acquire_many(48 << 20);
+/// assert_eq!(permit.permits(), 48 << 20);
+/// assert!(memory.try_acquire_many(32 << 20).is_err());
+///
+/// drop(permit);
+/// assert_eq!(memory.available_permits(), 64 << 20);
+/// ```
+pub struct Semaphore {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+struct State {
+    permits: usize,
+    // Waiters take a ticket and are served in ticket order.
+    next_ticket: u64,
+    serving: u64,
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Semaphore").field("permits", &self.available_permits()).finish()
+    }
+}
+
+impl Semaphore {
+    /// Creates a new `Semaphore` with `permits` permits available.
+    #[inline]
+    pub fn new(permits: usize) -> Semaphore {
+        Semaphore {
+            state: Mutex::new(State {
+                permits,
+                next_ticket: 0,
+                serving: 0,
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Returns the number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        self.state.lock().permits
+    }
+
+    /// Adds `n` permits to the semaphore.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of available permits overflows.
+    pub fn add_permits(&self, n: usize) {
+        let mut state = self.state.lock();
+        state.permits = state.permits.checked_add(n).expect("Semaphore permit count overflowed");
+        self.cond.notify_all();
+    }
+
+    /// Acquires a single permit, blocking the current thread until one is
+    /// available.
+    #[inline]
+    pub fn acquire<'a>(&'a self) -> SemaphorePermit<'a> {
+        self.acquire_many(1)
+    }
+
+    /// Acquires `n` permits at once, blocking the current thread until they
+    /// are available.
+    ///
+    /// This blocks forever if the semaphore never has `n` permits available,
+    /// and blocks acquisitions which arrive later in the meantime.
+    pub fn acquire_many<'a>(&'a self, n: usize) -> SemaphorePermit<'a> {
+        let mut state = self.state.lock();
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        while state.serving != ticket || state.permits < n {
+            state = self.cond.wait(state);
+        }
+        state.permits -= n;
+        state.serving += 1;
+        // The next waiter may be able to proceed as well.
+        self.cond.notify_all();
+        SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        }
+    }
+
+    /// Attempts to acquire a single permit without blocking.
+    #[inline]
+    pub fn try_acquire<'a>(&'a self) -> TryLockResult<SemaphorePermit<'a>> {
+        self.try_acquire_many(1)
+    }
+
+    /// Attempts to acquire `n` permits at once without blocking.
+    ///
+    /// This fails if other threads are waiting to acquire permits, even if
+    /// `n` are available, so as not to overtake them.
+    pub fn try_acquire_many<'a>(&'a self, n: usize) -> TryLockResult<SemaphorePermit<'a>> {
+        let mut state = self.state.lock();
+        if state.serving != state.next_ticket || state.permits < n {
+            return Err(TryLockError::WouldBlock);
+        }
+        state.permits -= n;
+        Ok(SemaphorePermit {
+            semaphore: self,
+            permits: n,
+        })
+    }
+}
+
+/// Permits acquired from a `Semaphore`, which are returned to it when this
+/// is dropped.
+///
+/// This is created by `Semaphore::acquire` and its variants.
+#[must_use]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+impl<'a> fmt::Debug for SemaphorePermit<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SemaphorePermit").field("permits", &self.permits).finish()
+    }
+}
+
+impl<'a> Drop for SemaphorePermit<'a> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.permits != 0 {
+            self.semaphore.add_permits(self.permits);
+        }
+    }
+}
+
+impl<'a> SemaphorePermit<'a> {
+    /// Returns the number of permits held.
+    #[inline]
+    pub fn permits(&self) -> usize {
+        self.permits
+    }
+
+    /// Splits `n` permits off into a separate permit, which can be released
+    /// independently.
+    ///
+    /// Returns `None` if fewer than `n` permits are held.
+    pub fn split(&mut self, n: usize) -> Option<SemaphorePermit<'a>> {
+        let rest = self.permits.checked_sub(n)?;
+        self.permits = rest;
+        Some(SemaphorePermit {
+            semaphore: self.semaphore,
+            permits: n,
+        })
+    }
+
+    /// Consumes the permit without returning its permits to the semaphore,
+    /// which permanently reduces the number available.
+    #[inline]
+    pub fn forget(mut self) {
+        mem::take(&mut self.permits);
+    }
+}
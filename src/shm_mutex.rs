@@ -0,0 +1,170 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+
+use raw::RawShmMutex;
+use {fmt_guard, GuardMarker, TryLockError, TryLockResult};
+
+/// A mutex which can be placed in shared memory and locked from multiple
+/// processes.
+///
+/// The mutex has a fixed `#[repr(C)]` layout with the lock state first, and
+/// contains no pointers, so every process mapping the memory can use it
+/// regardless of the address it is mapped at. One process initializes it in
+/// place with `init`, and the others attach to it with `from_ptr`.
+///
+/// The protected value must itself be meaningful in every process, so it
+/// should not contain pointers or handles.
+///
+/// A process which exits while holding the lock leaves it locked.
+///
+/// This is only available on Linux and Android.
+#[repr(C)]
+pub struct ShmMutex<T: ?Sized> {
+    raw: RawShmMutex,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for ShmMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for ShmMutex<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for ShmMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Ok(guard) => fmt.debug_struct("ShmMutex").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("ShmMutex").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> ShmMutex<T> {
+    /// Creates a new, unlocked mutex.
+    ///
+    /// The mutex can be moved into shared memory afterwards, as long as it is
+    /// not locked at the time.
+    #[inline]
+    pub const fn new(t: T) -> ShmMutex<T> {
+        ShmMutex {
+            raw: RawShmMutex::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Initializes a new, unlocked mutex at the specified location.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes and suitably aligned for a
+    /// `ShmMutex<T>`, and must remain valid for the lifetime `'a`. Any
+    /// previous contents are overwritten without being dropped, and no
+    /// process may be using a mutex at that location.
+    #[inline]
+    pub unsafe fn init<'a>(ptr: *mut ShmMutex<T>, t: T) -> &'a ShmMutex<T> {
+        ptr::write(ptr, ShmMutex::new(t));
+        &*ptr
+    }
+
+    /// Attaches to a mutex which another process has initialized.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a `ShmMutex<T>` initialized by `init` or `new`,
+    /// which must remain valid for the lifetime `'a`.
+    #[inline]
+    pub unsafe fn from_ptr<'a>(ptr: *const ShmMutex<T>) -> &'a ShmMutex<T> {
+        &*ptr
+    }
+
+    /// Like `Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> ShmMutex<T> {
+    /// Like `Mutex::lock`.
+    #[inline]
+    pub fn lock<'a>(&'a self) -> ShmMutexGuard<'a, T> {
+        self.raw.lock();
+        ShmMutexGuard::new(self)
+    }
+
+    /// Like `Mutex::try_lock`.
+    #[inline]
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<ShmMutexGuard<'a, T>> {
+        if self.raw.try_lock() {
+            Ok(ShmMutexGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: Default> Default for ShmMutex<T> {
+    fn default() -> Self {
+        ShmMutex::new(Default::default())
+    }
+}
+
+/// An RAII guard for a `ShmMutex`.
+#[must_use]
+pub struct ShmMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a ShmMutex<T>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for ShmMutexGuard<'a, T> {}
+
+impl<'a, T: ?Sized> ShmMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a ShmMutex<T>) -> ShmMutexGuard<'a, T> {
+        ShmMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for ShmMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "ShmMutexGuard", self.mutex as *const ShmMutex<T> as *const () as usize, &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for ShmMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for ShmMutexGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for ShmMutexGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for ShmMutexGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.mutex.raw.unlock() }
+    }
+}
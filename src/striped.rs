@@ -0,0 +1,135 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::slice;
+
+use CachePadded;
+
+/// A fixed set of locks, with keys mapped onto them by hash, like Guava's
+/// `Striped`.
+///
+/// Operations on keys which map to different stripes proceed in parallel,
+/// giving most of the concurrency of a lock per key with the memory cost of
+/// only a fixed number of locks. Keys which are equal always map to the same
+/// stripe, but unequal keys may share one.
+///
+/// Each stripe is padded to the length of a cache line to avoid false
+/// sharing between them.
+///
+/// ```
+/// use antidote::{Mutex, Striped};
+///
+/// let accounts: Striped<Mutex<()>> = Striped::new(16);
+/// let _guard = accounts.get("alice").lock();
+/// ```
+pub struct Striped<L, S = RandomState> {
+    stripes: Box<[CachePadded<L>]>,
+    hasher: S,
+}
+
+impl<L: fmt::Debug, S> fmt::Debug for Striped<L, S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Striped").field("stripes", &self.stripes).finish()
+    }
+}
+
+impl<L: Default> Striped<L> {
+    /// Creates a new `Striped` with at least `stripes` default-constructed
+    /// locks.
+    ///
+    /// The number of stripes is rounded up to a power of two.
+    pub fn new(stripes: usize) -> Striped<L> {
+        Striped::with_hasher(stripes, RandomState::new(), L::default)
+    }
+}
+
+impl<L, S: BuildHasher> Striped<L, S> {
+    /// Creates a new `Striped` with at least `stripes` locks created by `f`,
+    /// using `hasher` to map keys onto them.
+    ///
+    /// The number of stripes is rounded up to a power of two.
+    pub fn with_hasher<F>(stripes: usize, hasher: S, mut f: F) -> Striped<L, S>
+        where F: FnMut() -> L
+    {
+        let len = stripes.max(1).next_power_of_two();
+        Striped {
+            stripes: (0..len).map(|_| f()).map(CachePadded::new).collect(),
+            hasher,
+        }
+    }
+
+    /// Returns the lock for `key`.
+    #[inline]
+    pub fn get<Q: Hash + ?Sized>(&self, key: &Q) -> &L {
+        &self.stripes[self.index(key)]
+    }
+
+    /// Returns the index of the stripe for `key`.
+    #[inline]
+    pub fn index<Q: Hash + ?Sized>(&self, key: &Q) -> usize {
+        self.hasher.hash_one(key) as usize & (self.stripes.len() - 1)
+    }
+}
+
+impl<L, S> Striped<L, S> {
+    /// Returns the lock at the specified index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than the number of stripes.
+    #[inline]
+    pub fn get_at(&self, index: usize) -> &L {
+        &self.stripes[index]
+    }
+
+    /// Returns the number of stripes.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.stripes.len()
+    }
+
+    /// Always returns `false`, since there is at least one stripe.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns an iterator over the stripes, in index order.
+    ///
+    /// Locking every stripe in this order, and only in this order, excludes
+    /// all other users without risking deadlock.
+    #[inline]
+    pub fn iter<'a>(&'a self) -> Stripes<'a, L> {
+        Stripes(self.stripes.iter())
+    }
+}
+
+impl<'a, L, S> IntoIterator for &'a Striped<L, S> {
+    type Item = &'a L;
+    type IntoIter = Stripes<'a, L>;
+
+    #[inline]
+    fn into_iter(self) -> Stripes<'a, L> {
+        self.iter()
+    }
+}
+
+/// An iterator over the stripes of a `Striped`.
+#[derive(Debug, Clone)]
+pub struct Stripes<'a, L: 'a>(slice::Iter<'a, CachePadded<L>>);
+
+impl<'a, L> Iterator for Stripes<'a, L> {
+    type Item = &'a L;
+
+    #[inline]
+    fn next(&mut self) -> Option<&'a L> {
+        self.0.next().map(|l| &**l)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, L> ExactSizeIterator for Stripes<'a, L> {}
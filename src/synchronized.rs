@@ -0,0 +1,55 @@
+/// Makes a method's body run with a lock held.
+///
+/// `#[synchronized]` wraps the body of a method taking `&self` in
+/// `self.lock.lock()`, releasing the lock when the body returns, and
+/// `#[synchronized(field)]` locks `self.field` instead. The field can be any
+/// lock with a `lock` method, typically a `Mutex<()>` serializing the methods
+/// of a type. This requires the `derive` feature.
+///
+/// Methods may be generic, and take any arguments. A method taking
+/// `&mut self` already has exclusive access to the value, so its body runs
+/// without taking the lock.
+///
+/// ```
+/// extern crate antidote;
+///
+/// use antidote::{synchronized, Mutex};
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// #[derive(Default)]
+/// struct Console {
+///     lock: Mutex<()>,
+///     lines: AtomicUsize,
+/// }
+///
+/// impl Console {
+///     /// Prints lines without interleaving them with other threads' output.
+///     #[synchronized]
+///     pub fn print_all<S>(&self, lines: &[S])
+///         where S: AsRef<str>
+///     {
+///         for line in lines {
+///             println!("{}", line.as_ref());
+///         }
+///         self.lines.fetch_add(lines.len(), Ordering::Relaxed);
+///     }
+/// }
+///
+/// # fn main() {
+/// let console = Arc::new(Console::default());
+/// let threads = (0..4)
+///     .map(|i| {
+///         let console = console.clone();
+///         thread::spawn(move || console.print_all(&[format!("{} starting", i), format!("{} done", i)]))
+///     })
+///     .collect::<Vec<_>>();
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+/// assert_eq!(console.lines.load(Ordering::Relaxed), 8);
+/// assert!(console.lock.try_lock().is_ok());
+/// # }
+/// ```
+pub use antidote_derive::synchronized;
@@ -0,0 +1,251 @@
+//! Reproducible interleavings of lock acquisitions.
+//!
+//! A `Deterministic` run executes a set of closures on threads which are
+//! scheduled cooperatively: only one of them runs at a time, and control is
+//! passed between them at lock points, when they acquire a lock or wait on a
+//! condition variable. The next thread to run is chosen by a random number
+//! generator seeded by the caller, so a failing interleaving can be replayed
+//! by running again with the same seed.
+//!
+//! Only this crate's `Mutex`, `RwLock` and `Condvar` are lock points. Code
+//! which blocks in other ways, for example on channels, will hang the run.
+//!
+//! ```
+//! use antidote::Mutex;
+//! use antidote::testing::deterministic::Deterministic;
+//!
+//! for seed in 0..100 {
+//!     let log = Mutex::new(vec![]);
+//!
+//!     let mut run = Deterministic::new(seed);
+//!     run.spawn(|| log.lock().push(1));
+//!     run.spawn(|| log.lock().push(2));
+//!     run.run();
+//!
+//!     assert_eq!(log.lock().len(), 2);
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Instant;
+
+use testing::Rng;
+
+thread_local! {
+    static CURRENT: RefCell<Option<(Arc<Shared>, usize)>> = const { RefCell::new(None) };
+}
+
+struct State {
+    rng: Rng,
+    current: usize,
+    live: Vec<bool>,
+    steps: usize,
+    max_steps: usize,
+    aborted: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait_turn(&self, mut state: MutexGuard<'_, State>, id: usize) {
+        while state.current != id && !state.aborted {
+            state = self.cond.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        if state.aborted {
+            drop(state);
+            panic!("deterministic run aborted");
+        }
+    }
+
+    fn schedule(&self, state: &mut State) {
+        let live = state.live.iter().filter(|l| **l).count();
+        if live == 0 {
+            return;
+        }
+        let mut pick = (state.rng.next() % live as u64) as usize;
+        for (id, _) in state.live.iter().enumerate().filter(|&(_, l)| *l) {
+            if pick == 0 {
+                state.current = id;
+                break;
+            }
+            pick -= 1;
+        }
+        self.cond.notify_all();
+    }
+
+    fn yield_now(&self, id: usize) {
+        let mut state = self.lock();
+        state.steps += 1;
+        if state.steps > state.max_steps {
+            state.aborted = true;
+            self.cond.notify_all();
+            let max_steps = state.max_steps;
+            drop(state);
+            panic!("deterministic run exceeded {} steps; the threads may be deadlocked", max_steps);
+        }
+        self.schedule(&mut state);
+        self.wait_turn(state, id);
+    }
+
+    fn finish(&self, id: usize) {
+        let mut state = self.lock();
+        state.live[id] = false;
+        self.schedule(&mut state);
+    }
+}
+
+/// Returns whether the current thread is part of a deterministic run.
+#[inline]
+pub(crate) fn is_managed() -> bool {
+    CURRENT.try_with(|c| c.borrow().is_some()).unwrap_or(false)
+}
+
+/// Passes control to another thread of the current deterministic run.
+pub(crate) fn yield_now() {
+    let current = CURRENT.with(|c| c.borrow().clone());
+    if let Some((shared, id)) = current {
+        shared.yield_now(id);
+    }
+}
+
+/// Acquires a lock on a managed thread.
+///
+/// Rather than blocking, the thread yields until the lock is available.
+pub(crate) fn acquire<F>(mut try_acquire: F)
+    where F: FnMut() -> bool
+{
+    loop {
+        yield_now();
+        if try_acquire() {
+            return;
+        }
+    }
+}
+
+/// Like `acquire`, but gives up once the deadline has passed.
+pub(crate) fn acquire_until<F>(deadline: Option<Instant>, mut try_acquire: F) -> bool
+    where F: FnMut() -> bool
+{
+    loop {
+        yield_now();
+        if try_acquire() {
+            return true;
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return false;
+        }
+    }
+}
+
+/// A deterministic run of a set of threads.
+pub struct Deterministic<'a> {
+    seed: u64,
+    max_steps: usize,
+    threads: Vec<Box<dyn FnOnce() + Send + 'a>>,
+}
+
+impl<'a> fmt::Debug for Deterministic<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Deterministic")
+            .field("seed", &self.seed)
+            .field("max_steps", &self.max_steps)
+            .field("threads", &self.threads.len())
+            .finish()
+    }
+}
+
+impl<'a> Deterministic<'a> {
+    /// Creates a new run which schedules threads using the specified seed.
+    pub fn new(seed: u64) -> Deterministic<'a> {
+        Deterministic {
+            seed,
+            max_steps: 1_000_000,
+            threads: vec![],
+        }
+    }
+
+    /// Sets the maximum number of lock points the run may pass through.
+    ///
+    /// Threads which are deadlocked keep yielding to each other forever, so
+    /// the run is aborted with a panic once this limit is reached. It
+    /// defaults to 1,000,000.
+    pub fn max_steps(&mut self, max_steps: usize) -> &mut Deterministic<'a> {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Adds a thread to the run.
+    pub fn spawn<F>(&mut self, f: F) -> &mut Deterministic<'a>
+        where F: FnOnce() + Send + 'a
+    {
+        self.threads.push(Box::new(f));
+        self
+    }
+
+    /// Runs the threads to completion.
+    ///
+    /// # Panics
+    ///
+    /// If any thread panics, the panic is resumed once all threads have
+    /// finished, after printing the seed to stderr.
+    pub fn run(self) {
+        let seed = self.seed;
+        let mut rng = Rng::new(seed);
+        let first = if self.threads.is_empty() {
+            0
+        } else {
+            (rng.next() % self.threads.len() as u64) as usize
+        };
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                rng,
+                current: first,
+                live: vec![true; self.threads.len()],
+                steps: 0,
+                max_steps: self.max_steps,
+                aborted: false,
+            }),
+            cond: Condvar::new(),
+        });
+
+        let results = thread::scope(|s| {
+            let threads = self.threads
+                .into_iter()
+                .enumerate()
+                .map(|(id, f)| {
+                    let shared = shared.clone();
+                    s.spawn(move || {
+                        CURRENT.with(|c| *c.borrow_mut() = Some((shared.clone(), id)));
+                        let r = panic::catch_unwind(AssertUnwindSafe(|| {
+                            shared.wait_turn(shared.lock(), id);
+                            f()
+                        }));
+                        CURRENT.with(|c| *c.borrow_mut() = None);
+                        shared.finish(id);
+                        r
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            threads.into_iter().map(|t| t.join().unwrap()).collect::<Vec<_>>()
+        });
+
+        for result in results {
+            if let Err(e) = result {
+                eprintln!("deterministic run failed with seed {}", seed);
+                panic::resume_unwind(e);
+            }
+        }
+    }
+}
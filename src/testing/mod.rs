@@ -0,0 +1,23 @@
+//! Utilities for testing code built on this crate's locks.
+//!
+//! This module is only available with the `testing` feature.
+
+pub mod deterministic;
+pub mod model;
+pub mod stress;
+
+// xorshift64*, which is plenty for picking operations and threads.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
@@ -0,0 +1,386 @@
+//! Linearizability checking of concurrent operations against a sequential
+//! model.
+//!
+//! A check runs a sequence of operations per thread against a shared value,
+//! recording when each one started and finished, and then searches for an
+//! ordering of all of them which is consistent with both the real-time order
+//! of non-overlapping operations and a sequential `Model` of the value. If
+//! there is none, the shared value is not linearizable.
+//!
+//! The per-thread sequences are plain vectors, so they can be generated by
+//! `random_ops` or by a property testing library, which is then able to
+//! shrink a failing case. The search is exponential in the worst case, so
+//! histories should be kept to tens of operations; many small runs cover
+//! more ground than a few large ones.
+//!
+//! With the `proptest` feature, `op_sequences` and `op_sequences_from`
+//! provide `proptest` strategies for the sequences.
+//!
+//! ```
+//! use antidote::Mutex;
+//! use antidote::testing::model::{self, Model};
+//! use std::collections::VecDeque;
+//!
+//! #[derive(Debug, Clone)]
+//! enum Op {
+//!     Push(u32),
+//!     Pop,
+//! }
+//!
+//! #[derive(Clone, PartialEq, Eq, Hash, Default)]
+//! struct Queue(VecDeque<u32>);
+//!
+//! impl Model for Queue {
+//!     type Op = Op;
+//!     type Ret = Option<u32>;
+//!
+//!     fn apply(&mut self, op: &Op) -> Option<u32> {
+//!         match *op {
+//!             Op::Push(v) => {
+//!                 self.0.push_back(v);
+//!                 None
+//!             }
+//!             Op::Pop => self.0.pop_front(),
+//!         }
+//!     }
+//! }
+//!
+//! for seed in 0..20 {
+//!     let ops = model::random_ops(seed, 3, 5, &[Op::Push(1), Op::Push(2), Op::Pop]);
+//!     let queue = Mutex::new(VecDeque::new());
+//!     model::check(&queue, Queue::default(), &ops, |q, op| match *op {
+//!         Op::Push(v) => {
+//!             q.lock().push_back(v);
+//!             None
+//!         }
+//!         Op::Pop => q.lock().pop_front(),
+//!     }).unwrap();
+//! }
+//! ```
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::panic;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Barrier;
+use std::thread;
+
+#[cfg(feature = "proptest")]
+use proptest::collection::{self, SizeRange};
+#[cfg(feature = "proptest")]
+use proptest::sample;
+#[cfg(feature = "proptest")]
+use proptest::strategy::Strategy;
+
+use testing::Rng;
+
+/// A sequential specification of a shared value.
+pub trait Model: Clone + Eq + Hash {
+    /// An operation on the value.
+    type Op: fmt::Debug;
+
+    /// The result of an operation.
+    type Ret: fmt::Debug + PartialEq;
+
+    /// Applies an operation to the model, returning the result the shared
+    /// value is expected to produce.
+    fn apply(&mut self, op: &Self::Op) -> Self::Ret;
+}
+
+/// One completed operation in a history.
+pub struct Event<'a, M: Model + 'a> {
+    /// The thread which performed the operation.
+    pub thread: usize,
+    /// The operation.
+    pub op: &'a M::Op,
+    /// The result produced by the shared value.
+    pub ret: M::Ret,
+    /// A logical timestamp taken before the operation started.
+    pub start: u64,
+    /// A logical timestamp taken after the operation finished.
+    pub end: u64,
+}
+
+impl<'a, M: Model> Clone for Event<'a, M>
+    where M::Ret: Clone
+{
+    fn clone(&self) -> Self {
+        Event {
+            thread: self.thread,
+            op: self.op,
+            ret: self.ret.clone(),
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+impl<'a, M: Model> fmt::Debug for Event<'a, M> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Event")
+            .field("thread", &self.thread)
+            .field("op", self.op)
+            .field("ret", &self.ret)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish()
+    }
+}
+
+/// The error returned when a history has no linearization.
+pub struct NonLinearizable<'a, M: Model + 'a> {
+    history: Vec<Event<'a, M>>,
+}
+
+impl<'a, M: Model> NonLinearizable<'a, M> {
+    /// Returns the history which could not be linearized, ordered by start
+    /// time.
+    pub fn history(&self) -> &[Event<'a, M>] {
+        &self.history
+    }
+}
+
+impl<'a, M: Model> fmt::Debug for NonLinearizable<'a, M> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("NonLinearizable").field("history", &self.history).finish()
+    }
+}
+
+impl<'a, M: Model> fmt::Display for NonLinearizable<'a, M> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("history is not linearizable:")?;
+        for event in &self.history {
+            write!(fmt,
+                   "\n  [{}, {}] thread {}: {:?} -> {:?}",
+                   event.start,
+                   event.end,
+                   event.thread,
+                   event.op,
+                   event.ret)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, M: Model> Error for NonLinearizable<'a, M> {}
+
+/// Runs each sequence of operations on its own thread against `state`, and
+/// checks that the results are linearizable with respect to `model`.
+///
+/// `apply` performs a single operation on the shared value.
+///
+/// # Panics
+///
+/// Resumes the panic of `apply` if it panicked on any thread.
+pub fn check<'a, S, M, F>(state: &S,
+                          model: M,
+                          threads: &'a [Vec<M::Op>],
+                          apply: F)
+                          -> Result<(), NonLinearizable<'a, M>>
+    where S: ?Sized + Sync,
+          M: Model,
+          M::Op: Sync,
+          M::Ret: Send,
+          F: Fn(&S, &M::Op) -> M::Ret + Sync
+{
+    let clock = AtomicU64::new(0);
+    let barrier = Barrier::new(threads.len());
+
+    let results = thread::scope(|s| {
+        let handles = threads.iter()
+            .enumerate()
+            .map(|(thread, ops)| {
+                let (clock, barrier, apply) = (&clock, &barrier, &apply);
+                s.spawn(move || {
+                    barrier.wait();
+                    ops.iter()
+                        .map(|op| {
+                            let start = clock.fetch_add(1, Ordering::SeqCst);
+                            let ret = apply(state, op);
+                            let end = clock.fetch_add(1, Ordering::SeqCst);
+                            Event {
+                                thread,
+                                op,
+                                ret,
+                                start,
+                                end,
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles.into_iter().map(|h| h.join()).collect::<Vec<_>>()
+    });
+
+    let mut history = vec![];
+    for result in results {
+        match result {
+            Ok(events) => history.extend(events),
+            Err(e) => panic::resume_unwind(e),
+        }
+    }
+    check_history(model, history)
+}
+
+/// Checks that an already recorded history is linearizable with respect to
+/// `model`.
+pub fn check_history<M: Model>(model: M, mut history: Vec<Event<M>>) -> Result<(), NonLinearizable<M>> {
+    history.sort_by_key(|e| e.start);
+
+    let mut search = Search {
+        history: &history,
+        done: vec![false; history.len()],
+        visited: HashSet::new(),
+    };
+    if search.linearize(model, history.len()) {
+        Ok(())
+    } else {
+        Err(NonLinearizable { history })
+    }
+}
+
+struct Search<'h, 'a: 'h, M: Model + 'a> {
+    history: &'h [Event<'a, M>],
+    done: Vec<bool>,
+    // Combinations of linearized operations and model states which are known
+    // to lead nowhere.
+    visited: HashSet<(Vec<bool>, M)>,
+}
+
+impl<'h, 'a, M: Model> Search<'h, 'a, M> {
+    fn linearize(&mut self, model: M, remaining: usize) -> bool {
+        if remaining == 0 {
+            return true;
+        }
+        if !self.visited.insert((self.done.clone(), model.clone())) {
+            return false;
+        }
+
+        // An operation can come next if it started before every remaining
+        // operation finished.
+        let first_end = self.pending().map(|e| e.1.end).min().unwrap();
+        let candidates = self.pending()
+            .filter(|e| e.1.start < first_end)
+            .map(|e| e.0)
+            .collect::<Vec<_>>();
+
+        for i in candidates {
+            let mut next = model.clone();
+            if next.apply(self.history[i].op) != self.history[i].ret {
+                continue;
+            }
+            self.done[i] = true;
+            if self.linearize(next, remaining - 1) {
+                return true;
+            }
+            self.done[i] = false;
+        }
+        false
+    }
+
+    fn pending<'s>(&'s self) -> impl Iterator<Item = (usize, &'h Event<'a, M>)> + 's {
+        let history = self.history;
+        self.done.iter().enumerate().filter(|e| !*e.1).map(move |e| (e.0, &history[e.0]))
+    }
+}
+
+/// Generates `threads` sequences of `len` operations each, picked uniformly
+/// at random from `ops`.
+///
+/// The same seed always produces the same sequences.
+///
+/// # Panics
+///
+/// Panics if `ops` is empty.
+pub fn random_ops<T: Clone>(seed: u64, threads: usize, len: usize, ops: &[T]) -> Vec<Vec<T>> {
+    assert!(!ops.is_empty(), "no operations to pick from");
+    let mut rng = Rng::new(seed);
+    (0..threads)
+        .map(|_| (0..len).map(|_| ops[(rng.next() % ops.len() as u64) as usize].clone()).collect())
+        .collect()
+}
+
+/// Returns a `proptest` strategy which generates per-thread sequences of
+/// operations for `check`.
+///
+/// The number of threads is drawn from `threads`, the length of each
+/// thread's sequence from `len`, and each operation from `op`. Failing cases
+/// shrink towards fewer threads, shorter sequences and simpler operations.
+///
+/// This function is only available with the `proptest` feature.
+///
+/// ```
+/// # extern crate antidote;
+/// # #[macro_use] extern crate proptest;
+/// use antidote::Mutex;
+/// use antidote::testing::model::{self, Model};
+/// use proptest::prelude::*;
+///
+/// #[derive(Debug, Clone)]
+/// enum Op {
+///     Add(u32),
+///     Get,
+/// }
+///
+/// #[derive(Clone, PartialEq, Eq, Hash, Default)]
+/// struct Counter(u32);
+///
+/// impl Model for Counter {
+///     type Op = Op;
+///     type Ret = u32;
+///
+///     fn apply(&mut self, op: &Op) -> u32 {
+///         if let Op::Add(v) = *op {
+///             self.0 += v;
+///         }
+///         self.0
+///     }
+/// }
+///
+/// # fn main() {
+/// let op = prop_oneof![(0..10u32).prop_map(Op::Add), Just(Op::Get)];
+/// proptest!(ProptestConfig::with_cases(20), |(ops in model::op_sequences(op, 2..4, 1..5))| {
+///     let counter = Mutex::new(0);
+///     let result = model::check(&counter, Counter::default(), &ops, |c, op| {
+///         let mut c = c.lock();
+///         if let Op::Add(v) = *op {
+///             *c += v;
+///         }
+///         *c
+///     });
+///     prop_assert!(result.is_ok(), "{}", result.unwrap_err());
+/// });
+/// # }
+/// ```
+#[cfg(feature = "proptest")]
+pub fn op_sequences<S>(op: S,
+                       threads: impl Into<SizeRange>,
+                       len: impl Into<SizeRange>)
+                       -> impl Strategy<Value = Vec<Vec<S::Value>>>
+    where S: Strategy
+{
+    collection::vec(collection::vec(op, len), threads)
+}
+
+/// Like `op_sequences`, but picks each operation from `ops`, like
+/// `random_ops`.
+///
+/// This function is only available with the `proptest` feature.
+///
+/// # Panics
+///
+/// Panics if `ops` is empty.
+#[cfg(feature = "proptest")]
+pub fn op_sequences_from<T>(ops: &[T],
+                            threads: impl Into<SizeRange>,
+                            len: impl Into<SizeRange>)
+                            -> impl Strategy<Value = Vec<Vec<T>>>
+    where T: Clone + fmt::Debug + 'static
+{
+    assert!(!ops.is_empty(), "no operations to pick from");
+    op_sequences(sample::select(ops.to_vec()), threads, len)
+}
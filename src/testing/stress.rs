@@ -0,0 +1,248 @@
+//! A configurable harness for stress testing concurrent data structures.
+//!
+//! A stress test hammers a shared value from many threads at once, each
+//! repeatedly picking one of a weighted set of operations at random, while
+//! periodically checking invariants which must hold whenever no operation
+//! is in progress on the current thread.
+//!
+//! ```
+//! use antidote::Mutex;
+//! use antidote::testing::stress::Stress;
+//!
+//! let balances = Mutex::new(vec![100, 100]);
+//!
+//! let report = Stress::new()
+//!     .threads(4)
+//!     .iterations(1000)
+//!     .op("transfer", 3, |b: &Mutex<Vec<i32>>| {
+//!         let mut b = b.lock();
+//!         b[0] -= 1;
+//!         b[1] += 1;
+//!     })
+//!     .op("reverse", 1, |b| b.lock().reverse())
+//!     .invariant(|b| assert_eq!(b.lock().iter().sum::<i32>(), 200))
+//!     .run(&balances);
+//!
+//! assert_eq!(report.total_ops(), 4000);
+//! ```
+
+use std::fmt;
+use std::panic;
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use testing::Rng;
+
+type Callback<'a, S> = Box<dyn Fn(&S) + Sync + 'a>;
+
+struct Op<'a, S: ?Sized> {
+    name: &'static str,
+    weight: u32,
+    f: Callback<'a, S>,
+}
+
+/// A stress test of a shared value of type `S`.
+pub struct Stress<'a, S: ?Sized> {
+    threads: usize,
+    iterations: usize,
+    check_interval: usize,
+    seed: u64,
+    ops: Vec<Op<'a, S>>,
+    invariants: Vec<Callback<'a, S>>,
+}
+
+impl<'a, S: ?Sized> fmt::Debug for Stress<'a, S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Stress")
+            .field("threads", &self.threads)
+            .field("iterations", &self.iterations)
+            .field("check_interval", &self.check_interval)
+            .field("seed", &self.seed)
+            .field("ops", &self.ops.iter().map(|o| (o.name, o.weight)).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<'a, S: ?Sized + Sync> Default for Stress<'a, S> {
+    fn default() -> Self {
+        Stress::new()
+    }
+}
+
+impl<'a, S: ?Sized + Sync> Stress<'a, S> {
+    /// Creates a new stress test with no operations.
+    ///
+    /// By default, it runs one thread per available CPU (but at least 4),
+    /// each performing 10,000 operations, and checks invariants every 100
+    /// operations.
+    pub fn new() -> Stress<'a, S> {
+        let cpus = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Stress {
+            threads: cpus.max(4),
+            iterations: 10_000,
+            check_interval: 100,
+            seed: 0x2545_F491_4F6C_DD1D,
+            ops: vec![],
+            invariants: vec![],
+        }
+    }
+
+    /// Sets the number of threads to run.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Sets the number of operations each thread performs.
+    pub fn iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Sets the number of operations each thread performs between invariant
+    /// checks.
+    pub fn check_interval(mut self, check_interval: usize) -> Self {
+        self.check_interval = check_interval.max(1);
+        self
+    }
+
+    /// Sets the seed of the random number generators used to select
+    /// operations.
+    ///
+    /// Scheduling is still up to the operating system, so a seed does not
+    /// make a run deterministic, but it does fix each thread's sequence of
+    /// operations.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Adds an operation.
+    ///
+    /// Each thread picks operations at random with probability proportional
+    /// to their weights.
+    pub fn op<F>(mut self, name: &'static str, weight: u32, f: F) -> Self
+        where F: Fn(&S) + Sync + 'a
+    {
+        self.ops.push(Op {
+            name,
+            weight,
+            f: Box::new(f),
+        });
+        self
+    }
+
+    /// Adds an invariant check.
+    ///
+    /// Invariants are checked periodically by every thread between
+    /// operations, and once more after all threads have finished. They
+    /// should panic if the invariant does not hold.
+    pub fn invariant<F>(mut self, f: F) -> Self
+        where F: Fn(&S) + Sync + 'a
+    {
+        self.invariants.push(Box::new(f));
+        self
+    }
+
+    /// Runs the stress test against the shared value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no operations have been added, or resumes the panic of an
+    /// operation or invariant check which failed on any thread.
+    pub fn run(&self, state: &S) -> Report {
+        let total_weight = self.ops.iter().map(|o| o.weight as u64).sum::<u64>();
+        assert!(total_weight > 0, "a stress test needs at least one operation with a nonzero weight");
+
+        let barrier = Barrier::new(self.threads);
+        let start = Instant::now();
+
+        let results = thread::scope(|s| {
+            let threads = (0..self.threads)
+                .map(|i| {
+                    let barrier = &barrier;
+                    s.spawn(move || {
+                        let mut rng = Rng::new(self.seed.wrapping_add(i as u64));
+                        let mut counts = vec![0; self.ops.len()];
+                        barrier.wait();
+
+                        for n in 0..self.iterations {
+                            let mut pick = rng.next() % total_weight;
+                            let idx = self.ops
+                                .iter()
+                                .position(|o| {
+                                    if pick < o.weight as u64 {
+                                        true
+                                    } else {
+                                        pick -= o.weight as u64;
+                                        false
+                                    }
+                                })
+                                .unwrap();
+                            (self.ops[idx].f)(state);
+                            counts[idx] += 1;
+
+                            if (n + 1) % self.check_interval == 0 {
+                                self.check(state);
+                            }
+                        }
+
+                        counts
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            threads.into_iter().map(|t| t.join()).collect::<Vec<_>>()
+        });
+
+        let mut counts = vec![0; self.ops.len()];
+        for result in results {
+            match result {
+                Ok(c) => {
+                    for (total, c) in counts.iter_mut().zip(c) {
+                        *total += c;
+                    }
+                }
+                Err(e) => panic::resume_unwind(e),
+            }
+        }
+
+        self.check(state);
+
+        Report {
+            ops: self.ops.iter().map(|o| o.name).zip(counts).collect(),
+            elapsed: start.elapsed(),
+        }
+    }
+
+    fn check(&self, state: &S) {
+        for invariant in &self.invariants {
+            invariant(state);
+        }
+    }
+}
+
+/// The results of a stress test run.
+#[derive(Debug, Clone)]
+pub struct Report {
+    ops: Vec<(&'static str, usize)>,
+    elapsed: Duration,
+}
+
+impl Report {
+    /// Returns the number of times each operation was performed.
+    pub fn ops(&self) -> &[(&'static str, usize)] {
+        &self.ops
+    }
+
+    /// Returns the total number of operations performed.
+    pub fn total_ops(&self) -> usize {
+        self.ops.iter().map(|o| o.1).sum()
+    }
+
+    /// Returns the time taken by the run.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
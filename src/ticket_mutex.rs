@@ -0,0 +1,152 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+use held::{self, Mode};
+use raw::RawTicketMutex;
+use {fmt_guard, GuardMarker, TryLockError, TryLockResult};
+
+/// A mutex which grants the lock to waiting threads in strict FIFO order.
+///
+/// Each thread takes a ticket when it starts waiting, and the lock is handed
+/// to tickets in order. This makes the order of lock grants predictable,
+/// where `Mutex` lets threads barge ahead of ones which have waited longer,
+/// but a preempted waiter also holds up every thread behind it, so
+/// throughput under contention is lower.
+pub struct TicketMutex<T: ?Sized> {
+    raw: RawTicketMutex,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: ?Sized + Send> Send for TicketMutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for TicketMutex<T> {}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for TicketMutex<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Ok(guard) => fmt.debug_struct("TicketMutex").field("data", &&*guard).finish(),
+            Err(_) => fmt.debug_struct("TicketMutex").field("data", &format_args!("<locked>")).finish(),
+        }
+    }
+}
+
+impl<T> TicketMutex<T> {
+    /// Creates a new FIFO mutex.
+    #[inline]
+    pub fn new(t: T) -> TicketMutex<T> {
+        TicketMutex {
+            raw: RawTicketMutex::new(),
+            data: UnsafeCell::new(t),
+        }
+    }
+
+    /// Like `Mutex::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> TicketMutex<T> {
+    /// Like `Mutex::lock`.
+    ///
+    /// Threads blocked here acquire the lock in the order they called this
+    /// method.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn lock<'a>(&'a self) -> TicketMutexGuard<'a, T> {
+        held::check("TicketMutex", None, self.addr(), Mode::Exclusive);
+        self.raw.lock();
+        held::acquired("TicketMutex", None, self.addr(), Mode::Exclusive);
+        TicketMutexGuard::new(self)
+    }
+
+    /// Like `Mutex::try_lock`.
+    ///
+    /// This only succeeds if the lock is free and no thread is waiting for
+    /// it, so it never jumps the queue.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<TicketMutexGuard<'a, T>> {
+        if self.raw.try_lock() {
+            held::acquired("TicketMutex", None, self.addr(), Mode::Exclusive);
+            Ok(TicketMutexGuard::new(self))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Like `Mutex::get_mut`.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.data.get() }
+    }
+
+    #[inline]
+    fn addr(&self) -> usize {
+        &self.raw as *const RawTicketMutex as usize
+    }
+}
+
+impl<T: Default> Default for TicketMutex<T> {
+    fn default() -> Self {
+        TicketMutex::new(Default::default())
+    }
+}
+
+/// An RAII guard for a `TicketMutex`.
+#[must_use]
+pub struct TicketMutexGuard<'a, T: ?Sized + 'a> {
+    mutex: &'a TicketMutex<T>,
+    _marker: PhantomData<GuardMarker>,
+}
+
+unsafe impl<'a, T: ?Sized + Sync + 'a> Sync for TicketMutexGuard<'a, T> {}
+
+impl<'a, T: ?Sized> TicketMutexGuard<'a, T> {
+    #[inline]
+    fn new(mutex: &'a TicketMutex<T>) -> TicketMutexGuard<'a, T> {
+        TicketMutexGuard {
+            mutex,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for TicketMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt, "TicketMutexGuard", self.mutex.addr(), &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for TicketMutexGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for TicketMutexGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for TicketMutexGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for TicketMutexGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        let owned = held::released(self.mutex.addr(), Mode::Exclusive);
+        unsafe { self.mutex.raw.unlock() };
+        held::check_released(owned);
+    }
+}
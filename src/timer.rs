@@ -0,0 +1,184 @@
+//! Timeouts for the async locks.
+//!
+//! To avoid depending on any particular runtime's timer, deadlines are
+//! tracked by a single background thread which is started the first time one
+//! is needed and wakes tasks through their `Waker`s.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use {Condvar, deadline_after, Mutex, TryLockError, TryLockResult};
+
+struct Timers {
+    next_id: u64,
+    entries: BTreeMap<(Instant, u64), Waker>,
+}
+
+struct Shared {
+    timers: Mutex<Timers>,
+    cond: Condvar,
+}
+
+fn shared() -> &'static Shared {
+    static SHARED: OnceLock<Shared> = OnceLock::new();
+    SHARED.get_or_init(|| {
+        thread::Builder::new()
+            .name("antidote-timer".to_string())
+            .spawn(run)
+            .expect("failed to spawn timer thread");
+        Shared {
+            timers: Mutex::new(Timers {
+                next_id: 0,
+                entries: BTreeMap::new(),
+            }),
+            cond: Condvar::new(),
+        }
+    })
+}
+
+fn run() {
+    let shared = shared();
+    let mut timers = shared.timers.lock();
+    loop {
+        let now = Instant::now();
+        let pending = timers.entries.split_off(&(now, u64::MAX));
+        let expired = mem::replace(&mut timers.entries, pending);
+        if !expired.is_empty() {
+            drop(timers);
+            for (_, waker) in expired {
+                waker.wake();
+            }
+            timers = shared.timers.lock();
+            continue;
+        }
+
+        timers = match timers.entries.keys().next() {
+            Some(&(deadline, _)) => shared.cond.wait_timeout(timers, deadline - now).0,
+            None => shared.cond.wait(timers),
+        };
+    }
+}
+
+// A registration with the timer thread which wakes a task at a deadline.
+struct Delay {
+    deadline: Option<Instant>,
+    key: Option<(Instant, u64)>,
+}
+
+impl Delay {
+    fn new(deadline: Option<Instant>) -> Delay {
+        Delay {
+            deadline,
+            key: None,
+        }
+    }
+
+    // Returns whether the deadline has passed, and otherwise arranges for the
+    // task to be woken when it does.
+    fn poll_expired(&mut self, cx: &mut Context) -> bool {
+        let deadline = match self.deadline {
+            Some(deadline) => deadline,
+            None => return false,
+        };
+        if Instant::now() >= deadline {
+            self.cancel();
+            return true;
+        }
+
+        let shared = shared();
+        let mut timers = shared.timers.lock();
+        if let Some(waker) = self.key.and_then(|key| timers.entries.get_mut(&key)) {
+            if !waker.will_wake(cx.waker()) {
+                *waker = cx.waker().clone();
+            }
+            return false;
+        }
+
+        let key = (deadline, timers.next_id);
+        timers.next_id += 1;
+        timers.entries.insert(key, cx.waker().clone());
+        self.key = Some(key);
+        if timers.entries.keys().next() == Some(&key) {
+            shared.cond.notify_one();
+        }
+        false
+    }
+
+    fn cancel(&mut self) {
+        if let Some(key) = self.key.take() {
+            shared().timers.lock().entries.remove(&key);
+        }
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// A future which resolves to a guard, or to an error if the lock could not
+/// be acquired before a timeout.
+///
+/// This is created by methods like `AsyncMutex::lock_timeout` and
+/// `AsyncMutex::lock_until`.
+#[must_use = "futures do nothing unless polled"]
+pub struct TimeoutFuture<F> {
+    // Dropped as soon as the timeout expires, releasing its place in the
+    // lock's queue.
+    future: Option<F>,
+    delay: Delay,
+}
+
+impl<F> TimeoutFuture<F> {
+    #[inline]
+    pub(crate) fn new(future: F, timeout: Duration) -> TimeoutFuture<F> {
+        TimeoutFuture::until_inner(future, deadline_after(timeout))
+    }
+
+    #[inline]
+    pub(crate) fn until(future: F, deadline: Instant) -> TimeoutFuture<F> {
+        TimeoutFuture::until_inner(future, Some(deadline))
+    }
+
+    fn until_inner(future: F, deadline: Option<Instant>) -> TimeoutFuture<F> {
+        TimeoutFuture {
+            future: Some(future),
+            delay: Delay::new(deadline),
+        }
+    }
+}
+
+impl<F> fmt::Debug for TimeoutFuture<F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("TimeoutFuture").field("deadline", &self.delay.deadline).finish()
+    }
+}
+
+impl<F: Future + Unpin> Future for TimeoutFuture<F> {
+    type Output = TryLockResult<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<TryLockResult<F::Output>> {
+        let this = &mut *self;
+        let future = this.future.as_mut().expect("TimeoutFuture polled after completion");
+        if let Poll::Ready(guard) = Pin::new(future).poll(cx) {
+            this.future = None;
+            this.delay.cancel();
+            return Poll::Ready(Ok(guard));
+        }
+
+        if this.delay.poll_expired(cx) {
+            this.future = None;
+            Poll::Ready(Err(TryLockError::Timeout))
+        } else {
+            Poll::Pending
+        }
+    }
+}
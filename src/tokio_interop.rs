@@ -0,0 +1,97 @@
+//! Conversions between the async locks and `tokio::sync`, for codebases
+//! migrating from one to the other.
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use tokio::sync;
+
+use {AsyncMutex, AsyncMutexGuard, AsyncRwLock, AsyncRwLockReadGuard, AsyncRwLockWriteGuard, TryLockError};
+
+impl<T: ?Sized> AsyncMutex<T> {
+    /// Like `tokio::sync::Mutex::blocking_lock`.
+    ///
+    /// This blocks the current thread, so it must not be called from within
+    /// an async context.
+    #[inline]
+    pub fn blocking_lock<'a>(&'a self) -> AsyncMutexGuard<'a, T> {
+        block_on(self.lock())
+    }
+}
+
+impl<T: ?Sized> AsyncRwLock<T> {
+    /// Like `tokio::sync::RwLock::blocking_read`.
+    ///
+    /// This blocks the current thread, so it must not be called from within
+    /// an async context.
+    #[inline]
+    pub fn blocking_read<'a>(&'a self) -> AsyncRwLockReadGuard<'a, T> {
+        block_on(self.read())
+    }
+
+    /// Like `tokio::sync::RwLock::blocking_write`.
+    ///
+    /// This blocks the current thread, so it must not be called from within
+    /// an async context.
+    #[inline]
+    pub fn blocking_write<'a>(&'a self) -> AsyncRwLockWriteGuard<'a, T> {
+        block_on(self.write())
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(v) => return v,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+impl<T> From<sync::Mutex<T>> for AsyncMutex<T> {
+    #[inline]
+    fn from(mutex: sync::Mutex<T>) -> AsyncMutex<T> {
+        AsyncMutex::new(mutex.into_inner())
+    }
+}
+
+impl<T> From<AsyncMutex<T>> for sync::Mutex<T> {
+    #[inline]
+    fn from(mutex: AsyncMutex<T>) -> sync::Mutex<T> {
+        sync::Mutex::new(mutex.into_inner())
+    }
+}
+
+impl<T> From<sync::RwLock<T>> for AsyncRwLock<T> {
+    #[inline]
+    fn from(rwlock: sync::RwLock<T>) -> AsyncRwLock<T> {
+        AsyncRwLock::new(rwlock.into_inner())
+    }
+}
+
+impl<T> From<AsyncRwLock<T>> for sync::RwLock<T> {
+    #[inline]
+    fn from(rwlock: AsyncRwLock<T>) -> sync::RwLock<T> {
+        sync::RwLock::new(rwlock.into_inner())
+    }
+}
+
+impl From<sync::TryLockError> for TryLockError {
+    #[inline]
+    fn from(_: sync::TryLockError) -> TryLockError {
+        TryLockError::WouldBlock
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use std::sync::{self, PoisonError};
+
+use lock_traits::from_std;
+use TryLockResult;
+
+/// A view of a standard library lock with this crate's poison-free API.
+///
+/// This crate's locks are not built on the standard library's, so a
+/// `std::sync::Mutex` owned by other code cannot be turned into a `Mutex`.
+/// Instead, `Unpoisoned::from_std_ref` reinterprets a reference to it, and
+/// the methods ignore poisoning just like this crate's own locks:
+///
+/// ```
+/// use antidote::Unpoisoned;
+/// use std::sync;
+///
+/// let mutex = sync::Mutex::new(0);
+/// let _ = std::panic::catch_unwind(|| {
+///     let _guard = mutex.lock().unwrap();
+///     panic!();
+/// });
+/// assert!(mutex.is_poisoned());
+///
+/// *Unpoisoned::from_std_ref(&mutex).lock() += 1;
+/// ```
+#[repr(transparent)]
+pub struct Unpoisoned<L: ?Sized>(L);
+
+impl<L: ?Sized> Unpoisoned<L> {
+    /// Views a standard library lock through this crate's API.
+    #[inline]
+    pub fn from_std_ref(lock: &L) -> &Unpoisoned<L> {
+        // `Unpoisoned` is a transparent wrapper around `L`.
+        unsafe { &*(lock as *const L as *const Unpoisoned<L>) }
+    }
+
+    /// Returns the underlying standard library lock.
+    #[inline]
+    pub fn as_std(&self) -> &L {
+        &self.0
+    }
+}
+
+impl<T: ?Sized> Unpoisoned<sync::Mutex<T>> {
+    /// Like `Mutex::lock`.
+    #[inline]
+    pub fn lock(&self) -> sync::MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Like `Mutex::try_lock`.
+    #[inline]
+    pub fn try_lock(&self) -> TryLockResult<sync::MutexGuard<'_, T>> {
+        from_std(self.0.try_lock())
+    }
+}
+
+impl<T: ?Sized> Unpoisoned<sync::RwLock<T>> {
+    /// Like `RwLock::read`.
+    #[inline]
+    pub fn read(&self) -> sync::RwLockReadGuard<'_, T> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Like `RwLock::try_read`.
+    #[inline]
+    pub fn try_read(&self) -> TryLockResult<sync::RwLockReadGuard<'_, T>> {
+        from_std(self.0.try_read())
+    }
+
+    /// Like `RwLock::write`.
+    #[inline]
+    pub fn write(&self) -> sync::RwLockWriteGuard<'_, T> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Like `RwLock::try_write`.
+    #[inline]
+    pub fn try_write(&self) -> TryLockResult<sync::RwLockWriteGuard<'_, T>> {
+        from_std(self.0.try_write())
+    }
+}
+
+impl<L: ?Sized + fmt::Debug> fmt::Debug for Unpoisoned<L> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("Unpoisoned").field(&&self.0).finish()
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use {fmt_guard, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// An `RwLock` which counts its writes, so that readers can find out when the
+/// value has changed.
+///
+/// Every write lock increments the version when it is released, whether or
+/// not the value was modified. Readers remember the version they last saw
+/// and pass it to `read_if_changed` or `wait_for_change`.
+///
+/// ```
+/// use antidote::VersionedRwLock;
+///
+/// let config = VersionedRwLock::new("v1".to_string());
+/// let seen = config.version();
+/// assert!(config.read_if_changed(seen).is_none());
+///
+/// *config.write() = "v2".to_string();
+///
+/// let (seen, value) = config.read_if_changed(seen).unwrap();
+/// assert_eq!(*value, "v2");
+/// assert_eq!(seen, config.version());
+/// ```
+pub struct VersionedRwLock<T: ?Sized> {
+    version: Mutex<u64>,
+    changed: Condvar,
+    lock: RwLock<T>,
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for VersionedRwLock<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("VersionedRwLock")
+            .field("version", &self.version())
+            .field("lock", &&self.lock)
+            .finish()
+    }
+}
+
+impl<T: Default> Default for VersionedRwLock<T> {
+    fn default() -> VersionedRwLock<T> {
+        VersionedRwLock::new(T::default())
+    }
+}
+
+impl<T> VersionedRwLock<T> {
+    /// Creates a new `VersionedRwLock` at version 0.
+    #[inline]
+    pub fn new(t: T) -> VersionedRwLock<T> {
+        VersionedRwLock {
+            version: Mutex::new(0),
+            changed: Condvar::new(),
+            lock: RwLock::new(t),
+        }
+    }
+
+    /// Like `RwLock::into_inner`.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.lock.into_inner()
+    }
+}
+
+impl<T: ?Sized> VersionedRwLock<T> {
+    /// Returns the current version.
+    pub fn version(&self) -> u64 {
+        *self.version.lock()
+    }
+
+    /// Like `RwLock::read`.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read<'a>(&'a self) -> RwLockReadGuard<'a, T> {
+        self.lock.read()
+    }
+
+    /// Read-locks the value if its version differs from `since`, returning
+    /// the version along with the guard.
+    ///
+    /// The version returned is that of the value behind the guard.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn read_if_changed<'a>(&'a self, since: u64) -> Option<(u64, RwLockReadGuard<'a, T>)> {
+        let guard = self.lock.read();
+        let version = self.version();
+        if version == since {
+            None
+        } else {
+            Some((version, guard))
+        }
+    }
+
+    /// Write-locks the value, incrementing the version when the guard is
+    /// dropped.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn write<'a>(&'a self) -> VersionedRwLockWriteGuard<'a, T> {
+        VersionedRwLockWriteGuard {
+            lock: self,
+            guard: self.lock.write(),
+        }
+    }
+
+    /// Blocks the current thread until the version differs from `since`,
+    /// returning the new version.
+    pub fn wait_for_change(&self, since: u64) -> u64 {
+        let version = self.changed.wait_while(self.version.lock(), |version| *version == since);
+        *version
+    }
+
+    /// Like `wait_for_change`, but gives up after `timeout`, returning
+    /// `None`.
+    pub fn wait_for_change_timeout(&self, since: u64, timeout: Duration) -> Option<u64> {
+        let (version, result) =
+            self.changed.wait_timeout_while(self.version.lock(), timeout, |version| *version == since);
+        if result.timed_out() {
+            None
+        } else {
+            Some(*version)
+        }
+    }
+
+    /// Like `wait_for_change`, but gives up once `deadline` passes, returning
+    /// `None`.
+    pub fn wait_for_change_until(&self, since: u64, deadline: Instant) -> Option<u64> {
+        let (version, result) =
+            self.changed.wait_while_until(self.version.lock(), deadline, |version| *version == since);
+        if result.timed_out() {
+            None
+        } else {
+            Some(*version)
+        }
+    }
+
+    /// Like `RwLock::get_mut`, incrementing the version.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        *self.version.get_mut() += 1;
+        self.lock.get_mut()
+    }
+}
+
+/// An RAII write guard for a `VersionedRwLock`.
+///
+/// The version is incremented when the guard is dropped, before the write
+/// lock is released.
+#[must_use]
+pub struct VersionedRwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a VersionedRwLock<T>,
+    guard: RwLockWriteGuard<'a, T>,
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for VersionedRwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt_guard(fmt,
+                  "VersionedRwLockWriteGuard",
+                  self.lock as *const VersionedRwLock<T> as *const () as usize,
+                  &**self)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Display> fmt::Display for VersionedRwLockWriteGuard<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&**self, fmt)
+    }
+}
+
+impl<'a, T: ?Sized> Deref for VersionedRwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for VersionedRwLockWriteGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<'a, T: ?Sized> Drop for VersionedRwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        // Readers check the version while holding the read lock, so it must
+        // change before the write lock is released.
+        let mut version = self.lock.version.lock();
+        *version = version.wrapping_add(1);
+        self.lock.changed.notify_all();
+    }
+}
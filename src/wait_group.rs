@@ -0,0 +1,72 @@
+use std::fmt;
+
+use {Condvar, Mutex};
+
+/// Waits for a collection of tasks to finish, like Go's `sync.WaitGroup`.
+///
+/// The coordinator calls `add` for each task it starts, each task calls
+/// `done` when it finishes, and `wait` blocks until every task has done so.
+/// Unlike joining threads, this does not require the tasks' join handles, so
+/// it works for work submitted to an existing thread pool.
+///
+/// A task which panics without calling `done` leaves the group waiting
+/// forever; it does not poison it.
+pub struct WaitGroup {
+    count: Mutex<usize>,
+    cond: Condvar,
+}
+
+impl fmt::Debug for WaitGroup {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("WaitGroup").field("count", &*self.count.lock()).finish()
+    }
+}
+
+impl WaitGroup {
+    /// Creates a new `WaitGroup` with no pending tasks.
+    #[inline]
+    pub fn new() -> WaitGroup {
+        WaitGroup {
+            count: Mutex::new(0),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Adds `n` pending tasks to the group.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of pending tasks overflows.
+    pub fn add(&self, n: usize) {
+        let mut count = self.count.lock();
+        *count = count.checked_add(n).expect("WaitGroup counter overflowed");
+    }
+
+    /// Marks one pending task as finished, waking the waiting threads if it
+    /// was the last.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no pending tasks.
+    pub fn done(&self) {
+        let mut count = self.count.lock();
+        *count = count.checked_sub(1).expect("WaitGroup::done called with no pending tasks");
+        if *count == 0 {
+            self.cond.notify_all();
+        }
+    }
+
+    /// Blocks the current thread until there are no pending tasks.
+    pub fn wait(&self) {
+        let mut count = self.count.lock();
+        while *count != 0 {
+            count = self.cond.wait(count);
+        }
+    }
+}
+
+impl Default for WaitGroup {
+    fn default() -> WaitGroup {
+        WaitGroup::new()
+    }
+}
@@ -0,0 +1,179 @@
+//! A queue of tasks waiting on an async primitive.
+//!
+//! The async primitives block tasks by storing their `Waker`s here rather
+//! than through any particular runtime, so they work with every executor.
+//! The queue itself is protected by a regular `Mutex`, which is only ever
+//! held briefly.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::Waker;
+
+use {Mutex, MutexGuard};
+
+pub struct WaitQueue {
+    inner: Mutex<Inner>,
+    // The number of queued waiters, readable without locking the queue.
+    len: AtomicUsize,
+}
+
+struct Inner {
+    next_id: u64,
+    waiters: VecDeque<Waiter>,
+}
+
+struct Waiter {
+    id: u64,
+    waker: Waker,
+    exclusive: bool,
+}
+
+impl WaitQueue {
+    pub fn new() -> WaitQueue {
+        WaitQueue {
+            inner: Mutex::new(Inner {
+                next_id: 0,
+                waiters: VecDeque::new(),
+            }),
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn lock<'a>(&'a self) -> Queue<'a> {
+        Queue {
+            inner: self.inner.lock(),
+            len: &self.len,
+        }
+    }
+
+    /// Returns whether any waiters are queued.
+    ///
+    /// A primitive checks this after releasing its state, and a waiter
+    /// retries the acquisition after queuing itself, so with sequentially
+    /// consistent accesses on both sides no wakeup can be missed.
+    #[inline]
+    pub fn has_waiters(&self) -> bool {
+        self.len.load(Ordering::SeqCst) != 0
+    }
+}
+
+pub struct Queue<'a> {
+    inner: MutexGuard<'a, Inner>,
+    len: &'a AtomicUsize,
+}
+
+impl<'a> Queue<'a> {
+    /// Queues the waiter identified by `key`, or updates its waker if it is
+    /// already queued.
+    ///
+    /// A new waiter joins the back of the queue. A waiter which was woken
+    /// but then failed to acquire the primitive rejoins at the front, so that
+    /// it doesn't lose its place.
+    pub fn register(&mut self, key: &mut Option<u64>, waker: &Waker, exclusive: bool) {
+        if let Some(id) = *key {
+            if let Some(waiter) = self.inner.waiters.iter_mut().find(|w| w.id == id) {
+                if !waiter.waker.will_wake(waker) {
+                    waiter.waker = waker.clone();
+                }
+                return;
+            }
+        }
+
+        let rejoining = key.is_some();
+        let id = match *key {
+            Some(id) => id,
+            None => {
+                let id = self.inner.next_id;
+                self.inner.next_id += 1;
+                *key = Some(id);
+                id
+            }
+        };
+        let waiter = Waiter {
+            id,
+            waker: waker.clone(),
+            exclusive,
+        };
+        if rejoining {
+            self.inner.waiters.push_front(waiter);
+        } else {
+            self.inner.waiters.push_back(waiter);
+        }
+        self.len.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Returns whether the waiter identified by `key` is queued.
+    pub fn contains(&self, key: u64) -> bool {
+        self.inner.waiters.iter().any(|w| w.id == key)
+    }
+
+    /// Returns whether the waiter identified by `key` is at the front of
+    /// the queue.
+    pub fn is_front(&self, key: u64) -> bool {
+        self.inner.waiters.front().is_some_and(|w| w.id == key)
+    }
+
+    /// Removes the waiter identified by `key` from the queue, returning
+    /// `false` if it was not queued because it has already been woken.
+    pub fn remove(&mut self, key: u64) -> bool {
+        match self.inner.waiters.iter().position(|w| w.id == key) {
+            Some(idx) => {
+                self.inner.waiters.remove(idx);
+                self.len.fetch_sub(1, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wakes the waiter at the front of the queue. If it is waiting for
+    /// shared access, the shared waiters directly behind it are woken too.
+    pub fn wake_front(&mut self) {
+        while let Some(waiter) = self.inner.waiters.pop_front() {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+            let exclusive = waiter.exclusive;
+            waiter.waker.wake();
+            if exclusive || self.inner.waiters.front().is_none_or(|w| w.exclusive) {
+                break;
+            }
+        }
+    }
+
+    /// Wakes the first waiter for exclusive access, returning `false` if
+    /// there is none.
+    pub fn wake_exclusive(&mut self) -> bool {
+        match self.inner.waiters.iter().position(|w| w.exclusive) {
+            Some(idx) => {
+                let waiter = self.inner.waiters.remove(idx).unwrap();
+                self.len.fetch_sub(1, Ordering::SeqCst);
+                waiter.waker.wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wakes every waiter for shared access, returning `false` if there are
+    /// none.
+    pub fn wake_shared(&mut self) -> bool {
+        let before = self.inner.waiters.len();
+        self.inner.waiters.retain(|w| {
+            if w.exclusive {
+                return true;
+            }
+            w.waker.wake_by_ref();
+            false
+        });
+        let woken = before - self.inner.waiters.len();
+        self.len.fetch_sub(woken, Ordering::SeqCst);
+        woken != 0
+    }
+
+    /// Wakes every queued waiter.
+    pub fn wake_all(&mut self) {
+        self.len.fetch_sub(self.inner.waiters.len(), Ordering::SeqCst);
+        for waiter in self.inner.waiters.drain(..) {
+            waiter.waker.wake();
+        }
+    }
+}
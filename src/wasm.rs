@@ -0,0 +1,52 @@
+//! Support for WebAssembly with threads.
+//!
+//! With the `atomics` target feature, blocked threads wait with
+//! `memory.atomic.wait32`. Browsers forbid that instruction on the main
+//! thread, where it traps, so a thread which must not block has to be marked
+//! as such with `set_blocking_allowed`. Such a thread busy-waits for
+//! contended locks instead of blocking:
+//!
+//! ```no_run
+//! // At the start of the code running on the browser's main thread:
+//! antidote::wasm::set_blocking_allowed(false);
+//! ```
+//!
+//! Busy-waiting ties up the main thread, so locks it acquires should only be
+//! held briefly by the other threads.
+
+use std::cell::Cell;
+use std::hint;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Instant;
+
+thread_local! {
+    static BLOCKING_ALLOWED: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Sets whether the current thread may block.
+///
+/// Threads may block by default.
+pub fn set_blocking_allowed(allowed: bool) {
+    BLOCKING_ALLOWED.with(|b| b.set(allowed));
+}
+
+/// Returns whether the current thread may block.
+pub fn blocking_allowed() -> bool {
+    BLOCKING_ALLOWED.try_with(|b| b.get()).unwrap_or(true)
+}
+
+// Stands in for a futex wait on threads which may not block. Like a futex
+// wait this may return spuriously, which sends the caller around its loop to
+// check the lock again.
+pub(crate) fn spin_wait(futex: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+    if deadline.is_some_and(|d| Instant::now() >= d) {
+        return false;
+    }
+    for _ in 0..100 {
+        if futex.load(Ordering::Relaxed) != expected {
+            break;
+        }
+        hint::spin_loop();
+    }
+    true
+}
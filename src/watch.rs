@@ -0,0 +1,354 @@
+//! A channel which broadcasts the latest value of some state.
+//!
+//! A `Sender` publishes values, and any number of `Receiver`s observe them.
+//! Only the most recent value is retained, so a slow receiver skips
+//! intermediate values rather than queueing them. Receivers wait for changes
+//! either by blocking the current thread with `changed`, or asynchronously
+//! with `changed_async`.
+//!
+//! ```
+//! use antidote::watch;
+//! use std::thread;
+//!
+//! let (tx, mut rx) = watch::channel("starting");
+//!
+//! let observer = thread::spawn(move || {
+//!     while rx.changed().is_ok() {
+//!         if *rx.borrow() == "ready" {
+//!             return true;
+//!         }
+//!     }
+//!     false
+//! });
+//!
+//! tx.send("ready");
+//! assert!(observer.join().unwrap());
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use wait_queue::WaitQueue;
+use {Condvar, deadline_after, Mutex, RwLock, RwLockReadGuard};
+
+/// Creates a channel with an initial value, returning its sender and a
+/// receiver.
+///
+/// The initial value counts as seen by the receiver.
+pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(init),
+        state: Mutex::new(State {
+            version: 0,
+            closed: false,
+        }),
+        changed: Condvar::new(),
+        queue: WaitQueue::new(),
+    });
+    let receiver = Receiver {
+        shared: shared.clone(),
+        seen: 0,
+    };
+    (Sender { shared }, receiver)
+}
+
+/// The error returned when waiting for a change to a channel whose sender
+/// has been dropped.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RecvError(());
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("watch channel sender was dropped")
+    }
+}
+
+impl Error for RecvError {}
+
+/// The error returned by the timed waits for a change.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RecvTimeoutError {
+    /// The value did not change before the timeout expired.
+    Timeout,
+    /// The sender was dropped.
+    Closed,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecvTimeoutError::Timeout => fmt.write_str("timed out waiting for a change to a watch channel"),
+            RecvTimeoutError::Closed => fmt.write_str("watch channel sender was dropped"),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
+
+struct Shared<T> {
+    value: RwLock<T>,
+    state: Mutex<State>,
+    // Receivers blocking the current thread wait on `changed`, and async
+    // receivers in `queue`.
+    changed: Condvar,
+    queue: WaitQueue,
+}
+
+struct State {
+    version: u64,
+    closed: bool,
+}
+
+impl<T> Shared<T> {
+    // Called with the value write-locked, if it changed, so that a receiver
+    // which reads the version while holding a read lock sees the version of
+    // the value it's reading.
+    fn notify(&self, close: bool) {
+        {
+            let mut state = self.state.lock();
+            if close {
+                state.closed = true;
+            } else {
+                state.version = state.version.wrapping_add(1);
+            }
+        }
+        self.changed.notify_all();
+        // Async receivers queue themselves as shared waiters, so this wakes
+        // all of them.
+        if self.queue.has_waiters() {
+            self.queue.lock().wake_front();
+        }
+    }
+
+    // Returns whether the value has changed since `seen`, updating `seen`, or
+    // an error if it hasn't and never will.
+    fn check(&self, seen: &mut u64) -> Option<Result<(), RecvError>> {
+        let state = self.state.lock();
+        if state.version != *seen {
+            *seen = state.version;
+            Some(Ok(()))
+        } else if state.closed {
+            Some(Err(RecvError(())))
+        } else {
+            None
+        }
+    }
+}
+
+/// The sending half of a watch channel.
+///
+/// Dropping the sender closes the channel, which wakes every receiver waiting
+/// for a change.
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Sender<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Sender").field("value", &self.shared.value).finish()
+    }
+}
+
+impl<T> Sender<T> {
+    /// Replaces the value, notifying the receivers.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn send(&self, value: T) {
+        self.send_modify(|v| *v = value);
+    }
+
+    /// Modifies the value in place, notifying the receivers.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn send_modify<F>(&self, f: F)
+        where F: FnOnce(&mut T)
+    {
+        let mut value = self.shared.value.write();
+        f(&mut value);
+        self.shared.notify(false);
+    }
+
+    /// Read-locks the current value.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn borrow<'a>(&'a self) -> RwLockReadGuard<'a, T> {
+        self.shared.value.read()
+    }
+
+    /// Creates a new receiver, which has seen the current value.
+    pub fn subscribe(&self) -> Receiver<T> {
+        Receiver {
+            shared: self.shared.clone(),
+            seen: self.shared.state.lock().version,
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.notify(true);
+    }
+}
+
+/// The receiving half of a watch channel.
+///
+/// Each receiver tracks which value it has seen. Cloning a receiver creates
+/// one which has seen the same value.
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    seen: u64,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Receiver<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Receiver").field("value", &self.shared.value).finish()
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Receiver<T> {
+        Receiver {
+            shared: self.shared.clone(),
+            seen: self.seen,
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Read-locks the current value, without marking it as seen.
+    #[inline]
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn borrow<'a>(&'a self) -> RwLockReadGuard<'a, T> {
+        self.shared.value.read()
+    }
+
+    /// Read-locks the current value, and marks it as seen.
+    #[cfg_attr(debug_assertions, track_caller)]
+    pub fn borrow_and_update<'a>(&'a mut self) -> RwLockReadGuard<'a, T> {
+        let value = self.shared.value.read();
+        self.seen = self.shared.state.lock().version;
+        value
+    }
+
+    /// Returns whether the value has changed since it was last seen.
+    ///
+    /// Returns an error if it hasn't and the sender has been dropped.
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        let mut seen = self.seen;
+        match self.shared.check(&mut seen) {
+            Some(Ok(())) => Ok(true),
+            Some(Err(e)) => Err(e),
+            None => Ok(false),
+        }
+    }
+
+    /// Blocks the current thread until the value has changed since it was
+    /// last seen, and marks the new value as seen.
+    ///
+    /// Returns an error if the sender is dropped without changing the value.
+    pub fn changed(&mut self) -> Result<(), RecvError> {
+        match self.changed_until_inner(None) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(RecvError(())),
+        }
+    }
+
+    /// Like `changed`, but gives up after `timeout`.
+    pub fn changed_timeout(&mut self, timeout: Duration) -> Result<(), RecvTimeoutError> {
+        self.changed_until_inner(deadline_after(timeout))
+    }
+
+    /// Like `changed`, but gives up once `deadline` passes.
+    pub fn changed_until(&mut self, deadline: Instant) -> Result<(), RecvTimeoutError> {
+        self.changed_until_inner(Some(deadline))
+    }
+
+    fn changed_until_inner(&mut self, deadline: Option<Instant>) -> Result<(), RecvTimeoutError> {
+        let seen = self.seen;
+        let mut state = self.shared.state.lock();
+        loop {
+            if state.version != seen {
+                self.seen = state.version;
+                return Ok(());
+            }
+            if state.closed {
+                return Err(RecvTimeoutError::Closed);
+            }
+            state = match deadline {
+                Some(deadline) => {
+                    let (state, result) = self.shared.changed.wait_until(state, deadline);
+                    if result.timed_out() && state.version == seen && !state.closed {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    state
+                }
+                None => self.shared.changed.wait(state),
+            };
+        }
+    }
+
+    /// Returns a future which resolves once the value has changed since it
+    /// was last seen, and marks the new value as seen.
+    ///
+    /// The future resolves to an error if the sender is dropped without
+    /// changing the value.
+    pub fn changed_async<'a>(&'a mut self) -> Changed<'a, T> {
+        Changed {
+            receiver: self,
+            key: None,
+        }
+    }
+}
+
+/// A future which resolves when a watch channel's value changes.
+///
+/// This is created by `Receiver::changed_async`.
+#[must_use = "futures do nothing unless polled"]
+pub struct Changed<'a, T: 'a> {
+    receiver: &'a mut Receiver<T>,
+    // Identifies this future in the wait queue once it has been queued.
+    key: Option<u64>,
+}
+
+impl<'a, T> fmt::Debug for Changed<'a, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("Changed").field("queued", &self.key.is_some()).finish()
+    }
+}
+
+impl<'a, T> Future for Changed<'a, T> {
+    type Output = Result<(), RecvError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), RecvError>> {
+        let this = &mut *self;
+        let shared = &*this.receiver.shared;
+        if this.key.is_none() {
+            if let Some(result) = shared.check(&mut this.receiver.seen) {
+                return Poll::Ready(result);
+            }
+        }
+
+        let mut queue = shared.queue.lock();
+        queue.register(&mut this.key, cx.waker(), false);
+        match shared.check(&mut this.receiver.seen) {
+            Some(result) => {
+                queue.remove(this.key.take().unwrap());
+                Poll::Ready(result)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<'a, T> Drop for Changed<'a, T> {
+    fn drop(&mut self) {
+        // Every queued receiver is woken together, so a wakeup this future
+        // didn't act on needn't be passed on.
+        if let Some(key) = self.key {
+            self.receiver.shared.queue.lock().remove(key);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+use std::mem::{self, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+
+/// Several guards acquired together, bundled into one value.
+///
+/// This is created by `Mutex::lock_both`, or by `ZipGuard::new` from a tuple
+/// of any guards. The guards are accessed as tuple fields through `Deref`,
+/// and are released in the reverse of their order in the tuple when the
+/// `ZipGuard` is dropped.
+///
+/// ```
+/// use antidote::{Mutex, ZipGuard};
+///
+/// let from = Mutex::new(100);
+/// let to = Mutex::new(0);
+///
+/// let mut accounts = Mutex::lock_both(&from, &to);
+/// *accounts.0 -= 10;
+/// *accounts.1 += 10;
+/// drop(accounts);
+///
+/// assert_eq!(*from.lock(), 90);
+/// ```
+#[must_use]
+pub struct ZipGuard<G: ZipGuards> {
+    guards: ManuallyDrop<G>,
+}
+
+impl<G: ZipGuards> ZipGuard<G> {
+    /// Bundles a tuple of guards.
+    #[inline]
+    pub fn new(guards: G) -> ZipGuard<G> {
+        ZipGuard { guards: ManuallyDrop::new(guards) }
+    }
+
+    /// Unbundles the guards, which are then released independently.
+    ///
+    /// This is an associated function rather than a method to avoid
+    /// conflicting with methods of the guards.
+    #[inline]
+    pub fn into_inner(this: Self) -> G {
+        let mut this = ManuallyDrop::new(this);
+        unsafe { ManuallyDrop::take(&mut this.guards) }
+    }
+}
+
+impl<G: ZipGuards + fmt::Debug> fmt::Debug for ZipGuard<G> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_tuple("ZipGuard").field(&*self.guards).finish()
+    }
+}
+
+impl<G: ZipGuards> Deref for ZipGuard<G> {
+    type Target = G;
+
+    #[inline]
+    fn deref(&self) -> &G {
+        &self.guards
+    }
+}
+
+impl<G: ZipGuards> DerefMut for ZipGuard<G> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut G {
+        &mut self.guards
+    }
+}
+
+impl<G: ZipGuards> Drop for ZipGuard<G> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { ManuallyDrop::take(&mut self.guards) }.release();
+    }
+}
+
+/// The tuples of guards a `ZipGuard` can hold.
+#[doc(hidden)]
+pub trait ZipGuards: Sized {
+    /// Drops the guards in reverse order.
+    fn release(self);
+}
+
+macro_rules! zip_guards {
+    ($(($($ty:ident $guard:ident),+) => ($($rev:ident),+);)*) => {
+        $(
+            impl<$($ty),+> ZipGuards for ($($ty,)+) {
+                #[inline]
+                fn release(self) {
+                    let ($($guard,)+) = self;
+                    $(mem::drop($rev);)+
+                }
+            }
+        )*
+    };
+}
+
+zip_guards! {
+    (A a, B b) => (b, a);
+    (A a, B b, C c) => (c, b, a);
+    (A a, B b, C c, D d) => (d, c, b, a);
+    (A a, B b, C c, D d, E e) => (e, d, c, b, a);
+    (A a, B b, C c, D d, E e, F f) => (f, e, d, c, b, a);
+}
@@ -0,0 +1,216 @@
+extern crate antidote;
+
+use antidote::{AsyncMutex, AsyncRwLock, AsyncRwLockPolicy, AsyncSemaphore, Mutex, TryLockError};
+use std::future::Future;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// A minimal executor, standing in for whatever runtime the async locks are used with.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(v) => return v,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn async_mutex_contended() {
+    let mutex = Arc::new(AsyncMutex::new(0));
+    let threads = (0..THREADS)
+        .map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                *block_on(mutex.lock()) += 1;
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*mutex.try_lock().unwrap(), THREADS * ITERS);
+}
+
+#[test]
+fn async_mutex_cancelled_waiter() {
+    let mutex = AsyncMutex::new(0);
+    let guard = mutex.try_lock().unwrap();
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    {
+        let mut future = pin!(mutex.lock());
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+    }
+    drop(guard);
+    *block_on(mutex.lock()) += 1;
+    assert_eq!(mutex.into_inner(), 1);
+}
+
+#[test]
+fn async_owned_guards() {
+    let mutex = Arc::new(AsyncMutex::new(0));
+    let rwlock = Arc::new(AsyncRwLock::new(0));
+    let guard = block_on(mutex.clone().lock_owned());
+    let write_guard = block_on(rwlock.clone().write_owned());
+    let thread = thread::spawn(move || {
+        let mut guard = guard;
+        let mut write_guard = write_guard;
+        *guard += 1;
+        *write_guard += 1;
+    });
+    let read_guard = block_on(rwlock.clone().read_owned());
+    assert_eq!(*read_guard, 1);
+    assert!(rwlock.clone().try_write_owned().is_err());
+    drop(read_guard);
+    thread.join().unwrap();
+    assert_eq!(*mutex.try_lock_owned().unwrap(), 1);
+    assert_eq!(*rwlock.try_read_owned().unwrap(), 1);
+}
+
+#[test]
+fn async_semaphore_close() {
+    let semaphore = Arc::new(AsyncSemaphore::new(2));
+    let in_use = Arc::new(AtomicUsize::new(0));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let semaphore = semaphore.clone();
+            let in_use = in_use.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                let permit = block_on(semaphore.clone().acquire_many_owned(i % 2 + 1)).unwrap();
+                assert!(in_use.fetch_add(permit.permits(), Ordering::SeqCst) + permit.permits() <= 2);
+                in_use.fetch_sub(permit.permits(), Ordering::SeqCst);
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(semaphore.available_permits(), 2);
+
+    let permit = block_on(semaphore.acquire_many(2)).unwrap();
+    let waiter = {
+        let semaphore = semaphore.clone();
+        thread::spawn(move || block_on(semaphore.acquire()).err())
+    };
+    thread::sleep(Duration::from_millis(10));
+    semaphore.close();
+    assert_eq!(waiter.join().unwrap(), Some(TryLockError::Closed));
+    assert_eq!(semaphore.try_acquire().err(), Some(TryLockError::Closed));
+    drop(permit);
+    assert_eq!(semaphore.available_permits(), 2);
+}
+
+#[test]
+fn mutex_lock_async_via() {
+    let mutex = Arc::new(Mutex::new(0));
+    let mut guard = mutex.lock();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let future = mutex.clone().lock_async_via(|task| tx.send(thread::spawn(task)).unwrap());
+    let thread = thread::spawn(move || {
+        let mut guard = block_on(future);
+        *guard += 1;
+    });
+    thread::sleep(Duration::from_millis(10));
+    *guard += 1;
+    drop(guard);
+    thread.join().unwrap();
+    rx.recv().unwrap().join().unwrap();
+    assert_eq!(*mutex.lock(), 2);
+}
+
+#[test]
+fn async_lock_timeout() {
+    let mutex = AsyncMutex::new(0);
+    let rwlock = AsyncRwLock::new(0);
+    let guard = mutex.try_lock().unwrap();
+    let write_guard = rwlock.try_write().unwrap();
+    assert_eq!(block_on(mutex.lock_timeout(Duration::from_millis(10))).err(), Some(TryLockError::Timeout));
+    assert_eq!(block_on(rwlock.read_timeout(Duration::from_millis(10))).err(), Some(TryLockError::Timeout));
+    drop(write_guard);
+    let _read_guard = block_on(rwlock.read_timeout(Duration::from_millis(10))).unwrap();
+    assert_eq!(block_on(rwlock.write_timeout(Duration::from_millis(10))).err(), Some(TryLockError::Timeout));
+    assert_eq!(block_on(rwlock.write_until(Instant::now())).err(), Some(TryLockError::Timeout));
+    drop(guard);
+    *block_on(mutex.lock_timeout(Duration::from_secs(60))).unwrap() += 1;
+    assert_eq!(mutex.into_inner(), 1);
+}
+
+#[test]
+fn async_rwlock_contended() {
+    let rwlock = Arc::new(AsyncRwLock::new(0));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let rwlock = rwlock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                if i % 2 == 0 {
+                    *block_on(rwlock.write()) += 1;
+                } else {
+                    let guard = block_on(rwlock.read());
+                    assert!(*guard <= THREADS / 2 * ITERS);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*rwlock.try_read().unwrap(), THREADS / 2 * ITERS);
+}
+
+#[test]
+fn async_rwlock_policies() {
+    for &policy in &[AsyncRwLockPolicy::Fair, AsyncRwLockPolicy::PreferWriters, AsyncRwLockPolicy::PreferReaders] {
+        let rwlock = Arc::new(AsyncRwLock::new_with_policy(0, policy));
+        let threads = (0..THREADS)
+            .map(|i| {
+                let rwlock = rwlock.clone();
+                thread::spawn(move || for _ in 0..ITERS {
+                    if i % 2 == 0 {
+                        *block_on(rwlock.write()) += 1;
+                    } else {
+                        drop(block_on(rwlock.read()));
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(*rwlock.try_read().unwrap(), THREADS / 2 * ITERS);
+
+        // With a reader holding the lock, queue a writer and then a reader.
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let guard = rwlock.try_read().unwrap();
+        let mut write = pin!(rwlock.write());
+        assert!(write.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(rwlock.try_read().is_ok(), policy == AsyncRwLockPolicy::PreferReaders);
+        let mut read = pin!(rwlock.read());
+        let read_first = read.as_mut().poll(&mut cx).is_ready();
+        assert_eq!(read_first, policy == AsyncRwLockPolicy::PreferReaders);
+        if !read_first {
+            drop(guard);
+            assert!(read.as_mut().poll(&mut cx).is_pending());
+            assert!(write.as_mut().poll(&mut cx).is_ready());
+        }
+    }
+}
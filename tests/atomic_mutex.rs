@@ -0,0 +1,32 @@
+extern crate antidote;
+
+use antidote::{AtomicMutex, Plain};
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn atomic_mutex_update() {
+    fn check<T: Plain + PartialEq + std::fmt::Debug + Send>(init: T, f: fn(T) -> T, expected: T) {
+        let cell = AtomicMutex::new(init);
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                s.spawn(|| {
+                    for _ in 0..ITERS {
+                        cell.update(f);
+                    }
+                });
+            }
+        });
+        assert_eq!(cell.swap(init), expected);
+        assert_eq!(cell.into_inner(), init);
+    }
+
+    assert!(AtomicMutex::<[u8; 3]>::is_lock_free());
+    assert!(!AtomicMutex::<[u64; 3]>::is_lock_free());
+    let n = (THREADS * ITERS) as u8;
+    check([0u8, 1, 2], |a| [a[0].wrapping_add(1), a[1], a[2]], [n, 1, 2]);
+    let n = (THREADS * ITERS) as u64;
+    check([0u64, 1, 2], |a| [a[0] + 1, a[1], a[2]], [n, 1, 2]);
+}
@@ -0,0 +1,38 @@
+extern crate antidote;
+
+use antidote::backoff::{self, ExponentialBackoff};
+use antidote::{Mutex, RwLock};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn backoff_strategies() {
+    static NO_SPIN: ExponentialBackoff = ExponentialBackoff::new().spin_rounds(0).yield_rounds(1);
+    static SLEEPY: ExponentialBackoff =
+        ExponentialBackoff::new().spin_rounds(1).yield_rounds(0).sleep_rounds(2, Duration::from_micros(1));
+
+    // Other tests run concurrently, so the replacement must be a sensible
+    // strategy in its own right.
+    backoff::set_default(&NO_SPIN);
+    let mutex = Arc::new(Mutex::new_with_backoff(0, &SLEEPY));
+    let rwlock = Arc::new(RwLock::new_with_backoff(0, &SLEEPY));
+    let threads = (0..THREADS)
+        .map(|_| {
+            let mutex = mutex.clone();
+            let rwlock = rwlock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                *mutex.lock() += 1;
+                *rwlock.write() += 1;
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*mutex.lock(), THREADS * ITERS);
+    assert_eq!(*rwlock.read(), THREADS * ITERS);
+}
@@ -0,0 +1,31 @@
+extern crate antidote;
+
+use antidote::{Barrier, BarrierError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn barrier_breaks_on_timeout() {
+    let barrier = Arc::new(Barrier::new(3));
+    let threads = (0..2)
+        .map(|_| {
+            let barrier = barrier.clone();
+            thread::spawn(move || barrier.wait().unwrap().is_leader())
+        })
+        .collect::<Vec<_>>();
+    let mut results = vec![barrier.wait().unwrap().is_leader()];
+    results.extend(threads.into_iter().map(|t| t.join().unwrap()));
+    results.sort();
+    assert_eq!(results, [false, false, true]);
+
+    let waiter = {
+        let barrier = barrier.clone();
+        thread::spawn(move || barrier.wait())
+    };
+    assert_eq!(barrier.wait_timeout(Duration::from_millis(10)), Err(BarrierError::Timeout));
+    assert_eq!(waiter.join().unwrap(), Err(BarrierError::Broken));
+    assert!(barrier.is_broken());
+    barrier.reset();
+    assert!(!barrier.is_broken());
+}
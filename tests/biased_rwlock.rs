@@ -0,0 +1,36 @@
+extern crate antidote;
+
+use antidote::BiasedRwLock;
+use std::sync::Arc;
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn biased_rwlock_contended() {
+    let lock = Arc::new(BiasedRwLock::new((0, 0)));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let lock = lock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                if i == 0 {
+                    let mut guard = lock.write();
+                    guard.0 += 1;
+                    guard.1 += 1;
+                } else {
+                    let guard = lock.read();
+                    assert_eq!(guard.0, guard.1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let guard = lock.read();
+    assert!(lock.try_write().is_err());
+    drop(guard);
+    assert_eq!(lock.write().0, ITERS);
+}
@@ -0,0 +1,29 @@
+extern crate antidote;
+
+use antidote::{BrandCell, BrandedMutex};
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn branded_mutex() {
+    BrandedMutex::with(|mutex| {
+        let nodes = (0..10).map(|_| BrandCell::new(vec![])).collect::<Vec<_>>();
+        thread::scope(|s| {
+            for i in 0..THREADS {
+                let (mutex, nodes) = (&mutex, &nodes);
+                s.spawn(move || {
+                    for _ in 0..ITERS / 10 {
+                        let mut token = mutex.lock();
+                        for node in nodes {
+                            node.borrow_mut(&mut token).push(i);
+                        }
+                    }
+                });
+            }
+        });
+        let token = mutex.lock();
+        assert!(nodes.iter().all(|n| n.borrow(&token).len() == THREADS * (ITERS / 10)));
+    });
+}
@@ -0,0 +1,76 @@
+extern crate antidote;
+
+use antidote::compat::{Condvar, Mutex, RwLock};
+use std::sync::{Arc, TryLockError};
+use std::thread;
+use std::time::Duration;
+
+// Starts with the standard library's condvar example, changing only the
+// imports.
+#[test]
+fn compat_condvar_wakes_waiter() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let pair2 = pair.clone();
+    thread::spawn(move || {
+        let (lock, cvar) = &*pair2;
+        let mut started = lock.lock().unwrap();
+        *started = true;
+        cvar.notify_one();
+    });
+
+    let (lock, cvar) = &*pair;
+    let mut started = lock.lock().unwrap();
+    while !*started {
+        started = cvar.wait(started).unwrap();
+    }
+
+    let (guard, result) = cvar.wait_timeout_while(started, Duration::from_millis(10), |s| *s).unwrap();
+    assert!(result.timed_out());
+    let (_guard, result) = cvar.wait_timeout(guard, Duration::from_millis(10)).unwrap();
+    assert!(result.timed_out());
+}
+
+#[test]
+fn compat_locks_are_never_poisoned() {
+    let mutex = Arc::new(Mutex::new(0));
+    let rwlock = Arc::new(RwLock::new(0));
+    let (mutex2, rwlock2) = (mutex.clone(), rwlock.clone());
+    let r = thread::spawn(move || {
+        let _m = mutex2.lock().unwrap();
+        let _r = rwlock2.write().unwrap();
+        panic!();
+    })
+    .join();
+    assert!(r.is_err());
+
+    assert!(!mutex.is_poisoned());
+    assert!(!rwlock.is_poisoned());
+    *mutex.lock().unwrap() += 1;
+    *rwlock.write().unwrap() += 1;
+    assert_eq!(*rwlock.read().unwrap(), 1);
+
+    let mutex = Arc::try_unwrap(mutex).unwrap();
+    assert_eq!(mutex.into_inner().unwrap(), 1);
+}
+
+#[test]
+fn compat_try_lock_would_block() {
+    let mutex = Mutex::new(());
+    let guard = mutex.lock().unwrap();
+    match mutex.try_lock() {
+        Err(TryLockError::WouldBlock) => {}
+        _ => panic!("expected WouldBlock"),
+    }
+    drop(guard);
+    assert!(mutex.try_lock().is_ok());
+
+    let rwlock = RwLock::new(());
+    let read = rwlock.read().unwrap();
+    assert!(rwlock.try_read().is_ok());
+    match rwlock.try_write() {
+        Err(TryLockError::WouldBlock) => {}
+        _ => panic!("expected WouldBlock"),
+    }
+    drop(read);
+    assert!(rwlock.try_write().is_ok());
+}
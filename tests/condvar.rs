@@ -0,0 +1,163 @@
+extern crate antidote;
+
+use antidote::{Condvar, Mutex, MutexBuilder};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn condvar_handoff() {
+    let pair = Arc::new((Mutex::new(None), Condvar::new()));
+    let pair2 = pair.clone();
+    let thread = thread::spawn(move || {
+        let mut guard = pair2.0.lock();
+        while guard.is_none() {
+            guard = pair2.1.wait(guard);
+        }
+        guard.take().unwrap()
+    });
+    *pair.0.lock() = Some(String::from("hello"));
+    pair.1.notify_all();
+    assert_eq!(thread.join().unwrap(), "hello");
+}
+
+#[test]
+fn condvar_timeout() {
+    let mutex = Mutex::new(());
+    let condvar = Condvar::new();
+    let (_guard, result) = condvar.wait_timeout(mutex.lock(), Duration::from_millis(1));
+    assert!(result.timed_out());
+    assert!(!condvar.notify_one());
+    assert_eq!(condvar.notify_all(), 0);
+}
+
+#[test]
+fn condvar_wait_while_until() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let (ref mutex, ref condvar) = *pair;
+    let deadline = Instant::now() + Duration::from_millis(1);
+    let (guard, result) = condvar.wait_while_until(mutex.lock(), deadline, |ready| !*ready);
+    assert!(result.timed_out());
+    drop(guard);
+
+    let notifier = {
+        let pair = pair.clone();
+        thread::spawn(move || {
+            *pair.0.lock() = true;
+            pair.1.notify_all();
+        })
+    };
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let (guard, result) = condvar.wait_while_until(mutex.lock(), deadline, |ready| !*ready);
+    assert!(!result.timed_out());
+    assert!(*guard);
+    drop(guard);
+    notifier.join().unwrap();
+    assert!(*condvar.wait_while(mutex.lock(), |ready| !*ready));
+}
+
+#[test]
+fn condvar_holder_info() {
+    // Whether the waiter has started waiting, and whether it has been told
+    // to stop.
+    let mutex = MutexBuilder::new().track_holder(true).build((false, false));
+    let condvar = Condvar::new();
+    thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = mutex.lock();
+            guard.0 = true;
+            while !guard.1 {
+                guard = condvar.wait(guard);
+            }
+            // The holder is recorded again once the wait reacquires the mutex.
+            let info = mutex.holder_info().unwrap();
+            assert_eq!(info.thread_id(), thread::current().id());
+            if cfg!(debug_assertions) {
+                assert_eq!(info.location().unwrap().file(), file!());
+            }
+        });
+
+        let mut guard = loop {
+            let guard = mutex.lock();
+            if guard.0 {
+                break guard;
+            }
+        };
+        guard.1 = true;
+        condvar.notify_one();
+    });
+    assert!(mutex.holder_info().is_none());
+}
+
+// Waiters are counted by the condition variable itself, so the notify
+// methods report exactly how many threads they woke on every platform.
+#[test]
+fn condvar_notify_counts() {
+    const WAITERS: usize = 3;
+    // The number of threads which have started waiting, and which have been
+    // woken.
+    let mutex = Mutex::new((0, 0));
+    let condvar = Condvar::new();
+    let wait_for_waiters = || loop {
+        // A waiter only releases the mutex once it is counted as waiting.
+        if mutex.lock().0 == WAITERS {
+            break;
+        }
+        thread::yield_now();
+    };
+
+    thread::scope(|s| {
+        for _ in 0..WAITERS {
+            s.spawn(|| {
+                let mut guard = mutex.lock();
+                guard.0 += 1;
+                guard = condvar.wait(guard);
+                guard.1 += 1;
+            });
+        }
+        wait_for_waiters();
+        for _ in 0..WAITERS {
+            assert!(condvar.notify_one());
+        }
+        assert!(!condvar.notify_one());
+        assert_eq!(condvar.notify_all(), 0);
+    });
+    assert_eq!(mutex.lock().1, WAITERS);
+
+    *mutex.lock() = (0, 0);
+    thread::scope(|s| {
+        for _ in 0..WAITERS {
+            s.spawn(|| {
+                let mut guard = mutex.lock();
+                guard.0 += 1;
+                guard = condvar.wait(guard);
+                guard.1 += 1;
+            });
+        }
+        wait_for_waiters();
+        assert!(condvar.notify_one());
+        assert_eq!(condvar.notify_all(), WAITERS - 1);
+        assert_eq!(condvar.notify_all(), 0);
+    });
+    assert_eq!(mutex.lock().1, WAITERS);
+}
+
+// A notification racing with a timeout is either reported to the notifier
+// or to the waiter as a timeout, never lost or counted twice.
+#[test]
+fn condvar_notify_races_timeout() {
+    let mutex = Mutex::new(());
+    let condvar = Condvar::new();
+    for _ in 0..200 {
+        let (notified, woken) = thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                let (_guard, result) = condvar.wait_timeout(mutex.lock(), Duration::from_micros(100));
+                !result.timed_out()
+            });
+            thread::sleep(Duration::from_micros(100));
+            (condvar.notify_one(), waiter.join().unwrap())
+        });
+        assert_eq!(woken, notified);
+        assert!(!condvar.notify_one());
+    }
+}
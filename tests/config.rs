@@ -0,0 +1,46 @@
+extern crate antidote;
+
+use antidote::config::{self, Defaults};
+use antidote::{Mutex, MutexBuilder, RwLock, RwLockBuilder, RwLockPolicy};
+use std::thread;
+use std::time::Duration;
+
+// The defaults are process-wide, so this is the only test in its binary.
+#[test]
+fn defaults_apply_to_new_locks() {
+    assert_eq!(config::defaults(), Defaults::new());
+    config::set_defaults(config::defaults().stats(true).fair(true).rwlock_policy(RwLockPolicy::PreferReaders));
+    assert!(format!("{:?}", MutexBuilder::new()).contains("fair: true"));
+    assert!(format!("{:?}", RwLockBuilder::new()).contains("fair: true"));
+
+    let rwlock = RwLock::new(0);
+    *rwlock.write() += 1;
+    assert_eq!(rwlock.stats().unwrap().acquisitions(), 1);
+
+    let mutex = Mutex::new(0);
+    thread::scope(|s| {
+        let guard = mutex.lock();
+        s.spawn(|| {
+            let mut guard = mutex.lock();
+            thread::sleep(Duration::from_millis(20));
+            *guard += 1;
+        });
+        while !mutex.is_contended() {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(20));
+        drop(guard);
+        // Like `MutexBuilder::fair`, platforms whose wakeups don't report
+        // whether a thread was woken take the lock back instead.
+        if cfg!(any(not(feature = "futex"), target_os = "linux", target_os = "android")) {
+            assert!(mutex.try_lock().is_err());
+        }
+    });
+    assert_eq!(*mutex.lock(), 1);
+    assert_eq!(mutex.stats().unwrap().acquisitions(), 3);
+
+    // Builders start from the defaults, but can override them.
+    assert!(format!("{:?}", MutexBuilder::new().fair(false)).contains("fair: false"));
+    config::set_defaults(Defaults::new());
+    assert!(Mutex::new(0).stats().is_none());
+}
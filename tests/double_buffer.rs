@@ -0,0 +1,35 @@
+extern crate antidote;
+
+use antidote::DoubleBuffer;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn double_buffer_publishes_whole_frames() {
+    let frames = DoubleBuffer::new(vec![0; 4], vec![0; 4]);
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        for _ in 0..THREADS - 1 {
+            s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    let frame = frames.read();
+                    assert!(frame.iter().all(|v| *v == frame[0]));
+                }
+            });
+        }
+        s.spawn(|| {
+            for i in 1..=ITERS {
+                for v in frames.write().iter_mut() {
+                    *v = i;
+                }
+                frames.swap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+    });
+    assert_eq!(frames.snapshot(), [ITERS; 4]);
+    assert_eq!(frames.into_inner().1, [ITERS - 1; 4]);
+}
@@ -0,0 +1,55 @@
+extern crate antidote;
+
+use antidote::Event;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 4;
+
+#[test]
+fn event_releases_waiters() {
+    let event = Arc::new(Event::new());
+    assert!(!event.is_set());
+    let waiters = (0..THREADS)
+        .map(|_| {
+            let event = event.clone();
+            thread::spawn(move || event.wait())
+        })
+        .collect::<Vec<_>>();
+    thread::sleep(Duration::from_millis(10));
+    event.set();
+    for waiter in waiters {
+        waiter.join().unwrap();
+    }
+
+    // The event stays set, so later and repeated waits return immediately.
+    assert!(event.is_set());
+    event.wait();
+    event.wait();
+    assert!(!event.wait_timeout(Duration::from_secs(0)).timed_out());
+
+    event.reset();
+    assert!(!event.is_set());
+    let waiter = {
+        let event = event.clone();
+        thread::spawn(move || event.wait_timeout(Duration::from_secs(60)))
+    };
+    thread::sleep(Duration::from_millis(10));
+    event.set();
+    assert!(!waiter.join().unwrap().timed_out());
+
+    assert!(Event::with_state(true).is_set());
+}
+
+#[test]
+fn event_wait_times_out() {
+    let event = Event::new();
+    let start = Instant::now();
+    assert!(event.wait_timeout(Duration::from_millis(10)).timed_out());
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert!(event.wait_until(Instant::now()).timed_out());
+    // A timeout too large to represent waits without one.
+    event.set();
+    assert!(!event.wait_timeout(Duration::MAX).timed_out());
+}
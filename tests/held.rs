@@ -0,0 +1,94 @@
+#![cfg(all(debug_assertions, not(feature = "send_guard")))]
+extern crate antidote;
+
+use antidote::{Mutex, RwLock};
+#[cfg(feature = "raw")]
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+struct AssertSend<T>(T);
+unsafe impl<T> Send for AssertSend<T> {}
+
+fn panics<F: FnOnce() -> R, R>(f: F) -> bool {
+    panic::catch_unwind(AssertUnwindSafe(f)).is_err()
+}
+
+#[test]
+fn timed_relock_panics() {
+    let clock = Instant::now;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mutex = Mutex::new(0);
+    let _guard = mutex.lock();
+    assert!(panics(|| mutex.try_lock_for(Duration::from_secs(10))));
+    assert!(panics(|| mutex.try_lock_until(deadline)));
+    assert!(panics(|| mutex.try_lock_until_clock(&clock, deadline)));
+
+    let rwlock = RwLock::new(0);
+    let _guard = rwlock.write();
+    assert!(panics(|| rwlock.try_read_for(Duration::from_secs(10))));
+    assert!(panics(|| rwlock.try_write_until(deadline)));
+    assert!(panics(|| rwlock.try_write_until_clock(&clock, deadline)));
+}
+
+#[test]
+fn arc_relock_panics() {
+    let mutex = Arc::new(Mutex::new(0));
+    let guard = mutex.lock();
+    assert!(panics(|| mutex.lock_arc()));
+    drop(guard);
+    drop(mutex.lock_arc());
+
+    let rwlock = Arc::new(RwLock::new(0));
+    let guard = rwlock.write();
+    assert!(panics(|| rwlock.read_arc()));
+    assert!(panics(|| rwlock.write_arc()));
+    drop(guard);
+    drop(rwlock.write_arc());
+}
+
+#[test]
+fn guard_dropped_on_another_thread_panics() {
+    let mutex = Mutex::new(0);
+    thread::scope(|s| {
+        let guard = AssertSend(mutex.lock());
+        let r = s.spawn(move || drop(guard)).join();
+        assert!(r.is_err());
+    });
+    // The lock is released before the panic, so it isn't left locked.
+    assert!(mutex.try_lock().is_ok());
+}
+
+#[test]
+fn guard_dropped_on_another_thread_is_forgotten() {
+    let mutex = Mutex::new(0);
+    let rwlock = RwLock::new(0);
+    thread::scope(|s| {
+        let guards = AssertSend((mutex.lock(), rwlock.read()));
+        assert!(s.spawn(move || drop(guards)).join().is_err());
+    });
+    // The acquiring thread no longer counts the locks as held, so locking
+    // them again isn't mistaken for a deadlock.
+    *mutex.lock() += 1;
+    *rwlock.write() += 1;
+}
+
+#[cfg(feature = "raw")]
+#[test]
+fn force_unlock_on_another_thread() {
+    let mutex = Mutex::new(0);
+    let rwlock = RwLock::new(0);
+    mem::forget(mutex.lock());
+    mem::forget(rwlock.write());
+    thread::scope(|s| {
+        s.spawn(|| unsafe {
+            mutex.force_unlock();
+            rwlock.force_unlock_write();
+        });
+    });
+    // The leaked guards' locks are no longer counted as held by this thread.
+    *mutex.lock() += 1;
+    *rwlock.write() += 1;
+}
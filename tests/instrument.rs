@@ -0,0 +1,229 @@
+extern crate antidote;
+
+use antidote::{ArcRwLockReadGuard, Barrier, Condvar, LockMode, MutexBuilder, MutexGuard, RwLockBuilder,
+               RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
+use std::mem;
+use std::panic;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn mutex_on_unlock() {
+    let events = Arc::new(std::sync::Mutex::new(vec![]));
+    let mutex = Arc::new(MutexBuilder::new()
+        .on_unlock({
+            let events = events.clone();
+            move |e| events.lock().unwrap().push(e.panicking())
+        })
+        .build((0, 0)));
+
+    let guard = MutexGuard::map(mutex.lock(), |m| &mut m.0);
+    drop(guard);
+    drop(mutex.lock_arc());
+    let r = {
+        let mutex = mutex.clone();
+        thread::spawn(move || {
+            let _guard = mutex.lock();
+            panic!();
+        })
+    };
+    assert!(r.join().is_err());
+    // The hook may lock the mutex itself.
+    assert_eq!(*mutex.lock(), (0, 0));
+    assert_eq!(*events.lock().unwrap(), [false, false, true, false]);
+
+    // Waiting on a condition variable releases the mutex.
+    let condvar = Condvar::new();
+    let (guard, _) = condvar.wait_timeout(mutex.lock(), Duration::from_millis(1));
+    drop(guard);
+    assert_eq!(events.lock().unwrap().len(), 6);
+}
+
+#[test]
+fn rwlock_on_unlock() {
+    let events = Arc::new(std::sync::Mutex::new(vec![]));
+    let rwlock = Arc::new(RwLockBuilder::new()
+        .on_unlock({
+            let events = events.clone();
+            move |e| events.lock().unwrap().push(e.mode())
+        })
+        .build((0, 0)));
+    let take = || mem::take(&mut *events.lock().unwrap());
+
+    drop(RwLockWriteGuard::map(rwlock.write(), |v| &mut v.0));
+    drop(rwlock.write_arc());
+    assert_eq!(take(), [LockMode::Exclusive, LockMode::Exclusive]);
+
+    drop(rwlock.read());
+    drop(RwLockReadGuard::map(rwlock.read(), |v| &v.0));
+    drop(rwlock.read_arc());
+    drop(ArcRwLockReadGuard::map(rwlock.read_arc(), |v| &v.1));
+    assert_eq!(take(), [LockMode::Shared; 4]);
+
+    drop(rwlock.upgradable_read());
+    assert_eq!(take(), [LockMode::Upgradable]);
+
+    // A downgrade releases the write lock, but an upgrade does not release
+    // the upgradable read lock.
+    let guard = RwLockWriteGuard::downgrade_to_upgradable(rwlock.write());
+    assert_eq!(take(), [LockMode::Exclusive]);
+    let guard = RwLockUpgradableReadGuard::upgrade(guard);
+    assert_eq!(take(), []);
+    drop(guard);
+    assert_eq!(take(), [LockMode::Exclusive]);
+
+    // Read locks overlap, so each is timed separately.
+    let held = Arc::new(std::sync::Mutex::new(vec![]));
+    let rwlock = RwLockBuilder::new()
+        .on_unlock({
+            let held = held.clone();
+            move |e| held.lock().unwrap().push(e.held())
+        })
+        .build(0);
+    let first = rwlock.read();
+    thread::sleep(Duration::from_millis(50));
+    let second = RwLockReadGuard::duplicate(&first);
+    drop(first);
+    drop(second);
+    let held = held.lock().unwrap();
+    assert!(held[0] >= Duration::from_millis(50));
+    assert!(held[1] < held[0]);
+}
+
+#[test]
+fn rwlock_on_unlock_panicking() {
+    let events = Arc::new(std::sync::Mutex::new(vec![]));
+    let rwlock = RwLockBuilder::new()
+        .on_unlock({
+            let events = events.clone();
+            move |e| events.lock().unwrap().push((e.mode(), e.panicking()))
+        })
+        .build(0);
+
+    for mode in [LockMode::Exclusive, LockMode::Shared, LockMode::Upgradable] {
+        let r = panic::catch_unwind(panic::AssertUnwindSafe(|| match mode {
+            LockMode::Exclusive => {
+                let _guard = rwlock.write();
+                panic!();
+            }
+            LockMode::Shared => {
+                let _guard = rwlock.read();
+                panic!();
+            }
+            _ => {
+                let _guard = rwlock.upgradable_read();
+                panic!();
+            }
+        }));
+        assert!(r.is_err());
+    }
+    assert_eq!(*events.lock().unwrap(),
+               [(LockMode::Exclusive, true), (LockMode::Shared, true), (LockMode::Upgradable, true)]);
+}
+
+#[test]
+fn mutex_holder_info() {
+    let mutex = MutexBuilder::new().track_holder(true).build(0);
+    assert!(mutex.holder_info().is_none());
+
+    let (locked, unlock) = (Barrier::new(2), Barrier::new(2));
+    thread::scope(|s| {
+        let holder = thread::Builder::new()
+            .name("holder".to_string())
+            .spawn_scoped(s, || {
+                let _guard = MutexGuard::map(mutex.lock(), |n| n);
+                locked.wait().unwrap();
+                unlock.wait().unwrap();
+            })
+            .unwrap();
+        locked.wait().unwrap();
+        let info = mutex.holder_info().unwrap();
+        assert_eq!(info.thread_id(), holder.thread().id());
+        assert_eq!(info.thread_name(), Some("holder"));
+        if cfg!(debug_assertions) {
+            assert_eq!(info.location().unwrap().file(), file!());
+        }
+        unlock.wait().unwrap();
+    });
+    assert!(mutex.holder_info().is_none());
+
+    if let Ok(guard) = mutex.try_lock() {
+        assert_eq!(mutex.holder_info().unwrap().thread_id(), thread::current().id());
+        drop(guard);
+    }
+    assert!(mutex.holder_info().is_none());
+}
+
+#[test]
+fn rwlock_upgradable_read_stats() {
+    let rwlock = RwLockBuilder::new().stats(true).build(0);
+    drop(rwlock.upgradable_read());
+    drop(rwlock.try_upgradable_read().unwrap());
+    let stats = rwlock.stats().unwrap();
+    assert_eq!(stats.acquisitions(), 2);
+    assert_eq!(stats.contended_acquisitions(), 0);
+
+    thread::scope(|s| {
+        let guard = rwlock.upgradable_read();
+        assert!(rwlock.try_upgradable_read().is_err());
+        s.spawn(|| drop(rwlock.upgradable_read()));
+        while !rwlock.has_waiters() {
+            thread::yield_now();
+        }
+        drop(guard);
+    });
+    let stats = rwlock.stats().unwrap();
+    assert_eq!(stats.acquisitions(), 4);
+    assert_eq!(stats.contended_acquisitions(), 1);
+}
+
+#[test]
+fn mutex_timed_lock_stats() {
+    let mutex = MutexBuilder::new().stats(true).track_holder(true).build(0);
+    let clock = Instant::now;
+    *mutex.try_lock_for(Duration::from_secs(1)).unwrap() += 1;
+    *mutex.try_lock_until(Instant::now() + Duration::from_secs(1)).unwrap() += 1;
+    {
+        let _guard = mutex.try_lock_until_clock(&clock, Instant::now() + Duration::from_secs(1)).unwrap();
+        assert_eq!(mutex.holder_info().unwrap().thread_id(), thread::current().id());
+    }
+    assert_eq!(mutex.stats().unwrap().acquisitions(), 3);
+
+    thread::scope(|s| {
+        let guard = mutex.lock();
+        s.spawn(|| *mutex.try_lock_for(Duration::from_secs(10)).unwrap() += 1);
+        while mutex.waiter_count() == Some(0) {
+            thread::yield_now();
+        }
+        drop(guard);
+    });
+    let stats = mutex.stats().unwrap();
+    assert_eq!(stats.acquisitions(), 5);
+    assert_eq!(stats.contended_acquisitions(), 1);
+    assert!(mutex.holder_info().is_none());
+}
+
+#[test]
+fn rwlock_upgrade_stats() {
+    let rwlock = RwLockBuilder::new().stats(true).build(0);
+    let guard = RwLockUpgradableReadGuard::upgrade(rwlock.upgradable_read());
+    drop(RwLockWriteGuard::downgrade_to_upgradable(guard));
+    let stats = rwlock.stats().unwrap();
+    assert_eq!(stats.acquisitions(), 2);
+    assert_eq!(stats.contended_acquisitions(), 0);
+
+    thread::scope(|s| {
+        let read = rwlock.read();
+        s.spawn(|| drop(RwLockUpgradableReadGuard::upgrade(rwlock.upgradable_read())));
+        // The upgrade has to wait for the other reader to leave.
+        while rwlock.stats().unwrap().acquisitions() < 4 {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(20));
+        drop(read);
+    });
+    let stats = rwlock.stats().unwrap();
+    assert_eq!(stats.acquisitions(), 5);
+    assert_eq!(stats.contended_acquisitions(), 1);
+}
@@ -0,0 +1,70 @@
+extern crate antidote;
+
+use antidote::CountDownLatch;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 4;
+
+#[test]
+fn latch_start_and_finish_gates() {
+    let start = Arc::new(CountDownLatch::new(1));
+    let finish = Arc::new(CountDownLatch::new(THREADS));
+    let started = Arc::new(AtomicUsize::new(0));
+    let threads = (0..THREADS)
+        .map(|_| {
+            let (start, finish, started) = (start.clone(), finish.clone(), started.clone());
+            thread::spawn(move || {
+                start.wait();
+                started.fetch_add(1, Ordering::SeqCst);
+                finish.count_down();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(started.load(Ordering::SeqCst), 0);
+    assert_eq!(finish.count(), THREADS);
+    start.count_down();
+    finish.wait();
+    assert_eq!(started.load(Ordering::SeqCst), THREADS);
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    // An open latch stays open, however many times it is waited on or
+    // counted down.
+    finish.wait();
+    finish.count_down();
+    assert_eq!(finish.count(), 0);
+    assert!(!finish.wait_timeout(Duration::from_secs(0)).timed_out());
+}
+
+#[test]
+fn latch_with_zero_count_is_open() {
+    let latch = CountDownLatch::new(0);
+    latch.wait();
+    latch.count_down();
+    assert_eq!(latch.count(), 0);
+    assert!(!latch.wait_until(Instant::now()).timed_out());
+}
+
+#[test]
+fn latch_wait_times_out() {
+    let latch = Arc::new(CountDownLatch::new(2));
+    latch.count_down();
+    let start = Instant::now();
+    assert!(latch.wait_timeout(Duration::from_millis(10)).timed_out());
+    assert!(start.elapsed() >= Duration::from_millis(10));
+    assert_eq!(latch.count(), 1);
+
+    let waiter = {
+        let latch = latch.clone();
+        thread::spawn(move || latch.wait_timeout(Duration::from_secs(60)))
+    };
+    thread::sleep(Duration::from_millis(10));
+    latch.count_down();
+    assert!(!waiter.join().unwrap().timed_out());
+}
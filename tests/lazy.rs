@@ -0,0 +1,61 @@
+extern crate antidote;
+
+use antidote::Lazy;
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Barrier;
+use std::thread;
+
+const THREADS: usize = 4;
+
+#[test]
+fn lazy_initializes_once() {
+    let calls = AtomicUsize::new(0);
+    let barrier = Barrier::new(THREADS);
+    let lazy = Lazy::new(|| {
+        calls.fetch_add(1, Ordering::SeqCst);
+        String::from("hello")
+    });
+    assert_eq!(Lazy::get(&lazy), None);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                barrier.wait();
+                assert_eq!(*lazy, "hello");
+            });
+        }
+    });
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    assert_eq!(Lazy::get(&lazy).map(|s| &**s), Some("hello"));
+    assert_eq!(Lazy::into_inner(lazy).ok(), Some(String::from("hello")));
+}
+
+#[test]
+fn lazy_retries_after_panic() {
+    let fail = AtomicBool::new(true);
+    let lazy = Lazy::new(|| {
+        if fail.swap(false, Ordering::SeqCst) {
+            panic!("initialization failed");
+        }
+        5
+    });
+    assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| *lazy)).is_err());
+    // The panic leaves the value uninitialized rather than poisoned, so the
+    // next access, from any thread, runs the function again.
+    assert_eq!(Lazy::get(&lazy), None);
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(*lazy, 5));
+    });
+    assert_eq!(*lazy, 5);
+}
+
+#[test]
+fn lazy_force_mut() {
+    let mut lazy = Lazy::new(|| vec![1]);
+    Lazy::force_mut(&mut lazy).push(2);
+    lazy.push(3);
+    assert_eq!(*lazy, [1, 2, 3]);
+
+    let lazy: Lazy<Vec<i32>> = Lazy::default();
+    assert!(Lazy::into_inner(lazy).is_err());
+}
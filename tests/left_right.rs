@@ -0,0 +1,33 @@
+extern crate antidote;
+
+use antidote::LeftRight;
+use std::sync::Arc;
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn left_right_contended() {
+    let lock = Arc::new(LeftRight::new((0, 0)));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let lock = lock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                if i % 2 == 0 {
+                    lock.write(|v| {
+                        v.0 += 1;
+                        v.1 += 1;
+                    });
+                } else {
+                    let guard = lock.read();
+                    assert_eq!(guard.0, guard.1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(Arc::try_unwrap(lock).unwrap().into_inner().0, THREADS / 2 * ITERS);
+}
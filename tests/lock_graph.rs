@@ -0,0 +1,38 @@
+#![cfg(all(debug_assertions, not(feature = "send_guard")))]
+extern crate antidote;
+
+use antidote::lock_graph;
+use antidote::{MutexBuilder, RwLock};
+use std::thread;
+
+const THREADS: usize = 4;
+
+#[test]
+fn lock_graph_records_nested_acquisitions() {
+    let outer = MutexBuilder::new().name("outer").build(());
+    let inner = RwLock::new(());
+
+    lock_graph::start();
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                let _outer = outer.lock();
+                let _inner = inner.write();
+            });
+        }
+    });
+    let graph = lock_graph::stop();
+    assert!(!graph.is_empty());
+
+    let mut json = vec![];
+    graph.write_json(&mut json).unwrap();
+    let json = String::from_utf8(json).unwrap();
+    assert!(json.contains(r#""kind":"Mutex","name":"outer""#));
+    assert!(json.contains(&format!(r#""count":{},"held_at""#, THREADS)));
+
+    let mut dot = vec![];
+    graph.write_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph locks {"));
+    assert!(dot.contains(r#"label="Mutex \"outer\""#));
+}
@@ -0,0 +1,28 @@
+#![cfg(all(debug_assertions, not(feature = "send_guard")))]
+extern crate antidote;
+
+use antidote::{LockTracker, Mutex, RwLock};
+use std::thread;
+
+#[test]
+fn lock_tracker_reports_held_guards() {
+    let mutex = Mutex::new(0);
+    let rwlock = RwLock::new(0);
+    thread::scope(|s| {
+        let r = s.spawn(|| {
+                let _guard;
+                let _tracker = LockTracker::new();
+                *rwlock.write() += 1;
+                _guard = mutex.lock();
+            })
+            .join();
+        assert!(r.is_err());
+
+        let guard = rwlock.read();
+        rwlock.assert_no_writers();
+        assert!(s.spawn(|| rwlock.assert_unlocked()).join().is_err());
+        drop(guard);
+    });
+    mutex.assert_unlocked();
+    rwlock.assert_unlocked();
+}
@@ -0,0 +1,91 @@
+extern crate antidote;
+
+use antidote::{MutexHashMap, RwLockHashMap};
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+// Keys which all hash alike, so that only equality tells them apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Colliding(u32);
+
+impl Hash for Colliding {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        0.hash(state);
+    }
+}
+
+#[test]
+fn mutex_hash_map_entry_is_atomic() {
+    let map = MutexHashMap::new();
+    thread::scope(|s| {
+        for i in 0..THREADS {
+            let map = &map;
+            s.spawn(move || for _ in 0..ITERS {
+                *map.entry(Colliding((i % 2) as u32)).or_default() += 1;
+            });
+        }
+    });
+    assert_eq!(map.len(), 2);
+    assert_eq!(map.get_cloned(&Colliding(0)), Some(THREADS / 2 * ITERS));
+    assert_eq!(map.get_cloned(&Colliding(1)), Some(THREADS / 2 * ITERS));
+
+    assert_eq!(*map.entry(Colliding(0)).and_modify(|v| *v = 0).or_insert(1), 0);
+    assert_eq!(*map.entry(Colliding(2)).and_modify(|v| *v = 0).or_insert(1), 1);
+    assert_eq!(map.get_cloned(&Colliding(0)), Some(0));
+    assert_eq!(map.entry(Colliding(1)).remove(), Some(THREADS / 2 * ITERS));
+    assert_eq!(map.entry(Colliding(1)).remove(), None);
+    assert!(!map.contains_key(&Colliding(1)));
+    assert_eq!(map.insert(Colliding(0), 5), Some(0));
+    assert_eq!(map.remove(&Colliding(0)), Some(5));
+
+    let map = map.into_inner();
+    assert_eq!(map, HashMap::from([(Colliding(2), 1)]));
+}
+
+#[test]
+fn mutex_hash_map_entry_holds_lock() {
+    let map = &MutexHashMap::from(HashMap::from([(1, 1)]));
+    let value = map.entry(1).or_insert(0);
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        s.spawn(move || {
+            map.insert(2, 2);
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(10)).is_err());
+        assert_eq!(*value, 1);
+        drop(value);
+        rx.recv().unwrap();
+    });
+    assert_eq!(map.len(), 2);
+}
+
+#[test]
+fn rwlock_hash_map_reads_in_parallel() {
+    let map = &RwLockHashMap::new();
+    map.insert("a", 1);
+    let read = map.read();
+    thread::scope(|s| {
+        // Lookups only need a read lock.
+        s.spawn(|| assert_eq!(map.get_cloned("a"), Some(1)));
+
+        let (tx, rx) = mpsc::channel();
+        s.spawn(move || {
+            *map.entry("a").or_insert(0) += 1;
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(10)).is_err());
+        assert_eq!(read["a"], 1);
+        drop(read);
+        rx.recv().unwrap();
+    });
+    assert_eq!(map.get_cloned("a"), Some(2));
+    assert_eq!(map.write().remove("a"), Some(2));
+    assert!(map.is_empty());
+}
@@ -0,0 +1,34 @@
+extern crate antidote;
+
+use antidote::{MaybeMutex, SingleThreaded, TryLockError};
+use std::sync::Arc;
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn maybe_mutex() {
+    let mutex = MaybeMutex::<_, SingleThreaded>::new(vec![1]);
+    let mut guard = mutex.lock();
+    guard.push(2);
+    assert_eq!(mutex.try_lock().err(), Some(TryLockError::WouldBlock));
+    drop(guard);
+    assert_eq!(*mutex.lock(), [1, 2]);
+
+    let mutex = Arc::new(MaybeMutex::<_>::new(0));
+    let threads = (0..THREADS)
+        .map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || {
+                for _ in 0..ITERS {
+                    *mutex.lock() += 1;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*mutex.lock(), THREADS * ITERS);
+}
@@ -0,0 +1,337 @@
+//! Tests exercising the crate's unsafe code, intended to be run under Miri:
+//!
+//! ```text
+//! cargo +nightly miri test --features raw,ffi --test miri
+//! ```
+//!
+//! Features which bind the platform's C library, such as `futex` and
+//! `named_mutex`, can't be interpreted by Miri and must be left out, as must
+//! `mutex-trait`. The tests also run as part of the normal test suite.
+#[macro_use]
+extern crate antidote;
+
+use antidote::{ArcMutexGuard, ArcRwLockReadGuard, ArcRwLockWriteGuard, MappedArcMutexGuard, MappedArcRwLockReadGuard,
+               MappedArcRwLockWriteGuard, MappedMutexGuard, Mutex, MutexGuard, PinMutex, RwLock, RwLockReadGuard,
+               RwLockWriteGuard, TryLockError, Unpoisoned};
+use std::marker::PhantomPinned;
+use std::panic;
+use std::sync::{self, Arc};
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = if cfg!(miri) { 20 } else { 1000 };
+
+#[test]
+fn mutex_unsized() {
+    let mutex: &Mutex<[i32]> = &Mutex::new([1, 2, 3]);
+    mutex.lock()[1] = 4;
+    assert_eq!(&*mutex.lock(), &[1, 4, 3]);
+}
+
+#[test]
+fn mapped_guards() {
+    let mutex = Mutex::new((1, vec![2, 3]));
+    {
+        let guard = MutexGuard::map(mutex.lock(), |v| &mut v.1);
+        let mut guard = MappedMutexGuard::map(guard, |v| &mut v[..]);
+        guard[0] = 4;
+        assert!(mutex.try_lock().is_err());
+    }
+    let guard = MutexGuard::try_map(mutex.lock(), |v| v.1.get_mut(2)).err().unwrap();
+    assert_eq!(*guard, (1, vec![4, 3]));
+    drop(guard);
+
+    let lock = RwLock::new((1, 2));
+    {
+        let a = RwLockReadGuard::map(lock.read(), |v| &v.0);
+        let b = RwLockReadGuard::map(lock.read(), |v| &v.1);
+        assert_eq!((*a, *b), (1, 2));
+        assert!(lock.try_write().is_err());
+    }
+    *RwLockWriteGuard::map(lock.write(), |v| &mut v.1) = 3;
+    assert_eq!(*lock.read(), (1, 3));
+}
+
+#[test]
+fn split_guards() {
+    let mutex = Mutex::new((1, (vec![2], String::from("a"))));
+    let (mut count, rest) = MutexGuard::split_map(mutex.lock(), |v| (&mut v.0, &mut v.1));
+    let (mut items, mut name) = MappedMutexGuard::split_map(rest, |v| (&mut v.0, &mut v.1));
+    *count += 1;
+    items.push(3);
+    drop(count);
+    name.push('b');
+    drop(items);
+    assert!(mutex.try_lock().is_err());
+    let name = MappedMutexGuard::map(name, |s| s.as_mut_str());
+    assert_eq!(&*name, "ab");
+    drop(name);
+    assert_eq!(*mutex.lock(), (2, (vec![2, 3], String::from("ab"))));
+
+    let lock = RwLock::new([1, 2]);
+    let (a, mut b) = RwLockWriteGuard::split_map(lock.write(), |v| {
+        let (a, b) = v.split_at_mut(1);
+        (&mut a[0], &mut b[0])
+    });
+    *b += *a;
+    drop(b);
+    assert!(lock.try_read().is_err());
+    drop(a);
+    assert_eq!(*lock.read(), [1, 3]);
+}
+
+#[test]
+fn guard_project() {
+    let mutex = Mutex::new((1, (2, 3)));
+    let guard = mutex.lock();
+    let guard = guard_project!(guard.1);
+    let mut guard = guard_project!(guard.0);
+    *guard += 1;
+    drop(guard);
+    assert_eq!(*mutex.lock(), (1, (3, 3)));
+
+    let lock = RwLock::new((1, 2));
+    let guard = lock.read();
+    assert_eq!(*guard_project!(guard.1), 2);
+    let guard = lock.write();
+    *guard_project!(guard.0) = 4;
+    assert_eq!(*lock.read(), (4, 2));
+}
+
+#[test]
+fn arc_guards() {
+    let mutex = Mutex::new_arc(0);
+    let mut guard = mutex.lock_arc();
+    assert_eq!(mutex.try_lock_arc().err(), Some(TryLockError::WouldBlock));
+    thread::spawn(move || *guard += 1).join().unwrap();
+    assert_eq!(*mutex.try_lock_arc().unwrap(), 1);
+
+    let rwlock = RwLock::new_arc(0);
+    let read = rwlock.read_arc();
+    assert!(rwlock.try_read_arc().is_ok());
+    assert_eq!(rwlock.try_write_arc().err(), Some(TryLockError::WouldBlock));
+    drop(read);
+    let mut write = rwlock.write_arc();
+    assert_eq!(rwlock.try_read_arc().err(), Some(TryLockError::WouldBlock));
+    thread::spawn(move || *write += 1).join().unwrap();
+    assert_eq!(*rwlock.read_arc(), 1);
+}
+
+#[test]
+fn mapped_arc_guards() {
+    let mutex = Mutex::new_arc((0, String::from("a")));
+    let mut name = ArcMutexGuard::map(mutex.lock_arc(), |v| &mut v.1);
+    assert_eq!(mutex.try_lock_arc().err(), Some(TryLockError::WouldBlock));
+    name.push('b');
+    let name = MappedArcMutexGuard::try_map(name, |s| s.as_mut_str().get_mut(1..)).unwrap();
+    thread::spawn(move || assert_eq!(&*name, "b")).join().unwrap();
+    assert_eq!(mutex.lock().1, "ab");
+
+    let rwlock = RwLock::new_arc((0, vec![1, 2]));
+    let items = ArcRwLockReadGuard::map(rwlock.read_arc(), |v| &v.1);
+    let second = MappedArcRwLockReadGuard::map(MappedArcRwLockReadGuard::duplicate(&items), |v| &v[1]);
+    drop(items);
+    assert!(rwlock.try_write_arc().is_err());
+    assert_eq!(*second, 2);
+    drop(second);
+    let mut count = ArcRwLockWriteGuard::map(rwlock.write_arc(), |v| &mut v.0);
+    *count += 1;
+    let count = MappedArcRwLockWriteGuard::try_map(count, |_| None::<&mut u8>).unwrap_err();
+    assert!(rwlock.try_read_arc().is_err());
+    drop(count);
+    assert_eq!(rwlock.read().0, 1);
+}
+
+#[cfg(feature = "raw")]
+#[test]
+fn raw_lock_force_unlock() {
+    let mutex = Mutex::new(1);
+    mutex.raw_lock();
+    assert!(mutex.try_lock().is_err());
+    unsafe {
+        *mutex.data_ptr() += 1;
+        mutex.force_unlock();
+    }
+    assert_eq!(*mutex.lock(), 2);
+
+    let guard = mutex.lock();
+    std::mem::forget(guard);
+    unsafe {
+        mutex.force_unlock();
+    }
+    assert_eq!(*mutex.lock(), 2);
+}
+
+#[cfg(feature = "raw")]
+#[test]
+fn raw_rwlock_force_unlock() {
+    let lock = RwLock::new(1);
+    lock.raw_read();
+    lock.raw_read();
+    assert!(lock.try_write().is_err());
+    unsafe {
+        assert_eq!(*lock.data_ptr(), 1);
+        lock.force_unlock_read();
+        lock.force_unlock_read();
+    }
+
+    lock.raw_write();
+    assert!(lock.try_read().is_err());
+    unsafe {
+        *lock.data_ptr() = 2;
+        lock.force_unlock_write();
+    }
+    assert_eq!(*lock.read(), 2);
+}
+
+#[test]
+fn leaked_guards() {
+    let mutex = Mutex::new(vec![1]);
+    let data = MutexGuard::leak(mutex.lock());
+    data.push(2);
+    assert!(mutex.try_lock().is_err());
+    assert_eq!(*data, [1, 2]);
+
+    let lock = RwLock::new(1);
+    let a = RwLockReadGuard::leak(lock.read());
+    let b = RwLockReadGuard::leak(lock.read());
+    assert_eq!(*a + *b, 2);
+    assert!(lock.try_write().is_err());
+    assert_eq!(*lock.read(), 1);
+
+    let lock = RwLock::new(String::from("a"));
+    let data = RwLockWriteGuard::leak(lock.write());
+    data.push('b');
+    assert!(lock.try_read().is_err());
+    assert_eq!(data, "ab");
+}
+
+#[cfg(feature = "raw")]
+#[test]
+fn leaked_guards_force_unlock() {
+    let mutex = Mutex::new(1);
+    *MutexGuard::leak(mutex.lock()) += 1;
+    unsafe { mutex.force_unlock() };
+    assert_eq!(*mutex.lock(), 2);
+
+    let lock = RwLock::new(1);
+    assert_eq!(*RwLockReadGuard::leak(lock.read()), 1);
+    unsafe { lock.force_unlock_read() };
+    *RwLockWriteGuard::leak(lock.write()) += 1;
+    assert!(lock.try_read().is_err());
+    unsafe { lock.force_unlock_write() };
+    assert_eq!(*lock.read(), 2);
+}
+
+#[test]
+fn from_std_ref() {
+    let mutex = sync::Mutex::new(0);
+    let _ = panic::catch_unwind(|| {
+        let _guard = mutex.lock().unwrap();
+        panic!();
+    });
+    assert!(mutex.is_poisoned());
+
+    let unpoisoned = Unpoisoned::from_std_ref(&mutex);
+    assert!(std::ptr::eq(unpoisoned.as_std(), &mutex));
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| for _ in 0..ITERS {
+                *unpoisoned.lock() += 1;
+            });
+        }
+    });
+    let guard = unpoisoned.lock();
+    assert_eq!(unpoisoned.try_lock().err(), Some(TryLockError::WouldBlock));
+    drop(guard);
+    assert_eq!(*unpoisoned.try_lock().unwrap(), THREADS * ITERS);
+
+    let lock = sync::RwLock::new(vec![1]);
+    let unpoisoned = Unpoisoned::from_std_ref(&lock);
+    unpoisoned.write().push(2);
+    let a = unpoisoned.read();
+    let b = unpoisoned.try_read().unwrap();
+    assert_eq!(*a, *b);
+    assert_eq!(unpoisoned.try_write().err(), Some(TryLockError::WouldBlock));
+    drop((a, b));
+    assert_eq!(*unpoisoned.try_write().unwrap(), [1, 2]);
+
+    let slice: &sync::Mutex<[i32]> = &sync::Mutex::new([1, 2]);
+    Unpoisoned::from_std_ref(slice).lock()[0] = 3;
+    assert_eq!(*slice.lock().unwrap(), [3, 2]);
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn ffi_locks() {
+    use antidote::ffi::*;
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    unsafe {
+        let mutex = antidote_mutex_new(b"ffi\0".as_ptr() as *const _);
+        let handle = &*mutex;
+        let count = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for _ in 0..THREADS {
+                let count = &count;
+                s.spawn(move || {
+                    for _ in 0..ITERS {
+                        let mutex: *const Mutex<()> = handle;
+                        antidote_mutex_lock(mutex);
+                        let n = count.load(Ordering::Relaxed);
+                        count.store(n + 1, Ordering::Relaxed);
+                        antidote_mutex_unlock(mutex);
+                    }
+                });
+            }
+        });
+        assert_eq!(count.load(Ordering::Relaxed), THREADS * ITERS);
+        assert!(antidote_mutex_try_lock(mutex));
+        assert!((*mutex).try_lock().is_err());
+        antidote_mutex_unlock(mutex);
+        drop((*mutex).lock());
+        antidote_mutex_free(mutex);
+
+        let rwlock = antidote_rwlock_new(ptr::null());
+        antidote_rwlock_read(rwlock);
+        assert!(antidote_rwlock_try_read(rwlock));
+        assert!(!antidote_rwlock_try_write(rwlock));
+        antidote_rwlock_read_unlock(rwlock);
+        antidote_rwlock_read_unlock(rwlock);
+        antidote_rwlock_write(rwlock);
+        assert!(!antidote_rwlock_try_read(rwlock));
+        antidote_rwlock_write_unlock(rwlock);
+        assert!(antidote_rwlock_try_write(rwlock));
+        antidote_rwlock_write_unlock(rwlock);
+        antidote_rwlock_free(rwlock);
+        antidote_mutex_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn pin_mutex_map_pin() {
+    struct Node {
+        value: usize,
+        _pin: PhantomPinned,
+    }
+
+    let node = Arc::pin(PinMutex::new(Node {
+        value: 0,
+        _pin: PhantomPinned,
+    }));
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            let node = node.as_ref();
+            s.spawn(move || {
+                for _ in 0..ITERS {
+                    let guard = node.lock();
+                    let value = MutexGuard::map_pin(guard, |n| unsafe { n.map_unchecked_mut(|n| &mut n.value) });
+                    let mut value = MappedMutexGuard::map_pin(value, |v| v);
+                    *value += 1;
+                }
+            });
+        }
+    });
+    assert_eq!(node.as_ref().lock().value, THREADS * ITERS);
+}
@@ -0,0 +1,270 @@
+extern crate antidote;
+
+use antidote::{Interrupt, Mutex, MutexBuilder, RwLock, TryLockError, ZipGuard};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn mutex_contended() {
+    let mutex = Arc::new(Mutex::new(0));
+    let threads = (0..THREADS)
+        .map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                *mutex.lock() += 1;
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*mutex.lock(), THREADS * ITERS);
+}
+
+// Threads which relock the mutex as soon as they release it would starve a
+// parked thread without the occasional fair handoff.
+#[test]
+fn mutex_waiter_not_starved() {
+    let mutex = Mutex::new(0);
+    let done = AtomicBool::new(false);
+    thread::scope(|s| {
+        for _ in 0..2 {
+            s.spawn(|| while !done.load(Ordering::Relaxed) {
+                let _guard = mutex.lock();
+                thread::sleep(Duration::from_micros(100));
+            });
+        }
+        for _ in 0..10 {
+            *mutex.lock() += 1;
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+    assert_eq!(*mutex.lock(), 10);
+}
+
+// A fair mutex hands itself to the parked waiter when unlocked, so it can't
+// be taken by another thread in between. Platforms whose wakeups don't
+// report whether a thread was woken take the lock back instead.
+#[cfg(any(not(feature = "futex"), target_os = "linux", target_os = "android"))]
+#[test]
+fn fair_mutex_hands_off() {
+    let mutex = MutexBuilder::new().fair(true).build(0);
+    thread::scope(|s| {
+        let guard = mutex.lock();
+        s.spawn(|| {
+            let mut guard = mutex.lock();
+            thread::sleep(Duration::from_millis(20));
+            *guard += 1;
+        });
+        while !mutex.is_contended() {
+            thread::yield_now();
+        }
+        thread::sleep(Duration::from_millis(20));
+        drop(guard);
+        assert!(mutex.try_lock().is_err());
+    });
+    assert_eq!(*mutex.lock(), 1);
+}
+
+#[test]
+fn mutex_panic_does_not_poison() {
+    let mutex = Arc::new(Mutex::new(vec![1]));
+    let mutex2 = mutex.clone();
+    let _ = thread::spawn(move || {
+            let mut guard = mutex2.lock();
+            guard.push(2);
+            panic!();
+        })
+        .join();
+    assert_eq!(*mutex.lock(), [1, 2]);
+    assert_eq!(Arc::try_unwrap(mutex).unwrap().into_inner(), [1, 2]);
+}
+
+#[test]
+fn mutex_swap_both_directions() {
+    let a = Arc::new(Mutex::new(vec![1]));
+    let b = Arc::new(Mutex::new(vec![2]));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let (a, b) = if i % 2 == 0 { (a.clone(), b.clone()) } else { (b.clone(), a.clone()) };
+            thread::spawn(move || {
+                for _ in 0..ITERS {
+                    a.swap(&b);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let mut values = vec![a.lock()[0], b.lock()[0]];
+    values.sort();
+    assert_eq!(values, [1, 2]);
+    a.swap(&a);
+}
+
+#[test]
+fn lock_interruptible() {
+    let mutex = Arc::new(Mutex::new(0));
+    let interrupt = Arc::new(Interrupt::new());
+    assert!(mutex.lock_interruptible(&interrupt).is_ok());
+
+    let guard = mutex.lock();
+    let waiters = (0..THREADS)
+        .map(|_| {
+            let mutex = mutex.clone();
+            let interrupt = interrupt.clone();
+            thread::spawn(move || mutex.lock_interruptible(&interrupt).err())
+        })
+        .collect::<Vec<_>>();
+    while !mutex.is_contended() {
+        thread::yield_now();
+    }
+    // The waiters are woken by the interrupt rather than noticing it later,
+    // and the mutex is still held by this thread afterwards.
+    interrupt.interrupt();
+    assert!(interrupt.is_interrupted());
+    for waiter in waiters {
+        assert_eq!(waiter.join().unwrap(), Some(TryLockError::Interrupted));
+    }
+    assert!(mutex.try_lock().is_err());
+    drop(guard);
+    assert_eq!(mutex.lock_interruptible(&interrupt).err(), Some(TryLockError::Interrupted));
+
+    // An uninterrupted waiter takes the lock once it is released.
+    let interrupt = Interrupt::new();
+    let guard = mutex.lock();
+    thread::scope(|s| {
+        let waiter = s.spawn(|| *mutex.lock_interruptible(&interrupt).unwrap() += 1);
+        while !mutex.is_contended() {
+            thread::yield_now();
+        }
+        drop(guard);
+        waiter.join().unwrap();
+    });
+    assert_eq!(*mutex.lock(), 1);
+}
+
+#[test]
+fn lock_interruptible_instrumented() {
+    let mutex = MutexBuilder::new().stats(true).watchdog(Duration::from_secs(60)).build(0);
+    let interrupt = Interrupt::new();
+    let guard = mutex.lock();
+    thread::scope(|s| {
+        let waiter = s.spawn(|| mutex.lock_interruptible(&interrupt).err());
+        while mutex.waiter_count() == Some(0) {
+            thread::yield_now();
+        }
+        interrupt.interrupt();
+        assert_eq!(waiter.join().unwrap(), Some(TryLockError::Interrupted));
+    });
+    drop(guard);
+    // Only successful acquisitions are counted.
+    assert_eq!(mutex.stats().unwrap().acquisitions(), 1);
+}
+
+#[test]
+fn owned_iterators() {
+    let mutex = Arc::new(Mutex::new(vec![String::from("a"), String::from("b")]));
+    let mut iter = mutex.clone().lock_iter(String::clone);
+    assert!(mutex.try_lock().is_err());
+    assert_eq!(iter.next_back().as_deref(), Some("b"));
+    let iter = thread::spawn(move || {
+        assert_eq!(iter.len(), 1);
+        iter
+    }).join().unwrap();
+    assert_eq!(iter.collect::<Vec<_>>(), ["a"]);
+    mutex.lock().push(String::from("c"));
+
+    let rwlock = Arc::new(RwLock::new(vec![1, 2, 3]));
+    let iter = rwlock.clone().read_iter(|n| *n);
+    assert!(rwlock.try_read().is_ok());
+    assert!(rwlock.try_write().is_err());
+    assert_eq!(iter.sum::<i32>(), 6);
+    rwlock.write().push(4);
+
+    let map = Arc::new(RwLock::new(HashMap::from([(1, String::from("a"))])));
+    let iter = map.clone().read_iter(|(k, v)| (*k, v.clone()));
+    assert!(map.try_write().is_err());
+    assert_eq!(iter.collect::<Vec<_>>(), [(1, String::from("a"))]);
+    assert!(map.try_write().is_ok());
+}
+
+#[test]
+fn lock_both_either_order() {
+    let a = Arc::new(Mutex::new(0));
+    let b = Arc::new(Mutex::new(0));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let (a, b) = if i % 2 == 0 { (a.clone(), b.clone()) } else { (b.clone(), a.clone()) };
+            thread::spawn(move || {
+                for _ in 0..ITERS {
+                    let mut guards = Mutex::lock_both(&a, &b);
+                    *guards.0 += 1;
+                    *guards.1 -= 1;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*a.lock() + *b.lock(), 0);
+
+    let guards = ZipGuard::into_inner(Mutex::lock_both(&a, &b));
+    drop(guards.1);
+    assert!(b.try_lock().is_ok());
+    assert!(a.try_lock().is_err());
+}
+
+#[test]
+fn contention_hints() {
+    let mutex = Mutex::new(0);
+    let rwlock = RwLock::new(0);
+    assert!(!mutex.is_contended());
+    assert!(!rwlock.has_waiters());
+
+    thread::scope(|s| {
+        let guard = mutex.lock();
+        let read = rwlock.read();
+        assert!(!mutex.is_contended());
+        assert!(!rwlock.has_waiters());
+
+        s.spawn(|| *mutex.lock() += 1);
+        s.spawn(|| *rwlock.write() += 1);
+        while !mutex.is_contended() || !rwlock.has_waiters() {
+            thread::yield_now();
+        }
+        drop(guard);
+        drop(read);
+    });
+    assert!(!mutex.is_contended());
+    assert!(!rwlock.has_waiters());
+}
+
+#[test]
+fn waiter_count() {
+    assert_eq!(Mutex::new(0).waiter_count(), None);
+
+    let mutex = MutexBuilder::new().name("queue").build(0);
+    assert_eq!(mutex.waiter_count(), Some(0));
+    thread::scope(|s| {
+        let guard = mutex.lock();
+        for _ in 0..THREADS {
+            s.spawn(|| *mutex.lock() += 1);
+        }
+        // Every waiter is counted, not just whether there are any.
+        while mutex.waiter_count() != Some(THREADS) {
+            thread::yield_now();
+        }
+        drop(guard);
+    });
+    assert_eq!(mutex.waiter_count(), Some(0));
+    assert_eq!(*mutex.lock(), THREADS);
+}
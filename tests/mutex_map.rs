@@ -0,0 +1,75 @@
+extern crate antidote;
+
+use antidote::MutexMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+// Keys which all hash alike, so that only equality tells them apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Colliding(u32);
+
+impl Hash for Colliding {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        0.hash(state);
+    }
+}
+
+#[test]
+fn mutex_map_serializes_per_key() {
+    let map = MutexMap::new();
+    let in_use = [AtomicUsize::new(0), AtomicUsize::new(0)];
+    thread::scope(|s| {
+        for i in 0..THREADS {
+            let (map, in_use) = (&map, &in_use);
+            s.spawn(move || for _ in 0..ITERS {
+                let key = i % 2;
+                let guard = map.lock(key);
+                assert_eq!(*guard.key(), key);
+                assert_eq!(in_use[key].fetch_add(1, Ordering::SeqCst), 0);
+                in_use[key].fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+    // Entries are removed once nobody holds or waits for them.
+    assert!(map.is_empty());
+}
+
+#[test]
+fn mutex_map_blocks_same_key_only() {
+    let map = &MutexMap::new();
+    let guard = map.lock(Colliding(1));
+    assert_eq!(map.len(), 1);
+    assert!(map.try_lock(Colliding(1)).is_err());
+    // The failed attempt doesn't leave an entry behind.
+    assert_eq!(map.len(), 1);
+
+    thread::scope(|s| {
+        // A different key with the same hash is a different lock.
+        s.spawn(|| drop(map.try_lock(Colliding(2)).unwrap()));
+
+        let (tx, rx) = mpsc::channel();
+        s.spawn(move || {
+            let _guard = map.lock(Colliding(1));
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(10)).is_err());
+        drop(guard);
+        rx.recv().unwrap();
+    });
+    assert!(map.is_empty());
+}
+
+#[cfg(all(debug_assertions, not(feature = "send_guard")))]
+#[test]
+#[should_panic(expected = "deadlock detected")]
+fn mutex_map_relock_same_key_panics() {
+    let map = MutexMap::new();
+    let _guard = map.lock(1);
+    let _ = map.lock(1);
+}
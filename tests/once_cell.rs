@@ -0,0 +1,25 @@
+extern crate antidote;
+
+use antidote::OnceCell;
+use std::sync::Arc;
+use std::thread;
+
+const THREADS: usize = 4;
+
+#[test]
+fn once_cell_contended() {
+    let cell = Arc::new(OnceCell::new());
+    let threads = (0..THREADS)
+        .map(|i| {
+            let cell = cell.clone();
+            thread::spawn(move || cell.get_or_init(|| i.to_string()).clone())
+        })
+        .collect::<Vec<_>>();
+    let values = threads.into_iter().map(|t| t.join().unwrap()).collect::<Vec<_>>();
+    assert!(values.iter().all(|v| *v == values[0]));
+
+    let mut cell = Arc::try_unwrap(cell).unwrap();
+    assert_eq!(cell.take(), Some(values[0].clone()));
+    assert_eq!(cell.get_or_try_init(|| Err(())), Err(()));
+    assert_eq!(cell.get_or_init(|| String::from("hello")), "hello");
+}
@@ -0,0 +1,62 @@
+#![cfg(feature = "proptest")]
+
+extern crate antidote;
+extern crate proptest;
+
+use antidote::Mutex;
+use antidote::testing::model::{self, Model};
+use proptest::prelude::*;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Push(u32),
+    Pop,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Default)]
+struct Queue(VecDeque<u32>);
+
+impl Model for Queue {
+    type Op = Op;
+    type Ret = Option<u32>;
+
+    fn apply(&mut self, op: &Op) -> Option<u32> {
+        match *op {
+            Op::Push(v) => {
+                self.0.push_back(v);
+                None
+            }
+            Op::Pop => self.0.pop_front(),
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn op_sequences_from_sizes(ops in model::op_sequences_from(&[Op::Push(1), Op::Pop], 2..4, 1..=3)) {
+        prop_assert!(ops.len() >= 2 && ops.len() < 4);
+        for thread in &ops {
+            prop_assert!(!thread.is_empty() && thread.len() <= 3);
+            prop_assert!(thread.iter().all(|op| *op == Op::Push(1) || *op == Op::Pop));
+        }
+    }
+
+    #[test]
+    fn mutex_queue_is_linearizable(ops in model::op_sequences(prop_oneof![any::<u32>().prop_map(Op::Push),
+                                                                         Just(Op::Pop)],
+                                                              2..4,
+                                                              1..5)) {
+        let queue = Mutex::new(VecDeque::new());
+        let result = model::check(&queue, Queue::default(), &ops, |q, op| match *op {
+            Op::Push(v) => {
+                q.lock().push_back(v);
+                None
+            }
+            Op::Pop => q.lock().pop_front(),
+        });
+        prop_assert!(result.is_ok(), "{}", result.unwrap_err());
+    }
+}
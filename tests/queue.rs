@@ -0,0 +1,50 @@
+extern crate antidote;
+
+use antidote::{BlockingQueue, BoundedQueue};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn blocking_queue() {
+    let queue = Arc::new(BlockingQueue::new());
+    assert_eq!(queue.pop_timeout(Duration::from_millis(1)), None);
+
+    let consumers = (0..THREADS)
+        .map(|_| {
+            let queue = queue.clone();
+            thread::spawn(move || (0..ITERS).map(|_| queue.pop()).sum::<usize>())
+        })
+        .collect::<Vec<_>>();
+    for i in 0..THREADS * ITERS {
+        queue.push(i);
+    }
+    let sum = consumers.into_iter().map(|c| c.join().unwrap()).sum::<usize>();
+    assert_eq!(sum, (0..THREADS * ITERS).sum());
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn bounded_queue_backpressure() {
+    let queue = Arc::new(BoundedQueue::new(2));
+    let producers = (0..THREADS)
+        .map(|_| {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                for i in 0..ITERS {
+                    queue.push(i);
+                    assert!(queue.len() <= 2);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    let sum = (0..THREADS * ITERS).map(|_| queue.pop()).sum::<usize>();
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    assert_eq!(sum, THREADS * (0..ITERS).sum::<usize>());
+    assert_eq!(queue.try_pop(), None);
+}
@@ -0,0 +1,251 @@
+extern crate antidote;
+
+use antidote::{MappedRwLockReadGuard, RwLock, RwLockBuilder, RwLockPolicy, RwLockReadGuard, RwLockUpgradableReadGuard,
+               RwLockWriteGuard};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn rwlock_contended() {
+    let lock = Arc::new(RwLock::new((0, 0)));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let lock = lock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                if i % 2 == 0 {
+                    let mut guard = lock.write();
+                    guard.0 += 1;
+                    guard.1 += 1;
+                } else {
+                    let guard = lock.read();
+                    assert_eq!(guard.0, guard.1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(lock.read().0, THREADS / 2 * ITERS);
+}
+
+// A fair lock must neither starve nor strand readers or writers, including
+// ones which give up waiting.
+#[test]
+fn fair_rwlock_contended() {
+    let lock = Arc::new(RwLockBuilder::new().policy(RwLockPolicy::PreferReaders).fair(true).build((0, 0)));
+    let threads = (0..THREADS * 2)
+        .map(|i| {
+            let lock = lock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                match i % 4 {
+                    0 => {
+                        let mut guard = lock.write();
+                        guard.0 += 1;
+                        guard.1 += 1;
+                    }
+                    1 => {
+                        if let Ok(guard) = lock.try_read_for(Duration::from_micros(50)) {
+                            assert_eq!(guard.0, guard.1);
+                        }
+                    }
+                    2 => {
+                        if let Ok(mut guard) = lock.try_write_for(Duration::from_micros(50)) {
+                            guard.0 += 1;
+                            guard.1 += 1;
+                        }
+                    }
+                    _ => {
+                        let guard = lock.read();
+                        assert_eq!(guard.0, guard.1);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert!(lock.read().0 >= THREADS / 2 * ITERS);
+}
+
+#[test]
+fn rwlock_upgrade() {
+    let lock = Arc::new(RwLock::new(0));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let lock = lock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                if i % 2 == 0 {
+                    let guard = lock.upgradable_read();
+                    let value = *guard;
+                    match RwLockUpgradableReadGuard::try_upgrade_for(guard, Duration::from_millis(1)) {
+                        Ok(mut guard) => {
+                            assert_eq!(*guard, value);
+                            *guard += 1;
+                        }
+                        Err(guard) => assert_eq!(*guard, value),
+                    }
+                } else {
+                    let _ = *lock.read();
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let guard = lock.upgradable_read();
+    let reader = lock.read();
+    let guard = RwLockUpgradableReadGuard::try_upgrade(guard).err().unwrap();
+    drop(reader);
+    let guard = RwLockUpgradableReadGuard::upgrade(guard);
+    assert!(lock.try_read().is_err());
+    drop(guard);
+}
+
+#[test]
+fn rwlock_downgrade_to_upgradable() {
+    let lock = Arc::new(RwLock::new(0));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let lock = lock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                match i % 3 {
+                    0 => {
+                        let mut guard = lock.write();
+                        *guard += 1;
+                        let value = *guard;
+                        let guard = RwLockWriteGuard::downgrade_to_upgradable(guard);
+                        assert_eq!(*guard, value);
+                        let mut guard = RwLockUpgradableReadGuard::upgrade(guard);
+                        assert_eq!(*guard, value);
+                        *guard += 1;
+                    }
+                    1 => {
+                        let guard = lock.upgradable_read();
+                        let value = *guard;
+                        assert_eq!(*RwLockUpgradableReadGuard::upgrade(guard), value);
+                    }
+                    _ => {
+                        let _ = *lock.read();
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*lock.read(), 2 * ITERS * THREADS.div_ceil(3));
+}
+
+#[test]
+fn rwlock_optimistic_read() {
+    let lock = RwLock::new([1, 2]);
+    let read = lock.optimistic_read();
+    assert_eq!(read.read(|v| v[0] + v[1]), 3);
+    assert!(read.validate());
+
+    *lock.write() = [3, 4];
+    assert!(!read.validate());
+    assert_eq!(read.read(|v| v[0] + v[1]), 7);
+
+    let guard = lock.write();
+    let read = lock.optimistic_read();
+    assert!(!read.validate());
+    drop(guard);
+    assert_eq!(read.read(|v| *v), [3, 4]);
+}
+
+#[test]
+fn rwlock_optimistic_read_invalidated_by_other_thread() {
+    let lock = RwLock::new([0u64; 4]);
+    let read = lock.optimistic_read();
+    // Readers don't invalidate the snapshot.
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(*lock.read(), [0; 4]));
+    });
+    assert!(read.validate());
+
+    thread::scope(|s| {
+        s.spawn(|| *lock.write() = [1; 4]);
+    });
+    assert!(!read.validate());
+    assert_eq!(read.read(|v| *v), [1; 4]);
+}
+
+#[test]
+fn rwlock_optimistic_read_contended() {
+    let lock = Arc::new(RwLock::new([0, 0]));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let lock = lock.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                if i % 2 == 0 {
+                    let mut guard = lock.write();
+                    guard[0] += 1;
+                    guard[1] += 1;
+                } else {
+                    let read = lock.optimistic_read();
+                    let [a, b] = read.read(|v| *v);
+                    assert_eq!(a, b);
+                    if read.validate() {
+                        assert!(lock.read()[0] >= a);
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(lock.read()[0], THREADS / 2 * ITERS);
+}
+
+#[test]
+fn read_recursive_with_writer_waiting() {
+    let rwlock = Arc::new(RwLock::new(0));
+    let guard = rwlock.read();
+    let writer = {
+        let rwlock = rwlock.clone();
+        thread::spawn(move || *rwlock.write() += 1)
+    };
+    // Once the writer is waiting, new readers are held back.
+    while rwlock.try_read().is_ok() {
+        thread::yield_now();
+    }
+    assert_eq!(*rwlock.read_recursive(), 0);
+    drop(guard);
+    writer.join().unwrap();
+    assert_eq!(*rwlock.read_recursive(), 1);
+}
+
+#[test]
+fn duplicate_read_guards() {
+    let rwlock = Arc::new(RwLock::new(vec![1, 2]));
+    let guard = rwlock.read();
+    let writer = {
+        let rwlock = rwlock.clone();
+        thread::spawn(move || rwlock.write().push(3))
+    };
+    while rwlock.try_read().is_ok() {
+        thread::yield_now();
+    }
+    let copy = RwLockReadGuard::duplicate(&guard);
+    let first = RwLockReadGuard::map(guard, |v| &v[0]);
+    let first_copy = MappedRwLockReadGuard::duplicate(&first);
+    drop(first);
+    assert_eq!(copy.len(), 2);
+    assert_eq!(*first_copy, 1);
+    drop(copy);
+    assert!(rwlock.try_write().is_err());
+    drop(first_copy);
+    writer.join().unwrap();
+    assert_eq!(*rwlock.read(), [1, 2, 3]);
+}
@@ -0,0 +1,36 @@
+extern crate antidote;
+
+use antidote::Semaphore;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn semaphore_bounds_weight() {
+    const CAPACITY: usize = 5;
+    let semaphore = Semaphore::new(CAPACITY);
+    let in_use = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for i in 0..THREADS {
+            let (semaphore, in_use) = (&semaphore, &in_use);
+            s.spawn(move || {
+                for _ in 0..ITERS {
+                    let weight = i % CAPACITY + 1;
+                    let mut permit = semaphore.acquire_many(weight);
+                    let total = in_use.fetch_add(weight, Ordering::SeqCst) + weight;
+                    assert!(total <= CAPACITY);
+                    in_use.fetch_sub(weight, Ordering::SeqCst);
+                    drop(permit.split(weight / 2).unwrap());
+                }
+            });
+        }
+    });
+    assert_eq!(semaphore.available_permits(), CAPACITY);
+
+    let permit = semaphore.try_acquire_many(CAPACITY).unwrap();
+    assert!(semaphore.try_acquire().is_err());
+    permit.forget();
+    assert_eq!(semaphore.available_permits(), 0);
+}
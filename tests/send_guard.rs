@@ -0,0 +1,18 @@
+#![cfg(feature = "send_guard")]
+extern crate antidote;
+
+use antidote::Mutex;
+use std::thread;
+
+#[test]
+fn guard_released_on_another_thread() {
+    let mutex = Mutex::new(vec![1]);
+    thread::scope(|s| {
+        let mut guard = mutex.lock();
+        s.spawn(move || {
+            guard.push(2);
+            drop(guard);
+        });
+    });
+    assert_eq!(*mutex.lock(), [1, 2]);
+}
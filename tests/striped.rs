@@ -0,0 +1,66 @@
+extern crate antidote;
+
+use antidote::{Mutex, Striped};
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+const KEYS: usize = 10;
+
+#[test]
+fn striped_stripe_count() {
+    assert_eq!(Striped::<Mutex<()>>::new(0).len(), 1);
+    assert_eq!(Striped::<Mutex<()>>::new(5).len(), 8);
+    assert_eq!(Striped::<Mutex<()>>::new(8).iter().len(), 8);
+}
+
+#[test]
+fn striped_counts_per_key() {
+    // Far fewer stripes than keys, so that keys share them.
+    let counts = Striped::<Mutex<HashMap<usize, usize>>>::new(2);
+    thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| for i in 0..ITERS {
+                let key = i % KEYS;
+                *counts.get(&key).lock().entry(key).or_insert(0) += 1;
+            });
+        }
+    });
+
+    let mut total = 0;
+    for stripe in &counts {
+        for (key, count) in stripe.lock().iter() {
+            assert_eq!(counts.get(key) as *const _, stripe as *const _);
+            assert_eq!(*count, THREADS * ITERS / KEYS);
+            total += count;
+        }
+    }
+    assert_eq!(total, THREADS * ITERS);
+}
+
+#[test]
+fn striped_colliding_keys_share_a_lock() {
+    let striped = Striped::<Mutex<()>>::new(4);
+    let other = (1..).find(|k| striped.index(k) == striped.index(&0)).unwrap();
+    let distinct = (1..).find(|k| striped.index(k) != striped.index(&0)).unwrap();
+    assert_eq!(striped.get(&0) as *const _, striped.get_at(striped.index(&0)) as *const _);
+
+    let guard = striped.get(&0).lock();
+    assert!(striped.get(&other).try_lock().is_err());
+    assert!(striped.get(&distinct).try_lock().is_ok());
+
+    thread::scope(|s| {
+        let (tx, rx) = mpsc::channel();
+        let striped = &striped;
+        s.spawn(move || {
+            let _guard = striped.get(&other).lock();
+            tx.send(()).unwrap();
+        });
+        assert!(rx.recv_timeout(Duration::from_millis(10)).is_err());
+        drop(guard);
+        rx.recv().unwrap();
+    });
+}
@@ -0,0 +1,179 @@
+#![cfg(feature = "testing")]
+
+extern crate antidote;
+
+use antidote::Mutex;
+use antidote::testing::deterministic::Deterministic;
+use antidote::testing::model::{self, Event, Model};
+use antidote::testing::stress::Stress;
+use std::collections::HashSet;
+use std::panic;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+struct Register(u32);
+
+#[derive(Debug, Clone)]
+enum Op {
+    Write(u32),
+    Read,
+}
+
+impl Model for Register {
+    type Op = Op;
+    type Ret = Option<u32>;
+
+    fn apply(&mut self, op: &Op) -> Option<u32> {
+        match *op {
+            Op::Write(v) => {
+                self.0 = v;
+                None
+            }
+            Op::Read => Some(self.0),
+        }
+    }
+}
+
+fn event(thread: usize, op: &Op, ret: Option<u32>, start: u64, end: u64) -> Event<'_, Register> {
+    Event {
+        thread,
+        op,
+        ret,
+        start,
+        end,
+    }
+}
+
+#[test]
+fn stress_runs_weighted_ops() {
+    let counter = Mutex::new((0, 0));
+    let report = Stress::new()
+        .threads(4)
+        .iterations(500)
+        .check_interval(10)
+        .op("both", 1, |c: &Mutex<(u32, u32)>| {
+            let mut c = c.lock();
+            c.0 += 1;
+            c.1 += 1;
+        })
+        .op("never", 0, |_| panic!("picked an operation with no weight"))
+        .invariant(|c| {
+            let c = c.lock();
+            assert_eq!(c.0, c.1);
+        })
+        .run(&counter);
+    assert_eq!(report.total_ops(), 2000);
+    assert_eq!(report.ops(), [("both", 2000), ("never", 0)]);
+    assert_eq!(counter.lock().0, 2000);
+}
+
+#[test]
+#[should_panic(expected = "invariant broken")]
+fn stress_reports_broken_invariant() {
+    let counter = Mutex::new((0, 0));
+    Stress::new()
+        .threads(2)
+        .iterations(100)
+        .op("first", 1, |c: &Mutex<(u32, u32)>| c.lock().0 += 1)
+        .invariant(|c| {
+            let c = c.lock();
+            assert!(c.0 == c.1, "invariant broken");
+        })
+        .run(&counter);
+}
+
+#[test]
+fn model_accepts_linearizable_histories() {
+    let (write, read) = (Op::Write(1), Op::Read);
+    // A read overlapping a write may see either value.
+    for ret in [0, 1] {
+        let history = vec![event(0, &write, None, 1, 2), event(1, &read, Some(ret), 0, 3)];
+        model::check_history(Register::default(), history).unwrap();
+    }
+
+    let threads = vec![vec![Op::Write(1), Op::Read], vec![Op::Write(2), Op::Read]];
+    let register = Mutex::new(0);
+    model::check(&register, Register::default(), &threads, |r, op| match *op {
+        Op::Write(v) => {
+            *r.lock() = v;
+            None
+        }
+        Op::Read => Some(*r.lock()),
+    })
+    .unwrap();
+}
+
+#[test]
+fn model_rejects_non_linearizable_histories() {
+    let (write, read) = (Op::Write(1), Op::Read);
+
+    // A read which starts after a write has finished must see it.
+    let history = vec![event(0, &write, None, 0, 1), event(1, &read, Some(0), 2, 3)];
+    let err = model::check_history(Register::default(), history).unwrap_err();
+    assert_eq!(err.history().len(), 2);
+    assert!(err.to_string().starts_with("history is not linearizable"));
+
+    // Once a read has seen the write, later reads can't go back to the old
+    // value, even though the write is still in progress.
+    let history = vec![event(0, &write, None, 0, 9),
+                       event(1, &read, Some(1), 2, 3),
+                       event(1, &read, Some(0), 4, 5)];
+    assert!(model::check_history(Register::default(), history).is_err());
+}
+
+// Runs two threads which each log two entries under separate acquisitions
+// of the lock, returning the order in which the entries were logged.
+fn interleaving(seed: u64) -> Vec<u32> {
+    let log = Mutex::new(vec![]);
+    let mut run = Deterministic::new(seed);
+    for thread in 0..2 {
+        let log = &log;
+        run.spawn(move || for i in 0..2 {
+            log.lock().push(thread * 10 + i);
+        });
+    }
+    run.run();
+    log.into_inner()
+}
+
+#[test]
+fn deterministic_explores_interleavings() {
+    let orders = (0..200).map(interleaving).collect::<HashSet<_>>();
+    // Every interleaving which keeps each thread's entries in order shows
+    // up, and no other.
+    assert_eq!(orders.len(), 6);
+    for order in &orders {
+        for thread in [0, 10] {
+            let entries = order.iter().filter(|&&e| e / 10 * 10 == thread).collect::<Vec<_>>();
+            assert_eq!(entries, [&thread, &(thread + 1)]);
+        }
+    }
+}
+
+#[test]
+fn deterministic_replays_seed() {
+    for seed in 0..20 {
+        assert_eq!(interleaving(seed), interleaving(seed));
+    }
+}
+
+#[test]
+fn deterministic_aborts_deadlock() {
+    let mut outcomes = HashSet::new();
+    for seed in 0..50 {
+        let (a, b) = (Mutex::new(()), Mutex::new(()));
+        let mut run = Deterministic::new(seed);
+        run.max_steps(1000);
+        run.spawn(|| {
+            let _a = a.lock();
+            let _b = b.lock();
+        });
+        run.spawn(|| {
+            let _b = b.lock();
+            let _a = a.lock();
+        });
+        // Whether the threads deadlock depends on the schedule, but the run
+        // always finishes, with a panic if they did.
+        outcomes.insert(panic::catch_unwind(panic::AssertUnwindSafe(|| run.run())).is_ok());
+    }
+    assert_eq!(outcomes.len(), 2);
+}
@@ -0,0 +1,25 @@
+extern crate antidote;
+
+use antidote::TicketMutex;
+use std::sync::Arc;
+use std::thread;
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn ticket_mutex_contended() {
+    let mutex = Arc::new(TicketMutex::new(0));
+    let threads = (0..THREADS)
+        .map(|_| {
+            let mutex = mutex.clone();
+            thread::spawn(move || for _ in 0..ITERS {
+                *mutex.lock() += 1;
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(*mutex.lock(), THREADS * ITERS);
+}
@@ -0,0 +1,94 @@
+extern crate antidote;
+
+use antidote::clock::{Clock, StdClock};
+use antidote::{Mutex, RwLock, TryLockError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 4;
+const ITERS: usize = 1000;
+
+#[test]
+fn timed_locks_contended() {
+    let mutex = Arc::new(Mutex::new(0));
+    let rwlock = Arc::new(RwLock::new(0));
+    let threads = (0..THREADS)
+        .map(|i| {
+            let mutex = mutex.clone();
+            let rwlock = rwlock.clone();
+            thread::spawn(move || {
+                let mut acquired = 0;
+                for _ in 0..ITERS {
+                    // Short timeouts give up in the middle of a wait, which
+                    // must not swallow a wakeup meant for another thread.
+                    if let Ok(mut guard) = mutex.try_lock_for(Duration::from_micros(10)) {
+                        *guard += 1;
+                        acquired += 1;
+                    }
+                    if i % 2 == 0 {
+                        if let Ok(mut guard) = rwlock.try_write_for(Duration::from_micros(10)) {
+                            *guard += 1;
+                        }
+                    } else if let Ok(guard) = rwlock.try_read_for(Duration::from_micros(10)) {
+                        assert!(*guard <= THREADS / 2 * ITERS);
+                    }
+                }
+                acquired
+            })
+        })
+        .collect::<Vec<_>>();
+    let acquired = threads.into_iter().map(|t| t.join().unwrap()).sum::<usize>();
+    assert_eq!(*mutex.lock(), acquired);
+
+    // The locks are held by another thread, since relocking one on the same
+    // thread panics in debug builds.
+    let guard = mutex.lock();
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(mutex.try_lock_for(Duration::from_millis(1)).err(), Some(TryLockError::Timeout)));
+    });
+    drop(guard);
+    assert!(mutex.try_lock_until(Instant::now()).is_ok());
+    let guard = rwlock.write();
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(rwlock.try_read_for(Duration::from_millis(1)).err(), Some(TryLockError::Timeout)));
+    });
+    drop(guard);
+    let guard = rwlock.read();
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(rwlock.try_write_until(Instant::now()).err(), Some(TryLockError::Timeout)));
+    });
+    drop(guard);
+    assert!(rwlock.try_read_until(Instant::now()).is_ok());
+}
+
+#[test]
+fn lock_until_clock() {
+    let mutex = Arc::new(Mutex::new(0));
+    let rwlock = Arc::new(RwLock::new(0));
+    // A clock which advances one tick each time it is read.
+    let ticks = AtomicUsize::new(0);
+    let clock = || ticks.fetch_add(1, Ordering::SeqCst);
+
+    let guard = mutex.lock();
+    let read_guard = rwlock.read();
+    let thread = {
+        let mutex = mutex.clone();
+        let rwlock = rwlock.clone();
+        thread::spawn(move || {
+            let clock = StdClock;
+            *mutex.try_lock_until_clock(&clock, clock.now() + Duration::from_secs(60)).unwrap() += 1;
+            *rwlock.try_write_until_clock(&clock, clock.now() + Duration::from_secs(60)).unwrap() += 1;
+        })
+    };
+    thread::scope(|s| {
+        s.spawn(|| assert_eq!(mutex.try_lock_until_clock(&clock, 3).err(), Some(TryLockError::Timeout)));
+    });
+    assert!(rwlock.try_read_until_clock(&clock, 0).is_ok());
+    drop(guard);
+    drop(read_guard);
+    thread.join().unwrap();
+    assert_eq!(*mutex.try_lock_until_clock(&clock, 0).unwrap(), 1);
+    assert_eq!(*rwlock.try_read_until_clock(&clock, 0).unwrap(), 1);
+}
@@ -0,0 +1,24 @@
+extern crate antidote;
+
+use antidote::VersionedRwLock;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn versioned_rwlock_wait_for_change() {
+    let lock = Arc::new(VersionedRwLock::new(0));
+    let seen = lock.version();
+    assert_eq!(lock.wait_for_change_timeout(seen, Duration::from_millis(1)), None);
+
+    let writer = {
+        let lock = lock.clone();
+        thread::spawn(move || *lock.write() = 1)
+    };
+    let version = lock.wait_for_change(seen);
+    assert_ne!(version, seen);
+    let (_, value) = lock.read_if_changed(seen).unwrap();
+    assert_eq!(*value, 1);
+    drop(value);
+    writer.join().unwrap();
+}
@@ -0,0 +1,64 @@
+extern crate antidote;
+
+use antidote::WaitGroup;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const THREADS: usize = 4;
+
+#[test]
+fn wait_group_waits_for_tasks() {
+    let group = Arc::new(WaitGroup::new());
+    // With nothing pending, waiting returns immediately.
+    group.wait();
+
+    let finished = Arc::new(AtomicUsize::new(0));
+    group.add(THREADS);
+    for _ in 0..THREADS {
+        let (group, finished) = (group.clone(), finished.clone());
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            finished.fetch_add(1, Ordering::SeqCst);
+            group.done();
+        });
+    }
+
+    // Every waiter is released once the last task finishes.
+    let waiters = (0..2)
+        .map(|_| {
+            let (group, finished) = (group.clone(), finished.clone());
+            thread::spawn(move || {
+                group.wait();
+                finished.load(Ordering::SeqCst)
+            })
+        })
+        .collect::<Vec<_>>();
+    group.wait();
+    assert_eq!(finished.load(Ordering::SeqCst), THREADS);
+    for waiter in waiters {
+        assert_eq!(waiter.join().unwrap(), THREADS);
+    }
+
+    // Waiting again, and reusing the group, both work.
+    group.wait();
+    group.add(1);
+    let task = {
+        let group = group.clone();
+        thread::spawn(move || group.done())
+    };
+    group.wait();
+    task.join().unwrap();
+}
+
+#[test]
+fn wait_group_done_without_tasks_panics() {
+    let group = WaitGroup::new();
+    group.add(1);
+    group.done();
+    assert!(panic::catch_unwind(|| group.done()).is_err());
+    // The failed call doesn't poison the group.
+    group.wait();
+}
@@ -0,0 +1,50 @@
+extern crate antidote;
+
+use antidote::watch;
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::Duration;
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(v) => return v,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn watch_channel() {
+    let (tx, mut rx) = watch::channel(0);
+    let mut async_rx = tx.subscribe();
+    assert_eq!(rx.has_changed(), Ok(false));
+    assert_eq!(rx.changed_timeout(Duration::from_millis(1)), Err(watch::RecvTimeoutError::Timeout));
+
+    let sender = thread::spawn(move || {
+        tx.send(1);
+        tx.send_modify(|v| *v += 1);
+    });
+    rx.changed().unwrap();
+    block_on(async_rx.changed_async()).unwrap();
+    sender.join().unwrap();
+
+    assert_eq!(*rx.borrow_and_update(), 2);
+    assert!(rx.changed().is_err());
+    assert_eq!(*async_rx.borrow_and_update(), 2);
+    assert!(block_on(async_rx.changed_async()).is_err());
+}